@@ -0,0 +1,347 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+
+// Everything a key can do on the main screen. Keys are translated into actions first and
+// the actions are then run on the app, so bindings are kept apart from what they do
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    MoveCursor(KeyCode),
+    // Move the cursor, starting a selection from where it was if there isn't one
+    Select(KeyCode),
+    InsertChar(char),
+    // Indents the selected lines when the selection spans rows, inserts a tab otherwise
+    Indent,
+    Unindent,
+    // Indents the selected lines, or the current line without a selection
+    IndentLines,
+    // Comment out the selected lines or the current line, or uncomment them when they all are
+    ToggleComment,
+    // Start a selection at the cursor without moving it, or drop the one there is
+    ToggleSelectionAnchor,
+    Delete,
+    Backspace,
+    Cut,
+    Copy,
+    Paste,
+    // Choose the register the next cut, copy or paste uses
+    SelectRegister,
+    OpenPasteHistory,
+    // Offer words from the open buffers that start like the word before the cursor
+    Complete,
+    Undo,
+    Redo,
+    RepeatLastEdit,
+    // One more digit of the count given to the next repeat
+    CountDigit(u32),
+    Save,
+    // Closes the buffer, asking first when it has unsaved changes
+    Quit,
+    // Stop the editor and return to the shell, `fg` brings it back
+    Suspend,
+    // Like `Quit`, but closing the last buffer leaves an empty one instead of quitting
+    CloseBuffer,
+    // Insert a character given by its codepoint or name
+    InsertUnicode,
+    // Insert the next key as the character it sends, even when it's bound to something
+    Verbatim,
+    // Show the codepoints, bytes and width of the character under the cursor
+    InspectChar,
+    // Rewrap the paragraph or the selected lines to the fill column
+    Justify,
+    // Turn breaking lines at the fill column while typing on or off, whatever the file type
+    ToggleHardWrap,
+    // Turn underlining misspelled words on or off, whatever the file type
+    ToggleSpellCheck,
+    // Pick a correction for the misspelled word at the cursor, or accept the word as it is
+    SpellSuggest,
+    SpellAddWord,
+    SpellIgnoreWord,
+    // Work out the arithmetic in the selection and put the result in its place, or show what the
+    // expression under the cursor comes to
+    Evaluate,
+    // Type the date and time in a format from the config, chosen from a list when there's more than one
+    InsertTimestamp,
+    // Fold the indented block at the cursor, or open the fold it's on
+    ToggleFold,
+    // Fold every outermost block, or open every fold
+    FoldAll,
+    UnfoldAll,
+    // Open an empty buffer without a file
+    NewBuffer,
+    // Move the file to another name, keeping the buffer open on it
+    RenameFile,
+    // Save in an encoding chosen from a prompt
+    SaveEncoding,
+    SwitchBuffer { forward: bool },
+    // Pick a buffer from a list of all of them
+    OpenBufferList,
+    // Pick a file from the archive named on the command line again
+    OpenArchiveList,
+    SortLines { descending: bool },
+    DedupeLines,
+    SetMark,
+    JumpToMark,
+    JumpBack,
+    JumpForward,
+    JumpToStart,
+    JumpToEnd,
+    GotoLine,
+    Search,
+    // Find the last search again, wrapping around the buffer
+    SearchAgain { forward: bool },
+    // Replace every match of a search in the buffer
+    Replace,
+    // Replace matches in every file under the working directory, after previewing them
+    ReplaceInFiles,
+    GotoConflict { forward: bool },
+    ResolveConflict,
+    // Operate on a word, quoted string or bracketed block around the cursor
+    TextObject,
+    // Move the viewport a row at a time, only dragging the cursor along once it would leave the screen
+    ScrollViewport(isize),
+    // Move the viewport and cursor together by half the screen
+    ScrollHalfPage { down: bool },
+    CenterCursorLine,
+    ToggleColumnMode,
+    NextCell,
+    PreviousCell,
+    OpenDiff,
+    OpenMessages,
+    OpenUndoTree,
+    ExportHtml,
+    // Run the project's build in the background, its errors are listed when it finishes
+    Build,
+    // Show or focus the pane listing the last build's errors or search's matches
+    ToggleQuickfix,
+    // Go to the next or previous item of that list
+    QuickfixStep { forward: bool },
+    // List the lines matching a search in every file under the working directory
+    SearchInFiles,
+    // Jump to where the word under the cursor is defined according to the project's tags file
+    GotoDefinition,
+    GenerateTags,
+    // Put the change under the cursor in git's index, take a staged one back out, or undo it in the buffer
+    StageHunk,
+    UnstageHunk,
+    RevertHunk,
+    // Show the project tree beside the buffer and move to it, or hide it when it's already focused
+    ToggleFileTree,
+    // Switch between the dark and light theme
+    ToggleTheme,
+    // Typed characters replace the ones under the cursor instead of pushing them along
+    ToggleOverwrite,
+}
+
+#[allow(clippy::missing_const_for_fn)]
+impl Action {
+    // Actions that can be bound to chords in the config, by the names they're given there
+    const NAMES: [(&'static str, Self); 74] = [
+        ("save", Self::Save),
+        ("quit", Self::Quit),
+        ("suspend", Self::Suspend),
+        ("close_buffer", Self::CloseBuffer),
+        ("new_buffer", Self::NewBuffer),
+        ("rename_file", Self::RenameFile),
+        ("save_encoding", Self::SaveEncoding),
+        ("cut", Self::Cut),
+        ("copy", Self::Copy),
+        ("paste", Self::Paste),
+        ("paste_history", Self::OpenPasteHistory),
+        ("select_register", Self::SelectRegister),
+        ("complete", Self::Complete),
+        ("undo", Self::Undo),
+        ("redo", Self::Redo),
+        ("repeat_last_edit", Self::RepeatLastEdit),
+        ("indent", Self::IndentLines),
+        ("unindent", Self::Unindent),
+        ("toggle_comment", Self::ToggleComment),
+        ("toggle_selection", Self::ToggleSelectionAnchor),
+        ("next_buffer", Self::SwitchBuffer { forward: true }),
+        ("previous_buffer", Self::SwitchBuffer { forward: false }),
+        ("buffer_list", Self::OpenBufferList),
+        ("archive_list", Self::OpenArchiveList),
+        ("sort_lines", Self::SortLines { descending: false }),
+        ("sort_lines_descending", Self::SortLines { descending: true }),
+        ("dedupe_lines", Self::DedupeLines),
+        ("set_mark", Self::SetMark),
+        ("jump_to_mark", Self::JumpToMark),
+        ("jump_back", Self::JumpBack),
+        ("jump_forward", Self::JumpForward),
+        ("goto_line", Self::GotoLine),
+        ("search", Self::Search),
+        ("search_next", Self::SearchAgain { forward: true }),
+        ("search_previous", Self::SearchAgain { forward: false }),
+        ("replace", Self::Replace),
+        ("replace_in_files", Self::ReplaceInFiles),
+        ("search_in_files", Self::SearchInFiles),
+        ("resolve_conflict", Self::ResolveConflict),
+        ("text_object", Self::TextObject),
+        ("center_line", Self::CenterCursorLine),
+        ("half_page_down", Self::ScrollHalfPage { down: true }),
+        ("half_page_up", Self::ScrollHalfPage { down: false }),
+        ("scroll_line_down", Self::ScrollViewport(1)),
+        ("scroll_line_up", Self::ScrollViewport(-1)),
+        ("column_mode", Self::ToggleColumnMode),
+        ("diff", Self::OpenDiff),
+        ("messages", Self::OpenMessages),
+        ("undo_tree", Self::OpenUndoTree),
+        ("export_html", Self::ExportHtml),
+        ("build", Self::Build),
+        ("quickfix", Self::ToggleQuickfix),
+        ("goto_definition", Self::GotoDefinition),
+        ("generate_tags", Self::GenerateTags),
+        ("stage_hunk", Self::StageHunk),
+        ("unstage_hunk", Self::UnstageHunk),
+        ("revert_hunk", Self::RevertHunk),
+        ("file_tree", Self::ToggleFileTree),
+        ("toggle_theme", Self::ToggleTheme),
+        ("toggle_overwrite", Self::ToggleOverwrite),
+        ("insert_unicode", Self::InsertUnicode),
+        ("verbatim", Self::Verbatim),
+        ("inspect_char", Self::InspectChar),
+        ("justify", Self::Justify),
+        ("toggle_hard_wrap", Self::ToggleHardWrap),
+        ("toggle_spell_check", Self::ToggleSpellCheck),
+        ("spell_suggest", Self::SpellSuggest),
+        ("spell_add_word", Self::SpellAddWord),
+        ("spell_ignore_word", Self::SpellIgnoreWord),
+        ("evaluate", Self::Evaluate),
+        ("insert_timestamp", Self::InsertTimestamp),
+        ("toggle_fold", Self::ToggleFold),
+        ("fold_all", Self::FoldAll),
+        ("unfold_all", Self::UnfoldAll),
+    ];
+
+    /// # Errors
+    ///
+    /// Will return `Err` if no action is called `name`
+    pub fn from_name(name: &str) -> Result<Self> {
+        Self::NAMES
+            .iter()
+            .find(|(action_name, _)| *action_name == name)
+            .map(|(_, action)| *action)
+            .ok_or_else(|| eyre!("unknown action `{name}`"))
+    }
+    // The name `from_name` takes, or the debug form for actions that can't be bound by name
+    #[must_use]
+    pub fn name(&self) -> String {
+        Self::NAMES
+            .iter()
+            .find(|(_, action)| action == self)
+            .map_or_else(|| format!("{self:?}"), |(name, _)| (*name).to_owned())
+    }
+    // The action bound to a key on the main screen. While a repeat count is being typed
+    // Alt-6 adds a digit, otherwise it copies
+    #[must_use]
+    pub fn from_key(key: KeyEvent, counting: bool) -> Option<Self> {
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+
+        let action = match (key.modifiers, key.code) {
+            (KeyModifiers::CONTROL, KeyCode::Char('x')) => Self::Quit,
+            (KeyModifiers::CONTROL, KeyCode::Char('o')) => Self::Save,
+            (KeyModifiers::CONTROL, KeyCode::Char('z')) => Self::Suspend,
+            // Only told apart from Ctrl-U by terminals that report Shift with Control
+            (_, KeyCode::Char('U' | 'u')) if key.modifiers == KeyModifiers::CONTROL | KeyModifiers::SHIFT => Self::InsertUnicode,
+            (KeyModifiers::CONTROL, KeyCode::Char('k')) => Self::Cut,
+            (KeyModifiers::CONTROL, KeyCode::Char('v')) => Self::Paste,
+            (KeyModifiers::CONTROL, KeyCode::Char('d')) => Self::ScrollHalfPage { down: true },
+            (KeyModifiers::CONTROL, KeyCode::Char('u')) => Self::ScrollHalfPage { down: false },
+            (KeyModifiers::CONTROL, KeyCode::Char('l')) => Self::CenterCursorLine,
+            (KeyModifiers::CONTROL, KeyCode::Char('j')) => Self::Justify,
+            (KeyModifiers::CONTROL, KeyCode::Char('n')) => Self::Complete,
+            (KeyModifiers::CONTROL, KeyCode::Char('b')) => Self::OpenBufferList,
+            (KeyModifiers::CONTROL, KeyCode::Char('w')) => Self::CloseBuffer,
+            (_, KeyCode::F(6)) => Self::Search,
+            // Terminals send Ctrl-\\ as the same byte as Ctrl-4
+            (KeyModifiers::CONTROL, KeyCode::Char('\\' | '4')) => Self::Replace,
+            (_, KeyCode::F(5)) => Self::Build,
+            (_, KeyCode::F(4)) => Self::ToggleQuickfix,
+            (KeyModifiers::SHIFT, KeyCode::F(8)) => Self::QuickfixStep { forward: false },
+            (_, KeyCode::F(8)) => Self::QuickfixStep { forward: true },
+            (KeyModifiers::CONTROL, KeyCode::Char('f')) => Self::SearchInFiles,
+            // Terminals send Ctrl-] as the same byte as Ctrl-5
+            (KeyModifiers::CONTROL, KeyCode::Char(']' | '5')) => Self::GotoDefinition,
+            (KeyModifiers::ALT, KeyCode::Char(']')) => Self::GenerateTags,
+            (KeyModifiers::CONTROL, KeyCode::Left) => Self::PreviousCell,
+            (KeyModifiers::CONTROL, KeyCode::Right) => Self::NextCell,
+            (KeyModifiers::CONTROL, KeyCode::Up | KeyCode::Char('y')) => Self::ScrollViewport(-1),
+            (KeyModifiers::CONTROL, KeyCode::Down | KeyCode::Char('e')) => Self::ScrollViewport(1),
+            (KeyModifiers::CONTROL, KeyCode::Home) => Self::JumpToStart,
+            (KeyModifiers::CONTROL, KeyCode::End) => Self::JumpToEnd,
+            (KeyModifiers::ALT, KeyCode::Char('n')) => Self::NewBuffer,
+            (KeyModifiers::ALT, KeyCode::Char('v')) => Self::Verbatim,
+            (KeyModifiers::ALT, KeyCode::Char('i')) => Self::InspectChar,
+            (KeyModifiers::ALT, KeyCode::Char('z')) => Self::ToggleFold,
+            (KeyModifiers::ALT, KeyCode::Char('y')) => Self::OpenPasteHistory,
+            (KeyModifiers::ALT, KeyCode::Char('r')) => Self::SelectRegister,
+            (KeyModifiers::ALT, KeyCode::Char('=')) => Self::OpenDiff,
+            (KeyModifiers::ALT, KeyCode::Char('l')) => Self::OpenMessages,
+            (KeyModifiers::ALT, KeyCode::Char('t')) => Self::OpenUndoTree,
+            (KeyModifiers::ALT, KeyCode::Char('h')) => Self::ExportHtml,
+            (KeyModifiers::ALT, KeyCode::Char('c')) => Self::ToggleColumnMode,
+            (KeyModifiers::ALT, KeyCode::Char('b')) => Self::ToggleFileTree,
+            (KeyModifiers::ALT, KeyCode::Char('u')) => Self::Undo,
+            (KeyModifiers::ALT, KeyCode::Char('e')) => Self::Redo,
+            (KeyModifiers::ALT, KeyCode::Char('s')) => Self::SortLines { descending: false },
+            (_, KeyCode::Char('S')) if alt => Self::SortLines { descending: true },
+            (KeyModifiers::ALT, KeyCode::Char('d')) => Self::DedupeLines,
+            (KeyModifiers::ALT, KeyCode::Char('m')) => Self::SetMark,
+            (KeyModifiers::ALT, KeyCode::Char('j')) => Self::JumpToMark,
+            (KeyModifiers::ALT, KeyCode::Char('k')) => Self::ResolveConflict,
+            (KeyModifiers::ALT, KeyCode::Char('g')) => Self::GotoLine,
+            (KeyModifiers::ALT, KeyCode::Char('w')) => Self::SearchAgain { forward: true },
+            (KeyModifiers::ALT, KeyCode::Char('f')) => Self::ReplaceInFiles,
+            (KeyModifiers::ALT, KeyCode::Char('q')) => Self::SearchAgain { forward: false },
+            (KeyModifiers::ALT, KeyCode::Char('o')) => Self::TextObject,
+            (KeyModifiers::ALT, KeyCode::Char('a')) => Self::ToggleSelectionAnchor,
+            (KeyModifiers::ALT, KeyCode::Char('/')) => Self::ToggleComment,
+            (_, KeyCode::Char('}')) if alt => Self::IndentLines,
+            (_, KeyCode::Char('{')) if alt => Self::Unindent,
+            (KeyModifiers::ALT, KeyCode::Up) => Self::GotoConflict { forward: false },
+            (KeyModifiers::ALT, KeyCode::Down) => Self::GotoConflict { forward: true },
+            (KeyModifiers::ALT, KeyCode::Left) => Self::JumpBack,
+            (KeyModifiers::ALT, KeyCode::Right) => Self::JumpForward,
+            (_, KeyCode::Char('<')) if alt => Self::SwitchBuffer { forward: false },
+            (_, KeyCode::Char('>')) if alt => Self::SwitchBuffer { forward: true },
+            (KeyModifiers::ALT, KeyCode::Char('.')) => Self::RepeatLastEdit,
+            (KeyModifiers::ALT, KeyCode::Char(c @ '0'..='9')) if c != '6' || counting => Self::CountDigit(c.to_digit(10)?),
+            (KeyModifiers::ALT, KeyCode::Char('6' | '^')) => Self::Copy,
+            (_, KeyCode::Insert) => Self::ToggleOverwrite,
+            (_, KeyCode::Tab) => Self::Indent,
+            (_, KeyCode::BackTab) => Self::Unindent,
+            (_, KeyCode::Enter) => Self::InsertChar('\n'),
+            (_, KeyCode::Char(c)) => Self::InsertChar(c),
+            (_, KeyCode::Delete) => Self::Delete,
+            (_, KeyCode::Backspace) => Self::Backspace,
+            (KeyModifiers::SHIFT, code) if is_motion(code) => Self::Select(code),
+            (_, code) if is_motion(code) => Self::MoveCursor(code),
+            _ => return None,
+        };
+
+        Some(action)
+    }
+    // Vertical movement remembers the column it started from, everything else forgets it
+    #[must_use]
+    pub fn keeps_desired_column(&self) -> bool {
+        match self {
+            Self::MoveCursor(code) | Self::Select(code) => matches!(code, KeyCode::Up | KeyCode::Down | KeyCode::PageUp | KeyCode::PageDown),
+            Self::ScrollViewport(_) | Self::ScrollHalfPage { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+const fn is_motion(code: KeyCode) -> bool {
+    matches!(
+        code,
+        KeyCode::Up
+        | KeyCode::Down
+        | KeyCode::Left
+        | KeyCode::Right
+        | KeyCode::PageUp
+        | KeyCode::PageDown
+        | KeyCode::End
+        | KeyCode::Home
+    )
+}
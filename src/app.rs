@@ -1,299 +1,4390 @@
-use std::env;
-use std::time::Instant;
+use std::borrow::Cow;
+use std::{fs, io, iter};
+use std::path::{Path, PathBuf};
+use std::cmp::Ordering;
+use std::time::{Duration, Instant};
 use color_eyre::Result;
+use crossterm::cursor::SetCursorStyle;
 use crossterm::event;
-use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
 use ratatui::backend::Backend;
+use ratatui::layout;
 use ratatui::layout::Rect;
 use ratatui::Terminal;
-use crate::doc::Doc;
-use crate::doc_row::Row;
+use unicode_segmentation::UnicodeSegmentation;
+use crate::action::Action;
+use crate::archive::{member_name, split_member, Archive, ArchiveKind};
+use crate::build::{default_command, BuildJob, BuildSeverity};
+use crate::calc::{evaluate, expression_at, format_number};
+use crate::cli::{Cli, FileArg};
+use crate::clipboard::{primary_selection, KillRing};
+use crate::completion::{buffer_words, is_word_char, Completion, COMPLETION_LIMIT};
+use crate::config::{find_project_config, fold_dir, prompt_history_file, snippets_dir, templates_dir, undo_dir, user_dictionary, Config};
+use crate::conflicts::Resolution;
+use crate::csv::{delimiter_for, ColumnLayout};
+use crate::diff::{diff, DiffLine, SideBySide};
+use crate::encoding::{raw_byte, Encoding};
+use crate::document::Document;
+use crate::filetree::FileTree;
+use crate::fold::{fold_at, outer_folds};
+use crate::remote::{is_remote, is_url};
+use crate::git::{staged_diff, write_index, GitStatus};
+use crate::row::{grapheme_width, Row};
+use crate::search::SearchPattern;
+use crate::SearchDirection;
+use crate::html;
+use crate::keymap::{key_from_event, key_name, Key, Lookup};
+use crate::indent::{closing_indent, comment_leader, leading_whitespace, toggle_line_comments};
+use crate::project;
+use crate::project::{find_changes, find_matches, preview_rows, FileChanges, PreviewRow};
+use crate::palette::set_color_support;
+use crate::popup::{fuzzy_score, PopupKey, PopupList};
+use crate::quickfix::{Diagnostic, ItemKind, QuickfixItem, QuickfixList};
+use crate::prompt::{PromptHistory, PromptKind, PromptState};
+use crate::snippets::SnippetLibrary;
+use crate::spell::{SpellChecker, Word};
+use crate::status::{Severity, StatusQueue};
+use crate::statusbar::{clock_text, git_branch, Segment, StatusBarLayout};
+use crate::tags::{find_tags, generate_tags, Tag, TAGS_FILE};
+use crate::switcher::{filter_entries, BufferEntry};
+use crate::textobject::TextObject;
+use crate::theme::{detect_background, Background, Theme};
+use crate::template::{author, load_template, Placeholders};
+use crate::timestamp::format_now;
 use crate::ui::ui;
+use crate::unicode;
+use crate::watch::{canonical, DiskWatcher};
+use crate::wrap::{break_point, continuation_prefix, justify, paragraph_bounds};
 
-const QUIT_TIMES: u8 = 0;
+const MOUSE_SCROLL_LINES: usize = 3;
+// The largest count a repeat prefix takes, so a slip of the keyboard can't queue millions of edits
+const MAX_REPEAT_COUNT: usize = 10_000;
+// Unchanged rows kept in view above a change the side by side diff moves to
+const DIFF_CONTEXT: usize = 3;
+pub const TAB_WIDTH: usize = 4;
+const TICK_INTERVAL: Duration = Duration::from_millis(250);
+// How often a selection dragged past the text scrolls further
+const DRAG_SCROLL_INTERVAL: Duration = Duration::from_millis(50);
+// How long a chord waits for its next key before showing what can follow
+const CHORD_HINT_DELAY: Duration = Duration::from_millis(750);
 
-#[derive(Default, Clone)]
+#[derive(Default, Clone, PartialEq, Eq)]
 pub struct Position {
     pub x: usize,
     pub y: usize,
 }
 
+// Positions are ordered the way they appear in the document, row first
+impl Ord for Position {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.y, self.x).cmp(&(other.y, other.x))
+    }
+}
+
+impl PartialOrd for Position {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// How the terminal draws the cursor, following what typing does
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CursorShape {
+    Bar,
+    Block,
+    Underline,
+}
+
+// Commands that read one more key before acting
+enum PendingKey {
+    SetMark,
+    JumpToMark,
+    ResolveConflict,
+    PrivilegedSave,
+    ReloadChanged,
+    LoadLargeFile,
+    // Whether to make a buffer read-only since another instance is editing its file
+    OpenLocked,
+    SelectRegister,
+    Verbatim,
+    // The keys typed so far, the operator then `i` or `a`, and the register chosen before them
+    TextObject { keys: Vec<char>, register: Option<char> },
+}
+
+#[derive(Debug)]
 pub enum CurrentScreen {
     Main,
     Saving,
+    // The encoding to save the buffer in
+    SaveEncoding,
+    GotoLine,
+    ExportHtml,
+    // The name the buffer's file is moved to
+    RenameFile,
+    // A codepoint or character name to insert
+    InsertUnicode,
+    Search,
+    // The search whose matches are replaced, then what they're replaced with
+    Replace,
+    ReplaceWith,
+    ConfirmQuit,
+    PasteHistory,
+    Diff,
+    Messages,
+    UndoTree,
+    Completion,
+    // What replacing in every file would change, for review before it's written
+    FileChanges,
+    // Keys go to the file tree sidebar
+    FileTree,
+    BufferList,
+    // Keys go to the quickfix pane
+    Quickfix,
+    // Choosing between the definitions a tag has
+    TagPicker,
+    // Choosing a file to open from an archive named on the command line
+    ArchiveList,
+    // Two files from `--diff` compared side by side
+    SideBySide,
+    // Corrections for the misspelled word at the cursor
+    SpellSuggestions,
+    // Choosing the format of a timestamp to insert
+    TimestampPicker,
 }
 
+#[allow(clippy::struct_excessive_bools)]
 pub struct App {
     pub current_screen: CurrentScreen,
+    config: Config,
     cursor_position: Position,
+    desired_column: Option<usize>,
+    selection_anchor: Option<Position>,
     offset: Position,
     terminal_size: Rect,
-    document: Doc,
-    status_message: StatusMessage,
+    header_area: Rect,
+    scrollbar_area: Rect,
+    sidebar_area: Rect,
+    gutter_area: Rect,
+    dragging_scrollbar: bool,
+    // Where the pointer is while a selection is dragged above or below the text, which keeps scrolling
+    drag_scroll: Option<layout::Position>,
+    document: Document,
+    buffers: Vec<Buffer>,
+    column_layout: Option<ColumnLayout>,
+    kill_ring: KillRing,
+    // Register chosen for the next cut, copy or paste
+    register: Option<char>,
+    paste_history: PopupList,
+    diff: Vec<DiffLine>,
+    diff_scroll: usize,
+    messages_scroll: usize,
+    undo_tree_index: usize,
+    completions: Vec<Completion>,
+    completion_list: PopupList,
+    last_edit: Option<Edit>,
+    insert_run: bool,
+    // Hard wrap turned on or off for every buffer, overriding the config
+    hard_wrap: Option<bool>,
+    // Spell checking turned on or off for every buffer, overriding the config
+    spell_check: Option<bool>,
+    // Read from the word list the first time a buffer is checked
+    spell: Option<SpellChecker>,
+    spell_word: Option<(usize, Word)>,
+    spell_suggestions: Vec<String>,
+    spell_list: PopupList,
+    // Each timestamp format's name and what it makes, taken when the picker opened
+    timestamps: Vec<(String, String)>,
+    timestamp_list: PopupList,
+    // Whether the last action cut a line, so cutting the next one adds it to the same clipboard entry
+    cut_run: bool,
+    repeat_count: Option<usize>,
+    pending_key: Option<PendingKey>,
+    // Esc was pressed on its own, so the next key is taken as Alt and that key
+    meta_pending: bool,
+    // The keys of a chord typed so far and when its first was pressed
+    chord: Vec<Key>,
+    chord_started: Option<Instant>,
+    // Diagnostics the mouse is over and the cell it's at, until it moves off them or a key is pressed
+    tooltip: Option<(layout::Position, Vec<QuickfixItem>)>,
+    // Typed characters replace the ones under the cursor
+    overwrite: bool,
+    // Opened with `--view`, single keys page and search like less does
+    pager: bool,
+    // The shape last set on the terminal, None when it may have been changed by something else
+    drawn_cursor_shape: Option<CursorShape>,
+    // The background colors are chosen for, and the colors
+    background: Background,
+    theme: Theme,
+    status: StatusQueue,
+    prompt: PromptState,
+    prompt_history: PromptHistory,
+    search: Option<SearchPattern>,
+    // Whether the search prompts take a regex, toggled with Alt-R while they're open
+    search_regex: bool,
+    // The search prompts are for every file under the working directory, not the buffer
+    in_files: bool,
+    file_changes: Vec<FileChanges>,
+    file_changes_index: usize,
+    buffer_list: PopupList,
+    build: Option<BuildJob>,
+    quickfix: Option<QuickfixList>,
+    show_quickfix: bool,
+    tag_matches: Vec<Tag>,
+    tag_list: PopupList,
+    archive: Option<Archive>,
+    archive_list: PopupList,
+    side_by_side: Option<SideBySide>,
+    side_by_side_scroll: usize,
+    // How the buffer differs from git's index, for the gutter and staging hunks
+    git_status: Option<GitStatus>,
+    // Checked once, so files git doesn't track don't run it every tick
+    git_untracked: Option<String>,
+    // What's being committed, shown below the commit message
+    staged_diff: Vec<DiffLine>,
+    // Kept for the status bar and updated each tick, so drawing doesn't read files
+    git_branch: Option<String>,
+    clock: String,
+    // Tells which open files other programs changed, None when files can't be watched here
+    disk_watcher: Option<DiskWatcher>,
+    snippets: SnippetLibrary,
+    // Built the first time the sidebar is shown, then kept with its open directories while it's hidden
+    file_tree: Option<FileTree>,
+    show_file_tree: bool,
+    quit_after_save: bool,
+    // Whether the buffer being closed is replaced by an empty one when it's the last, rather than quitting
+    keep_editor_open: bool,
+    privileged_save: bool,
+    suspend: bool,
     should_quit: bool,
-    quit_times: u8,
 }
 
-struct StatusMessage {
-    text: String,
-    time: Instant,
+// The most recent editing action, replayed by the repeat command
+#[derive(Clone)]
+enum Edit {
+    Insert(String),
+    Delete,
+    Backspace,
+    Paste(String),
+    CutLine,
+}
+
+// A document along with where its cursor and view were left, kept while another buffer is shown
+struct Buffer {
+    document: Document,
+    cursor_position: Position,
+    offset: Position,
 }
 
 #[allow(clippy::missing_const_for_fn)]
 impl App {
     pub fn run<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> Result<()> {
+        self.refresh_status_bar();
+
+        // The viewport size is only known after the first draw, so the starting line is centered then
+        terminal.draw(|f| ui(f, self))?;
+        self.center_cursor_line();
+        self.scroll();
+
+        let mut redraw = true;
+
         loop {
-            terminal.draw(|f| ui(f, self))?;
+            if redraw {
+                self.load_visible_rows();
+                terminal.draw(|f| ui(f, self))?;
+                self.update_cursor_shape(terminal)?;
+            }
 
             if self.should_quit {
                 return Ok(());
             }
 
-            self.process_keypress()?;
+            // Waking up between events lets timed state like status messages change without a keypress
+            let interval = if self.drag_scroll.is_some() { DRAG_SCROLL_INTERVAL } else { TICK_INTERVAL };
+
+            redraw = if event::poll(interval)? {
+                self.process_keypress()?;
+
+                if std::mem::take(&mut self.privileged_save) {
+                    self.save_privileged(terminal)?;
+                }
+
+                if std::mem::take(&mut self.suspend) {
+                    self.suspend(terminal)?;
+                }
+
+                true
+            } else {
+                self.tick()
+            };
+        }
+    }
+    // Write every buffer with unsaved changes beside its file as `name.ironn-crash`, unnamed ones into
+    // the working directory. Returns the files written
+    pub fn dump_dirty_buffers(&self) -> Vec<String> {
+        let documents = std::iter::once(&self.document).chain(self.buffers.iter().map(|buffer| &buffer.document));
+        let mut dumped = Vec::new();
+
+        for (index, document) in documents.enumerate().filter(|(_, document)| document.is_dirty()) {
+            let path = format!("{}.ironn-crash", document.file_name.clone().unwrap_or_else(|| format!("new-buffer-{}", index + 1)));
+
+            match document.write_crash_dump(Path::new(&path)) {
+                Ok(()) => dumped.push(path),
+                Err(error) => tracing::error!(path, %error, "crash dump failed"),
+            }
+        }
+
+        dumped
+    }
+    fn update_cursor_shape<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> Result<()> {
+        let shape = self.cursor_shape();
+
+        if !self.config.cursor_shape || self.drawn_cursor_shape == Some(shape) {
+            return Ok(());
         }
+
+        let style = match shape {
+            CursorShape::Bar => SetCursorStyle::SteadyBar,
+            CursorShape::Block => SetCursorStyle::SteadyBlock,
+            CursorShape::Underline => SetCursorStyle::SteadyUnderScore,
+        };
+
+        // Written past the backend, which has no way to set the shape
+        crossterm::execute!(io::stdout(), style)?;
+        terminal.flush()?;
+
+        self.drawn_cursor_shape = Some(shape);
+
+        Ok(())
     }
     pub fn process_keypress(&mut self) -> Result<()> {
-        if let Event::Key(pressed_key) = event::read()? {
-            #[allow(clippy::single_match)]
+        let event = event::read()?;
+
+        match &event {
+            Event::Mouse(mouse_event) => {
+                self.process_mouse(*mouse_event);
+
+                return Ok(());
+            },
+            // Pastes and IME commits arrive as one string and are inserted as a unit
+            Event::Paste(text) => self.insert_text(&paste_line_breaks(text), false),
+            _ => ()
+        }
+
+        if let Event::Key(pressed_key) = event {
+            // Terminals reporting key releases would otherwise insert every character twice
+            if pressed_key.kind == KeyEventKind::Release {
+                return Ok(());
+            }
+
+            tracing::debug!(code = ?pressed_key.code, modifiers = ?pressed_key.modifiers, screen = ?self.current_screen, "key");
+
+            self.status.advance();
+            self.tooltip = None;
+
             match self.current_screen {
-                CurrentScreen::Main => match (pressed_key.modifiers, pressed_key.code) {
-                    (KeyModifiers::CONTROL, KeyCode::Char('x')) => {
-                        if self.quit_times > 0 /*&& self.document.is_dirty()*/ {
-                            /*self.status_message = crate::editor::StatusMessage::from(format!(
-                                "WARNING! File has unsaved changes. Press Ctrl-Q {} more times to quit.",
-                                self.quit_times
-                            ));*/
-
-                            self.quit_times -= 1;
-
-                            return Ok(())
-                        }
-                        self.should_quit = true;
-                    },
-                    (KeyModifiers::CONTROL, KeyCode::Char('o')) => self.write_out(),
-                    (_, KeyCode::Enter) => {
-                        self.document.insert_newline(&self.cursor_position);
-                        self.move_cursor(KeyCode::Right);
-                    }
-                    (_, KeyCode::Char(c)) => {
-                        self.document.insert(&self.cursor_position, c);
-                        self.move_cursor(KeyCode::Right);
-                    },
-                    (_, KeyCode::Delete) => self.document.delete(&self.cursor_position),
-                    (_, KeyCode::Backspace) => {
-                        if self.cursor_position.x > 0 || self.cursor_position.y > 0 {
-                            self.move_cursor(KeyCode::Left);
-                            self.document.delete(&self.cursor_position);
-                        }
-                    }
-                    (_, KeyCode::Up
-                    | KeyCode::Down
-                    | KeyCode::Left
-                    | KeyCode::Right
-                    | KeyCode::PageUp
-                    | KeyCode::PageDown
-                    | KeyCode::End
-                    | KeyCode::Home) => self.move_cursor(pressed_key.code),
-                    _ => ()
-                },
-                CurrentScreen::Saving => match (pressed_key.modifiers, pressed_key.code) {
-                    (_, KeyCode::Char('f')) => {
-                        self.move_cursor(KeyCode::Right);
-                        //self.current_screen = CurrentScreen::Main;
-                    },
-                    _ => ()
-                }
-                _ => ()
+                CurrentScreen::Main => self.process_main_key(pressed_key),
+                CurrentScreen::Saving | CurrentScreen::SaveEncoding | CurrentScreen::RenameFile | CurrentScreen::GotoLine | CurrentScreen::InsertUnicode | CurrentScreen::ExportHtml | CurrentScreen::Search | CurrentScreen::Replace | CurrentScreen::ReplaceWith => self.process_prompt_key(pressed_key),
+                CurrentScreen::ConfirmQuit => self.process_confirm_quit_key(pressed_key),
+                CurrentScreen::PasteHistory => self.process_paste_history_key(pressed_key),
+                CurrentScreen::Diff => self.process_diff_key(pressed_key),
+                CurrentScreen::Messages => self.process_messages_key(pressed_key),
+                CurrentScreen::UndoTree => self.process_undo_tree_key(pressed_key),
+                CurrentScreen::Completion => self.process_completion_key(pressed_key),
+                CurrentScreen::FileChanges => self.process_file_changes_key(pressed_key),
+                CurrentScreen::FileTree => self.process_file_tree_key(pressed_key),
+                CurrentScreen::BufferList => self.process_buffer_list_key(pressed_key),
+                CurrentScreen::Quickfix => self.process_quickfix_key(pressed_key),
+                CurrentScreen::TagPicker => self.process_tag_picker_key(pressed_key),
+                CurrentScreen::ArchiveList => self.process_archive_list_key(pressed_key),
+                CurrentScreen::SideBySide => self.process_side_by_side_key(pressed_key),
+                CurrentScreen::SpellSuggestions => self.process_spell_suggestions_key(pressed_key),
+                CurrentScreen::TimestampPicker => self.process_timestamp_picker_key(pressed_key),
             }
         }
 
+        self.refresh_column_layout();
         self.scroll();
 
         Ok(())
     }
-    pub fn document(&self) -> &Doc {
-        &self.document
+    // Streamed files are read a screen ahead of the viewport
+    fn load_visible_rows(&mut self) {
+        let height = self.terminal_size.height as usize;
+
+        self.document.load_to(self.offset.y.saturating_add(height.saturating_mul(2)));
+
+        let read_rows = self.document.len();
+
+        // Rows read by jumps elsewhere can hit the error too, so it's reported here where every redraw passes
+        if let Some(error) = self.document.unshown_read_error() {
+            self.status.error(format!("Couldn't read past line {read_rows}: {error}"));
+        }
     }
-    pub fn cursor_position(&self) -> &Position {
-        &self.cursor_position
+    // Runs when no event arrived within a tick, returns whether the screen needs redrawing
+    fn tick(&mut self) -> bool {
+        if self.drag_scroll.is_some() {
+            self.drag_scroll();
+            return true;
+        }
+
+        // The message history and undo tree show how long ago each entry was
+        let ages_shown = matches!(self.current_screen, CurrentScreen::Messages | CurrentScreen::UndoTree);
+
+        // Redrawn until the chord's hints are shown, and while they are
+        if self.chord_started.is_some() {
+            return true;
+        }
+
+        // Prompts waiting on another key stay up until it's pressed
+        if self.pending_key.is_some() || self.repeat_count.is_some() {
+            return self.status.expire_toasts() || ages_shown;
+        }
+
+        // Another instance editing the file is asked about once the buffer is shown
+        if let Some(owner) = self.document.take_locked_by() {
+            let name = self.document.file_name.clone().unwrap_or_default();
+
+            self.status.warn(format!("{name} is open in another ironn (pid {} on {}). Open it read-only? (y/n): ", owner.pid, owner.host));
+            self.pending_key = Some(PendingKey::OpenLocked);
+
+            return true;
+        }
+
+        let reloaded = matches!(self.current_screen, CurrentScreen::Main) && self.check_disk_changes();
+        let built = self.poll_build();
+        let status_bar_changed = self.refresh_status_bar();
+        let git_changed = matches!(self.current_screen, CurrentScreen::Main) && self.refresh_git_status();
+        let dictionary_loaded = self.spell_checks() && self.spell.is_none() && self.load_spell_checker().is_some();
+
+        self.status.expire() || ages_shown || reloaded || built || status_bar_changed || git_changed || dictionary_loaded
     }
-    pub fn offset(&self) -> &Position {
-        &self.offset
+    // Compare the buffer with git's index when either changed, returns whether the gutter has to be
+    // drawn again
+    fn refresh_git_status(&mut self) -> bool {
+        let file_name = self.document.file_name.clone().filter(|file_name| {
+            !self.document.is_streamed() && !is_remote(file_name) && !is_url(file_name) && split_member(file_name).is_none() && Path::new(file_name).is_file()
+        });
+
+        let Some(file_name) = file_name.filter(|file_name| self.git_untracked.as_ref() != Some(file_name)) else {
+            return self.git_status.take().is_some();
+        };
+
+        let generation = self.document.generation();
+        let stale = self.git_status.as_ref().is_none_or(|status| status.is_stale(&file_name));
+
+        // The buffer's lines are only copied once it was edited or the index changed
+        if !stale && self.git_status.as_ref().is_some_and(|status| status.is_current(generation)) {
+            return false;
+        }
+
+        let lines = self.document.lines(0, self.document.len());
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+
+        match &mut self.git_status {
+            Some(status) if !stale => {
+                status.update(&lines, generation);
+
+                true
+            },
+            _ => {
+                let shown = self.git_status.take().is_some();
+
+                self.git_status = GitStatus::load(&file_name, &lines, generation);
+
+                if self.git_status.is_none() {
+                    self.git_untracked = Some(file_name);
+                }
+
+                shown || self.git_status.is_some()
+            },
+        }
     }
-    pub fn status_message(&self) -> &String {
-        &self.status_message.text
+    // Shown while the commit message it belongs to is
+    #[must_use]
+    pub fn staged_diff(&self) -> &[DiffLine] {
+        if self.document.file_type.is_commit_message() { &self.staged_diff } else { &[] }
     }
-    pub fn update_bounds(&mut self, rect: Rect) {
-        self.terminal_size = rect;
+    #[must_use]
+    pub fn git_status(&self) -> Option<&GitStatus> {
+        self.git_status.as_ref()
     }
-    fn write_out(&mut self) {
-        self.current_screen = CurrentScreen::Saving;
-        if self.document.file_name.is_none() {
-            let new_name = self.prompt("File Name to Write: ", |_,_,_| {}).unwrap_or(None);
+    // The status for staging from the buffer as it is now
+    fn current_git_status(&mut self) -> Option<&GitStatus> {
+        self.git_untracked = None;
+        self.refresh_git_status();
 
-            if new_name.is_none() {
-                self.status_message = StatusMessage::from("Cancelled".to_owned());
-                return;
+        if self.git_status.is_none() {
+            let name = self.document.file_name.clone().unwrap_or_else(|| "The buffer".to_owned());
+
+            self.status.warn(format!("{name} isn't a file git tracks."));
+        }
+
+        self.git_status.as_ref()
+    }
+    fn stage_hunk(&mut self) {
+        let y = self.cursor_position.y;
+        let lines = self.document.lines(0, self.document.len());
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+
+        let Some(status) = self.current_git_status() else {
+            return;
+        };
+
+        let Some(hunk) = status.hunk_at(y) else {
+            self.status.warn("No unstaged change on this line.");
+            return;
+        };
+
+        let result = write_index(&status.file_name, &status.staged_with(hunk, &lines));
+
+        self.report_index_write(result, "Staged");
+    }
+    fn unstage_hunk(&mut self) {
+        let y = self.cursor_position.y;
+
+        let Some(status) = self.current_git_status() else {
+            return;
+        };
+
+        let Some(hunk) = status.staged_hunk_at(y) else {
+            self.status.warn("No staged change on this line.");
+            return;
+        };
+
+        let result = write_index(&status.file_name, &status.unstaged_with(hunk));
+
+        self.report_index_write(result, "Unstaged");
+    }
+    fn report_index_write(&mut self, result: Result<()>, done: &str) {
+        match result {
+            Ok(()) => self.status.info(format!("{done} the hunk.")),
+            Err(error) => self.status.error(format!("Error updating the git index: {error:#}")),
+        }
+
+        self.refresh_git_status();
+    }
+    // Put the lines the index has back in place of the unstaged change under the cursor, as one undo step
+    fn revert_hunk(&mut self) {
+        if self.document.is_read_only() {
+            self.status.warn("The buffer is read-only.");
+            return;
+        }
+
+        let y = self.cursor_position.y;
+
+        let Some(status) = self.current_git_status() else {
+            return;
+        };
+
+        let Some(hunk) = status.hunk_at(y) else {
+            self.status.warn("No unstaged change on this line.");
+            return;
+        };
+
+        let lines = status.index_lines(hunk).to_vec();
+
+        if hunk.start < hunk.end {
+            self.document.replace_lines(hunk.start, hunk.end - 1, &lines);
+        } else if hunk.start < self.document.len() {
+            self.document.insert_str(&Position { x: 0, y: hunk.start }, &format!("{}\n", lines.join("\n")));
+        } else {
+            let end = Position { x: self.document.row(hunk.start - 1).map_or(0, Row::len), y: hunk.start - 1 };
+
+            self.document.insert_str(&end, &format!("\n{}", lines.join("\n")));
+        }
+
+        self.cursor_position = clamp_position(&self.document, &Position { x: 0, y: hunk.start });
+        self.refresh_git_status();
+        self.status.info("Reverted the hunk.");
+    }
+    // Update the parts of the status bar that change by themselves, returns whether any did
+    fn refresh_status_bar(&mut self) -> bool {
+        let layout = &self.config.status_bar;
+        let branch = layout
+            .contains(Segment::GitBranch)
+            .then(|| git_branch(&std::env::current_dir().unwrap_or_default()))
+            .flatten();
+        let clock = if layout.contains(Segment::Clock) { clock_text() } else { String::new() };
+        let changed = branch != self.git_branch || clock != self.clock;
+
+        self.git_branch = branch;
+        self.clock = clock;
+
+        changed
+    }
+    // Reload buffers whose files changed on disk, asking first when the shown one has unsaved changes.
+    // Returns whether anything changed
+    fn check_disk_changes(&mut self) -> bool {
+        let Some(watcher) = self.disk_watcher.as_mut() else {
+            return false;
+        };
+
+        let files = iter::once(&self.document)
+            .chain(self.buffers.iter().map(|buffer| &buffer.document))
+            .filter_map(|document| document.file_name.clone())
+            .filter(|file_name| !is_remote(file_name) && !is_url(file_name) && split_member(file_name).is_none())
+            .collect();
+
+        watcher.watch(files);
+
+        let paths = watcher.changed();
+
+        if paths.is_empty() {
+            return false;
+        }
+
+        // Saving here is reported too, the modification time tells those apart
+        let touched = |document: &Document| document.file_name.as_deref().and_then(canonical).filter(|path| paths.contains(path));
+        let mut changed = false;
+
+        for buffer in &mut self.buffers {
+            let Some(path) = touched(&buffer.document).filter(|_| buffer.document.changed_on_disk()) else {
+                continue;
+            };
+
+            // Hidden buffers with unsaved changes are left alone until they're shown again
+            if buffer.document.is_dirty() {
+                watcher.defer(path);
+                continue;
+            }
+
+            let name = buffer.document.file_name.clone().unwrap_or_default();
+
+            if let Err(error) = buffer.document.reload() {
+                buffer.document.ignore_disk_change();
+                self.status.error(format!("Error reloading {name}: {error}"));
+            } else {
+                buffer.cursor_position = clamp_position(&buffer.document, &buffer.cursor_position);
+                self.status.toast(format!("Reloaded {name}, it changed on disk."), Severity::Info);
+            }
+
+            changed = true;
+        }
+
+        if touched(&self.document).is_some() && self.document.changed_on_disk() {
+            if self.document.is_dirty() {
+                self.start_pending_key(PendingKey::ReloadChanged);
+            } else {
+                self.reload_document();
             }
 
-            self.document.file_name = new_name;
+            changed = true;
         }
 
-        if self.document.write_out().is_ok() {
-            self.status_message = StatusMessage::from("File saves successfully.".to_owned());
+        changed
+    }
+    fn reload_document(&mut self) {
+        let name = self.document.file_name.clone().unwrap_or_default();
+
+        match self.document.reload() {
+            Ok(()) => {
+                self.selection_anchor = None;
+                self.cursor_position = clamp_position(&self.document, &self.cursor_position);
+                self.status.toast(format!("Reloaded {name}, it changed on disk."), Severity::Info);
+            },
+            Err(error) => {
+                self.document.ignore_disk_change();
+                self.status.error(format!("Error reloading {name}: {error}"));
+            },
+        }
+    }
+    fn process_main_key(&mut self, pressed_key: KeyEvent) {
+        if let Some(action) = self.pending_key.take() {
+            self.insert_run = false;
+            self.cut_run = false;
+            self.process_pending_key(&action, pressed_key);
+            return;
+        }
+
+        // Terminals without a meta key send Esc before the key instead, the same as typing Esc then the key
+        let pressed_key = if std::mem::take(&mut self.meta_pending) {
+            KeyEvent::new(pressed_key.code, pressed_key.modifiers | KeyModifiers::ALT)
+        } else if pressed_key.code == KeyCode::Esc && pressed_key.modifiers.is_empty() {
+            self.meta_pending = true;
+            return;
         } else {
-            self.status_message = StatusMessage::from("Error writing file.".to_owned());
+            pressed_key
+        };
+
+        if (self.pager && self.process_pager_key(pressed_key)) || self.process_chord_key(pressed_key) {
+            return;
+        }
+
+        let action = Action::from_key(pressed_key, self.repeat_count.is_some());
+
+        if !action.is_some_and(|action| action.keeps_desired_column()) {
+            self.desired_column = None;
+        }
+
+        if let Some(action) = action {
+            self.execute(action);
+        } else {
+            self.insert_run = false;
+            self.cut_run = false;
+            self.repeat_count = None;
         }
     }
-    fn move_cursor(&mut self, key: KeyCode) {
-        let terminal_height = self.terminal_size.height as usize;
-        let Position { mut x, mut y} = self.cursor_position;
-        let height = self.document.len();
-        let mut width = self.document.row(y).map_or(0, Row::len);
+    // Keys like less's, returns whether one was pressed. Everything else still works like it does in
+    // the editor, edits being refused since the buffers are read-only
+    fn process_pager_key(&mut self, pressed_key: KeyEvent) -> bool {
+        if !(pressed_key.modifiers - KeyModifiers::SHIFT).is_empty() {
+            return false;
+        }
 
-        match key {
-            KeyCode::Up => {
-                y = y.saturating_sub(1);
+        let page = self.page_rows();
+
+        let action = match pressed_key.code {
+            KeyCode::Char(' ' | 'f') => Action::ScrollViewport(page),
+            KeyCode::Char('b') => Action::ScrollViewport(-page),
+            KeyCode::Char('j') | KeyCode::Enter => Action::ScrollViewport(1),
+            KeyCode::Char('k') => Action::ScrollViewport(-1),
+            KeyCode::Char('g' | '<') => Action::JumpToStart,
+            KeyCode::Char('G' | '>') => Action::JumpToEnd,
+            KeyCode::Char('/') => Action::Search,
+            KeyCode::Char('n') => Action::SearchAgain { forward: true },
+            KeyCode::Char('N') => Action::SearchAgain { forward: false },
+            KeyCode::Char('q') => Action::Quit,
+            _ => return false,
+        };
+
+        self.execute(action);
+
+        true
+    }
+    // Add the key to the chord being typed, returns whether a chord took it
+    fn process_chord_key(&mut self, pressed_key: KeyEvent) -> bool {
+        self.chord.push(key_from_event(pressed_key));
+
+        match self.config.keymap.lookup(&self.chord) {
+            Lookup::Prefix => {
+                self.chord_started.get_or_insert_with(Instant::now);
             },
-            KeyCode::Down => {
-                if y < height {
-                    y = y.saturating_add(1);
+            Lookup::Action(action) => {
+                self.cancel_chord();
+                self.desired_column = None;
+                self.execute(action);
+            },
+            Lookup::Unbound if self.chord.len() > 1 => {
+                let keys: Vec<String> = self.chord.iter().map(|key| key_name(*key)).collect();
+
+                self.cancel_chord();
+                self.repeat_count = None;
+                self.status.info(format!("{} isn't bound", keys.join(" ")));
+            },
+            Lookup::Unbound => {
+                self.cancel_chord();
+                return false;
+            },
+        }
+
+        true
+    }
+    fn cancel_chord(&mut self) {
+        self.chord.clear();
+        self.chord_started = None;
+    }
+    pub fn execute(&mut self, action: Action) {
+        tracing::debug!(?action, "action");
+
+        let continues_insert = std::mem::replace(&mut self.insert_run, false);
+        let continues_cut = std::mem::replace(&mut self.cut_run, false);
+        let repeat_count = self.repeat_count.take();
+        let register = self.register.take();
+
+        match action {
+            Action::MoveCursor(code) => {
+                self.selection_anchor = None;
+                self.move_cursor(code);
+            },
+            Action::Select(code) => {
+                if self.selection_anchor.is_none() {
+                    self.selection_anchor = Some(self.cursor_position.clone());
                 }
+
+                self.move_cursor(code);
             },
-            KeyCode::Left => {
-                if x > 0 {
-                    x -= 1;
-                } else if y > 0 {
-                    y -= 1;
+            Action::InsertChar('\n') if self.config.continue_comments => {
+                let leader = comment_leader(&self.document, &self.cursor_position).unwrap_or_default();
 
-                    if let Some(row) = self.document.row(y) {
-                        x = row.len();
-                    } else {
-                        x = 0;
-                    }
+                self.type_text(&format!("\n{leader}"), continues_insert);
+            },
+            Action::InsertChar(c) if self.overwrite && c != '\n' && self.selection_anchor.is_none() => {
+                let under_cursor = self.document.row(self.cursor_position.y).is_some_and(|row| self.cursor_position.x < row.len());
+
+                self.document.begin_transaction();
+
+                if under_cursor {
+                    self.document.delete(&self.cursor_position);
                 }
+
+                self.type_text(c.encode_utf8(&mut [0; 4]), continues_insert);
+                self.document.commit();
             },
-            KeyCode::Right => {
-                if x < width {
-                    x += 1;
-                } else if y < height {
-                    y += 1;
-                    x = 0;
+            Action::InsertChar(c) => {
+                self.type_text(c.encode_utf8(&mut [0; 4]), continues_insert);
+                self.electric_dedent();
+                self.hard_wrap();
+            },
+            Action::Indent if self.selection().is_some_and(|(start, end)| start.y != end.y) => self.indent_lines(),
+            Action::Indent => {
+                if !self.expand_snippet() && !self.next_tab_stop() {
+                    self.type_text(&self.indent_unit(), continues_insert);
                 }
             },
-            KeyCode::PageUp => {
-                y = if y > terminal_height {
-                    y.saturating_sub(terminal_height)
+            Action::Unindent => self.unindent_lines(),
+            Action::IndentLines => self.indent_lines(),
+            Action::ToggleComment => self.toggle_comment(),
+            Action::Justify => self.justify(),
+            Action::ToggleHardWrap => {
+                let hard_wrap = !self.hard_wraps();
+
+                self.hard_wrap = Some(hard_wrap);
+                self.status.info(if hard_wrap { "Hard wrap on" } else { "Hard wrap off" });
+            },
+            Action::ToggleSelectionAnchor => {
+                if self.selection_anchor.take().is_none() {
+                    self.selection_anchor = Some(self.cursor_position.clone());
+                    self.status.info("Mark set");
                 } else {
-                    0
+                    self.status.info("Mark unset");
                 }
             },
-            KeyCode::PageDown => {
-                y = if y.saturating_add(terminal_height) < height {
-                    y.saturating_add(terminal_height)
+            Action::Delete | Action::Backspace if self.selection_anchor.is_some() => {
+                self.delete_selection();
+            },
+            Action::Delete => {
+                self.document.delete(&self.cursor_position);
+                self.last_edit = Some(Edit::Delete);
+            },
+            Action::Backspace => {
+                self.backspace();
+                self.last_edit = Some(Edit::Backspace);
+            },
+            Action::Cut => self.cut(register, continues_cut),
+            Action::Copy => self.copy(register),
+            Action::Paste => self.paste_register(register),
+            Action::SelectRegister => self.start_pending_key(PendingKey::SelectRegister),
+            Action::OpenPasteHistory => self.open_paste_history(),
+            Action::Complete => self.open_completion(),
+            Action::Undo => self.undo(),
+            Action::Redo => self.redo(),
+            Action::RepeatLastEdit => self.repeat_last_edit(repeat_count.unwrap_or(1)),
+            Action::CountDigit(digit) => self.add_count_digit(repeat_count, digit),
+            Action::Save => self.write_out(),
+            Action::Quit | Action::CloseBuffer => {
+                self.keep_editor_open = matches!(action, Action::CloseBuffer);
+
+                if self.document.is_dirty() {
+                    self.current_screen = CurrentScreen::ConfirmQuit;
                 } else {
-                    height
+                    self.close_buffer();
                 }
-            }
-            KeyCode::Home => x = 0,
-            KeyCode::End => x = width,
-            _ => ()
+            },
+            Action::Suspend => self.suspend = true,
+            Action::Verbatim => self.start_pending_key(PendingKey::Verbatim),
+            Action::TextObject => self.start_pending_key(PendingKey::TextObject { keys: Vec::new(), register }),
+            action => self.run_command(action),
         }
+    }
+    // Actions that move around or open something rather than edit
+    fn run_command(&mut self, action: Action) {
+        match action {
+            Action::SwitchBuffer { forward } => self.switch_buffer(forward),
+            Action::OpenBufferList => self.open_buffer_list(),
+            Action::OpenArchiveList => self.open_archive_list(),
+            Action::ToggleSpellCheck => {
+                let spell_check = !self.spell_checks();
 
-        width = self.document.row(y).map_or(0, Row::len);
-        
-        if x > width {
-            x = width;
+                self.spell_check = Some(spell_check);
+                self.status.info(if spell_check { "Spell check on" } else { "Spell check off" });
+            },
+            Action::SpellSuggest => self.open_spell_suggestions(),
+            Action::SpellAddWord => self.spell_add_word(),
+            Action::SpellIgnoreWord => self.spell_ignore_word(),
+            Action::NewBuffer => {
+                self.bring_up_buffer(Buffer::from(Document::default()));
+                self.status.info("New buffer");
+            },
+            Action::RenameFile => self.open_rename_file(),
+            Action::InspectChar => self.inspect_char(),
+            Action::Evaluate => self.evaluate(),
+            Action::InsertTimestamp => self.open_timestamp_picker(),
+            Action::ToggleFold => self.toggle_fold(),
+            Action::FoldAll => {
+                for (first, last) in outer_folds(&self.document) {
+                    self.document.folds_mut().close(first, last);
+                }
+            },
+            Action::UnfoldAll => self.document.folds_mut().open_all(),
+            Action::InsertUnicode => {
+                self.prompt.clear();
+                self.current_screen = CurrentScreen::InsertUnicode;
+            },
+            Action::SortLines { descending } => self.sort_lines(descending),
+            Action::DedupeLines => self.dedupe_lines(),
+            Action::SetMark => self.start_pending_key(PendingKey::SetMark),
+            Action::JumpToMark => self.start_pending_key(PendingKey::JumpToMark),
+            Action::JumpBack => self.jump_back(),
+            Action::JumpForward => self.jump_forward(),
+            Action::JumpToStart => self.jump_to(Position::default()),
+            Action::JumpToEnd => self.jump_to_end(),
+            Action::GotoLine => self.open_goto_line(),
+            Action::Search => self.open_search(CurrentScreen::Search, false),
+            Action::SearchAgain { forward } => self.search_again(forward),
+            Action::Replace => self.open_search(CurrentScreen::Replace, false),
+            Action::ReplaceInFiles => self.open_search(CurrentScreen::Replace, true),
+            Action::GotoConflict { forward } => self.goto_conflict(forward),
+            Action::ResolveConflict => self.start_pending_key(PendingKey::ResolveConflict),
+            Action::ScrollViewport(lines) => self.scroll_viewport(lines),
+            Action::ScrollHalfPage { down } => self.scroll_half_page(down),
+            Action::CenterCursorLine => self.center_cursor_line(),
+            Action::ToggleColumnMode => self.toggle_column_mode(),
+            // Outside column mode these move by character like the plain arrow keys
+            Action::NextCell if self.column_layout.is_some() => self.next_cell(),
+            Action::PreviousCell if self.column_layout.is_some() => self.previous_cell(),
+            Action::NextCell => self.execute(Action::MoveCursor(KeyCode::Right)),
+            Action::PreviousCell => self.execute(Action::MoveCursor(KeyCode::Left)),
+            Action::OpenDiff => self.open_diff(),
+            Action::OpenMessages => self.open_messages(),
+            Action::OpenUndoTree => self.open_undo_tree(),
+            Action::ExportHtml => self.open_export_html(),
+            Action::SaveEncoding => {
+                self.prompt.set(self.document.encoding().name());
+                self.current_screen = CurrentScreen::SaveEncoding;
+            },
+            Action::ToggleFileTree => self.toggle_file_tree(),
+            Action::Build => self.start_build(),
+            Action::ToggleQuickfix => self.toggle_quickfix(),
+            Action::QuickfixStep { forward } => self.quickfix_step(forward),
+            Action::SearchInFiles => self.open_search(CurrentScreen::Search, true),
+            Action::GotoDefinition => self.goto_definition(),
+            Action::GenerateTags => self.generate_tags(),
+            Action::StageHunk => self.stage_hunk(),
+            Action::UnstageHunk => self.unstage_hunk(),
+            Action::RevertHunk => self.revert_hunk(),
+            Action::ToggleTheme => self.toggle_theme(),
+            Action::ToggleOverwrite => {
+                self.overwrite = !self.overwrite;
+                self.status.info(if self.overwrite { "Overwriting" } else { "Inserting" });
+            },
+            // Editing actions are run by `execute`
+            _ => (),
         }
+    }
+    fn toggle_theme(&mut self) {
+        self.background = self.background.toggled();
+        self.theme = self.config.theme(self.background);
 
-        self.cursor_position = Position { x, y }
+        let name = match self.background {
+            Background::Dark => "dark",
+            Background::Light => "light",
+        };
+
+        self.status.info(format!("Switched to the {name} theme."));
     }
-    fn scroll(&mut self) {
-        let Position { x, y } = self.cursor_position;
-        let width = self.terminal_size.width as usize;
-        let height = self.terminal_size.height as usize;
-        let offset = &mut self.offset;
+    fn start_pending_key(&mut self, action: PendingKey) {
+        let prompt = match &action {
+            PendingKey::SetMark => "Set mark (a-z, 0-9): ",
+            PendingKey::JumpToMark => "Jump to mark (a-z, 0-9): ",
+            PendingKey::ResolveConflict => "Keep (o)urs, (t)heirs or (b)oth: ",
+            PendingKey::PrivilegedSave => "Permission denied. Save with elevated privileges? (y/n): ",
+            PendingKey::ReloadChanged => "The file changed on disk. (r)eload it or (k)eep your changes: ",
+            PendingKey::LoadLargeFile => "Load all of the file for editing? (y/n): ",
+            PendingKey::OpenLocked => "Open the file read-only? (y/n): ",
+            PendingKey::SelectRegister => "Register (a-z, A-Z to append): ",
+            PendingKey::Verbatim => "Verbatim input: ",
+            PendingKey::TextObject { keys, .. } => match keys.len() {
+                0 => "(d)elete, (c)hange, (y)ank or (v) select: ",
+                1 => "(i)nner or (a)round: ",
+                _ => "Object (w, \", ', `, (, [, {, <): ",
+            },
+        };
 
-        if y < offset.y {
-            offset.y = y;
-        } else if y >= offset.y.saturating_add(height) {
-            offset.y = y.saturating_sub(height).saturating_add(1);
-        }
+        self.pending_key = Some(action);
+        self.status.info(prompt);
+    }
+    fn process_pending_key(&mut self, action: &PendingKey, pressed_key: KeyEvent) {
+        if matches!(action, PendingKey::PrivilegedSave) {
+            if matches!(pressed_key.code, KeyCode::Char('y' | 'Y')) {
+                self.privileged_save = true;
+            } else {
+                self.quit_after_save = false;
+                self.status.info("Cancelled");
+            }
 
-        if x < offset.x {
-            offset.x = x;
-        } else if x >= offset.x.saturating_add(width) {
-            offset.x = x.saturating_sub(width).saturating_add(1);
+            return;
         }
-    }
-    fn prompt<C>(&mut self, prompt: &str, mut callback: C) -> Result<Option<String>> where C: FnMut(&mut Self, KeyEvent, &String) {
-        let mut result = String::new();
-        
-        loop {
-            self.status_message = StatusMessage::from(format!("{prompt}{result}"));
-            
-            if let Event::Key(pressed_key) = event::read()? {
-                match (pressed_key.modifiers, pressed_key.code) {
-                    (_, KeyCode::Backspace) => result.truncate(result.len().saturating_sub(1)),
-                    (_, KeyCode::Enter) => break,
-                    (_, KeyCode::Char(c)) => result.push(c),
-                    (_, KeyCode::Esc) => {
-                        result.truncate(0);
-                        break;
-                    },
-                    _ => ()
-                }
-                
-                callback(self, pressed_key, &result);
+
+        if matches!(action, PendingKey::LoadLargeFile) {
+            if matches!(pressed_key.code, KeyCode::Char('y' | 'Y')) {
+                match self.document.load_all() {
+                    Ok(()) => self.status.info(format!("Read {} lines.", self.document.len())),
+                    Err(error) => self.status.error(format!("{error}, so the file stays read-only.")),
+                }
+            } else {
+                self.status.info("The file stays read-only.");
             }
+
+            return;
         }
-        
-        self.status_message = StatusMessage::from(String::new());
 
-        self.current_screen = CurrentScreen::Main;
-        
-        if result.is_empty() {
-            return  Ok(None);
+        if matches!(action, PendingKey::OpenLocked) {
+            if matches!(pressed_key.code, KeyCode::Char('y' | 'Y')) {
+                self.document.set_read_only(true);
+                self.status.info("Opened read-only.");
+            } else {
+                self.status.warn("Editing anyway, saving may overwrite the other session's changes.");
+            }
+
+            return;
         }
-        
-        Ok(Some(result))
-    }
-}
 
-impl Default for App {
-    fn default() -> Self {
-        let args: Vec<String> = env::args().collect();
-        let mut initial_status = String::from("Welcome to IronN.");
-
-        let document = args.get(1).map_or_else(Doc::default, |file_name| {
-            #[allow(clippy::option_if_let_else)]
-            match Doc::open(file_name) { 
-                Ok(doc) => {
-                    initial_status = format!("Read {} lines.", doc.len());
-                    doc
-                },
-                Err(_) => Doc::default(),
+        if matches!(action, PendingKey::ReloadChanged) {
+            if matches!(pressed_key.code, KeyCode::Char('r' | 'R')) {
+                self.reload_document();
+            } else {
+                self.document.ignore_disk_change();
+                self.status.info("Kept your changes, saving will overwrite the file on disk.");
             }
-        });
 
-        Self {
-            current_screen: CurrentScreen::Main,
-            cursor_position: Position::default(),
+            return;
+        }
+
+        if matches!(action, PendingKey::Verbatim) {
+            match verbatim_char(pressed_key) {
+                Some(c) => self.type_text(c.encode_utf8(&mut [0; 4]), false),
+                None => self.status.warn(format!("{} can't be inserted verbatim.", key_name(key_from_event(pressed_key)))),
+            }
+
+            return;
+        }
+
+        let KeyCode::Char(name) = pressed_key.code else {
+            self.status.info("Cancelled");
+            return;
+        };
+
+        if let PendingKey::TextObject { keys, register } = action {
+            self.process_text_object_key(keys, *register, name);
+            return;
+        }
+
+        if matches!(action, PendingKey::SelectRegister) {
+            if name.is_ascii_alphabetic() {
+                self.register = Some(name);
+                self.status.info(format!("Register '{name}' selected."));
+            } else {
+                self.status.warn(format!("Invalid register name '{name}'."));
+            }
+
+            return;
+        }
+
+        if matches!(action, PendingKey::ResolveConflict) {
+            match name {
+                'o' => self.resolve_conflict(&Resolution::Ours),
+                't' => self.resolve_conflict(&Resolution::Theirs),
+                'b' => self.resolve_conflict(&Resolution::Both),
+                _ => self.status.info("Cancelled"),
+            }
+
+            return;
+        }
+
+        if !name.is_ascii_alphanumeric() {
+            self.status.warn(format!("Invalid mark name '{name}'."));
+            return;
+        }
+
+        match action {
+            PendingKey::SetMark => {
+                self.document.set_mark(name, self.cursor_position.clone());
+                self.status.info(format!("Mark '{name}' set."));
+            },
+            PendingKey::JumpToMark => {
+                if let Some(position) = self.document.mark(name).cloned() {
+                    self.jump_to(position);
+                    self.status.info(format!("Jumped to mark '{name}'."));
+                } else {
+                    self.status.warn(format!("Mark '{name}' isn't set."));
+                }
+            },
+            PendingKey::ResolveConflict
+            | PendingKey::PrivilegedSave
+            | PendingKey::ReloadChanged
+            | PendingKey::LoadLargeFile
+            | PendingKey::OpenLocked
+            | PendingKey::SelectRegister
+            | PendingKey::Verbatim
+            | PendingKey::TextObject { .. } => (),
+        }
+    }
+    // Collect the operator, `i` or `a`, and the object name, then run the operator on the object
+    fn process_text_object_key(&mut self, keys: &[char], register: Option<char>, c: char) {
+        let valid = match keys.len() {
+            0 => matches!(c, 'd' | 'c' | 'y' | 'v'),
+            1 => matches!(c, 'i' | 'a'),
+            _ => TextObject::from_char(c).is_some(),
+        };
+
+        if !valid {
+            self.status.info("Cancelled");
+            return;
+        }
+
+        let keys = [keys, &[c]].concat();
+
+        let [operator, scope, name] = keys[..] else {
+            self.start_pending_key(PendingKey::TextObject { keys, register });
+            return;
+        };
+
+        let object = TextObject::from_char(name).unwrap_or(TextObject::Word);
+        let Some((start, end)) = object.find(&self.document, &self.cursor_position, scope == 'i') else {
+            self.status.warn("No such text object at the cursor.");
+            return;
+        };
+
+        match operator {
+            'y' => {
+                self.kill_ring.store(register, self.document.text_range(&start, &end));
+                self.status.info("Copied to the clipboard.");
+            },
+            'v' => {
+                self.selection_anchor = Some(start);
+                self.cursor_position = end;
+            },
+            // Without a separate insert mode changing is deleting and typing the replacement
+            _ => {
+                let text = self.document.delete_range(&start, &end);
+
+                self.kill_ring.store(register, text);
+
+                self.selection_anchor = None;
+                self.cursor_position = start;
+            },
+        }
+    }
+    fn process_prompt_key(&mut self, pressed_key: KeyEvent) {
+        match (pressed_key.modifiers, pressed_key.code) {
+            (KeyModifiers::CONTROL, KeyCode::Char('c')) | (_, KeyCode::Esc) => self.cancel_prompt(),
+            (_, KeyCode::Enter) => {
+                self.remember_prompt();
+
+                match self.current_screen {
+                    CurrentScreen::GotoLine => self.confirm_goto_line(),
+                    CurrentScreen::ExportHtml => self.confirm_export_html(),
+                    CurrentScreen::SaveEncoding => self.confirm_save_encoding(),
+                    CurrentScreen::RenameFile => self.confirm_rename_file(),
+                    CurrentScreen::InsertUnicode => self.confirm_insert_unicode(),
+                    CurrentScreen::Search | CurrentScreen::Replace => self.confirm_search(),
+                    CurrentScreen::ReplaceWith if self.in_files => self.preview_file_changes(),
+                    CurrentScreen::ReplaceWith => self.replace_all(),
+                    _ => self.confirm_save(),
+                }
+            },
+            (_, KeyCode::Up) => self.prompt.history_previous(self.prompt_history.entries(self.prompt_kind())),
+            (_, KeyCode::Down) => self.prompt.history_next(self.prompt_history.entries(self.prompt_kind())),
+            (_, KeyCode::Tab) if matches!(self.current_screen, CurrentScreen::Saving | CurrentScreen::RenameFile | CurrentScreen::ExportHtml) => self.complete_path(),
+            // Tab goes through the encodings there are
+            (_, KeyCode::Tab) if matches!(self.current_screen, CurrentScreen::SaveEncoding) => {
+                let names = Encoding::NAMES.map(|(name, _)| name);
+                let next = names.iter().position(|name| *name == self.prompt.text()).map_or(0, |index| (index + 1) % names.len());
+
+                self.prompt.set(names[next]);
+            },
+            (KeyModifiers::ALT, KeyCode::Char('r')) if matches!(self.current_screen, CurrentScreen::Search | CurrentScreen::Replace) => {
+                self.search_regex = !self.search_regex;
+            },
+            (KeyModifiers::CONTROL, KeyCode::Char('u' | 'v')) => {
+                if let Some(text) = self.kill_ring.get(0).cloned() {
+                    self.prompt.insert_str(&text);
+                }
+            },
+            _ => {
+                self.prompt.process_key(pressed_key);
+            }
+        }
+    }
+    fn prompt_kind(&self) -> PromptKind {
+        match self.current_screen {
+            CurrentScreen::GotoLine => PromptKind::Line,
+            CurrentScreen::Search | CurrentScreen::Replace => PromptKind::Search,
+            CurrentScreen::ReplaceWith => PromptKind::Replace,
+            CurrentScreen::SaveEncoding => PromptKind::Encoding,
+            _ => PromptKind::File,
+        }
+    }
+    // Add the prompt's input to its history and write the history out so other sessions see it
+    fn remember_prompt(&mut self) {
+        self.prompt_history.add(self.prompt_kind(), self.prompt.text());
+
+        if let Some(path) = prompt_history_file() {
+            if let Err(error) = self.prompt_history.save(&path) {
+                tracing::warn!(%error, "writing prompt history failed");
+            }
+        }
+    }
+    fn process_confirm_quit_key(&mut self, pressed_key: KeyEvent) {
+        match pressed_key.code {
+            KeyCode::Char('s' | 'S' | 'y' | 'Y') => {
+                self.quit_after_save = true;
+
+                if self.document.file_name.is_none() {
+                    self.write_out();
+                } else {
+                    self.current_screen = CurrentScreen::Main;
+                    self.finish_save();
+                }
+            },
+            KeyCode::Char('d' | 'D' | 'n' | 'N') => self.close_buffer(),
+            KeyCode::Char('c' | 'C') | KeyCode::Esc => {
+                self.current_screen = CurrentScreen::Main;
+                self.status.info("Cancelled");
+            },
+            _ => ()
+        }
+    }
+    // Alt-Y goes on through the entries like it does in other editors' yank-pop
+    fn process_paste_history_key(&mut self, pressed_key: KeyEvent) {
+        match self.paste_history.handle_key(pressed_key, self.kill_ring.len(), self.page_height()) {
+            PopupKey::Chosen(index) => {
+                self.current_screen = CurrentScreen::Main;
+                self.paste(index);
+            },
+            PopupKey::Closed => self.current_screen = CurrentScreen::Main,
+            PopupKey::Other if (pressed_key.modifiers, pressed_key.code) == (KeyModifiers::ALT, KeyCode::Char('y')) => {
+                self.paste_history.index = self.paste_history.index
+                    .saturating_add(1)
+                    .checked_rem(self.kill_ring.len())
+                    .unwrap_or(0);
+            },
+            PopupKey::Handled | PopupKey::Other => (),
+        }
+    }
+    // Any key the popup doesn't use closes it and goes on to the buffer, so typing carries on
+    fn process_completion_key(&mut self, pressed_key: KeyEvent) {
+        match self.completion_list.handle_key(pressed_key, self.completions.len(), self.page_height()) {
+            PopupKey::Handled => (),
+            PopupKey::Closed => self.current_screen = CurrentScreen::Main,
+            PopupKey::Chosen(_) => {
+                self.current_screen = CurrentScreen::Main;
+                self.accept_completion();
+            },
+            PopupKey::Other if pressed_key.code == KeyCode::Tab => {
+                self.current_screen = CurrentScreen::Main;
+                self.accept_completion();
+            },
+            PopupKey::Other => {
+                self.current_screen = CurrentScreen::Main;
+                self.process_main_key(pressed_key);
+            },
+        }
+    }
+    fn process_diff_key(&mut self, pressed_key: KeyEvent) {
+        if let Some(scroll) = self.modal_scroll(self.diff_scroll, self.diff.len(), pressed_key.code) {
+            self.diff_scroll = scroll;
+        } else if is_close_key(pressed_key) {
+            self.current_screen = CurrentScreen::Main;
+            self.diff.clear();
+        }
+    }
+    fn process_messages_key(&mut self, pressed_key: KeyEvent) {
+        if let Some(scroll) = self.modal_scroll(self.messages_scroll, self.status.history().len(), pressed_key.code) {
+            self.messages_scroll = scroll;
+        } else if is_close_key(pressed_key) {
+            self.current_screen = CurrentScreen::Main;
+        }
+    }
+    fn process_undo_tree_key(&mut self, pressed_key: KeyEvent) {
+        let tree = self.document.history().tree();
+
+        if let Some(index) = self.modal_scroll(self.undo_tree_index, tree.len(), pressed_key.code) {
+            self.undo_tree_index = index;
+        } else if pressed_key.code == KeyCode::Enter {
+            self.current_screen = CurrentScreen::Main;
+            self.selection_anchor = None;
+
+            if let Some(position) = self.document.goto_undo_state(tree[self.undo_tree_index].0) {
+                self.cursor_position = clamp_position(&self.document, &position);
+            }
+        } else if is_close_key(pressed_key) {
+            self.current_screen = CurrentScreen::Main;
+        }
+    }
+    // Lines a full screen modal shows inside its border
+    fn page_height(&self) -> usize {
+        (self.terminal_size.height as usize).saturating_sub(2).max(1)
+    }
+    // New scroll position for a scrolling key in a full screen modal of `len` lines
+    fn modal_scroll(&self, scroll: usize, len: usize, key: KeyCode) -> Option<usize> {
+        let page = self.page_height();
+        let last = len.saturating_sub(1);
+
+        match key {
+            KeyCode::Up => Some(scroll.saturating_sub(1)),
+            KeyCode::Down => Some(scroll.saturating_add(1).min(last)),
+            KeyCode::PageUp => Some(scroll.saturating_sub(page)),
+            KeyCode::PageDown => Some(scroll.saturating_add(page).min(last)),
+            KeyCode::Home => Some(0),
+            KeyCode::End => Some(last),
+            _ => None,
+        }
+    }
+    pub fn show_gutter(&self) -> bool {
+        (self.config.mark_gutter && !self.document.marks().is_empty())
+            || !self.document.folds().is_empty()
+            || !self.buffer_diagnostics().is_empty()
+            || self.git_status.as_ref().is_some_and(|status| !status.hunks.is_empty() || !status.staged.is_empty())
+    }
+    // The document rows filling `count` screen rows from the top of the viewport, past folded ones
+    #[must_use]
+    pub fn visible_rows(&self, count: usize) -> Vec<usize> {
+        let folds = self.document.folds();
+        let mut y = folds.shown_row(self.offset.y);
+        let mut rows = Vec::new();
+
+        while rows.len() < count && y < self.document.len() {
+            rows.push(y);
+            y = folds.next_visible(y);
+        }
+
+        rows
+    }
+    // The screen row document row `y` is drawn on, None when it's above the viewport or folded away
+    #[must_use]
+    pub fn screen_row(&self, y: usize) -> Option<usize> {
+        let folds = self.document.folds();
+        let top = folds.shown_row(self.offset.y);
+
+        (y >= top && !folds.is_hidden(y)).then(|| y - top - folds.hidden_between(top, y))
+    }
+    // The document row drawn on screen row `row`
+    fn row_at_screen(&self, row: usize) -> usize {
+        let folds = self.document.folds();
+
+        folds.step(folds.shown_row(self.offset.y), row.try_into().unwrap_or(isize::MAX), self.document.len())
+    }
+    // Errors, warnings and notes from the quickfix list that are in the shown buffer. Each underlines the
+    // word at its column, or the one grapheme there when it isn't on a word
+    #[must_use]
+    pub fn buffer_diagnostics(&self) -> Vec<Diagnostic<'_>> {
+        let (Some(list), Some(file_name)) = (&self.quickfix, &self.document.file_name) else {
+            return Vec::new();
+        };
+
+        let path = Path::new(file_name);
+        let canonical = fs::canonicalize(path).ok();
+
+        list.items()
+            .iter()
+            .filter(|item| item.kind != ItemKind::Match)
+            // Comparing names first keeps most items from being looked up on disk each frame
+            .filter(|item| Path::new(&item.path).file_name() == path.file_name())
+            .filter(|item| canonical.is_some() && fs::canonicalize(&item.path).ok() == canonical)
+            .filter_map(|item| {
+                let y = item.line.checked_sub(1)?;
+                let start = item.column.saturating_sub(1).min(self.document.row(y)?.len());
+                let end = TextObject::Word
+                    .find(&self.document, &Position { x: start, y }, true)
+                    .filter(|(word_start, word_end)| word_start.x <= start && start < word_end.x)
+                    .map_or(start + 1, |(_, word_end)| word_end.x);
+
+                Some(Diagnostic { item, y, start, end })
+            })
+            .collect()
+    }
+    pub fn document(&self) -> &Document {
+        &self.document
+    }
+    pub fn cursor_position(&self) -> &Position {
+        &self.cursor_position
+    }
+    pub fn offset(&self) -> &Position {
+        &self.offset
+    }
+    // The first and last display columns in view, counted from 1, and the width of the widest row
+    // shown. None while every shown row fits, or in column mode where rows are laid out differently
+    pub fn shown_columns(&self) -> Option<(usize, usize, usize)> {
+        if self.column_layout.is_some() {
+            return None;
+        }
+
+        let width = self.terminal_size.width as usize;
+        let widest = self
+            .visible_rows(self.terminal_size.height as usize)
+            .into_iter()
+            .filter_map(|y| self.document.row(y))
+            .map(|row| row.width_to(row.len()))
+            .max()
+            .unwrap_or_default();
+
+        if self.offset.x == 0 && widest <= width {
+            return None;
+        }
+
+        Some((self.offset.x + 1, self.offset.x.saturating_add(width).min(widest.max(self.offset.x + 1)), widest))
+    }
+    // Display column of the cursor, which differs from `cursor_position.x` once wide graphemes are involved
+    pub fn cursor_column(&self) -> usize {
+        self.document.row(self.cursor_position.y).map_or(0, |row| {
+            self.column_layout.as_ref().map_or_else(
+                || row.width_to(self.cursor_position.x),
+                |layout| layout.width_to(row, self.cursor_position.x),
+            )
+        })
+    }
+    // Grapheme index on row `y` shown at display column `column`
+    fn index_at_column(&self, y: usize, column: usize) -> usize {
+        self.document.row(y).map_or(0, |row| {
+            self.column_layout.as_ref().map_or_else(
+                || row.index_at_width(column),
+                |layout| layout.index_at_width(row, column),
+            )
+        })
+    }
+    #[must_use]
+    pub fn column_layout(&self) -> Option<&ColumnLayout> {
+        self.column_layout.as_ref()
+    }
+    // Selection bounds ordered so that the first position comes before the second
+    pub fn selection(&self) -> Option<(Position, Position)> {
+        let anchor = self.selection_anchor.as_ref()?;
+        let cursor = &self.cursor_position;
+
+        if anchor <= cursor {
+            Some((anchor.clone(), cursor.clone()))
+        } else {
+            Some((cursor.clone(), anchor.clone()))
+        }
+    }
+    pub fn kill_ring(&self) -> &KillRing {
+        &self.kill_ring
+    }
+    pub fn paste_history_index(&self) -> usize {
+        self.paste_history.index
+    }
+    #[must_use]
+    pub fn completions(&self) -> &[Completion] {
+        &self.completions
+    }
+    #[must_use]
+    pub fn completion_index(&self) -> usize {
+        self.completion_list.index
+    }
+    #[must_use]
+    pub fn spell_suggestions(&self) -> &[String] {
+        &self.spell_suggestions
+    }
+    #[must_use]
+    pub fn spell_word(&self) -> Option<&Word> {
+        self.spell_word.as_ref().map(|(_, word)| word)
+    }
+    #[must_use]
+    pub fn spell_suggestion_index(&self) -> usize {
+        self.spell_list.index
+    }
+    #[must_use]
+    pub fn diff(&self) -> &[DiffLine] {
+        &self.diff
+    }
+    #[must_use]
+    pub fn diff_scroll(&self) -> usize {
+        self.diff_scroll
+    }
+    pub fn status(&self) -> &StatusQueue {
+        &self.status
+    }
+    #[must_use]
+    pub fn indent_guides(&self) -> bool {
+        self.config.indent_guides
+    }
+    #[must_use]
+    pub fn keep_editor_open(&self) -> bool {
+        self.keep_editor_open
+    }
+    pub fn messages_scroll(&self) -> usize {
+        self.messages_scroll
+    }
+    pub fn undo_tree_index(&self) -> usize {
+        self.undo_tree_index
+    }
+    pub fn prompt(&self) -> &PromptState {
+        &self.prompt
+    }
+    pub fn bounds(&self) -> Rect {
+        self.terminal_size
+    }
+    pub fn update_bounds(&mut self, rect: Rect) {
+        self.terminal_size = rect;
+    }
+    pub fn update_header_bounds(&mut self, rect: Rect) {
+        self.header_area = rect;
+    }
+    pub fn update_scrollbar_bounds(&mut self, rect: Rect) {
+        self.scrollbar_area = rect;
+    }
+    pub fn update_gutter_bounds(&mut self, rect: Rect) {
+        self.gutter_area = rect;
+    }
+    fn process_mouse(&mut self, mouse_event: MouseEvent) {
+        let position = layout::Position::new(mouse_event.column, mouse_event.row);
+
+        self.tooltip = None;
+
+        // Moving the cursor with a click starts a new cut
+        self.cut_run &= !matches!(mouse_event.kind, MouseEventKind::Down(_));
+
+        match mouse_event.kind {
+            MouseEventKind::Moved => self.hover_diagnostics(position),
+            MouseEventKind::Down(MouseButton::Left) if self.file_tree().is_some() && self.sidebar_area.contains(position) => {
+                self.click_file_tree(mouse_event.row);
+            },
+            MouseEventKind::ScrollUp | MouseEventKind::ScrollDown if self.file_tree().is_some() && self.sidebar_area.contains(position) => {
+                let height = self.sidebar_area.height as usize;
+
+                if let Some(tree) = &mut self.file_tree {
+                    tree.scroll_by(MOUSE_SCROLL_LINES, mouse_event.kind == MouseEventKind::ScrollDown, height);
+                }
+            },
+            MouseEventKind::Down(MouseButton::Left) => {
+                if matches!(self.current_screen, CurrentScreen::FileTree) {
+                    self.current_screen = CurrentScreen::Main;
+                }
+
+                if self.scrollbar_area.contains(position) {
+                    self.dragging_scrollbar = true;
+                    self.scroll_to_scrollbar_row(mouse_event.row);
+                } else if self.terminal_size.contains(position) || self.header_area.contains(position) {
+                    self.selection_anchor = None;
+                    self.move_cursor_to_cell(position);
+                }
+            },
+            MouseEventKind::Drag(MouseButton::Left) if self.dragging_scrollbar => {
+                self.scroll_to_scrollbar_row(mouse_event.row);
+            },
+            MouseEventKind::Drag(MouseButton::Left) => {
+                if self.selection_anchor.is_none() {
+                    self.selection_anchor = Some(self.cursor_position.clone());
+                }
+
+                let text_rows = self.terminal_size.y..self.terminal_size.bottom();
+
+                self.drag_scroll = (!text_rows.contains(&position.y) && self.column_layout.is_none()).then_some(position);
+                self.move_cursor_to_cell(position);
+            },
+            MouseEventKind::Down(MouseButton::Middle) if self.terminal_size.contains(position) => self.paste_primary(position),
+            MouseEventKind::Up(MouseButton::Left) => {
+                self.dragging_scrollbar = false;
+                self.drag_scroll = None;
+            },
+            MouseEventKind::ScrollUp | MouseEventKind::ScrollDown => {
+                let lines = MOUSE_SCROLL_LINES.try_into().unwrap_or(isize::MAX);
+                let lines = if mouse_event.kind == MouseEventKind::ScrollUp { -lines } else { lines };
+                let folds = self.document.folds();
+                let top = folds.step(folds.shown_row(self.offset.y), lines, self.document.len().saturating_sub(1));
+
+                self.offset.y = top.max(self.first_scrolled_row());
+            },
+            _ => ()
+        }
+    }
+    // Scroll by as many rows as the pointer is past the text and select up to the row at that edge
+    fn drag_scroll(&mut self) {
+        let Some(cell) = self.drag_scroll else {
+            return;
+        };
+
+        let top = self.terminal_size.y;
+        let bottom = self.terminal_size.bottom().saturating_sub(1);
+        let lines = i32::from(cell.y.saturating_sub(bottom)) - i32::from(top.saturating_sub(cell.y));
+
+        self.scroll_viewport(lines.try_into().unwrap_or_default());
+        self.move_cursor_to_cell(layout::Position::new(cell.x, cell.y.clamp(top, bottom)));
+    }
+    // Show the diagnostics under the mouse, when it's on the gutter marker of their line or the text they
+    // underline
+    fn hover_diagnostics(&mut self, cell: layout::Position) {
+        let on_gutter = self.gutter_area.contains(cell);
+
+        if !matches!(self.current_screen, CurrentScreen::Main) || self.column_layout.is_some() || !(on_gutter || self.terminal_size.contains(cell)) {
+            return;
+        }
+
+        let y = self.row_at_screen(cell.y.saturating_sub(self.terminal_size.y) as usize);
+        let column = self.offset.x.saturating_add(cell.x.saturating_sub(self.terminal_size.x) as usize);
+        let x = self.index_at_column(y, column);
+        let past_end = self.document.row(y).is_none_or(|row| column >= row.width_to(row.len()));
+
+        let items: Vec<QuickfixItem> = self
+            .buffer_diagnostics()
+            .into_iter()
+            .filter(|diagnostic| diagnostic.y == y)
+            .filter(|diagnostic| on_gutter || (!past_end && (diagnostic.start..diagnostic.end).contains(&x)))
+            .map(|diagnostic| diagnostic.item.clone())
+            .collect();
+
+        if !items.is_empty() {
+            self.tooltip = Some((cell, items));
+        }
+    }
+    // Clicking a directory opens or closes it, clicking a file opens it
+    fn click_file_tree(&mut self, row: u16) {
+        let Some(tree) = &mut self.file_tree else {
+            return;
+        };
+
+        let index = tree.offset().saturating_add(row.saturating_sub(self.sidebar_area.y) as usize);
+
+        if index >= tree.entries().len() {
+            return;
+        }
+
+        tree.select(index);
+        self.current_screen = CurrentScreen::FileTree;
+
+        if let Some(path) = tree.activate() {
+            self.open_path(&path);
+        }
+    }
+    // Map a row on the scrollbar track to the matching document offset
+    fn scroll_to_scrollbar_row(&mut self, row: u16) {
+        let track_height = self.scrollbar_area.height.saturating_sub(1) as usize;
+        let relative_row = row.saturating_sub(self.scrollbar_area.y) as usize;
+        let max_offset = self.document.len().saturating_sub(1);
+
+        self.offset.y = relative_row
+            .saturating_mul(max_offset)
+            .checked_div(track_height)
+            .unwrap_or(0)
+            .min(max_offset);
+        self.offset.y = self.document.folds().shown_row(self.offset.y);
+    }
+    fn move_cursor_to_cell(&mut self, cell: layout::Position) {
+        // The pinned header row sits just above the document rows
+        let y = if self.header_area.contains(cell) {
+            0
+        } else {
+            self.row_at_screen(cell.y.saturating_sub(self.terminal_size.y) as usize)
+        };
+        let column = self.offset.x.saturating_add(cell.x.saturating_sub(self.terminal_size.x) as usize);
+        let x = self.index_at_column(y, column);
+
+        self.cursor_position = Position { x, y };
+        self.desired_column = None;
+        self.scroll();
+    }
+    // Typed text is recorded as one growing insert run until another key interrupts it
+    fn type_text(&mut self, text: &str, continues_insert: bool) {
+        self.insert_text(text, true);
+
+        match &mut self.last_edit {
+            Some(Edit::Insert(run)) if continues_insert => run.push_str(text),
+            _ => self.last_edit = Some(Edit::Insert(text.to_owned())),
+        }
+
+        self.insert_run = true;
+    }
+    // Replace the snippet trigger right before the cursor with the snippet, returns whether there was one
+    fn expand_snippet(&mut self) -> bool {
+        if self.selection_anchor.is_some() {
+            return false;
+        }
+
+        let Some(row) = self.document.row(self.cursor_position.y) else {
+            return false;
+        };
+
+        let before: Vec<&str> = row.as_str().graphemes(true).take(self.cursor_position.x).collect();
+        let length = before.iter().rev().take_while(|grapheme| !grapheme.chars().all(char::is_whitespace)).count();
+        let trigger = before[before.len() - length..].concat();
+        let indent = leading_whitespace(row.as_str()).to_owned();
+
+        let Some(snippet) = self.snippets.get(&self.document.file_type.name(), &trigger).filter(|_| length > 0) else {
+            return false;
+        };
+
+        let expansion = snippet.expand(&indent);
+        let start = Position { x: self.cursor_position.x - length, y: self.cursor_position.y };
+
+        // Stops are relative to the start of the snippet, only those on its first line are offset by its column
+        let at_start = |position: Position| Position {
+            x: if position.y == 0 { start.x + position.x } else { position.x },
+            y: start.y + position.y,
+        };
+
+        self.document.begin_transaction();
+        self.document.delete_range(&start, &self.cursor_position);
+        self.document.insert_str(&start, &expansion.text);
+        self.document.commit();
+        self.document.set_tab_stops(expansion.stops.into_iter().map(|(first, last)| (at_start(first), at_start(last))).collect());
+        self.last_edit = None;
+
+        self.next_tab_stop()
+    }
+    // Select the next snippet placeholder so typing replaces its default, returns whether there was one
+    fn next_tab_stop(&mut self) -> bool {
+        let Some((start, end)) = self.document.next_tab_stop() else {
+            return false;
+        };
+
+        self.selection_anchor = (start != end).then_some(start);
+        self.cursor_position = end;
+        self.scroll();
+
+        true
+    }
+    // Move the words typed past the fill column onto a new line carrying on the comment or list item
+    fn hard_wrap(&mut self) {
+        let y = self.cursor_position.y;
+        let leader = self.document.file_type.line_comment();
+
+        let Some(row) = self.document.row(y).filter(|_| self.hard_wraps()) else {
+            return;
+        };
+
+        let Some((start, end)) = break_point(row.as_str(), leader, self.fill_column()) else {
+            return;
+        };
+
+        let prefix = continuation_prefix(row.as_str(), leader);
+        let (start, end) = (Position { x: row.grapheme_index(start), y }, Position { x: row.grapheme_index(end), y });
+
+        self.document.begin_transaction();
+        self.document.delete_range(&start, &end);
+        self.document.insert_str(&start, &format!("\n{prefix}"));
+        self.document.commit();
+
+        // The prefix is spaces and ASCII comment leaders, so its length in bytes is its length in graphemes
+        if self.cursor_position.x >= end.x {
+            self.cursor_position = Position { x: prefix.len() + self.cursor_position.x - end.x, y: y + 1 };
+        } else {
+            self.cursor_position.x = self.cursor_position.x.min(start.x);
+        }
+    }
+    // The buffer's modeline can set its own
+    fn fill_column(&self) -> usize {
+        self.document.modeline.fill_column.unwrap_or(self.config.fill_column)
+    }
+    // What Tab and indenting lines put in, a tab unless the modeline asks for spaces
+    fn indent_unit(&self) -> String {
+        if self.document.modeline.expand_tab == Some(true) { " ".repeat(self.indent_width()) } else { "\t".to_owned() }
+    }
+    // Columns an indentation level of spaces takes
+    #[must_use]
+    pub fn indent_width(&self) -> usize {
+        self.document.modeline.indent_width.unwrap_or(TAB_WIDTH)
+    }
+    // Set by the config for the file type until it's toggled
+    fn hard_wraps(&self) -> bool {
+        self.hard_wrap.unwrap_or_else(|| {
+            let name = self.document.file_type.name();
+
+            self.config.hard_wrap.iter().any(|hard_wrapped| hard_wrapped.eq_ignore_ascii_case(&name))
+        })
+    }
+    // Set by the config for the file type until it's toggled, like hard wrap
+    #[must_use]
+    pub fn spell_checks(&self) -> bool {
+        self.spell_check.unwrap_or_else(|| {
+            let name = self.document.file_type.name();
+
+            self.config.spell_check.iter().any(|checked| checked.eq_ignore_ascii_case(&name))
+        })
+    }
+    // The dictionary is only read once, a missing or unreadable one leaves nothing flagged
+    fn load_spell_checker(&mut self) -> Option<&mut SpellChecker> {
+        if self.spell.is_none() {
+            let spell = SpellChecker::load(&self.config.dictionary, user_dictionary()).unwrap_or_else(|error| {
+                self.status.error(format!("Error reading the dictionary: {error:#}"));
+                SpellChecker::default()
+            });
+
+            self.spell = Some(spell);
+        }
+
+        self.spell.as_mut()
+    }
+    // Words on row `y` that aren't in the dictionary, leaving out the comments git strips from commit messages
+    #[must_use]
+    pub fn misspelled_words(&self, y: usize) -> Vec<Word> {
+        let (Some(spell), Some(row)) = (self.spell.as_ref().filter(|_| self.spell_checks()), self.document.row(y)) else {
+            return Vec::new();
+        };
+
+        if self.document.file_type.is_commit_message() && row.as_str().starts_with('#') {
+            return Vec::new();
+        }
+
+        spell.misspelled(row.as_str())
+    }
+    // The misspelled word the cursor is on or just after
+    fn misspelled_at_cursor(&mut self) -> Option<(usize, Word)> {
+        let Position { x, y } = self.cursor_position;
+        let dictionary = self.config.dictionary.clone();
+
+        if self.load_spell_checker().is_some_and(|spell| spell.is_empty()) {
+            self.status.warn(format!("No words in the dictionary {}.", dictionary.display()));
+            return None;
+        }
+
+        let word = self.misspelled_words(y).into_iter().find(|word| word.start <= x && x <= word.end);
+
+        if word.is_none() {
+            self.status.info("No misspelled word at the cursor.");
+        }
+
+        word.map(|word| (y, word))
+    }
+    fn open_spell_suggestions(&mut self) {
+        let Some((y, word)) = self.misspelled_at_cursor() else {
+            return;
+        };
+
+        self.spell_suggestions = self.spell.as_ref().map(|spell| spell.suggestions(&word.text)).unwrap_or_default();
+        self.spell_word = Some((y, word));
+        self.spell_list = PopupList::wrapping();
+        self.current_screen = CurrentScreen::SpellSuggestions;
+    }
+    // Corrections first, then adding the word to the dictionary and ignoring it
+    fn process_spell_suggestions_key(&mut self, pressed_key: KeyEvent) {
+        let len = self.spell_suggestions.len() + 2;
+
+        match self.spell_list.handle_key(pressed_key, len, self.page_height()) {
+            PopupKey::Handled | PopupKey::Other => (),
+            PopupKey::Closed => self.current_screen = CurrentScreen::Main,
+            PopupKey::Chosen(index) => {
+                self.current_screen = CurrentScreen::Main;
+
+                let Some((y, word)) = self.spell_word.take() else {
+                    return;
+                };
+
+                match index.checked_sub(self.spell_suggestions.len()) {
+                    Some(0) => self.add_to_dictionary(&word.text),
+                    Some(_) => self.ignore_word(&word.text),
+                    None => self.replace_word(y, &word, &self.spell_suggestions[index].clone()),
+                }
+            },
+        }
+    }
+    fn replace_word(&mut self, y: usize, word: &Word, replacement: &str) {
+        if self.document.is_read_only() {
+            self.status.warn("The buffer is read-only.");
+            return;
+        }
+
+        let start = Position { x: word.start, y };
+
+        self.document.begin_transaction();
+        self.document.delete_range(&start, &Position { x: word.end, y });
+        self.cursor_position = self.document.insert_str(&start, replacement);
+        self.document.commit();
+    }
+    fn spell_add_word(&mut self) {
+        if let Some((_, word)) = self.misspelled_at_cursor() {
+            self.add_to_dictionary(&word.text);
+        }
+    }
+    fn spell_ignore_word(&mut self) {
+        if let Some((_, word)) = self.misspelled_at_cursor() {
+            self.ignore_word(&word.text);
+        }
+    }
+    fn add_to_dictionary(&mut self, word: &str) {
+        let Some(spell) = self.load_spell_checker() else {
+            return;
+        };
+
+        match spell.add(word) {
+            Ok(()) => self.status.info(format!("Added \"{word}\" to the dictionary.")),
+            Err(error) => self.status.error(format!("Error adding to the dictionary: {error:#}")),
+        }
+    }
+    fn ignore_word(&mut self, word: &str) {
+        if let Some(spell) = self.load_spell_checker() {
+            spell.ignore(word);
+            self.status.info(format!("Ignoring \"{word}\" until ironn closes."));
+        }
+    }
+    // Line up a closing brace or block word typed at the start of a line with the row that opened its block
+    fn electric_dedent(&mut self) {
+        if self.document.is_read_only() {
+            self.status.warn("The buffer is read-only.");
+            return;
+        }
+
+        let Some(indent) = closing_indent(&self.document, &self.cursor_position) else {
+            return;
+        };
+
+        let y = self.cursor_position.y;
+        let current = self.document.row(y).map_or(0, |row| leading_whitespace(row.as_str()).len());
+
+        if self.document.row(y).is_some_and(|row| leading_whitespace(row.as_str()) == indent) {
+            return;
+        }
+
+        // Indentation is spaces and tabs, so its length in bytes is also its length in graphemes
+        self.document.begin_transaction();
+        self.document.delete_range(&Position { x: 0, y }, &Position { x: current, y });
+        self.document.insert_str(&Position { x: 0, y }, &indent);
+        self.document.commit();
+        self.cursor_position.x = self.cursor_position.x - current + indent.len();
+    }
+    fn backspace(&mut self) {
+        if self.cursor_position.x > 0 || self.cursor_position.y > 0 {
+            self.move_cursor(KeyCode::Left);
+            self.document.delete(&self.cursor_position);
+        }
+    }
+    // Add a digit to the end of the repeat count being typed, which stops at `MAX_REPEAT_COUNT`
+    fn add_count_digit(&mut self, repeat_count: Option<usize>, digit: u32) {
+        let count = repeat_count
+            .unwrap_or(0)
+            .saturating_mul(10)
+            .saturating_add(digit as usize);
+
+        self.repeat_count = Some(count.min(MAX_REPEAT_COUNT));
+
+        if count > MAX_REPEAT_COUNT {
+            self.status.warn(format!("Repeat count: {MAX_REPEAT_COUNT}, the most it can be"));
+        } else {
+            self.status.info(format!("Repeat count: {count}"));
+        }
+    }
+    fn repeat_last_edit(&mut self, count: usize) {
+        let Some(edit) = self.last_edit.clone() else {
+            self.status.warn("Nothing to repeat.");
+            return;
+        };
+
+        self.document.begin_transaction();
+        self.delete_selection();
+
+        for _ in 0..count {
+            match &edit {
+                Edit::Insert(text) | Edit::Paste(text) => {
+                    self.cursor_position = self.document.insert_str(&self.cursor_position, text);
+                },
+                Edit::Delete => self.document.delete(&self.cursor_position),
+                Edit::Backspace => self.backspace(),
+                Edit::CutLine => {
+                    if let Some(text) = self.cut_line() {
+                        self.kill_ring.push(text);
+                    }
+                },
+            }
+        }
+
+        self.document.commit();
+    }
+    fn undo(&mut self) {
+        self.selection_anchor = None;
+
+        if let Some(position) = self.document.undo() {
+            self.cursor_position = position;
+        } else {
+            self.status.warn("Nothing to undo.");
+        }
+    }
+    fn redo(&mut self) {
+        self.selection_anchor = None;
+
+        if let Some(position) = self.document.redo() {
+            self.cursor_position = position;
+        } else {
+            self.status.warn("Nothing to redo.");
+        }
+    }
+    // First and last row touched by the selection, a selection ending at the start of a row excludes that row
+    fn selected_lines(&self) -> Option<(usize, usize)> {
+        let (start, end) = self.selection()?;
+        let last = if end.x == 0 && end.y > start.y { end.y - 1 } else { end.y };
+
+        if start.y >= self.document.len() {
+            return None;
+        }
+
+        Some((start.y, last.min(self.document.len() - 1)))
+    }
+    fn sort_lines(&mut self, descending: bool) {
+        let Some((start, end)) = self.selected_lines() else {
+            self.status.warn("Select the lines to sort first.");
+            return;
+        };
+
+        let mut lines = self.document.lines(start, end);
+
+        lines.sort();
+
+        if descending {
+            lines.reverse();
+        }
+
+        self.replace_selected_lines(start, end, &lines);
+        self.status.info(format!("Sorted {} lines.", lines.len()));
+    }
+    fn dedupe_lines(&mut self) {
+        let Some((start, end)) = self.selected_lines() else {
+            self.status.warn("Select the lines to dedupe first.");
+            return;
+        };
+
+        let mut lines = self.document.lines(start, end);
+        let original_len = lines.len();
+
+        lines.dedup();
+
+        self.replace_selected_lines(start, end, &lines);
+        self.status.info(format!("Removed {} duplicate lines.", original_len - lines.len()));
+    }
+    // Add a tab to the start of the selected lines, or the current line without a selection
+    fn indent_lines(&mut self) {
+        let (start, end) = self.selected_lines().unwrap_or((self.cursor_position.y, self.cursor_position.y));
+
+        if start >= self.document.len() {
+            return;
+        }
+
+        let original = self.document.lines(start, end);
+        let lines: Vec<String> = original
+            .iter()
+            .map(|line| if line.is_empty() { line.clone() } else { format!("{}{line}", self.indent_unit()) })
+            .collect();
+
+        self.replace_lines_at_cursor(start, end, &original, &lines);
+    }
+    // Remove one tab or up to an indentation level of spaces from the selected lines, or the current line
+    // without a selection
+    fn unindent_lines(&mut self) {
+        let (start, end) = self.selected_lines().unwrap_or((self.cursor_position.y, self.cursor_position.y));
+
+        if start >= self.document.len() {
+            return;
+        }
+
+        let original = self.document.lines(start, end);
+        let lines: Vec<String> = original
+            .iter()
+            .map(|line| {
+                if let Some(rest) = line.strip_prefix('\t') {
+                    return rest.to_owned();
+                }
+
+                let spaces = line.chars().take(self.indent_width()).take_while(|c| *c == ' ').count();
+
+                line[spaces..].to_owned()
+            })
+            .collect();
+
+        self.replace_lines_at_cursor(start, end, &original, &lines);
+    }
+    fn toggle_comment(&mut self) {
+        let Some(leader) = self.document.file_type.line_comment() else {
+            self.status.warn(format!("{} has no line comments.", self.document.file_type.name()));
+            return;
+        };
+
+        let (start, end) = self.selected_lines().unwrap_or((self.cursor_position.y, self.cursor_position.y));
+
+        if start >= self.document.len() {
+            return;
+        }
+
+        let original = self.document.lines(start, end);
+        let lines = toggle_line_comments(&original, leader);
+
+        self.replace_lines_at_cursor(start, end, &original, &lines);
+    }
+    // Rewrap the selected lines, or the paragraph the cursor is in, leaving the cursor at its end
+    fn justify(&mut self) {
+        let leader = self.document.file_type.line_comment();
+        let bounds = self.selected_lines().or_else(|| paragraph_bounds(&self.document, self.cursor_position.y, leader));
+
+        let Some((start, end)) = bounds.filter(|(start, _)| *start < self.document.len()) else {
+            self.status.info("Nothing to justify.");
+            return;
+        };
+
+        let original = self.document.lines(start, end);
+        let lines = justify(&original, leader, self.fill_column());
+
+        if lines != original {
+            self.document.replace_lines(start, end, &lines);
+        }
+
+        let last = start + lines.len().saturating_sub(1);
+
+        self.selection_anchor = None;
+        self.cursor_position = Position { x: self.document.row(last).map_or(0, Row::len), y: last };
+    }
+    // Swap in lines changed only by ASCII added or removed at their start, keeping them selected when
+    // they were and moving the cursor along with the text of its line when they weren't
+    fn replace_lines_at_cursor(&mut self, start: usize, end: usize, original: &[String], lines: &[String]) {
+        if lines == original {
+            return;
+        }
+
+        if self.selection_anchor.is_some() {
+            self.replace_selected_lines(start, end, lines);
+        } else {
+            // The change is ASCII, so the byte difference is the column shift
+            let x = self.cursor_position.x + lines[0].len();
+
+            self.document.replace_lines(start, end, lines);
+            self.cursor_position.x = x.saturating_sub(original[0].len());
+        }
+    }
+    // Swap in the new lines and keep them selected
+    fn replace_selected_lines(&mut self, start: usize, end: usize, lines: &[String]) {
+        self.document.replace_lines(start, end, lines);
+
+        let last = start + lines.len().saturating_sub(1);
+
+        self.selection_anchor = Some(Position { x: 0, y: start });
+        self.cursor_position = Position { x: self.document.row(last).map_or(0, Row::len), y: last };
+    }
+    // Insert at the cursor or into the prompt, typed text is undone a word at a time
+    fn insert_text(&mut self, text: &str, typed: bool) {
+        match self.current_screen {
+            CurrentScreen::Main if self.document.is_read_only() => {
+                self.status.warn("The buffer is read-only.");
+            },
+            CurrentScreen::Main if self.selection_anchor.is_some() => {
+                self.document.begin_transaction();
+                self.delete_selection();
+                self.cursor_position = self.document.insert_str(&self.cursor_position, text);
+                self.document.commit();
+            },
+            CurrentScreen::Main if typed => {
+                self.cursor_position = self.document.type_str(&self.cursor_position, text);
+            },
+            CurrentScreen::Main => {
+                self.cursor_position = self.document.insert_str(&self.cursor_position, text);
+            },
+            CurrentScreen::Saving | CurrentScreen::SaveEncoding | CurrentScreen::RenameFile | CurrentScreen::GotoLine | CurrentScreen::InsertUnicode | CurrentScreen::ExportHtml | CurrentScreen::Search | CurrentScreen::Replace | CurrentScreen::ReplaceWith => {
+                self.prompt.insert_str(text);
+            },
+            CurrentScreen::ConfirmQuit | CurrentScreen::PasteHistory | CurrentScreen::Diff | CurrentScreen::Messages | CurrentScreen::UndoTree | CurrentScreen::Completion | CurrentScreen::FileChanges | CurrentScreen::FileTree | CurrentScreen::BufferList | CurrentScreen::Quickfix | CurrentScreen::TagPicker | CurrentScreen::ArchiveList | CurrentScreen::SideBySide | CurrentScreen::SpellSuggestions | CurrentScreen::TimestampPicker => ()
+        }
+    }
+    // Returns whether there was a selection to delete
+    fn delete_selection(&mut self) -> bool {
+        let Some((start, end)) = self.selection() else {
+            return false;
+        };
+
+        self.selection_anchor = None;
+        self.document.delete_range(&start, &end);
+        self.cursor_position = start;
+
+        true
+    }
+    // Cut the selection, or the whole current line when nothing is selected
+    // Lines cut one after another without anything in between are pasted back together
+    fn cut(&mut self, register: Option<char>, continues_cut: bool) {
+        if self.document.is_read_only() {
+            self.status.warn("The buffer is read-only.");
+            return;
+        }
+
+        if let Some((start, end)) = self.selection() {
+            self.selection_anchor = None;
+            self.cursor_position = start.clone();
+
+            let text = self.document.delete_range(&start, &end);
+
+            self.kill_ring.store(register, text);
+            return;
+        }
+
+        self.last_edit = Some(Edit::CutLine);
+
+        let Some(text) = self.cut_line() else {
+            return;
+        };
+
+        if continues_cut {
+            self.kill_ring.append(register, &text);
+        } else {
+            self.kill_ring.store(register, text);
+        }
+
+        self.cut_run = true;
+    }
+    // None for a read-only buffer, which has nothing to cut and leaves the kill ring alone
+    fn cut_line(&mut self) -> Option<String> {
+        if self.document.is_read_only() {
+            return None;
+        }
+
+        let y = self.cursor_position.y;
+        let Some(row) = self.document.row(y) else {
+            return Some(String::new());
+        };
+
+        let start = Position { x: 0, y };
+        let end = if y.saturating_add(1) < self.document.len() {
+            Position { x: 0, y: y + 1 }
+        } else {
+            Position { x: row.len(), y }
+        };
+        let mut text = self.document.delete_range(&start, &end);
+
+        if !text.ends_with('\n') {
+            text.push('\n');
+        }
+
+        self.cursor_position = start;
+
+        Some(text)
+    }
+    fn copy(&mut self, register: Option<char>) {
+        let text = if let Some((start, end)) = self.selection() {
+            self.selection_anchor = None;
+            self.document.text_range(&start, &end)
+        } else if let Some(row) = self.document.row(self.cursor_position.y) {
+            format!("{}\n", row.as_str())
+        } else {
+            return;
+        };
+
+        self.kill_ring.store(register, text);
+        self.status.info("Copied to the clipboard.");
+    }
+    // Paste from the chosen register, or the newest clipboard entry
+    fn paste_register(&mut self, register: Option<char>) {
+        let Some(name) = register else {
+            self.paste(0);
+            return;
+        };
+
+        if let Some(text) = self.kill_ring.register(name).cloned() {
+            self.insert_pasted(text);
+        } else {
+            self.status.warn(format!("Register '{name}' is empty."));
+        }
+    }
+    fn paste(&mut self, index: usize) {
+        if let Some(text) = self.kill_ring.get(index).cloned() {
+            self.insert_pasted(text);
+        } else {
+            self.status.warn("The clipboard is empty.");
+        }
+    }
+    fn insert_pasted(&mut self, text: String) {
+        self.document.begin_transaction();
+        self.delete_selection();
+        self.cursor_position = self.document.insert_str(&self.cursor_position, &text);
+        self.document.commit();
+        self.last_edit = Some(Edit::Paste(text));
+    }
+    // Paste at the clicked cell like X11 does. A selection in the buffer is what was last selected,
+    // since the terminal doesn't see selections made with the mouse while the editor captures it
+    fn paste_primary(&mut self, cell: layout::Position) {
+        if !matches!(self.current_screen, CurrentScreen::Main) {
+            return;
+        }
+
+        if self.document.is_read_only() {
+            self.status.warn("The buffer is read-only.");
+            return;
+        }
+
+        let selected = self.selection().map(|(start, end)| self.document.text_range(&start, &end)).filter(|text| !text.is_empty());
+
+        let Some(text) = selected.or_else(primary_selection) else {
+            self.status.warn("Nothing is selected to paste.");
+            return;
+        };
+
+        self.selection_anchor = None;
+        self.move_cursor_to_cell(cell);
+        self.insert_pasted(text);
+    }
+    fn open_paste_history(&mut self) {
+        if self.kill_ring.is_empty() {
+            self.status.warn("The clipboard is empty.");
+            return;
+        }
+
+        self.paste_history = PopupList::default();
+        self.current_screen = CurrentScreen::PasteHistory;
+    }
+    // Snippet triggers come first, then words from this buffer and the other open ones, then ones
+    // from the dictionary for prose
+    fn open_completion(&mut self) {
+        let prefix = self.completion_prefix();
+
+        if prefix.is_empty() {
+            self.status.warn("No word before the cursor to complete.");
+            return;
+        }
+
+        let dictionary_words = self.dictionary_words(&prefix);
+        let file_type = self.document.file_type.name();
+        let documents = std::iter::once(&self.document).chain(self.buffers.iter().map(|buffer| &buffer.document));
+
+        self.completions = self.snippets
+            .triggers(&file_type, &prefix)
+            .into_iter()
+            .map(|trigger| Completion::Snippet(trigger.to_owned()))
+            .chain(buffer_words(documents, &prefix).into_iter().map(Completion::Word))
+            .collect();
+
+        for word in dictionary_words {
+            if !self.completions.iter().any(|completion| completion.text() == word) {
+                self.completions.push(Completion::Dictionary(word));
+            }
+        }
+
+        if self.completions.is_empty() {
+            self.status.info(format!("No completions for \"{prefix}\"."));
+            return;
+        }
+
+        self.completion_list = PopupList::wrapping();
+        self.current_screen = CurrentScreen::Completion;
+    }
+    fn accept_completion(&mut self) {
+        let Some(completion) = self.completions.get(self.completion_list.index).cloned() else {
+            return;
+        };
+
+        let prefix = self.completion_prefix();
+        let rest = completion.text().strip_prefix(prefix.as_str()).unwrap_or_default();
+
+        if !rest.is_empty() {
+            self.type_text(rest, false);
+        }
+
+        if matches!(completion, Completion::Snippet(_)) {
+            self.expand_snippet();
+        }
+
+        self.completions.clear();
+    }
+    // Completions for `prefix` from the dictionary, when the file type has them turned on
+    fn dictionary_words(&mut self, prefix: &str) -> Vec<String> {
+        let name = self.document.file_type.name();
+
+        if !self.config.dictionary_completion.iter().any(|completed| completed.eq_ignore_ascii_case(&name)) {
+            return Vec::new();
+        }
+
+        self.load_spell_checker().map(|spell| spell.completions(prefix, COMPLETION_LIMIT)).unwrap_or_default()
+    }
+    // The word characters right before the cursor
+    fn completion_prefix(&self) -> String {
+        let Some(row) = self.document.row(self.cursor_position.y) else {
+            return String::new();
+        };
+
+        let before: Vec<&str> = row.as_str().graphemes(true).take(self.cursor_position.x).collect();
+        let length = before.iter().rev().take_while(|grapheme| grapheme.chars().all(is_word_char)).count();
+
+        before[before.len() - length..].concat()
+    }
+    // Compare the buffer with the file on disk, so unsaved changes can be reviewed before writing
+    fn open_diff(&mut self) {
+        let Some(file_name) = self.document.file_name.clone() else {
+            self.status.warn("The buffer hasn't been saved yet.");
+            return;
+        };
+
+        let saved = match fs::read_to_string(&file_name) {
+            Ok(saved) => saved,
+            Err(error) => {
+                self.status.error(format!("Error reading file: {error}"));
+                return;
+            }
+        };
+
+        let saved: Vec<&str> = saved.lines().collect();
+        let current = self.document.lines(0, self.document.len());
+        let current: Vec<&str> = current.iter().map(String::as_str).collect();
+
+        self.diff = diff(&saved, &current);
+
+        if self.diff.is_empty() {
+            self.status.info("No unsaved changes.");
+            return;
+        }
+
+        self.diff_scroll = 0;
+        self.current_screen = CurrentScreen::Diff;
+    }
+    // Review the status messages, newest first
+    fn open_messages(&mut self) {
+        self.messages_scroll = 0;
+        self.current_screen = CurrentScreen::Messages;
+    }
+    // Browse every state the buffer has been in, starting at the current one
+    fn open_undo_tree(&mut self) {
+        let history = self.document.history();
+
+        self.undo_tree_index = history
+            .tree()
+            .iter()
+            .position(|(node, _)| *node == history.current())
+            .unwrap_or(0);
+        self.current_screen = CurrentScreen::UndoTree;
+    }
+    fn write_out(&mut self) {
+        let file_name = self.document.file_name.clone().unwrap_or_default();
+
+        self.prompt.set(&file_name);
+        self.current_screen = CurrentScreen::Saving;
+    }
+    fn save(&mut self) -> bool {
+        match self.document.write_out() {
+            Ok(()) => {
+                let name = self.document.file_name.clone().unwrap_or_default();
+
+                self.status.info(format!("Wrote {} lines.", self.document.len()));
+
+                self.status.toast(format!("Saved {name}"), Severity::Info);
+                self.save_undo();
+                self.save_folds();
+                true
+            },
+            Err(error) if error.downcast_ref::<io::Error>().is_some_and(|error| error.kind() == io::ErrorKind::PermissionDenied) => {
+                self.start_pending_key(PendingKey::PrivilegedSave);
+                false
+            },
+            Err(error) => {
+                self.status.error(format!("Error writing file: {error}"));
+                false
+            }
+        }
+    }
+    fn save_undo(&mut self) {
+        let Some(dir) = undo_dir().filter(|_| self.config.undo_file) else {
+            return;
+        };
+
+        if let Err(error) = self.document.save_undo(&dir) {
+            self.status.warn(format!("Error writing undo file: {error}"));
+        }
+    }
+    fn save_folds(&mut self) {
+        let Some(dir) = fold_dir() else {
+            return;
+        };
+
+        if let Err(error) = self.document.save_folds(&dir) {
+            self.status.warn(format!("Error writing fold file: {error}"));
+        }
+    }
+    fn confirm_save(&mut self) {
+        if self.prompt.is_empty() {
+            self.cancel_prompt();
+            return;
+        }
+
+        self.document.file_name = Some(self.prompt.text().to_owned());
+        self.current_screen = CurrentScreen::Main;
+        self.finish_save();
+    }
+    // Save, then close the buffer if that's what the save was for
+    fn finish_save(&mut self) {
+        if self.save() && self.quit_after_save {
+            self.close_buffer();
+        }
+
+        // A save waiting on the privileged retry still closes the buffer once it's done
+        if self.pending_key.is_none() {
+            self.quit_after_save = false;
+        }
+    }
+    // Hand the terminal over while sudo or pkexec asks for a password, then take it back
+    fn save_privileged<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> Result<()> {
+        crate::restore()?;
+
+        println!("Saving {} with elevated privileges...", self.document.file_name.as_deref().unwrap_or_default());
+
+        let result = self.document.write_out_privileged();
+
+        crate::resume()?;
+        terminal.clear()?;
+
+        self.drawn_cursor_shape = None;
+
+        match result {
+            Ok(helper) => {
+                self.status.info(format!("Wrote {} lines with {helper}.", self.document.len()));
+                self.save_undo();
+
+                if self.quit_after_save {
+                    self.close_buffer();
+                }
+            },
+            Err(error) => self.status.error(format!("Error writing file: {error}")),
+        }
+
+        self.quit_after_save = false;
+
+        Ok(())
+    }
+    // Hand the terminal back to the shell and stop like Ctrl-Z does elsewhere. Raw mode keeps the
+    // terminal from sending the signal itself. Once continued the screen is set up and drawn again
+    fn suspend<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> Result<()> {
+        crate::restore()?;
+
+        unsafe {
+            libc::raise(libc::SIGTSTP);
+        }
+
+        crate::resume()?;
+        terminal.clear()?;
+
+        self.drawn_cursor_shape = None;
+
+        Ok(())
+    }
+    fn cancel_prompt(&mut self) {
+        self.current_screen = CurrentScreen::Main;
+        self.quit_after_save = false;
+        self.status.info("Cancelled");
+    }
+    fn open_export_html(&mut self) {
+        let file_name = self.document.file_name.as_ref().map(|file_name| format!("{file_name}.html")).unwrap_or_default();
+
+        self.prompt.set(&file_name);
+        self.current_screen = CurrentScreen::ExportHtml;
+    }
+    fn confirm_export_html(&mut self) {
+        if self.prompt.is_empty() {
+            self.cancel_prompt();
+            return;
+        }
+
+        let path = self.prompt.text().to_owned();
+
+        self.current_screen = CurrentScreen::Main;
+        match fs::write(&path, html::export(&self.document)) {
+            Ok(()) => self.status.info(format!("Exported HTML to {path}.")),
+            Err(error) => self.status.error(format!("Error writing file: {error}")),
+        }
+    }
+    // Write the buffer in another encoding, which it's saved in from then on. A buffer without a file
+    // asks for a name first
+    fn confirm_save_encoding(&mut self) {
+        let encoding = match Encoding::from_name(self.prompt.text()) {
+            Ok(encoding) => encoding,
+            Err(error) => {
+                self.current_screen = CurrentScreen::Main;
+                self.status.error(format!("{error:#}"));
+                return;
+            },
+        };
+
+        let previous = self.document.encoding();
+
+        self.document.set_encoding(encoding);
+
+        if self.document.file_name.is_none() {
+            self.write_out();
+            return;
+        }
+
+        self.current_screen = CurrentScreen::Main;
+
+        // Text the encoding can't hold keeps the one the file had
+        if !self.save() && !matches!(self.pending_key, Some(PendingKey::PrivilegedSave)) {
+            self.document.set_encoding(previous);
+        }
+    }
+    fn open_rename_file(&mut self) {
+        if self.document.is_streamed() {
+            self.status.warn("Large files can't be renamed.");
+            return;
+        }
+
+        let file_name = self.document.file_name.clone().unwrap_or_default();
+
+        self.prompt.set(&file_name);
+        self.current_screen = CurrentScreen::RenameFile;
+    }
+    fn confirm_rename_file(&mut self) {
+        if self.prompt.is_empty() || self.document.file_name.as_deref() == Some(self.prompt.text()) {
+            self.cancel_prompt();
+            return;
+        }
+
+        let new_name = self.prompt.text().to_owned();
+        let old_name = self.document.file_name.clone();
+
+        self.current_screen = CurrentScreen::Main;
+
+        match self.document.rename(&new_name) {
+            Ok(()) => {
+                self.save_undo();
+                self.status.info(match old_name {
+                    Some(old_name) => format!("Renamed {old_name} to {new_name}."),
+                    None => format!("Wrote {} lines to {new_name}.", self.document.len()),
+                });
+            },
+            Err(error) => self.status.error(format!("Error renaming file: {error}")),
+        }
+    }
+    fn confirm_insert_unicode(&mut self) {
+        if self.prompt.is_empty() {
+            self.cancel_prompt();
+            return;
+        }
+
+        self.current_screen = CurrentScreen::Main;
+
+        match unicode::parse_char(self.prompt.text()) {
+            Ok(c) => {
+                self.type_text(c.encode_utf8(&mut [0; 4]), false);
+                self.status.info(format!("Inserted U+{:04X}", u32::from(c)));
+            },
+            Err(error) => self.status.warn(error.to_string()),
+        }
+    }
+    // Like `"é" U+0065 U+0301, bytes 65 CC 81, offset 120, width 1`, with the character's name when
+    // it's a single one with a known name
+    // Open the fold the cursor is on, or fold the smallest block, comment or indented block it's in
+    fn toggle_fold(&mut self) {
+        let y = self.cursor_position.y;
+
+        if self.document.folds_mut().open(y) {
+            return;
+        }
+
+        let Some((start, end)) = fold_at(&self.document, y) else {
+            self.status.warn("Nothing to fold here");
+            return;
+        };
+
+        self.document.folds_mut().close(start, end);
+
+        if y != start {
+            let width = self.document.row(start).map_or(0, Row::len);
+
+            self.cursor_position = Position { x: self.cursor_position.x.min(width), y: start };
+        }
+    }
+    // A selection is replaced with what it comes to, an expression under the cursor is only shown
+    fn evaluate(&mut self) {
+        let Position { x, y } = self.cursor_position;
+        let selection = self.selection();
+
+        let expression = if let Some((start, end)) = &selection {
+            self.document.text_range(start, end)
+        } else {
+            let row = self.document.row(y).map(|row| row.as_str().graphemes(true).collect::<Vec<&str>>()).unwrap_or_default();
+
+            let Some((start, end)) = expression_at(&row, x) else {
+                self.status.warn("No expression at the cursor.");
+                return;
+            };
+
+            row[start..end].concat()
+        };
+
+        let result = match evaluate(&expression) {
+            Ok(value) => format_number(value),
+            Err(error) => {
+                self.status.warn(format!("Can't evaluate \"{}\": {error}", expression.trim()));
+                return;
+            },
+        };
+
+        if selection.is_none() || self.document.is_read_only() {
+            self.status.info(format!("{} = {result}", expression.trim()));
+            return;
+        }
+
+        self.document.begin_transaction();
+        self.delete_selection();
+        self.cursor_position = self.document.insert_str(&self.cursor_position, &result);
+        self.document.commit();
+    }
+    // Formats that fail, like ones too long for strftime, are left out with the first error shown
+    fn open_timestamp_picker(&mut self) {
+        let mut error = None;
+
+        self.timestamps = self.config.timestamps
+            .iter()
+            .filter_map(|(name, format)| match format_now(format) {
+                Ok(stamp) => Some((name.clone(), stamp)),
+                Err(failed) => {
+                    error.get_or_insert_with(|| format!("Error in the timestamp format {name}: {failed:#}"));
+                    None
+                },
+            })
+            .collect();
+
+        if let Some(error) = error {
+            self.status.error(error);
+        }
+
+        match self.timestamps.as_slice() {
+            [] => self.status.warn("No timestamp formats are set."),
+            [(_, stamp)] => self.insert_text(&stamp.clone(), false),
+            _ => {
+                self.timestamp_list = PopupList::default();
+                self.current_screen = CurrentScreen::TimestampPicker;
+            },
+        }
+    }
+    fn process_timestamp_picker_key(&mut self, pressed_key: KeyEvent) {
+        match self.timestamp_list.handle_key(pressed_key, self.timestamps.len(), self.page_height()) {
+            PopupKey::Chosen(index) => {
+                self.current_screen = CurrentScreen::Main;
+
+                if let Some((_, stamp)) = self.timestamps.get(index).cloned() {
+                    self.insert_text(&stamp, false);
+                }
+            },
+            PopupKey::Closed => self.current_screen = CurrentScreen::Main,
+            PopupKey::Other if is_close_key(pressed_key) => self.current_screen = CurrentScreen::Main,
+            PopupKey::Handled | PopupKey::Other => (),
+        }
+    }
+    fn inspect_char(&mut self) {
+        let offset = self.document.byte_offset(&self.cursor_position);
+        let grapheme = self.document
+            .row(self.cursor_position.y)
+            .and_then(|row| row.as_str().graphemes(true).nth(self.cursor_position.x));
+
+        let Some(grapheme) = grapheme else {
+            let place = if self.cursor_position.y + 1 < self.document.len() { "End of line" } else { "End of file" };
+
+            self.status.info(format!("{place}, offset {offset}"));
+            return;
+        };
+
+        // Bytes that weren't valid UTF-8 have no code point of their own
+        if let Some(byte) = grapheme.chars().next().and_then(raw_byte) {
+            self.status.info(format!("Invalid UTF-8 byte 0x{byte:02X}, offset {offset}"));
+            return;
+        }
+
+        let codepoints: Vec<String> = grapheme.chars().map(|c| format!("U+{:04X}", u32::from(c))).collect();
+        let bytes: Vec<String> = grapheme.bytes().map(|byte| format!("{byte:02X}")).collect();
+        let mut chars = grapheme.chars();
+        let name = match (chars.next().and_then(unicode::char_name), chars.next()) {
+            (Some(name), None) => format!(" ({name})"),
+            _ => String::new(),
+        };
+        let shown = grapheme.escape_debug();
+
+        self.status.info(format!(
+            "\"{shown}\" {}{name}, bytes {}, offset {offset}, width {}",
+            codepoints.join(" "),
+            bytes.join(" "),
+            grapheme_width(grapheme),
+        ));
+    }
+    fn open_goto_line(&mut self) {
+        self.prompt.clear();
+        self.current_screen = CurrentScreen::GotoLine;
+    }
+    // Accepts "line" or "line,column", both counted from 1
+    fn confirm_goto_line(&mut self) {
+        if self.prompt.is_empty() {
+            self.cancel_prompt();
+            return;
+        }
+
+        self.current_screen = CurrentScreen::Main;
+
+        let input = self.prompt.text().to_owned();
+        let (line, column) = input.split_once(',').unwrap_or((&input, "1"));
+        let (Ok(line), Ok(column)) = (line.trim().parse::<usize>(), column.trim().parse::<usize>()) else {
+            self.status.warn("Invalid line number.");
+            return;
+        };
+
+        let y = line.saturating_sub(1).min(self.document.len().saturating_sub(1));
+        let x = column.saturating_sub(1).min(self.document.row(y).map_or(0, Row::len));
+
+        self.jump_to(Position { x, y });
+    }
+    // Search or the search to replace matches of, starting empty so Enter repeats the last search
+    fn open_search(&mut self, screen: CurrentScreen, in_files: bool) {
+        self.prompt.clear();
+        self.in_files = in_files;
+        self.current_screen = screen;
+    }
+    // A bad regex keeps the prompt open so it can be fixed
+    fn confirm_search(&mut self) {
+        if self.prompt.is_empty() && self.search.is_none() {
+            self.cancel_prompt();
+            return;
+        }
+
+        if !self.prompt.is_empty() {
+            match SearchPattern::new(self.prompt.text(), self.search_regex) {
+                Ok(pattern) => self.search = Some(pattern),
+                Err(error) => {
+                    self.status.error(format!("Invalid regex: {error}"));
+                    return;
+                },
+            }
+        }
+
+        if matches!(self.current_screen, CurrentScreen::Replace) {
+            self.prompt.clear();
+            self.current_screen = CurrentScreen::ReplaceWith;
+        } else if self.in_files {
+            self.current_screen = CurrentScreen::Main;
+            self.search_files();
+        } else {
+            self.current_screen = CurrentScreen::Main;
+            self.search_again(true);
+        }
+    }
+    // Move to the next or previous match of the last search, wrapping around the buffer
+    fn search_again(&mut self, forward: bool) {
+        let Some(pattern) = &self.search else {
+            self.status.warn("No previous search.");
+            return;
+        };
+
+        let direction = if forward { SearchDirection::Forward } else { SearchDirection::Backward };
+        let (from, wrapped_from) = if forward {
+            (Position { x: self.cursor_position.x + 1, y: self.cursor_position.y }, Position::default())
+        } else {
+            (self.cursor_position.clone(), Position { x: usize::MAX, y: self.document.len().saturating_sub(1) })
+        };
+
+        let found = pattern
+            .find(&self.document, &from, direction)
+            .map(|found| (found, false))
+            .or_else(|| pattern.find(&self.document, &wrapped_from, direction).map(|found| (found, true)));
+
+        match found {
+            Some((found, wrapped)) => {
+                if wrapped {
+                    self.status.info("Search wrapped.");
+                }
+
+                self.jump_to(found.start);
+            },
+            None => self.status.warn(format!("\"{}\" not found.", pattern.query())),
+        }
+    }
+    // Replace every match in the buffer as one undo step, expanding `$1` and the like with a regex
+    fn replace_all(&mut self) {
+        self.current_screen = CurrentScreen::Main;
+
+        let Some(pattern) = &self.search else {
+            return;
+        };
+
+        if self.document.is_read_only() {
+            self.status.warn("The buffer is read-only.");
+            return;
+        }
+
+        let replacement = self.prompt.text().to_owned();
+        let mut position = Position::default();
+        let mut count = 0usize;
+
+        self.document.begin_transaction();
+
+        while let Some(found) = pattern.find(&self.document, &position, SearchDirection::Forward) {
+            self.document.delete_range(&found.start, &found.end);
+
+            let end = self.document.insert_str(&found.start, &found.expand(&replacement));
+
+            // An empty match would be found again where it was, so carry on after the next character
+            position = if found.start == found.end { Position { x: end.x + 1, y: end.y } } else { end };
+            count += 1;
+        }
+
+        self.document.commit();
+        self.cursor_position = clamp_position(&self.document, &self.cursor_position);
+
+        if count == 0 {
+            self.status.warn(format!("\"{}\" not found.", pattern.query()));
+        } else {
+            self.status.info(format!("Replaced {count} {}.", if count == 1 { "match" } else { "matches" }));
+        }
+    }
+    // Find what replacing in every file would change and list it for review
+    fn preview_file_changes(&mut self) {
+        self.current_screen = CurrentScreen::Main;
+
+        let Some(pattern) = &self.search else {
+            return;
+        };
+
+        let root = std::env::current_dir().unwrap_or_default();
+        let size_limit = u64::try_from(self.config.large_file_size).unwrap_or(u64::MAX).saturating_mul(1 << 20);
+
+        self.file_changes = find_changes(&root, pattern, self.prompt.text(), size_limit);
+
+        if self.file_changes.is_empty() {
+            self.status.warn(format!("\"{}\" not found in {}.", pattern.query(), root.display()));
+            return;
+        }
+
+        let hunks: usize = self.file_changes.iter().map(|file| file.hunks.len()).sum();
+
+        self.status.info(format!("{hunks} lines to change in {} files. Space toggles, Enter writes.", self.file_changes.len()));
+        self.file_changes_index = 0;
+        self.current_screen = CurrentScreen::FileChanges;
+    }
+    // Space on a file toggles all of its hunks
+    // List every line under the working directory the search matches
+    fn search_files(&mut self) {
+        let Some(pattern) = &self.search else {
+            return;
+        };
+
+        let root = std::env::current_dir().unwrap_or_default();
+        let size_limit = u64::try_from(self.config.large_file_size).unwrap_or(u64::MAX).saturating_mul(1 << 20);
+        let items = find_matches(&root, pattern, size_limit);
+
+        if items.is_empty() {
+            self.status.warn(format!("\"{}\" not found in {}.", pattern.query(), root.display()));
+        } else {
+            self.status.info(format!("{} matching lines, F8 goes to the next.", items.len()));
+        }
+
+        let title = format!("Search for {}", pattern.query());
+
+        self.set_quickfix(QuickfixList::new(title, items));
+    }
+    fn process_file_changes_key(&mut self, pressed_key: KeyEvent) {
+        let rows = preview_rows(&self.file_changes);
+
+        if let Some(index) = self.modal_scroll(self.file_changes_index, rows.len(), pressed_key.code) {
+            self.file_changes_index = index;
+        } else if pressed_key.code == KeyCode::Char(' ') {
+            match rows.get(self.file_changes_index) {
+                Some(PreviewRow::File(file)) => {
+                    let hunks = &mut self.file_changes[*file].hunks;
+                    let selected = !hunks.iter().all(|hunk| hunk.selected);
+
+                    for hunk in hunks {
+                        hunk.selected = selected;
+                    }
+                },
+                Some(PreviewRow::Hunk(file, hunk)) => {
+                    let hunk = &mut self.file_changes[*file].hunks[*hunk];
+
+                    hunk.selected = !hunk.selected;
+                },
+                None => (),
+            }
+        } else if pressed_key.code == KeyCode::Enter {
+            self.current_screen = CurrentScreen::Main;
+
+            let (written, errors) = project::apply(&self.file_changes, self.config.replace_backups);
+
+            self.file_changes.clear();
+
+            if self.config.replace_backups {
+                self.status.info(format!("Wrote {written} files, the originals are saved with a ~ after their names."));
+            } else {
+                self.status.info(format!("Wrote {written} files."));
+            }
+
+            for error in errors {
+                self.status.error(error);
+            }
+        } else if is_close_key(pressed_key) {
+            self.current_screen = CurrentScreen::Main;
+            self.file_changes.clear();
+            self.status.info("Cancelled");
+        }
+    }
+    #[must_use]
+    pub fn search_regex(&self) -> bool {
+        self.search_regex
+    }
+    #[must_use]
+    pub fn in_files(&self) -> bool {
+        self.in_files
+    }
+    #[must_use]
+    pub fn file_changes(&self) -> &[FileChanges] {
+        &self.file_changes
+    }
+    #[must_use]
+    pub fn file_changes_index(&self) -> usize {
+        self.file_changes_index
+    }
+    // Jump to the start of the next or previous conflict, wrapping around the document
+    fn goto_conflict(&mut self, forward: bool) {
+        let conflicts = self.document.conflicts();
+        let y = self.cursor_position.y;
+        let target = if forward {
+            conflicts.iter().find(|conflict| conflict.start > y).or_else(|| conflicts.first())
+        } else {
+            conflicts.iter().rev().find(|conflict| conflict.start < y).or_else(|| conflicts.last())
+        };
+
+        let Some(conflict) = target else {
+            self.status.warn("No merge conflicts.");
+            return;
+        };
+
+        let number = conflicts.iter().take_while(|other| other.start <= conflict.start).count();
+
+        self.status.info(format!("Conflict {number} of {}.", conflicts.len()));
+        self.jump_to(Position { x: 0, y: conflict.start });
+    }
+    fn resolve_conflict(&mut self, resolution: &Resolution) {
+        let conflicts = self.document.conflicts();
+        let Some(conflict) = conflicts.iter().find(|conflict| conflict.contains(self.cursor_position.y)) else {
+            self.status.warn("The cursor isn't inside a merge conflict.");
+            return;
+        };
+
+        let lines = conflict.resolve(&self.document.lines(conflict.start, conflict.end), resolution);
+
+        self.document.replace_lines(conflict.start, conflict.end, &lines);
+        self.selection_anchor = None;
+        self.cursor_position = Position { x: 0, y: conflict.start.min(self.document.len().saturating_sub(1)) };
+        self.status.info("Conflict resolved.");
+    }
+    // Move somewhere far away, leaving the current position in the jump list
+    fn jump_to(&mut self, position: Position) {
+        self.document.record_jump(self.cursor_position.clone());
+        self.selection_anchor = None;
+        self.cursor_position = position;
+    }
+    fn jump_to_end(&mut self) {
+        let y = self.document.len().saturating_sub(1);
+        let x = self.document.row(y).map_or(0, Row::len);
+
+        self.jump_to(Position { x, y });
+    }
+    fn jump_back(&mut self) {
+        match self.document.jump_back(self.cursor_position.clone()) {
+            Some(position) => self.move_to_jump(&position),
+            None => self.status.warn("No earlier jumps."),
+        }
+    }
+    fn jump_forward(&mut self) {
+        match self.document.jump_forward() {
+            Some(position) => self.move_to_jump(&position),
+            None => self.status.warn("No later jumps."),
+        }
+    }
+    fn move_to_jump(&mut self, position: &Position) {
+        self.selection_anchor = None;
+        self.cursor_position = clamp_position(&self.document, position);
+    }
+    // Extend the typed path to the longest prefix shared by every matching directory entry
+    fn complete_path(&mut self) {
+        let input = self.prompt.text();
+        let (directory, prefix) = input.rfind('/')
+            .map_or(("", input), |index| (&input[..=index], &input[index + 1..]));
+        let Ok(entries) = fs::read_dir(if directory.is_empty() { "." } else { directory }) else {
+            return;
+        };
+
+        let mut matches: Vec<String> = entries
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let mut name = entry.file_name().into_string().ok()?;
+
+                if !name.starts_with(prefix) {
+                    return None;
+                }
+
+                if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                    name.push('/');
+                }
+
+                Some(name)
+            })
+            .collect();
+
+        matches.sort();
+
+        let Some(completion) = common_prefix(&matches) else {
+            return;
+        };
+
+        if matches.len() > 1 {
+            self.status.info(matches.join("  "));
+        }
+
+        self.prompt.set(&format!("{directory}{completion}"));
+    }
+    fn move_cursor(&mut self, key: KeyCode) {
+        let start_column = self.cursor_column();
+        let Position { mut x, mut y} = self.cursor_position;
+        let height = self.document.len();
+        let mut width = self.document.row(y).map_or(0, Row::len);
+        let page = self.page_rows();
+        let folds = self.document.folds();
+
+        // Folded rows are stepped over as if they were one
+        match key {
+            KeyCode::Up => {
+                y = folds.previous_visible(y);
+            },
+            KeyCode::Down => {
+                if y < height {
+                    y = folds.next_visible(y).min(height);
+                }
+            },
+            KeyCode::Left => {
+                if x > 0 {
+                    x -= 1;
+                } else if y > 0 {
+                    y = folds.previous_visible(y);
+
+                    if let Some(row) = self.document.row(y) {
+                        x = row.len();
+                    } else {
+                        x = 0;
+                    }
+                }
+            },
+            KeyCode::Right => {
+                if x < width {
+                    x += 1;
+                } else if y < height {
+                    y = folds.next_visible(y).min(height);
+                    x = 0;
+                }
+            },
+            // The viewport moves with the cursor, so it stays on the same screen row
+            KeyCode::PageUp | KeyCode::PageDown => {
+                let lines = if key == KeyCode::PageUp { -page } else { page };
+
+                y = folds.step(y, lines, height);
+                self.offset.y = folds.step(folds.shown_row(self.offset.y), lines, height.saturating_sub(1));
+            },
+            KeyCode::Home => x = 0,
+            KeyCode::End => x = width,
+            _ => ()
+        }
+
+        width = self.document.row(y).map_or(0, Row::len);
+        
+        if x > width {
+            x = width;
+        }
+
+        // Vertical movement aims for the column the cursor started from, not wherever short lines clamped it to
+        if matches!(key, KeyCode::Up | KeyCode::Down | KeyCode::PageUp | KeyCode::PageDown) {
+            let column = *self.desired_column.get_or_insert(start_column);
+
+            x = self.index_at_column(y, column);
+        } else {
+            self.desired_column = None;
+        }
+
+        self.cursor_position = Position { x, y }
+    }
+    // Move the viewport by `lines`, only dragging the cursor along once it would leave the screen
+    fn scroll_viewport(&mut self, lines: isize) {
+        let height = self.terminal_size.height as usize;
+        let folds = self.document.folds();
+
+        self.offset.y = folds.step(folds.shown_row(self.offset.y), lines, self.document.len().saturating_sub(1));
+
+        let last_visible = self.row_at_screen(height.saturating_sub(1));
+        let y = self.document.folds().shown_row(self.cursor_position.y.clamp(self.offset.y, last_visible));
+
+        if y != self.cursor_position.y {
+            let width = self.document.row(y).map_or(0, Row::len);
+
+            self.cursor_position = Position { x: self.cursor_position.x.min(width), y };
+        }
+    }
+    // Rows a page moves by, the screen less the configured overlap
+    fn page_rows(&self) -> isize {
+        let height = self.terminal_size.height as usize;
+
+        height.saturating_sub(self.config.page_overlap).max(1).try_into().unwrap_or(isize::MAX)
+    }
+    // The cursor keeps its place on the screen while the text moves under it, unless the viewport is
+    // stopped by the start of the buffer
+    fn scroll_half_page(&mut self, down: bool) {
+        let half: isize = (self.terminal_size.height as usize / 2).max(1).try_into().unwrap_or(isize::MAX);
+        let lines = if down { half } else { -half };
+        let last = self.document.len().saturating_sub(1);
+        let column = self.desired_column.unwrap_or_else(|| self.cursor_column());
+        let folds = self.document.folds();
+
+        self.desired_column = Some(column);
+
+        self.offset.y = folds.step(folds.shown_row(self.offset.y), lines, last);
+
+        let y = folds.step(self.cursor_position.y, lines, last);
+
+        self.cursor_position = Position { x: self.index_at_column(y, column), y };
+    }
+    fn center_cursor_line(&mut self) {
+        let height = self.terminal_size.height as usize;
+
+        let lines: isize = (height / 2).try_into().unwrap_or(isize::MAX);
+
+        self.offset.y = self.document.folds().step(self.cursor_position.y, -lines, self.document.len());
+    }
+    fn switch_buffer(&mut self, forward: bool) {
+        let next = if forward {
+            (!self.buffers.is_empty()).then(|| self.buffers.remove(0))
+        } else {
+            self.buffers.pop()
+        };
+
+        let Some(next) = next else {
+            self.status.warn("No other buffers are open.");
+            return;
+        };
+
+        let previous = self.show_buffer(next);
+
+        // Buffers rotate, so switching one way and back returns to the same buffer
+        if forward {
+            self.buffers.push(previous);
+        } else {
+            self.buffers.insert(0, previous);
+        }
+
+        let name = self.document.file_name.clone().unwrap_or_else(|| "New Buffer".to_owned());
+
+        self.status.info(format!("Switched to {name}."));
+    }
+    // Close the current buffer and show the next one, quitting once the last buffer is closed
+    // Closing the last buffer quits, unless it was closed with the close buffer command
+    fn close_buffer(&mut self) {
+        self.save_folds();
+
+        if self.buffers.is_empty() && !self.keep_editor_open {
+            self.should_quit = true;
+            return;
+        }
+
+        let next = if self.buffers.is_empty() { Buffer::from(Document::default()) } else { self.buffers.remove(0) };
+        let closed = self.show_buffer(next);
+
+        self.status.info(format!("Closed {}", closed.document.file_name.as_deref().unwrap_or("[No Name]")));
+        self.current_screen = CurrentScreen::Main;
+    }
+    // Show `buffer`, leaving the one it replaces as the one switching back returns to
+    fn bring_up_buffer(&mut self, buffer: Buffer) {
+        let previous = self.show_buffer(buffer);
+
+        self.buffers.push(previous);
+    }
+    fn open_buffer_list(&mut self) {
+        self.buffer_list = PopupList::default();
+        self.current_screen = CurrentScreen::BufferList;
+    }
+    // Until `/` starts a filter, `d` closes the selected buffer
+    fn process_buffer_list_key(&mut self, pressed_key: KeyEvent) {
+        let entries = self.buffer_list_entries();
+        let selected = entries.get(self.buffer_list.index).map(|entry| entry.buffer);
+
+        match self.buffer_list.handle_key(pressed_key, entries.len(), self.page_height()) {
+            PopupKey::Handled => (),
+            PopupKey::Chosen(_) => {
+                self.current_screen = CurrentScreen::Main;
+
+                if let Some(buffer) = selected.filter(|buffer| *buffer > 0) {
+                    let buffer = self.buffers.remove(buffer - 1);
+
+                    self.bring_up_buffer(buffer);
+                }
+            },
+            PopupKey::Closed => self.current_screen = CurrentScreen::Main,
+            PopupKey::Other => match (pressed_key.modifiers, pressed_key.code) {
+                (_, KeyCode::Char('/')) => self.buffer_list.filtering = true,
+                (_, KeyCode::Char('d')) => {
+                    if let Some(buffer) = selected {
+                        self.close_listed_buffer(buffer);
+                    }
+                },
+                (KeyModifiers::CONTROL, KeyCode::Char('b')) => self.current_screen = CurrentScreen::Main,
+                _ if is_close_key(pressed_key) => self.current_screen = CurrentScreen::Main,
+                _ => (),
+            },
+        }
+    }
+    // Buffers with unsaved changes are kept, so nothing is lost without being asked
+    fn close_listed_buffer(&mut self, buffer: usize) {
+        let entry = if buffer == 0 { BufferEntry::new(0, &self.document) } else { BufferEntry::new(buffer, &self.buffers[buffer - 1].document) };
+
+        if entry.dirty {
+            self.status.warn(format!("{} has unsaved changes, switch to it to save or close it.", entry.name));
+            return;
+        }
+
+        if self.buffers.is_empty() {
+            self.status.warn("The last buffer is closed with Ctrl-X.");
+            return;
+        }
+
+        if buffer == 0 {
+            self.close_buffer();
+            self.current_screen = CurrentScreen::BufferList;
+        } else {
+            self.buffers.remove(buffer - 1);
+        }
+
+        self.buffer_list.clamp(self.buffers.len() + 1);
+        self.status.info(format!("Closed {}.", entry.name));
+    }
+    // The buffers matching the filter, the one being edited first while nothing is filtered out
+    #[must_use]
+    pub fn buffer_list_entries(&self) -> Vec<BufferEntry> {
+        let entries = std::iter::once(&self.document)
+            .chain(self.buffers.iter().map(|buffer| &buffer.document))
+            .enumerate()
+            .map(|(buffer, document)| BufferEntry::new(buffer, document))
+            .collect();
+
+        filter_entries(entries, &self.buffer_list.filter)
+    }
+    #[must_use]
+    pub fn buffer_list(&self) -> &PopupList {
+        &self.buffer_list
+    }
+    // Make `buffer` the one being edited, returning the buffer it replaced
+    fn show_buffer(&mut self, buffer: Buffer) -> Buffer {
+        let previous = Buffer {
+            document: std::mem::replace(&mut self.document, buffer.document),
+            cursor_position: std::mem::replace(&mut self.cursor_position, buffer.cursor_position),
+            offset: std::mem::replace(&mut self.offset, buffer.offset),
+        };
+
+        self.selection_anchor = None;
+        self.desired_column = None;
+        self.column_layout = None;
+
+        previous
+    }
+    fn toggle_file_tree(&mut self) {
+        if self.show_file_tree && matches!(self.current_screen, CurrentScreen::FileTree) {
+            self.show_file_tree = false;
+            self.current_screen = CurrentScreen::Main;
+            return;
+        }
+
+        match &mut self.file_tree {
+            Some(tree) if !self.show_file_tree => tree.refresh(),
+            Some(_) => (),
+            None => self.file_tree = Some(FileTree::new(std::env::current_dir().unwrap_or_default())),
+        }
+
+        self.show_file_tree = true;
+        self.current_screen = CurrentScreen::FileTree;
+        self.status.info("Enter opens, . shows hidden files, i shows ignored files, Esc returns to the buffer.");
+    }
+    fn process_file_tree_key(&mut self, pressed_key: KeyEvent) {
+        let Some((selected, len)) = self.file_tree.as_ref().map(|tree| (tree.selected(), tree.entries().len())) else {
+            self.current_screen = CurrentScreen::Main;
+            return;
+        };
+
+        let scrolled = self.modal_scroll(selected, len, pressed_key.code);
+        let Some(tree) = &mut self.file_tree else {
+            return;
+        };
+
+        if let Some(index) = scrolled {
+            tree.select(index);
+            return;
+        }
+
+        match (pressed_key.modifiers, pressed_key.code) {
+            (_, KeyCode::Enter) => {
+                if let Some(path) = tree.activate() {
+                    self.open_path(&path);
+                }
+            },
+            (_, KeyCode::Right) => tree.expand(),
+            (_, KeyCode::Left) => tree.collapse(),
+            (_, KeyCode::Char('.')) => {
+                let shown = if tree.toggle_hidden() { "Showing" } else { "Hiding" };
+
+                self.status.info(format!("{shown} hidden files."));
+            },
+            (_, KeyCode::Char('i')) => {
+                let shown = if tree.toggle_ignored() { "Showing" } else { "Hiding" };
+
+                self.status.info(format!("{shown} ignored files."));
+            },
+            (KeyModifiers::ALT, KeyCode::Char('b')) => self.toggle_file_tree(),
+            _ if is_close_key(pressed_key) => self.current_screen = CurrentScreen::Main,
+            _ => (),
+        }
+    }
+    // Show the file at `path`, switching to its buffer when it's already open. Returns whether it's shown
+    fn open_path(&mut self, path: &Path) -> bool {
+        let cwd = std::env::current_dir().unwrap_or_default();
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let name = path.strip_prefix(&cwd).unwrap_or(&path).to_string_lossy().into_owned();
+        let same_file = |document: &Document| document.file_name.as_deref().is_some_and(|file_name| fs::canonicalize(file_name).is_ok_and(|open| open == path));
+
+        self.current_screen = CurrentScreen::Main;
+
+        if same_file(&self.document) {
+            return true;
+        }
+
+        let buffer = if let Some(index) = self.buffers.iter().position(|buffer| same_file(&buffer.document)) {
+            self.buffers.remove(index)
+        } else {
+            let mut buffer = match Buffer::open(&FileArg { path: name.clone(), line: None, column: None }, self.config.large_file_size) {
+                Ok(buffer) => buffer,
+                Err(error) => {
+                    self.status.error(format!("Error opening {name}: {error:#}"));
+                    return false;
+                },
+            };
+
+            buffer.document.lock();
+
+            if self.config.modelines {
+                buffer.document.apply_modeline();
+            }
+
+            buffer
+        };
+
+        self.bring_up_buffer(buffer);
+        self.status.info(format!("Opened {name}."));
+
+        true
+    }
+    fn start_build(&mut self) {
+        if self.build.is_some() {
+            self.status.warn("A build is already running.");
+            return;
+        }
+
+        let root = std::env::current_dir().unwrap_or_default();
+        let Some(command) = self.config.build_command.clone().or_else(|| default_command(&root).map(str::to_owned)) else {
+            self.status.warn("No build command, set build_command in the config.");
+            return;
+        };
+
+        self.status.info(format!("Running {command}..."));
+        self.build = Some(BuildJob::spawn(&command));
+    }
+    // Show how a finished build went, listing its errors. Returns whether one finished
+    fn poll_build(&mut self) -> bool {
+        let Some(result) = self.build.as_ref().and_then(BuildJob::poll) else {
+            return false;
+        };
+
+        self.build = None;
+
+        match result {
+            Ok(result) => {
+                let outcome = if result.success { "succeeded" } else { "failed" };
+                let count = |severity| result.errors.iter().filter(|error| error.severity == severity).count();
+                let message = format!(
+                    "{} {outcome}, errors: {}, warnings: {}.",
+                    result.command,
+                    count(BuildSeverity::Error),
+                    count(BuildSeverity::Warning),
+                );
+
+                self.status.toast(message, if result.success { Severity::Info } else { Severity::Error });
+
+                let items = result.errors.into_iter().map(QuickfixItem::from).collect();
+
+                self.set_quickfix(QuickfixList::new(result.command, items));
+            },
+            Err(error) => self.status.error(format!("The build couldn't run: {error}")),
+        }
+
+        true
+    }
+    // Show a new list in the pane, replacing the last one. Empty lists close it
+    fn set_quickfix(&mut self, list: QuickfixList) {
+        self.show_quickfix = !list.is_empty();
+        self.quickfix = Some(list);
+
+        if !self.show_quickfix && matches!(self.current_screen, CurrentScreen::Quickfix) {
+            self.current_screen = CurrentScreen::Main;
+        }
+    }
+    // Show the pane and move to it, or hide it when it's already focused
+    fn toggle_quickfix(&mut self) {
+        if self.show_quickfix && matches!(self.current_screen, CurrentScreen::Quickfix) {
+            self.show_quickfix = false;
+            self.current_screen = CurrentScreen::Main;
+            return;
+        }
+
+        if self.quickfix.as_ref().is_none_or(QuickfixList::is_empty) {
+            self.status.info("The quickfix list is empty, build or search in files to fill it.");
+            return;
+        }
+
+        self.show_quickfix = true;
+        self.current_screen = CurrentScreen::Quickfix;
+    }
+    // Esc goes back to the buffer and leaves the pane open, so items can be fixed one by one
+    fn process_quickfix_key(&mut self, pressed_key: KeyEvent) {
+        let Some((index, len)) = self.quickfix.as_ref().map(|list| (list.index().unwrap_or(0), list.len())) else {
+            self.current_screen = CurrentScreen::Main;
+            return;
+        };
+
+        if let Some(index) = self.modal_scroll(index, len, pressed_key.code) {
+            if let Some(list) = &mut self.quickfix {
+                list.select(index);
+            }
+        } else if pressed_key.code == KeyCode::Enter {
+            if let Some(list) = &mut self.quickfix {
+                list.select(index);
+            }
+
+            self.goto_quickfix_item();
+        } else if pressed_key.code == KeyCode::F(4) {
+            self.toggle_quickfix();
+        } else if is_close_key(pressed_key) {
+            self.current_screen = CurrentScreen::Main;
+        }
+    }
+    fn quickfix_step(&mut self, forward: bool) {
+        let Some(list) = &mut self.quickfix else {
+            self.status.info("The quickfix list is empty, build or search in files to fill it.");
+            return;
+        };
+
+        if list.step(forward).is_none() {
+            let end = if forward { "last" } else { "first" };
+
+            self.status.info(format!("At the {end} item."));
+            return;
+        }
+
+        self.show_quickfix = true;
+        self.goto_quickfix_item();
+    }
+    // Open the file of the current item with the cursor on it
+    fn goto_quickfix_item(&mut self) {
+        let Some(item) = self.quickfix.as_ref().and_then(QuickfixList::current).cloned() else {
+            return;
+        };
+
+        if !self.open_path(Path::new(&item.path)) {
+            return;
+        }
+
+        self.document.load_to(item.line);
+
+        let position = Position { x: item.column.saturating_sub(1), y: item.line.saturating_sub(1) };
+        let position = clamp_position(&self.document, &position);
+
+        self.jump_to(position);
+        self.center_cursor_line();
+        self.status.info(item.text);
+    }
+    fn goto_definition(&mut self) {
+        let word = TextObject::Word
+            .find(&self.document, &self.cursor_position, true)
+            .and_then(|(start, end)| Some(self.document.row(start.y)?.substring(start.x, end.x)))
+            .filter(|word| !word.is_empty() && word.chars().all(is_word_char));
+
+        let Some(word) = word else {
+            self.status.warn("The cursor isn't on a word.");
+            return;
+        };
+
+        let tags_file = std::env::current_dir().unwrap_or_default().join(TAGS_FILE);
+
+        match find_tags(&tags_file, &word) {
+            Ok(tags) if tags.is_empty() => self.status.warn(format!("No tag for {word}.")),
+            Ok(mut tags) if tags.len() == 1 => self.goto_tag(&tags.remove(0)),
+            Ok(tags) => {
+                self.tag_matches = tags;
+                self.tag_list = PopupList::default();
+                self.current_screen = CurrentScreen::TagPicker;
+            },
+            Err(error) => self.status.error(format!("{error:#}, Alt-] generates it.")),
+        }
+    }
+    fn process_tag_picker_key(&mut self, pressed_key: KeyEvent) {
+        match self.tag_list.handle_key(pressed_key, self.tag_matches.len(), self.page_height()) {
+            PopupKey::Chosen(index) => {
+                self.current_screen = CurrentScreen::Main;
+
+                if let Some(tag) = self.tag_matches.get(index).cloned() {
+                    self.goto_tag(&tag);
+                }
+            },
+            PopupKey::Closed => self.current_screen = CurrentScreen::Main,
+            PopupKey::Other if is_close_key(pressed_key) => self.current_screen = CurrentScreen::Main,
+            PopupKey::Handled | PopupKey::Other => (),
+        }
+    }
+    // Enter opens the chosen file read-only, Ctrl-E opens it for editing when the archive can be
+    // written back to
+    fn process_archive_list_key(&mut self, pressed_key: KeyEvent) {
+        let entries = self.archive_entries();
+        let selected = entries.get(self.archive_list.index).map(|member| (*member).to_owned());
+
+        match self.archive_list.handle_key(pressed_key, entries.len(), self.page_height()) {
+            PopupKey::Handled => (),
+            PopupKey::Chosen(_) => self.open_archive_member(selected, false),
+            PopupKey::Closed => self.current_screen = CurrentScreen::Main,
+            PopupKey::Other => match (pressed_key.modifiers, pressed_key.code) {
+                (_, KeyCode::Char('/')) => self.archive_list.filtering = true,
+                (KeyModifiers::CONTROL, KeyCode::Char('e')) => self.open_archive_member(selected, true),
+                _ if is_close_key(pressed_key) => self.current_screen = CurrentScreen::Main,
+                _ => (),
+            },
+        }
+    }
+    fn open_archive_member(&mut self, member: Option<String>, writable: bool) {
+        let (Some(archive), Some(member)) = (&self.archive, member) else {
+            return;
+        };
+
+        if writable && !archive.can_write() {
+            self.status.warn("Only files in .zip archives can be saved back, Enter opens it read-only.");
+            return;
+        }
+
+        let name = member_name(&archive.path, &member);
+
+        self.current_screen = CurrentScreen::Main;
+
+        if let Some(index) = self.buffers.iter().position(|buffer| buffer.document.file_name.as_deref() == Some(name.as_str())) {
+            let buffer = self.buffers.remove(index);
+
+            self.bring_up_buffer(buffer);
+            return;
+        }
+
+        if self.document.file_name.as_deref() == Some(name.as_str()) {
+            return;
+        }
+
+        match Document::open_archive_member(&archive.path, &member, writable) {
+            Ok(document) => {
+                let buffer = Buffer::from(document);
+
+                // An untouched empty buffer is replaced rather than kept behind the file
+                if self.document.file_name.is_none() && !self.document.is_dirty() && self.document.is_empty() {
+                    self.show_buffer(buffer);
+                } else {
+                    self.bring_up_buffer(buffer);
+                }
+            },
+            Err(error) => self.status.error(format!("Error opening {name}: {error:#}")),
+        }
+    }
+    fn open_archive_list(&mut self) {
+        if self.archive.is_none() {
+            self.status.warn("No archive was opened.");
+            return;
+        }
+
+        self.archive_list = PopupList::default();
+        self.current_screen = CurrentScreen::ArchiveList;
+    }
+    // The archive's files matching the filter, best first
+    #[must_use]
+    pub fn archive_entries(&self) -> Vec<&str> {
+        let Some(archive) = &self.archive else {
+            return Vec::new();
+        };
+
+        let mut scored: Vec<(usize, &str)> = archive.members
+            .iter()
+            .filter_map(|member| Some((fuzzy_score(&self.archive_list.filter, member)?, member.as_str())))
+            .collect();
+
+        scored.sort_by_key(|(score, _)| *score);
+        scored.into_iter().map(|(_, member)| member).collect()
+    }
+    #[must_use]
+    pub fn archive(&self) -> Option<&Archive> {
+        self.archive.as_ref()
+    }
+    #[must_use]
+    pub fn archive_list(&self) -> &PopupList {
+        &self.archive_list
+    }
+    // `n` and `p` move between hunks, `q` quits and Esc leaves both files open for editing
+    fn process_side_by_side_key(&mut self, pressed_key: KeyEvent) {
+        let Some(side_by_side) = &self.side_by_side else {
+            self.current_screen = CurrentScreen::Main;
+            return;
+        };
+
+        let forward = match pressed_key.code {
+            KeyCode::Char('n' | ']') => Some(true),
+            KeyCode::Char('p' | 'N' | '[') => Some(false),
+            _ => None,
+        };
+
+        if let Some(forward) = forward {
+            let current = self.side_by_side_scroll + DIFF_CONTEXT;
+
+            match side_by_side.next_hunk(current, forward) {
+                Some(start) => self.side_by_side_scroll = start.saturating_sub(DIFF_CONTEXT),
+                None => self.status.info(if forward { "No more changes below." } else { "No more changes above." }),
+            }
+        } else if let Some(scroll) = self.modal_scroll(self.side_by_side_scroll, side_by_side.rows.len(), pressed_key.code) {
+            self.side_by_side_scroll = scroll;
+        } else if pressed_key.code == KeyCode::Char('q') {
+            self.execute(Action::Quit);
+        } else if is_close_key(pressed_key) {
+            self.current_screen = CurrentScreen::Main;
+        }
+    }
+    #[must_use]
+    pub fn side_by_side(&self) -> Option<&SideBySide> {
+        self.side_by_side.as_ref()
+    }
+    #[must_use]
+    pub fn side_by_side_scroll(&self) -> usize {
+        self.side_by_side_scroll
+    }
+    // Patterns are looked up in the file as it is on disk, like ctags saw it
+    fn goto_tag(&mut self, tag: &Tag) {
+        let line = fs::read_to_string(&tag.path).ok().and_then(|contents| tag.line_in(&contents));
+
+        let Some(y) = line else {
+            self.status.warn(format!("{} isn't in {} any more, regenerate the tags with Alt-].", tag.name, tag.path.display()));
+            return;
+        };
+
+        if !self.open_path(&tag.path) {
+            return;
+        }
+
+        self.document.load_to(y);
+
+        let x = self.document.row(y).and_then(|row| row.find(&tag.name, 0, SearchDirection::Forward)).unwrap_or(0);
+        let position = clamp_position(&self.document, &Position { x, y });
+
+        self.jump_to(position);
+        self.center_cursor_line();
+    }
+    fn generate_tags(&mut self) {
+        let root = std::env::current_dir().unwrap_or_default();
+
+        match generate_tags(&root) {
+            Ok(()) => self.status.info(format!("Wrote {}.", root.join(TAGS_FILE).display())),
+            Err(error) => self.status.error(format!("{error:#}")),
+        }
+    }
+    #[must_use]
+    pub fn tag_matches(&self) -> &[Tag] {
+        &self.tag_matches
+    }
+    #[must_use]
+    pub fn timestamps(&self) -> &[(String, String)] {
+        &self.timestamps
+    }
+    #[must_use]
+    pub fn timestamp_index(&self) -> usize {
+        self.timestamp_list.index
+    }
+    #[must_use]
+    pub fn tag_index(&self) -> usize {
+        self.tag_list.index
+    }
+    #[must_use]
+    pub fn overwrite(&self) -> bool {
+        self.overwrite
+    }
+    #[must_use]
+    pub fn pager(&self) -> bool {
+        self.pager
+    }
+    // A block while selecting, since typing would replace the selection, and an underline while overwriting
+    #[must_use]
+    pub fn cursor_shape(&self) -> CursorShape {
+        match self.current_screen {
+            CurrentScreen::Main if self.selection_anchor.is_some() => CursorShape::Block,
+            CurrentScreen::Main if self.overwrite => CursorShape::Underline,
+            _ => CursorShape::Bar,
+        }
+    }
+    #[must_use]
+    pub fn tooltip(&self) -> Option<&(layout::Position, Vec<QuickfixItem>)> {
+        self.tooltip.as_ref()
+    }
+    // The keys of the chord typed so far and what can follow them, once it's waited long enough
+    #[must_use]
+    pub fn chord_hints(&self) -> Option<(String, Vec<(String, String)>)> {
+        self.chord_started.filter(|started| started.elapsed() >= CHORD_HINT_DELAY)?;
+
+        let keys: Vec<String> = self.chord.iter().map(|key| key_name(*key)).collect();
+
+        Some((keys.join(" "), self.config.keymap.continuations(&self.chord)))
+    }
+    #[must_use]
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+    #[must_use]
+    pub fn status_bar_layout(&self) -> &StatusBarLayout {
+        &self.config.status_bar
+    }
+    #[must_use]
+    pub fn git_branch(&self) -> Option<&str> {
+        self.git_branch.as_deref()
+    }
+    #[must_use]
+    pub fn clock(&self) -> &str {
+        &self.clock
+    }
+    // Errors and warnings in the quickfix list, which outlive the pane being shown
+    #[must_use]
+    pub fn diagnostics(&self) -> (usize, usize) {
+        let items = self.quickfix.as_ref().map_or(&[][..], QuickfixList::items);
+        let count = |kind| items.iter().filter(|item| item.kind == kind).count();
+
+        (count(ItemKind::Error), count(ItemKind::Warning))
+    }
+    #[must_use]
+    pub fn quickfix(&self) -> Option<&QuickfixList> {
+        self.quickfix.as_ref().filter(|_| self.show_quickfix)
+    }
+    pub fn update_sidebar_bounds(&mut self, rect: Rect) {
+        self.sidebar_area = rect;
+
+        if let Some(tree) = &mut self.file_tree {
+            tree.scroll(rect.height as usize);
+        }
+    }
+    #[must_use]
+    pub fn file_tree(&self) -> Option<&FileTree> {
+        self.file_tree.as_ref().filter(|_| self.show_file_tree)
+    }
+    fn toggle_column_mode(&mut self) {
+        if self.column_layout.take().is_some() {
+            self.status.info("Column mode disabled.");
+            return;
+        }
+
+        if self.document.file_name.as_deref().and_then(delimiter_for).is_none() {
+            self.status.warn("Column mode needs a .csv or .tsv file.");
+            return;
+        }
+
+        self.build_column_layout();
+        self.status.info("Column mode enabled.");
+    }
+    // Column widths follow the text, so they're measured again after every edit
+    fn refresh_column_layout(&mut self) {
+        if self.column_layout.is_some() {
+            self.build_column_layout();
+        }
+    }
+    fn build_column_layout(&mut self) {
+        let Some(delimiter) = self.document.file_name.as_deref().and_then(delimiter_for) else {
+            self.column_layout = None;
+            return;
+        };
+
+        let rows = (0..self.document.len()).filter_map(|y| self.document.row(y));
+
+        self.column_layout = Some(ColumnLayout::new(rows, delimiter));
+    }
+    fn next_cell(&mut self) {
+        let Position { x, y } = self.cursor_position;
+
+        if let Some(start) = self.cell_starts(y).into_iter().find(|start| *start > x) {
+            self.cursor_position.x = start;
+        } else if y.saturating_add(1) < self.document.len() {
+            self.cursor_position = Position { x: 0, y: y + 1 };
+        }
+    }
+    fn previous_cell(&mut self) {
+        let Position { x, y } = self.cursor_position;
+
+        if let Some(start) = self.cell_starts(y).into_iter().rev().find(|start| *start < x) {
+            self.cursor_position.x = start;
+        } else if y > 0 {
+            let x = self.cell_starts(y - 1).last().copied().unwrap_or(0);
+
+            self.cursor_position = Position { x, y: y - 1 };
+        }
+    }
+    fn cell_starts(&self, y: usize) -> Vec<usize> {
+        self.document.row(y).zip(self.column_layout.as_ref()).map_or_else(Vec::new, |(row, layout)| {
+            layout.fields(row).iter().map(|field| field.start).collect()
+        })
+    }
+    // The header row is pinned in column mode, so the scrolled rows start below it
+    fn first_scrolled_row(&self) -> usize {
+        usize::from(self.column_layout.is_some() && self.document.len() > 1)
+    }
+    // Keep the cursor in view with at least `scrolloff` rows and `sidescrolloff` columns around it
+    fn scroll(&mut self) {
+        let y = self.cursor_position.y;
+
+        // Searches and jumps that land in a fold open it
+        if self.document.folds().is_hidden(y) {
+            self.document.folds_mut().open(y);
+        }
+
+        let x = self.cursor_column();
+        let width = self.terminal_size.width as usize;
+        let height = self.terminal_size.height as usize;
+        let row_width = self.document.row(y).map_or(0, |row| row.width_to(row.len()));
+        let vertical_margin = self.config.scrolloff.min(height.saturating_sub(1) / 2);
+        let horizontal_margin = self.config.sidescrolloff.min(width.saturating_sub(1) / 2);
+        // Don't scroll past the end of the document or row just to show empty margin
+        let margin_below = vertical_margin.min(self.document.len().saturating_sub(y));
+        let margin_right = horizontal_margin.min(row_width.saturating_sub(x));
+        let first_row = self.first_scrolled_row();
+        let folds = self.document.folds();
+        let offset = &mut self.offset;
+
+        offset.y = folds.shown_row(offset.y);
+
+        // Margins and the screen height count shown rows, folded ones don't take up room
+        let shown_above = y.saturating_sub(offset.y).saturating_sub(folds.hidden_between(offset.y, y));
+        let back = |rows: usize| folds.step(y, -rows.try_into().unwrap_or(isize::MAX), y);
+
+        // The pinned header is always visible, so moving onto it doesn't scroll
+        if y < first_row {
+            offset.y = offset.y.max(first_row);
+        } else if y < offset.y || shown_above < vertical_margin {
+            offset.y = back(vertical_margin);
+        } else if shown_above.saturating_add(margin_below) >= height {
+            offset.y = back(height.saturating_sub(1).saturating_sub(margin_below));
+        }
+
+        offset.y = offset.y.max(first_row);
+
+        if x < offset.x.saturating_add(horizontal_margin) {
+            offset.x = x.saturating_sub(horizontal_margin);
+        } else if x.saturating_add(margin_right) >= offset.x.saturating_add(width) {
+            offset.x = x.saturating_add(margin_right).saturating_sub(width).saturating_add(1);
+        }
+    }
+}
+
+impl App {
+    pub fn new(cli: &Cli, piped_text: Option<&str>) -> Self {
+        let mut status = StatusQueue::default();
+        let config = load_config(cli, &mut status);
+        let background = config.theme_background().or_else(detect_background).unwrap_or(Background::Dark);
+        let theme = config.theme(background);
+
+        let mut buffers = open_buffers(cli, piped_text, &config, &mut status);
+        let archive = open_archive(cli, &mut status);
+        let side_by_side = open_side_by_side(cli, &mut status);
+
+        let prompt_history = load_or_warn(prompt_history_file(), PromptHistory::load, "prompt history", &mut status);
+        let snippets = load_or_warn(snippets_dir(), SnippetLibrary::load, "snippets", &mut status);
+
+        let first = buffers.remove(0);
+
+        let pending_key = startup_status(cli, piped_text, &first.document, buffers.len() + 1, &config, &mut status);
+        let staged_diff = load_staged_diff(&first.document, &config, &mut status);
+
+        Self {
+            current_screen: startup_screen(archive.as_ref(), side_by_side.as_ref()),
+            config,
+            cursor_position: first.cursor_position,
+            desired_column: None,
+            selection_anchor: None,
             offset: Position::default(),
             terminal_size: Rect::default(),
-            document,
-            status_message: StatusMessage::from(initial_status),
+            header_area: Rect::default(),
+            scrollbar_area: Rect::default(),
+            sidebar_area: Rect::default(),
+            gutter_area: Rect::default(),
+            tooltip: None,
+            overwrite: false,
+            pager: cli.view,
+            drawn_cursor_shape: None,
+            background,
+            theme,
+            dragging_scrollbar: false,
+            drag_scroll: None,
+            document: first.document,
+            buffers,
+            column_layout: None,
+            kill_ring: KillRing::default(),
+            register: None,
+            paste_history: PopupList::default(),
+            diff: Vec::new(),
+            diff_scroll: 0,
+            messages_scroll: 0,
+            undo_tree_index: 0,
+            completions: Vec::new(),
+            completion_list: PopupList::wrapping(),
+            last_edit: None,
+            insert_run: false,
+            hard_wrap: None,
+            spell_check: None,
+            spell: None,
+            spell_word: None,
+            spell_suggestions: Vec::new(),
+            spell_list: PopupList::default(),
+            timestamps: Vec::new(),
+            timestamp_list: PopupList::default(),
+            cut_run: false,
+            repeat_count: None,
+            pending_key,
+            meta_pending: false,
+            chord: Vec::new(),
+            chord_started: None,
+            status,
+            prompt: PromptState::default(),
+            prompt_history,
+            search: None,
+            search_regex: false,
+            in_files: false,
+            file_changes: Vec::new(),
+            file_changes_index: 0,
+            buffer_list: PopupList::default(),
+            build: None,
+            quickfix: None,
+            show_quickfix: false,
+            tag_matches: Vec::new(),
+            tag_list: PopupList::default(),
+            archive,
+            archive_list: PopupList::default(),
+            side_by_side_scroll: side_by_side.as_ref().and_then(|side_by_side| side_by_side.hunks.first()).map_or(0, |start| start.saturating_sub(DIFF_CONTEXT)),
+            side_by_side,
+            git_status: None,
+            git_untracked: None,
+            staged_diff,
+            git_branch: None,
+            clock: String::new(),
+            disk_watcher: DiskWatcher::new(),
+            snippets,
+            file_tree: None,
+            show_file_tree: false,
+            quit_after_save: false,
+            keep_editor_open: false,
+            privileged_save: false,
+            suspend: false,
             should_quit: false,
-            quit_times: QUIT_TIMES,
         }
     }
 }
 
-impl StatusMessage {
-    fn from(message: String) -> Self {
-        Self {
-            text: message,
-            time: Instant::now(),
+impl Buffer {
+    // Open a file named on the command line, where positions count from 1.
+    // Files over `large_file_size` MiB are streamed
+    /// # Errors
+    ///
+    /// Will return `Err` if the file can't be read, or it's on another machine or a URL and
+    /// downloading it fails
+    fn open(file: &FileArg, large_file_size: usize) -> Result<Self> {
+        let limit = u64::try_from(large_file_size).unwrap_or(u64::MAX).saturating_mul(1 << 20);
+        let too_large = large_file_size > 0 && fs::metadata(&file.path).is_ok_and(|metadata| metadata.len() > limit);
+        let mut document = if is_remote(&file.path) {
+            Document::open_remote(&file.path)?
+        } else if is_url(&file.path) {
+            Document::open_url(&file.path)?
+        } else if too_large {
+            Document::open_streamed(&file.path)?
+        } else {
+            Document::open(&file.path)?
+        };
+
+        document.load_to(file.line.unwrap_or(1).saturating_sub(1));
+
+        let y = file.line.unwrap_or(1).saturating_sub(1).min(document.len().saturating_sub(1));
+        let x = file.column.unwrap_or(1).saturating_sub(1).min(document.row(y).map_or(0, Row::len));
+
+        Ok(Self { document, cursor_position: Position { x, y }, offset: Position::default() })
+    }
+}
+
+impl From<Document> for Buffer {
+    fn from(document: Document) -> Self {
+        Self { document, cursor_position: Position::default(), offset: Position::default() }
+    }
+}
+
+// The nearest position to `position` that exists in `document`
+fn clamp_position(document: &Document, position: &Position) -> Position {
+    let y = position.y.min(document.len());
+    let x = position.x.min(document.row(y).map_or(0, Row::len));
+
+    Position { x, y }
+}
+// Esc, q and Ctrl-C close the read-only modals
+const fn is_close_key(pressed_key: KeyEvent) -> bool {
+    matches!(
+        (pressed_key.modifiers, pressed_key.code),
+        (_, KeyCode::Esc | KeyCode::Char('q')) | (KeyModifiers::CONTROL, KeyCode::Char('c'))
+    )
+}
+fn common_prefix(candidates: &[String]) -> Option<String> {
+    let first = candidates.first()?;
+    let mut prefix_len = first.len();
+
+    for candidate in &candidates[1..] {
+        prefix_len = first
+            .char_indices()
+            .zip(candidate.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or_else(|| prefix_len.min(candidate.len()), |((index, _), _)| index.min(prefix_len));
+    }
+
+    Some(first[..prefix_len].to_owned())
+}
+// Something kept between sessions, empty when there's nowhere for it or it can't be read
+fn load_or_warn<T: Default>(path: Option<PathBuf>, load: impl Fn(&Path) -> Result<T>, name: &str, status: &mut StatusQueue) -> T {
+    path.map(|path| load(&path))
+        .transpose()
+        .unwrap_or_else(|error| {
+            status.warn(format!("Error reading {name}: {error:#}"));
+            None
+        })
+        .unwrap_or_default()
+}
+// The buffers named on the command line or piped in, or one empty buffer
+fn open_buffers(cli: &Cli, piped_text: Option<&str>, config: &Config, status: &mut StatusQueue) -> Vec<Buffer> {
+    let mut buffers: Vec<Buffer> = piped_text
+        .map(|text| Buffer::from(Document::from_text(text)))
+        .into_iter()
+        .chain(cli.files.iter().filter(|file| ArchiveKind::detect(&file.path).is_none()).filter_map(|file| {
+            if is_new_file(&file.path) {
+                return Some(Buffer::from(new_file(&file.path, config, status)));
+            }
+
+            Buffer::open(file, config.large_file_size)
+                .inspect_err(|error| status.error(format!("Error opening {}: {error}", file.path)))
+                .ok()
+        }))
+        .collect();
+
+    if buffers.is_empty() {
+        buffers.push(Buffer::from(Document::default()));
+    }
+
+    for buffer in &mut buffers {
+        // Streamed files and URLs open read-only by themselves
+        if cli.readonly {
+            buffer.document.set_read_only(true);
+        }
+
+        if !cli.readonly {
+            buffer.document.lock();
+        }
+
+        // Nothing is edited while paging, so piped in text doesn't need saving either
+        if cli.view {
+            buffer.document.set_dirty(false);
+        }
+
+        if let Some(final_newline) = config.insert_final_newline {
+            buffer.document.set_final_newline(final_newline);
+        }
+
+        if config.modelines {
+            buffer.document.apply_modeline();
+        }
+
+        if let Some(dir) = undo_dir().filter(|_| config.undo_file) {
+            if let Err(error) = buffer.document.load_undo(&dir) {
+                status.warn(format!("Error reading undo file: {error}"));
+            }
         }
+
+        if let Some(dir) = fold_dir() {
+            if let Err(error) = buffer.document.load_folds(&dir) {
+                status.warn(format!("Error reading fold file: {error}"));
+            }
+        }
+    }
+
+    buffers
+}
+
+fn is_new_file(path: &str) -> bool {
+    !is_remote(path) && !is_url(path) && !Path::new(path).exists()
+}
+// A file named on the command line that doesn't exist yet, filled from the template for its extension
+fn new_file(path: &str, config: &Config, status: &mut StatusQueue) -> Document {
+    let template = match templates_dir().filter(|_| config.templates).map(|dir| load_template(&dir, path)) {
+        Some(Ok(template)) => template.unwrap_or_default(),
+        Some(Err(error)) => {
+            status.warn(format!("Error reading the template: {error:#}"));
+            String::new()
+        },
+        None => String::new(),
+    };
+
+    if template.is_empty() {
+        return Document::new_file(path, "");
+    }
+
+    let date_format = config.timestamps.iter().find(|(name, _)| name == "date").map_or("%Y-%m-%d", |(_, format)| format.as_str());
+    let placeholders = Placeholders {
+        file_name: path.to_owned(),
+        date: format_now(date_format).unwrap_or_default(),
+        year: format_now("%Y").unwrap_or_default(),
+        author: author(config.author.as_deref()),
+    };
+
+    Document::new_file(path, &placeholders.fill(&template))
+}
+// The first archive named on the command line is listed for picking files from, the others are
+// left for opening later
+fn open_archive(cli: &Cli, status: &mut StatusQueue) -> Option<Archive> {
+    let file = cli.files.iter().find(|file| ArchiveKind::detect(&file.path).is_some())?;
+
+    Archive::open(&file.path)
+        .inspect_err(|error| status.error(format!("Error opening {}: {error:#}", file.path)))
+        .ok()
+        .filter(|archive| !archive.members.is_empty())
+}
+
+// The config with the project's settings and `--theme` applied over it. The project is found from the
+// first file named on the command line, or the working directory
+fn load_config(cli: &Cli, status: &mut StatusQueue) -> Config {
+    let mut config = Config::load(cli.config.as_deref()).unwrap_or_else(|error| {
+        status.error(format!("Config error: {error:#}"));
+        Config::default()
+    });
+
+    let start = cli.files
+        .first()
+        .filter(|file| !is_remote(&file.path) && !is_url(&file.path))
+        .map_or_else(|| PathBuf::from("."), |file| PathBuf::from(&file.path));
+
+    if let Some(path) = find_project_config(&start) {
+        match config.merge_project_file(&path) {
+            Ok(()) => tracing::info!(path = %path.display(), "project config loaded"),
+            Err(error) => status.error(format!("Project config error: {error:#}")),
+        }
+    }
+
+    if let Some(theme) = &cli.theme {
+        if let Err(error) = config.set_theme(theme) {
+            status.error(format!("Config error: {error:#}"));
+        }
+    }
+
+    if let Some(support) = config.color_support {
+        set_color_support(support);
+    }
+
+    config
+}
+fn load_staged_diff(document: &Document, config: &Config, status: &mut StatusQueue) -> Vec<DiffLine> {
+    if !config.commit_staged_diff || !document.file_type.is_commit_message() {
+        return Vec::new();
+    }
+
+    staged_diff().unwrap_or_else(|error| {
+        status.warn(format!("Error reading the staged changes: {error:#}"));
+        Vec::new()
+    })
+}
+
+// Files compared with `--diff` are shown first, then the list of an archive's files to open
+const fn startup_screen(archive: Option<&Archive>, side_by_side: Option<&SideBySide>) -> CurrentScreen {
+    if side_by_side.is_some() {
+        CurrentScreen::SideBySide
+    } else if archive.is_some() {
+        CurrentScreen::ArchiveList
+    } else {
+        CurrentScreen::Main
+    }
+}
+
+fn open_side_by_side(cli: &Cli, status: &mut StatusQueue) -> Option<SideBySide> {
+    let (true, [old, new]) = (cli.diff, cli.files.as_slice()) else {
+        return None;
+    };
+
+    let side_by_side = SideBySide::open(&old.path, &new.path)
+        .inspect_err(|error| status.error(format!("Error comparing {} and {}: {error}", old.path, new.path)))
+        .ok()?;
+
+    if side_by_side.hunks.is_empty() {
+        status.info(format!("{} and {} are the same.", old.path, new.path));
+    }
+
+    Some(side_by_side)
+}
+
+// Greet with what was read, or ask whether to load all of a large file. The prompt takes the place
+// of the welcome, so the first key answers it while it's on screen
+fn startup_status(cli: &Cli, piped_text: Option<&str>, first: &Document, buffer_count: usize, config: &Config, status: &mut StatusQueue) -> Option<PendingKey> {
+    if first.is_streamed() && !cli.readonly {
+        let name = first.file_name.as_deref().unwrap_or_default();
+
+        status.warn(format!(
+            "{name} is over {} MiB, so it's read-only and read as you scroll. Load all of it for editing? (y/n): ",
+            config.large_file_size
+        ));
+
+        return Some(PendingKey::LoadLargeFile);
+    }
+
+    let mut initial_status = String::from("Welcome to IronN.");
+
+    if piped_text.is_some() {
+        initial_status = format!("Read {} lines from stdin.", first.len());
+    } else if first.file_name.as_deref().is_some_and(is_new_file) {
+        initial_status = String::from(if first.is_dirty() { "New file, from its template." } else { "New file." });
+    } else if first.file_name.is_some() {
+        initial_status = format!("Read {} lines.", first.len());
+    }
+
+    if buffer_count > 1 {
+        initial_status = format!("{initial_status} {buffer_count} buffers open.");
+    }
+
+    status.info(initial_status);
+
+    None
+}
+
+// Pastes from some terminals break lines with a lone carriage return, or with CRLF
+fn paste_line_breaks(text: &str) -> Cow<'_, str> {
+    if text.contains('\r') {
+        Cow::Owned(text.replace("\r\n", "\n").replace('\r', "\n"))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+// The character a key sends to a terminal, Control with a letter giving the control character
+fn verbatim_char(key: KeyEvent) -> Option<char> {
+    match key.code {
+        KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::CONTROL) => match c {
+            '@' | ' ' => Some('\0'),
+            'a'..='z' | 'A'..='Z' | '['..='_' => char::from_u32(u32::from(c.to_ascii_uppercase()) & 0x1f),
+            '?' => Some('\x7f'),
+            _ => None,
+        },
+        KeyCode::Char(c) => Some(c),
+        KeyCode::Tab => Some('\t'),
+        KeyCode::Enter => Some('\r'),
+        KeyCode::Esc => Some('\x1b'),
+        KeyCode::Backspace => Some('\x7f'),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pasted_carriage_returns_break_lines_but_verbatim_ones_dont() {
+        let mut document = Document::default();
+        let end = document.insert_str(&Position::default(), &paste_line_breaks("a\rb\r\nc"));
+
+        assert_eq!(document.lines(0, document.len()), ["a", "b", "c"]);
+
+        let carriage_return = verbatim_char(KeyEvent::from(KeyCode::Enter)).unwrap();
+
+        document.insert_str(&end, carriage_return.encode_utf8(&mut [0; 4]));
+
+        assert_eq!(document.lines(0, document.len()), ["a", "b", "c\r"]);
     }
-}
\ No newline at end of file
+}
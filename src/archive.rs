@@ -0,0 +1,237 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Component, Path};
+use std::process::{Command, Output, Stdio};
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+use crate::save::private_directory;
+
+// Buffers opened from an archive are named `archive.zip!/member`
+const MEMBER_SEPARATOR: &str = "!/";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    TarGz,
+}
+
+impl ArchiveKind {
+    #[must_use]
+    pub fn detect(path: &str) -> Option<Self> {
+        let path = Path::new(path);
+        let has_extension = |path: &Path, wanted: &str| path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case(wanted));
+
+        if has_extension(path, "zip") {
+            Some(Self::Zip)
+        } else if has_extension(path, "tgz") || (has_extension(path, "gz") && path.file_stem().is_some_and(|stem| has_extension(Path::new(stem), "tar"))) {
+            Some(Self::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+// An archive named on the command line and the files in it, read with `unzip` or `tar`
+#[derive(Clone, Debug)]
+pub struct Archive {
+    pub path: String,
+    pub kind: ArchiveKind,
+    pub members: Vec<String>,
+}
+
+#[allow(clippy::missing_const_for_fn)]
+impl Archive {
+    // Directories aren't listed, only the files that can be opened
+    /// # Errors
+    ///
+    /// Will return `Err` if `path` isn't a `.zip` or `.tar.gz` archive, or it can't be listed
+    pub fn open(path: &str) -> Result<Self> {
+        let kind = ArchiveKind::detect(path).ok_or_else(|| eyre!("{path} isn't a .zip or .tar.gz archive"))?;
+        let output = match kind {
+            ArchiveKind::Zip => run("unzip", &["-Z1", "--", path])?,
+            ArchiveKind::TarGz => run("tar", &["-tz", "-f", &tar_archive(path)])?,
+        };
+
+        let members = String::from_utf8_lossy(&output)
+            .lines()
+            .filter(|member| !member.is_empty() && !member.ends_with('/'))
+            .map(str::to_owned)
+            .collect();
+
+        tracing::info!(path, ?kind, "archive listed");
+
+        Ok(Self { path: path.to_owned(), kind, members })
+    }
+    #[must_use]
+    pub fn can_write(&self) -> bool {
+        self.kind == ArchiveKind::Zip
+    }
+}
+
+// The name a buffer holding `member` of `archive` gets
+#[must_use]
+pub fn member_name(archive: &str, member: &str) -> String {
+    format!("{archive}{MEMBER_SEPARATOR}{member}")
+}
+// Split a buffer name back into its archive and member, when it names one
+#[must_use]
+pub fn split_member(file_name: &str) -> Option<(&str, &str)> {
+    let (archive, member) = file_name.split_once(MEMBER_SEPARATOR)?;
+
+    ArchiveKind::detect(archive).filter(|_| !member.is_empty() && Path::new(archive).is_file())?;
+
+    Some((archive, member))
+}
+// Extract one member's bytes
+/// # Errors
+///
+/// Will return `Err` if the archive can't be read or doesn't have `member`
+pub fn read_member(archive: &str, member: &str) -> Result<Vec<u8>> {
+    match ArchiveKind::detect(archive) {
+        Some(ArchiveKind::Zip) => run("unzip", &["-p", "--", archive, &unzip_literal(member)]),
+        Some(ArchiveKind::TarGz) => run("tar", &["-xzO", "-f", &tar_archive(archive), "--", member]),
+        None => Err(eyre!("{archive} isn't a .zip or .tar.gz archive")),
+    }
+}
+// Replace one member of a zip archive. `zip` takes member names from paths relative to where it
+// runs, so the contents are staged under a scratch directory with the member's path
+/// # Errors
+///
+/// Will return `Err` if the archive is a `.tar.gz`, the member's name would stage it outside the
+/// scratch directory, or `zip` isn't installed or fails
+pub fn write_member(archive: &str, member: &str, contents: &[u8]) -> Result<()> {
+    if ArchiveKind::detect(archive) != Some(ArchiveKind::Zip) {
+        return Err(eyre!("only members of .zip archives can be saved back"));
+    }
+
+    if !is_relative_member(member) {
+        return Err(eyre!("{member} can't be saved back, its name leaves the archive"));
+    }
+
+    let archive = fs::canonicalize(archive)?;
+    let scratch = private_directory("archive")?;
+    let staged = scratch.join(member);
+
+    let result = staged
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| fs::write(&staged, contents))
+        .map_err(Into::into)
+        .and_then(|()| {
+            let mut zip = Command::new("zip");
+
+            zip.current_dir(&scratch).arg("-q").arg(&archive).arg("--").arg(member);
+
+            output(&mut zip, "zip").map(drop)
+        });
+
+    let _ = fs::remove_dir_all(&scratch);
+
+    tracing::info!(archive = %archive.display(), member, ok = result.is_ok(), "archive member written");
+
+    result
+}
+
+// Only names made of plain components stay inside the directory they're joined to
+fn is_relative_member(member: &str) -> bool {
+    Path::new(member).components().all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+}
+
+// `unzip` matches member names as wildcards, a backslash makes a character plain
+fn unzip_literal(member: &str) -> String {
+    let mut literal = String::with_capacity(member.len());
+
+    for c in member.chars() {
+        if matches!(c, '[' | ']' | '*' | '?' | '\\') {
+            literal.push('\\');
+        }
+
+        literal.push(c);
+    }
+
+    literal
+}
+// `tar` reads `-` as stdin and `host:file` as a remote archive, neither of which a relative path
+// starting with `./` can be
+fn tar_archive(path: &str) -> String {
+    if Path::new(path).is_absolute() { path.to_owned() } else { format!("./{path}") }
+}
+
+fn run(program: &str, args: &[&str]) -> Result<Vec<u8>> {
+    output(Command::new(program).args(args), program)
+}
+
+// Stdout of a finished command, or the first line it complained with
+fn output(command: &mut Command, program: &str) -> Result<Vec<u8>> {
+    let Output { status, stdout, stderr } = command
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|error| match error.kind() {
+            ErrorKind::NotFound => eyre!("{program} isn't installed"),
+            _ => error.into(),
+        })?;
+
+    if status.success() {
+        return Ok(stdout);
+    }
+
+    let error = String::from_utf8_lossy(&stderr);
+    let reason = error.lines().find(|line| !line.trim().is_empty()).unwrap_or("failed");
+
+    Err(eyre!("{program}: {}", reason.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kinds_and_names() {
+        assert_eq!(ArchiveKind::detect("a.ZIP"), Some(ArchiveKind::Zip));
+        assert_eq!(ArchiveKind::detect("a.tar.gz"), Some(ArchiveKind::TarGz));
+        assert_eq!(ArchiveKind::detect("a.tgz"), Some(ArchiveKind::TarGz));
+        assert_eq!(ArchiveKind::detect("a.gz"), None);
+        assert_eq!(member_name("a.zip", "src/main.rs"), "a.zip!/src/main.rs");
+        assert_eq!(split_member("missing.zip!/x"), None);
+    }
+
+    #[test]
+    fn members_stay_inside_the_archive() {
+        assert!(is_relative_member("src/./main.rs"));
+        assert!(!is_relative_member("../escape"));
+        assert!(!is_relative_member("/etc/passwd"));
+        assert!(!is_relative_member("a/../../b"));
+    }
+
+    #[test]
+    fn names_are_passed_literally() {
+        assert_eq!(unzip_literal(r"a[1]*?\.txt"), r"a\[1\]\*\?\\.txt");
+        assert_eq!(tar_archive("-rf.tar.gz"), "./-rf.tar.gz");
+        assert_eq!(tar_archive("host:a.tgz"), "./host:a.tgz");
+        assert_eq!(tar_archive("/tmp/a.tgz"), "/tmp/a.tgz");
+    }
+
+    #[test]
+    fn wildcard_members_read_only_themselves() {
+        let Ok(scratch) = private_directory("archive-test") else {
+            return;
+        };
+
+        for (name, contents) in [("a[1].txt", "one"), ("a1.txt", "two"), ("*.txt", "star")] {
+            fs::write(scratch.join(name), contents).unwrap();
+        }
+
+        let archive = scratch.join("test.zip");
+        let zipped = Command::new("zip").current_dir(&scratch).args(["-q", "test.zip", "a[1].txt", "a1.txt", "*.txt"]).status();
+
+        if zipped.is_ok_and(|status| status.success()) {
+            let archive = archive.to_string_lossy();
+
+            assert_eq!(read_member(&archive, "a[1].txt").unwrap(), b"one");
+            assert_eq!(read_member(&archive, "*.txt").unwrap(), b"star");
+            assert_eq!(Archive::open(&archive).unwrap().members.len(), 3);
+        }
+
+        fs::remove_dir_all(&scratch).unwrap();
+    }
+}
@@ -0,0 +1,189 @@
+use std::path::Path;
+use std::process::Command;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use serde_json::Value;
+use crate::regex::Regex;
+
+// `file:line:column: message` as gcc, clang, go and most linters print it
+const LOCATION_PATTERN: &str = r"^(.+?):(\d+):(\d+):\s*(?:(error|warning|note)[^:]*:\s*)?(.*)$";
+
+// A problem the compiler found, lines and columns counting from 1 as it prints them
+#[derive(Clone, Debug)]
+pub struct BuildError {
+    pub path: String,
+    pub line: usize,
+    pub column: usize,
+    pub severity: BuildSeverity,
+    pub message: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BuildSeverity {
+    Error,
+    Warning,
+    Note,
+}
+
+// How a finished build went
+pub struct BuildResult {
+    pub command: String,
+    pub success: bool,
+    pub errors: Vec<BuildError>,
+}
+
+// A build running on another thread
+pub struct BuildJob {
+    receiver: Receiver<Result<BuildResult, String>>,
+}
+
+impl BuildJob {
+    // Run `command` through the shell in the working directory
+    #[must_use]
+    pub fn spawn(command: &str) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let command = command.to_owned();
+
+        thread::spawn(move || {
+            let result = Command::new("sh")
+                .args(["-c", &command])
+                .output()
+                .map(|output| {
+                    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+
+                    text.push('\n');
+                    text.push_str(&String::from_utf8_lossy(&output.stderr));
+
+                    BuildResult { errors: parse_output(&text), success: output.status.success(), command }
+                })
+                .map_err(|error| error.to_string());
+
+            // The app may have quit without waiting for the build
+            let _ = sender.send(result);
+        });
+
+        Self { receiver }
+    }
+    // The outcome once the build has finished
+    #[must_use]
+    pub fn poll(&self) -> Option<Result<BuildResult, String>> {
+        match self.receiver.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err("the build thread stopped".to_owned())),
+        }
+    }
+}
+
+// The command building the project in `root`, guessed from the files it has when none is configured
+#[must_use]
+pub fn default_command(root: &Path) -> Option<&'static str> {
+    if root.join("Cargo.toml").exists() {
+        Some("cargo build --message-format=json")
+    } else if root.join("Makefile").exists() || root.join("makefile").exists() {
+        Some("make")
+    } else {
+        None
+    }
+}
+
+// Errors and warnings from cargo's or rustc's JSON messages, or from lines giving a file, line and column
+#[must_use]
+pub fn parse_output(text: &str) -> Vec<BuildError> {
+    let location = Regex::new(LOCATION_PATTERN).ok();
+
+    text.lines()
+        .filter_map(|line| {
+            if line.starts_with('{') {
+                serde_json::from_str(line).ok().and_then(|json| parse_json_message(&json))
+            } else {
+                parse_location_line(location.as_ref()?, line)
+            }
+        })
+        .collect()
+}
+
+// `{"reason":"compiler-message","message":{...}}` from cargo, or the message on its own from rustc.
+// Messages without a place in the source, like the count of errors at the end, are left out
+fn parse_json_message(json: &Value) -> Option<BuildError> {
+    let message = match json.get("reason") {
+        Some(reason) if reason.as_str() == Some("compiler-message") => json.get("message")?,
+        Some(_) => return None,
+        None => json,
+    };
+
+    let severity = match message.get("level")?.as_str()? {
+        "error" | "error: internal compiler error" => BuildSeverity::Error,
+        "warning" => BuildSeverity::Warning,
+        "note" | "help" => BuildSeverity::Note,
+        _ => return None,
+    };
+
+    let spans = message.get("spans")?.as_array()?;
+    let span = spans
+        .iter()
+        .find(|span| span.get("is_primary").and_then(Value::as_bool) == Some(true))
+        .or_else(|| spans.first())?;
+
+    Some(BuildError {
+        path: span.get("file_name")?.as_str()?.to_owned(),
+        line: usize::try_from(span.get("line_start")?.as_u64()?).ok()?,
+        column: usize::try_from(span.get("column_start")?.as_u64()?).ok()?,
+        severity,
+        message: message.get("message")?.as_str()?.to_owned(),
+    })
+}
+
+fn parse_location_line(location: &Regex, line: &str) -> Option<BuildError> {
+    let captures = location.captures_at(line, 0)?;
+    let group = |index| captures.get(index).map(|(start, end)| &line[start..end]);
+
+    let severity = match group(4) {
+        Some("warning") => BuildSeverity::Warning,
+        Some("note") => BuildSeverity::Note,
+        _ => BuildSeverity::Error,
+    };
+
+    Some(BuildError {
+        path: group(1)?.to_owned(),
+        line: group(2)?.parse().ok()?,
+        column: group(3)?.parse().ok()?,
+        severity,
+        message: group(5).unwrap_or_default().to_owned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(error: &BuildError) -> (&str, usize, usize, BuildSeverity, &str) {
+        (&error.path, error.line, error.column, error.severity, &error.message)
+    }
+
+    #[test]
+    fn cargo_messages() {
+        let output = [
+            r#"{"reason":"compiler-artifact","target":{"name":"ironn"}}"#,
+            r#"{"reason":"compiler-message","message":{"level":"warning","message":"unused \"x\"","spans":[{"file_name":"src/a.rs","line_start":3,"column_start":9,"is_primary":false},{"file_name":"src/b.rs","line_start":12,"column_start":5,"is_primary":true}]}}"#,
+            r#"{"reason":"compiler-message","message":{"level":"error","message":"aborting due to 1 previous error","spans":[]}}"#,
+            r#"{"level":"error","message":"mismatched types","spans":[{"file_name":"main.rs","line_start":1,"column_start":2}]}"#,
+            r#"{"reason":"compiler-message","message":"#,
+        ]
+        .join("\n");
+        let errors = parse_output(&output);
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(summary(&errors[0]), ("src/b.rs", 12, 5, BuildSeverity::Warning, "unused \"x\""));
+        assert_eq!(summary(&errors[1]), ("main.rs", 1, 2, BuildSeverity::Error, "mismatched types"));
+    }
+
+    #[test]
+    fn location_lines() {
+        let errors = parse_output("make: entering\nsrc/x.c:4:7: warning: unused variable 'y'\nlib.go:10:2: undefined: z\n");
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(summary(&errors[0]), ("src/x.c", 4, 7, BuildSeverity::Warning, "unused variable 'y'"));
+        assert_eq!(summary(&errors[1]), ("lib.go", 10, 2, BuildSeverity::Error, "undefined: z"));
+    }
+}
@@ -0,0 +1,231 @@
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+
+// Results are rounded to this many decimal places, so 0.1 + 0.2 shows as 0.3
+const DECIMALS: usize = 12;
+
+// Characters an expression under the cursor is made of
+const EXPRESSION_CHARS: &str = "0123456789.+-*/%^()×÷ \t";
+
+// Arithmetic on decimal numbers with `+ - * / %`, `^` or `**` for powers, parentheses and unary minus.
+// `×` and `÷` work too, as prose often has them
+/// # Errors
+///
+/// Will return `Err` if the expression doesn't parse, divides by zero or overflows
+pub fn evaluate(expression: &str) -> Result<f64> {
+    let mut parser = Parser { chars: expression.chars().collect(), index: 0 };
+
+    parser.skip_spaces();
+
+    if parser.peek().is_none() {
+        return Err(eyre!("the expression is empty"));
+    }
+
+    let value = parser.sum()?;
+
+    if let Some(c) = parser.peek() {
+        return Err(eyre!("unexpected '{c}' at column {}", parser.index + 1));
+    }
+
+    if !value.is_finite() {
+        return Err(eyre!("the result is too large"));
+    }
+
+    Ok(value)
+}
+
+// Whole numbers without a decimal point, others without trailing zeros
+#[must_use]
+pub fn format_number(value: f64) -> String {
+    let text = format!("{value:.DECIMALS$}");
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+
+    if text == "-0" { "0".to_owned() } else { text.to_owned() }
+}
+
+// The graphemes of the expression the cursor at grapheme `x` is on or just after, without the
+// spaces around it. Expressions are ASCII apart from `×` and `÷`, which are single graphemes too
+#[must_use]
+pub fn expression_at(graphemes: &[&str], x: usize) -> Option<(usize, usize)> {
+    let is_expression = |index: usize| graphemes.get(index).is_some_and(|grapheme| grapheme.chars().all(|c| EXPRESSION_CHARS.contains(c)));
+    let x = if is_expression(x) { x } else { x.checked_sub(1).filter(|x| is_expression(*x))? };
+
+    let mut start = x;
+    let mut end = x + 1;
+
+    while start > 0 && is_expression(start - 1) {
+        start -= 1;
+    }
+
+    while is_expression(end) {
+        end += 1;
+    }
+
+    while start < end && graphemes[start].trim().is_empty() {
+        start += 1;
+    }
+
+    while end > start && graphemes[end - 1].trim().is_empty() {
+        end -= 1;
+    }
+
+    // A lone operator or parenthesis isn't worth evaluating
+    graphemes[start..end].iter().any(|grapheme| grapheme.chars().any(|c| c.is_ascii_digit())).then_some((start, end))
+}
+
+// Recursive descent, one method for each level of precedence
+struct Parser {
+    chars: Vec<char>,
+    index: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).copied()
+    }
+    fn skip_spaces(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.index += 1;
+        }
+    }
+    // Take `c` and the spaces after it when it's next
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() != Some(c) {
+            return false;
+        }
+
+        self.index += 1;
+        self.skip_spaces();
+
+        true
+    }
+    fn at_double_star(&self) -> bool {
+        self.chars.get(self.index..self.index + 2) == Some(&['*', '*'])
+    }
+    fn sum(&mut self) -> Result<f64> {
+        let mut value = self.product()?;
+
+        loop {
+            if self.eat('+') {
+                value += self.product()?;
+            } else if self.eat('-') {
+                value -= self.product()?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+    fn product(&mut self) -> Result<f64> {
+        let mut value = self.power()?;
+
+        loop {
+            // `**` is a power rather than two multiplications
+            if !self.at_double_star() && (self.eat('*') || self.eat('×')) {
+                value *= self.power()?;
+            } else if self.eat('/') || self.eat('÷') {
+                value /= nonzero(self.power()?)?;
+            } else if self.eat('%') {
+                value %= nonzero(self.power()?)?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+    // Powers group to the right, so 2^3^2 is 2^9
+    fn power(&mut self) -> Result<f64> {
+        let base = self.unary()?;
+
+        if self.eat('^') || (self.at_double_star() && self.eat('*') && self.eat('*')) {
+            return Ok(base.powf(self.power()?));
+        }
+
+        Ok(base)
+    }
+    fn unary(&mut self) -> Result<f64> {
+        if self.eat('-') {
+            return Ok(-self.unary()?);
+        }
+
+        if self.eat('+') {
+            return self.unary();
+        }
+
+        self.primary()
+    }
+    fn primary(&mut self) -> Result<f64> {
+        if self.eat('(') {
+            let value = self.sum()?;
+
+            if !self.eat(')') {
+                return Err(eyre!("missing ')' at column {}", self.index + 1));
+            }
+
+            return Ok(value);
+        }
+
+        let start = self.index;
+
+        while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '.') {
+            self.index += 1;
+        }
+
+        let number: String = self.chars[start..self.index].iter().collect();
+
+        self.skip_spaces();
+
+        match (number.parse(), self.chars.get(start)) {
+            (Ok(value), _) => Ok(value),
+            (Err(_), Some(c)) if number.is_empty() => Err(eyre!("unexpected '{c}' at column {}", start + 1)),
+            (Err(_), None) => Err(eyre!("the expression ends too early")),
+            (Err(_), Some(_)) => Err(eyre!("{number} isn't a number")),
+        }
+    }
+}
+
+fn nonzero(value: f64) -> Result<f64> {
+    if value == 0.0 { Err(eyre!("division by zero")) } else { Ok(value) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calculate(expression: &str) -> String {
+        format_number(evaluate(expression).unwrap())
+    }
+
+    #[test]
+    fn precedence_and_grouping() {
+        assert_eq!(calculate("1 + 2 * 3"), "7");
+        assert_eq!(calculate("(1 + 2) * 3"), "9");
+        assert_eq!(calculate("2 ^ 3 ^ 2"), "512");
+        assert_eq!(calculate("2 ** 3 * 2"), "16");
+        assert_eq!(calculate("-2 ^ 2"), "4");
+        assert_eq!(calculate("7 % 4 - -1"), "4");
+        assert_eq!(calculate("6 × 7 ÷ 2"), "21");
+        assert_eq!(calculate("0.1 + 0.2"), "0.3");
+        assert_eq!(calculate("1 / 8"), "0.125");
+    }
+
+    #[test]
+    fn errors() {
+        let error = |expression: &str| evaluate(expression).unwrap_err().to_string();
+
+        assert_eq!(error(""), "the expression is empty");
+        assert_eq!(error("1 / (2 - 2)"), "division by zero");
+        assert_eq!(error("(1 + 2"), "missing ')' at column 7");
+        assert_eq!(error("1 +"), "the expression ends too early");
+        assert_eq!(error("2 x 3"), "unexpected 'x' at column 3");
+        assert_eq!(error("1.2.3"), "1.2.3 isn't a number");
+        assert_eq!(error("10 ^ 400"), "the result is too large");
+    }
+
+    #[test]
+    fn expression_under_the_cursor() {
+        let graphemes: Vec<&str> = "total: 12 * 3 apples".split("").filter(|grapheme| !grapheme.is_empty()).collect();
+
+        assert_eq!(expression_at(&graphemes, 9), Some((7, 13)));
+        assert_eq!(expression_at(&graphemes, 13), Some((7, 13)));
+        assert_eq!(expression_at(&graphemes, 2), None);
+    }
+}
@@ -0,0 +1,17 @@
+use std::io::{stdout, Write};
+use color_eyre::Result;
+use crate::document::Document;
+
+// Print a file with ANSI syntax colors and exit, without starting the editor
+pub fn print(file_name: &str) -> Result<()> {
+    let document = Document::open(file_name)?;
+    let mut stdout = stdout().lock();
+
+    for row in document.highlighted_rows() {
+        writeln!(stdout, "{}", row.render_highlighted(0, row.width_to(row.len())))?;
+    }
+
+    stdout.flush()?;
+
+    Ok(())
+}
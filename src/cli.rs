@@ -0,0 +1,245 @@
+use std::io::{stdin, IsTerminal};
+#[cfg(test)]
+use std::iter;
+use std::path::{Path, PathBuf};
+use clap::builder::PossibleValuesParser;
+use clap::Parser;
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+use crate::config::THEMES;
+use crate::remote::{is_remote, is_url};
+
+const USAGE: &str = "ironn [OPTIONS] [[+LINE[,COLUMN]] FILE[:LINE[:COLUMN]]]... [-- FILE...]";
+
+const AFTER_HELP: &str = "\
+FILE can be an sftp://[USER@]HOST[:PORT]/PATH URI, it's copied with the sftp client and uploaded on save.
+An http:// or https:// URL is fetched with curl into a read-only buffer.
+A .zip or .tar.gz archive lists its files to open, read-only or, for a zip, to edit and save back.
+Files after -- are taken as they're named, so ones starting with + or - can be opened.";
+
+// The arguments as clap reads them, turned into a `Cli` once positions are matched up with files
+#[derive(Parser)]
+#[command(name = "ironn", version, override_usage = USAGE, after_help = AFTER_HELP)]
+#[allow(clippy::struct_excessive_bools)]
+struct Args {
+    #[arg(short = 'R', long, help = "Open the files without allowing edits")]
+    readonly: bool,
+    #[arg(long, help = "Page through the files like less: read-only, Space and b page, g and G jump, / searches, q quits")]
+    view: bool,
+    #[arg(long, value_name = "PATH", help = "Read settings from PATH instead of the default config file")]
+    config: Option<PathBuf>,
+    #[arg(long, value_name = "NAME", value_parser = PossibleValuesParser::new(THEMES), help = "Use the color theme NAME")]
+    theme: Option<String>,
+    #[arg(long, help = "Compare the two files side by side, n and p move between changes, Esc edits them")]
+    diff: bool,
+    #[arg(long, help = "Print the files with syntax colors instead of editing them")]
+    cat: bool,
+    #[arg(long, value_name = "PATH", help = "Run the edit commands in PATH on the files without opening the editor")]
+    script: Option<String>,
+    #[arg(long, value_name = "PATH", help = "Append debug logs to PATH, RUST_LOG sets which ones")]
+    log: Option<PathBuf>,
+    #[arg(value_name = "FILE", help = "Files to open, a +LINE[,COLUMN] before one opens it there and - reads the buffer from stdin")]
+    files: Vec<String>,
+    #[arg(last = true, hide = true)]
+    literal_files: Vec<String>,
+}
+
+// A file named on the command line, with the 1-based position to open it at
+#[derive(Default)]
+pub struct FileArg {
+    pub path: String,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+#[derive(Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Cli {
+    pub readonly: bool,
+    pub view: bool,
+    pub cat: bool,
+    pub diff: bool,
+    pub stdin: bool,
+    pub config: Option<PathBuf>,
+    pub theme: Option<String>,
+    pub script: Option<String>,
+    pub log: Option<PathBuf>,
+    pub files: Vec<FileArg>,
+}
+
+impl Cli {
+    // Help, the version and arguments clap can't read are printed by clap, which exits
+    /// # Errors
+    ///
+    /// Will return `Err` for a `+LINE` without a file after it, invalid line numbers, and options
+    /// missing the files they work on
+    pub fn from_env() -> Result<Self> {
+        Self::from_args(Args::parse())
+    }
+    /// # Errors
+    ///
+    /// Will return `Err` for arguments clap can't read, and those `from_env` rejects
+    #[cfg(test)]
+    pub fn parse(args: &[&str]) -> Result<Self> {
+        Self::from_args(Args::try_parse_from(iter::once("ironn").chain(args.iter().copied()))?)
+    }
+    fn from_args(args: Args) -> Result<Self> {
+        let mut cli = Self {
+            readonly: args.readonly || args.view,
+            view: args.view,
+            cat: args.cat,
+            diff: args.diff,
+            config: args.config,
+            theme: args.theme,
+            script: args.script,
+            log: args.log,
+            ..Self::default()
+        };
+        let mut position = None;
+
+        for arg in args.files {
+            if arg == "-" {
+                cli.stdin = true;
+                continue;
+            }
+
+            if let Some(jump) = arg.strip_prefix('+') {
+                position = Some(parse_position(jump).ok_or_else(|| eyre!("invalid line number '{arg}'"))?);
+                continue;
+            }
+
+            let mut file = split_position(&arg);
+
+            // An explicit +LINE[,COLUMN] wins over one written after the file name
+            if let Some((line, column)) = position.take() {
+                file.line = Some(line);
+                file.column = column;
+            }
+
+            cli.files.push(file);
+        }
+
+        // The file a +LINE is for can be the first one after `--`
+        let mut literal_files = args.literal_files.into_iter();
+
+        if let Some((line, column)) = position.take() {
+            let path = literal_files.next().ok_or_else(|| eyre!("a line number needs a file after it"))?;
+
+            cli.files.push(FileArg { path, line: Some(line), column });
+        }
+
+        cli.files.extend(literal_files.map(|path| FileArg { path, ..FileArg::default() }));
+
+        if cli.cat && cli.files.is_empty() {
+            return Err(eyre!("'--cat' needs a file to print"));
+        }
+
+        if cli.diff && cli.files.len() != 2 {
+            return Err(eyre!("'--diff' needs the two files to compare"));
+        }
+
+        if cli.script.is_some() && cli.files.is_empty() {
+            return Err(eyre!("'--script' needs a file to edit"));
+        }
+
+        Ok(cli)
+    }
+    // Read the buffer from stdin when asked to with `-`, or when something is piped in without a file
+    #[must_use]
+    pub fn reads_stdin(&self) -> bool {
+        self.stdin || (self.files.is_empty() && !stdin().is_terminal())
+    }
+}
+
+// Parse "LINE" or "LINE,COLUMN"
+fn parse_position(text: &str) -> Option<(usize, Option<usize>)> {
+    let (line, column) = text.split_once(',').map_or((text, None), |(line, column)| (line, Some(column)));
+    let column = column.map(str::parse).transpose().ok()?;
+
+    Some((line.parse().ok()?, column))
+}
+
+// Split "file:LINE" and "file:LINE:COLUMN", unless a file with the full name exists or it's a URL
+// or sftp path, where `host:22` or `host:8080` is a port and not a position
+fn split_position(arg: &str) -> FileArg {
+    let whole = FileArg { path: arg.to_owned(), ..FileArg::default() };
+
+    if is_url(arg) || is_remote(arg) || Path::new(arg).exists() {
+        return whole;
+    }
+
+    let parts: Vec<&str> = arg.rsplitn(3, ':').collect();
+    // The last part is always the path, even when it's made of digits
+    let numbers: Vec<&str> = parts[..parts.len() - 1]
+        .iter()
+        .copied()
+        .take_while(|part| !part.is_empty() && part.bytes().all(|byte| byte.is_ascii_digit()))
+        .collect();
+    let suffix_len: usize = numbers.iter().map(|number| number.len() + 1).sum();
+    let path = &arg[..arg.len() - suffix_len];
+
+    match numbers.as_slice() {
+        _ if path.is_empty() => whole,
+        [column, line] => FileArg { path: path.to_owned(), line: line.parse().ok(), column: column.parse().ok() },
+        [line] => FileArg { path: path.to_owned(), line: line.parse().ok(), column: None },
+        _ => whole,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(cli: &Cli) -> Vec<(&str, Option<usize>, Option<usize>)> {
+        cli.files.iter().map(|file| (file.path.as_str(), file.line, file.column)).collect()
+    }
+
+    #[test]
+    fn positions_parse_as_line_and_column() {
+        assert_eq!(parse_position("12"), Some((12, None)));
+        assert_eq!(parse_position("12,4"), Some((12, Some(4))));
+        assert_eq!(parse_position("x"), None);
+        assert_eq!(parse_position("3,"), None);
+    }
+
+    #[test]
+    fn positions_go_to_the_file_after_them() {
+        let cli = Cli::parse(&["+3,2", "a.rs", "b.rs:7:5", "c.rs:9"]).unwrap();
+
+        assert_eq!(positions(&cli), [("a.rs", Some(3), Some(2)), ("b.rs", Some(7), Some(5)), ("c.rs", Some(9), None)]);
+    }
+
+    #[test]
+    fn urls_and_remote_paths_keep_their_ports() {
+        let cli = Cli::parse(&["http://localhost:8080", "sftp://host:22", "+5", "https://example.com:443/a.rs"]).unwrap();
+
+        assert_eq!(
+            positions(&cli),
+            [("http://localhost:8080", None, None), ("sftp://host:22", None, None), ("https://example.com:443/a.rs", Some(5), None)]
+        );
+    }
+
+    #[test]
+    fn a_line_number_without_a_file_is_an_error() {
+        assert!(Cli::parse(&["a.rs", "+3"]).is_err());
+        assert!(Cli::parse(&["+x", "a.rs"]).is_err());
+    }
+
+    #[test]
+    fn files_after_dashes_are_taken_as_named() {
+        let cli = Cli::parse(&["+4", "--", "+plus", "-minus"]).unwrap();
+
+        assert_eq!(positions(&cli), [("+plus", Some(4), None), ("-minus", None, None)]);
+    }
+
+    #[test]
+    fn options_and_stdin() {
+        let cli = Cli::parse(&["--view", "--theme", "dark", "-"]).unwrap();
+
+        assert!(cli.view && cli.readonly && cli.stdin);
+        assert_eq!(cli.theme.as_deref(), Some("dark"));
+        assert!(Cli::parse(&["--theme", "neon"]).is_err());
+        assert!(Cli::parse(&["--diff", "a.rs"]).is_err());
+        assert!(Cli::parse(&["--bogus"]).is_err());
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::{BTreeMap, VecDeque};
+#[cfg(target_os = "linux")]
+use std::env;
+#[cfg(target_os = "linux")]
+use std::process::{Command, Stdio};
+
+const KILL_RING_SIZE: usize = 16;
+
+// The most recently cut or copied snippets, newest first, along with the named registers
+#[derive(Default)]
+pub struct KillRing {
+    entries: VecDeque<String>,
+    // Registers `a` to `z`, only written when one is chosen before a cut or copy
+    registers: BTreeMap<char, String>,
+}
+
+#[allow(clippy::missing_const_for_fn)]
+impl KillRing {
+    pub fn push(&mut self, text: String) {
+        if text.is_empty() {
+            return;
+        }
+
+        // Copying the same text again moves it to the front instead of filling the ring with duplicates
+        self.entries.retain(|entry| *entry != text);
+        self.entries.push_front(text);
+        self.entries.truncate(KILL_RING_SIZE);
+    }
+    // Push onto the ring and into `register` if one was chosen, an uppercase name appends to its register
+    pub fn store(&mut self, register: Option<char>, text: String) {
+        if let Some(name) = register.filter(|_| !text.is_empty()) {
+            let entry = self.registers.entry(name.to_ascii_lowercase()).or_default();
+
+            if name.is_ascii_uppercase() {
+                entry.push_str(&text);
+            } else {
+                entry.clone_from(&text);
+            }
+        }
+
+        self.push(text);
+    }
+    // Add to the newest entry instead of pushing a new one, for cuts that carry on the one before
+    pub fn append(&mut self, register: Option<char>, text: &str) {
+        let Some(entry) = self.entries.front_mut() else {
+            self.store(register, text.to_owned());
+            return;
+        };
+
+        entry.push_str(text);
+
+        if let Some(name) = register {
+            self.registers.entry(name.to_ascii_lowercase()).or_default().push_str(text);
+        }
+    }
+    #[must_use]
+    pub fn register(&self, name: char) -> Option<&String> {
+        self.registers.get(&name.to_ascii_lowercase())
+    }
+    #[must_use]
+    pub fn get(&self, index: usize) -> Option<&String> {
+        self.entries.get(index)
+    }
+    pub fn iter(&self) -> impl Iterator<Item = &String> {
+        self.entries.iter()
+    }
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+// The desktop's primary selection, the text last selected in any window, read through whichever of
+// `wl-paste`, `xclip` and `xsel` is installed. None without a display or any of them
+#[cfg(target_os = "linux")]
+#[must_use]
+pub fn primary_selection() -> Option<String> {
+    let wayland = env::var_os("WAYLAND_DISPLAY").is_some();
+    let x11 = env::var_os("DISPLAY").is_some();
+    let readers: [(bool, &[&str]); 3] = [
+        (wayland, &["wl-paste", "--primary", "--no-newline"]),
+        (x11, &["xclip", "-out", "-selection", "primary"]),
+        (x11, &["xsel", "--primary", "--output"]),
+    ];
+
+    readers.into_iter().filter(|(available, _)| *available).find_map(|(_, reader)| {
+        let output = Command::new(reader[0]).args(&reader[1..]).stdin(Stdio::null()).stderr(Stdio::null()).output().ok()?;
+
+        output.status.success().then(|| String::from_utf8_lossy(&output.stdout).into_owned()).filter(|text| !text.is_empty())
+    })
+}
+#[cfg(not(target_os = "linux"))]
+#[must_use]
+pub fn primary_selection() -> Option<String> {
+    None
+}
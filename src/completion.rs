@@ -0,0 +1,49 @@
+use crate::document::Document;
+
+// Most words offered at once, the popup only shows a few of them at a time
+pub const COMPLETION_LIMIT: usize = 50;
+
+// Something the completion popup offers for the word before the cursor
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Completion {
+    Word(String),
+    // A snippet trigger, expanded once it's chosen
+    Snippet(String),
+    // A word from the spelling dictionary rather than an open buffer
+    Dictionary(String),
+}
+
+impl Completion {
+    #[must_use]
+    pub fn text(&self) -> &str {
+        match self {
+            Self::Word(text) | Self::Snippet(text) | Self::Dictionary(text) => text,
+        }
+    }
+}
+
+// Words in `documents` that start with `prefix` and are longer than it, in the order they're first found
+pub fn buffer_words<'a>(documents: impl IntoIterator<Item = &'a Document>, prefix: &str) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+
+    for document in documents {
+        for row in (0..document.len()).filter_map(|y| document.row(y)) {
+            for word in row.as_str().split(|c: char| !is_word_char(c)) {
+                if words.len() >= COMPLETION_LIMIT {
+                    return words;
+                }
+
+                if word.len() > prefix.len() && word.starts_with(prefix) && !words.iter().any(|existing| existing == word) {
+                    words.push(word.to_owned());
+                }
+            }
+        }
+    }
+
+    words
+}
+
+#[must_use]
+pub fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
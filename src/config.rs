@@ -0,0 +1,449 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use serde::Deserialize;
+use crate::keymap::Keymap;
+use crate::palette::ColorSupport;
+use crate::statusbar::{Segment, StatusBarLayout};
+use crate::theme::{Background, Theme};
+use crate::timestamp::DEFAULT_FORMATS;
+
+const CONFIG_FILE_NAME: &str = "config.toml";
+// Settings for one project, found in the directory of the file being edited or one above it
+const PROJECT_CONFIG_FILE_NAME: &str = ".ironn.toml";
+// Built in color themes, selected with the `theme` key or `--theme`. The default one is dark or light to
+// match the terminal's background
+pub const THEMES: &[&str] = &["default", "dark", "light"];
+
+#[allow(clippy::struct_excessive_bools)]
+pub struct Config {
+    pub scrolloff: usize,
+    pub sidescrolloff: usize,
+    // Rows of the last page still shown after paging, so there's context to keep reading from
+    pub page_overlap: usize,
+    pub mark_gutter: bool,
+    pub theme: String,
+    pub undo_file: bool,
+    // Force a newline at the end of saved files on or off, unset keeps each file as it was
+    pub insert_final_newline: Option<bool>,
+    // Files bigger than this many MiB open read-only and are read as they're scrolled through, 0 turns this off
+    pub large_file_size: usize,
+    // The column justified paragraphs are wrapped at, and typing past it wraps in hard wrapped files
+    pub fill_column: usize,
+    // File types whose lines are broken at the fill column while typing, by name like `Markdown`
+    pub hard_wrap: Vec<String>,
+    // File types whose words are checked against the dictionary, by name like `Markdown`
+    pub spell_check: Vec<String>,
+    // The word list spelling is checked against, one word per line
+    pub dictionary: PathBuf,
+    // File types whose completions include words from the dictionary, apart from buffer words and snippets
+    pub dictionary_completion: Vec<String>,
+    // Start the line Enter opens inside a `//` or `/* */` comment with the comment leader
+    pub continue_comments: bool,
+    // Run by the build command through the shell, guessed from the project's files when unset
+    pub build_command: Option<String>,
+    // What the bar at the top shows, set under `[status_bar]` with `left`, `center` and `right` lists
+    pub status_bar: StatusBarLayout,
+    // Chords set under `[chords]` as `"C-x C-s" = "save"`, added to the default ones led by Ctrl-G
+    pub keymap: Keymap,
+    // Colors of the bars, messages, selection and popups changed from the theme's, set under `[colors]`
+    // as `header_bg = "blue"`
+    pub colors: Vec<(String, String)>,
+    // Draw a faint line down each indentation level
+    pub indent_guides: bool,
+    // Show what's staged below a git commit message being written
+    pub commit_staged_diff: bool,
+    // Copy each file a project-wide replace writes to `<name>~` first
+    pub replace_backups: bool,
+    // Honor the settings files ask for in Vim and Emacs modelines, turned off for files that aren't trusted
+    pub modelines: bool,
+    // Fill new files named on the command line from the skeleton for their extension
+    pub templates: bool,
+    // Put in templates for `{{author}}`, the name git commits with when unset
+    pub author: Option<String>,
+    // Named strftime formats offered by the insert timestamp command, set under `[timestamps]` as
+    // `date = "%Y-%m-%d"`
+    pub timestamps: Vec<(String, String)>,
+    // Change the cursor's shape with what typing does, a bar while inserting, an underline while
+    // overwriting and a block while selecting
+    pub cursor_shape: bool,
+    // How many colors the terminal shows, detected from `COLORTERM` and `TERM` when unset
+    pub color_support: Option<ColorSupport>,
+}
+
+// The config file as it's written, in TOML. Everything is optional so a project's file only changes
+// what it sets, and keys this version doesn't know are ignored so configs written for newer ones load
+#[derive(Deserialize)]
+struct ConfigFile {
+    scrolloff: Option<usize>,
+    sidescrolloff: Option<usize>,
+    page_overlap: Option<usize>,
+    mark_gutter: Option<bool>,
+    theme: Option<String>,
+    undo_file: Option<bool>,
+    insert_final_newline: Option<bool>,
+    large_file_size: Option<usize>,
+    continue_comments: Option<bool>,
+    fill_column: Option<usize>,
+    hard_wrap: Option<Vec<String>>,
+    spell_check: Option<Vec<String>>,
+    dictionary: Option<PathBuf>,
+    dictionary_completion: Option<Vec<String>>,
+    cursor_shape: Option<bool>,
+    indent_guides: Option<bool>,
+    commit_staged_diff: Option<bool>,
+    replace_backups: Option<bool>,
+    modelines: Option<bool>,
+    templates: Option<bool>,
+    author: Option<String>,
+    color_support: Option<String>,
+    build_command: Option<String>,
+    #[serde(default)]
+    status_bar: StatusBarFile,
+    #[serde(default)]
+    colors: BTreeMap<String, String>,
+    #[serde(default)]
+    timestamps: BTreeMap<String, String>,
+    #[serde(default)]
+    chords: BTreeMap<String, String>,
+}
+
+// Segment names for each part of the status bar
+#[derive(Deserialize, Default)]
+struct StatusBarFile {
+    left: Option<Vec<String>>,
+    center: Option<Vec<String>>,
+    right: Option<Vec<String>>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            scrolloff: 0,
+            sidescrolloff: 0,
+            page_overlap: 2,
+            mark_gutter: true,
+            theme: THEMES[0].to_owned(),
+            undo_file: false,
+            insert_final_newline: None,
+            large_file_size: 64,
+            continue_comments: true,
+            fill_column: 72,
+            hard_wrap: vec!["Markdown".to_owned(), "Text".to_owned(), "Git Commit".to_owned()],
+            spell_check: vec!["Markdown".to_owned(), "Text".to_owned(), "Git Commit".to_owned()],
+            dictionary: PathBuf::from("/usr/share/dict/words"),
+            dictionary_completion: vec!["Markdown".to_owned(), "Text".to_owned()],
+            build_command: None,
+            status_bar: StatusBarLayout::default(),
+            keymap: Keymap::default(),
+            colors: Vec::new(),
+            color_support: None,
+            cursor_shape: true,
+            indent_guides: false,
+            commit_staged_diff: true,
+            replace_backups: false,
+            modelines: true,
+            templates: true,
+            author: None,
+            timestamps: DEFAULT_FORMATS.iter().map(|(name, format)| ((*name).to_owned(), (*format).to_owned())).collect(),
+        }
+    }
+}
+
+impl Config {
+    /// # Errors
+    ///
+    /// Will return `Err` if the user's config file exists but can't be read or parsed,
+    /// or if an explicitly given `path` doesn't exist
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let mut config = Self::default();
+
+        if let Some(path) = path {
+            config.merge_file(path)?;
+        } else if let Some(path) = config_dir().map(|dir| dir.join(CONFIG_FILE_NAME)) {
+            if path.exists() {
+                config.merge_file(&path)?;
+            }
+        }
+
+        Ok(config)
+    }
+    /// # Errors
+    ///
+    /// Will return `Err` if the file can't be read, isn't valid, or sets a known key to the wrong type
+    pub fn merge_file(&mut self, path: &Path) -> Result<()> {
+        self.merge(path, true)
+    }
+    // A project's settings over the user's. Chords are left out, so a repository can't change what
+    // keys do
+    /// # Errors
+    ///
+    /// Will return `Err` if the file can't be read, isn't valid, or sets a known key to the wrong type
+    pub fn merge_project_file(&mut self, path: &Path) -> Result<()> {
+        self.merge(path, false)
+    }
+    fn merge(&mut self, path: &Path, chords: bool) -> Result<()> {
+        let contents = fs::read_to_string(path)
+            .wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+        let mut file = parse(&contents).wrap_err_with(|| format!("Invalid config {}", path.display()))?;
+
+        if !chords && !file.chords.is_empty() {
+            tracing::warn!(path = %path.display(), chords = file.chords.len(), "chords in project config ignored");
+            file.chords.clear();
+        }
+
+        self.apply(file)
+    }
+    fn apply(&mut self, file: ConfigFile) -> Result<()> {
+        let ConfigFile {
+            scrolloff,
+            sidescrolloff,
+            page_overlap,
+            mark_gutter,
+            theme,
+            undo_file,
+            insert_final_newline,
+            large_file_size,
+            continue_comments,
+            fill_column,
+            hard_wrap,
+            spell_check,
+            dictionary,
+            dictionary_completion,
+            cursor_shape,
+            indent_guides,
+            commit_staged_diff,
+            replace_backups,
+            modelines,
+            templates,
+            author,
+            color_support,
+            build_command,
+            status_bar,
+            colors,
+            timestamps,
+            chords,
+        } = file;
+
+        self.scrolloff = scrolloff.unwrap_or(self.scrolloff);
+        self.sidescrolloff = sidescrolloff.unwrap_or(self.sidescrolloff);
+        self.page_overlap = page_overlap.unwrap_or(self.page_overlap);
+        self.mark_gutter = mark_gutter.unwrap_or(self.mark_gutter);
+        self.undo_file = undo_file.unwrap_or(self.undo_file);
+        self.insert_final_newline = insert_final_newline.or(self.insert_final_newline);
+        self.large_file_size = large_file_size.unwrap_or(self.large_file_size);
+        self.continue_comments = continue_comments.unwrap_or(self.continue_comments);
+        self.fill_column = fill_column.map_or(self.fill_column, |column| column.max(1));
+        self.hard_wrap = hard_wrap.unwrap_or_else(|| std::mem::take(&mut self.hard_wrap));
+        self.spell_check = spell_check.unwrap_or_else(|| std::mem::take(&mut self.spell_check));
+        self.dictionary = dictionary.unwrap_or_else(|| std::mem::take(&mut self.dictionary));
+        self.dictionary_completion = dictionary_completion.unwrap_or_else(|| std::mem::take(&mut self.dictionary_completion));
+        self.cursor_shape = cursor_shape.unwrap_or(self.cursor_shape);
+        self.indent_guides = indent_guides.unwrap_or(self.indent_guides);
+        self.commit_staged_diff = commit_staged_diff.unwrap_or(self.commit_staged_diff);
+        self.replace_backups = replace_backups.unwrap_or(self.replace_backups);
+        self.modelines = modelines.unwrap_or(self.modelines);
+        self.templates = templates.unwrap_or(self.templates);
+        self.author = author.or_else(|| self.author.take());
+        self.build_command = build_command.or_else(|| self.build_command.take());
+
+        if let Some(name) = theme {
+            self.set_theme(&name)?;
+        }
+
+        if let Some(name) = color_support {
+            self.color_support = ColorSupport::from_name(&name)?;
+        }
+
+        let segments = |names: Vec<String>| names.iter().map(|name| Segment::from_name(name)).collect::<Result<Vec<_>>>();
+
+        if let Some(left) = status_bar.left {
+            self.status_bar.left = segments(left)?;
+        }
+
+        if let Some(center) = status_bar.center {
+            self.status_bar.center = segments(center)?;
+        }
+
+        if let Some(right) = status_bar.right {
+            self.status_bar.right = segments(right)?;
+        }
+
+        for (name, color) in colors {
+            // Checked now so mistakes are reported when the config loads
+            Theme::default().set(&name, &color)?;
+            self.colors.push((name, color));
+        }
+
+        // Setting a format that's already there changes it, so the defaults can be replaced
+        for (name, format) in timestamps {
+            if let Some(existing) = self.timestamps.iter_mut().find(|(existing, _)| *existing == name) {
+                existing.1 = format;
+            } else {
+                self.timestamps.push((name, format));
+            }
+        }
+
+        for (keys, action) in chords {
+            self.keymap.bind(&keys, &action).wrap_err_with(|| format!("Invalid chord `{keys}`"))?;
+        }
+
+        Ok(())
+    }
+    /// # Errors
+    ///
+    /// Will return `Err` if `name` isn't one of the built in themes
+    pub fn set_theme(&mut self, name: &str) -> Result<()> {
+        if !THEMES.contains(&name) {
+            return Err(eyre!("unknown theme `{name}`, expected one of: {}", THEMES.join(", ")));
+        }
+
+        name.clone_into(&mut self.theme);
+
+        Ok(())
+    }
+    // The theme's colors for `background` with the ones set in the config on top
+    #[must_use]
+    pub fn theme(&self, background: Background) -> Theme {
+        let mut theme = Theme::for_background(background);
+
+        for (name, color) in &self.colors {
+            // Checked when the config was loaded
+            let _ = theme.set(name, color);
+        }
+
+        theme
+    }
+    // The background the theme is chosen for, None when it's left to the terminal
+    #[must_use]
+    pub fn theme_background(&self) -> Option<Background> {
+        match self.theme.as_str() {
+            "dark" => Some(Background::Dark),
+            "light" => Some(Background::Light),
+            _ => None,
+        }
+    }
+}
+
+// `$XDG_CONFIG_HOME/ironn`, falling back to `~/.config/ironn`
+#[must_use]
+pub fn config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("ironn"))
+}
+
+// Snippet files named after the file type they're for, `all.snippets` applies to every file
+#[must_use]
+pub fn snippets_dir() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("snippets"))
+}
+// The nearest `.ironn.toml` in the directory of `path` or above it
+#[must_use]
+pub fn find_project_config(path: &Path) -> Option<PathBuf> {
+    let path = fs::canonicalize(path).ok().or_else(|| path.parent().and_then(|dir| fs::canonicalize(dir).ok()))?;
+
+    path.ancestors()
+        .filter(|dir| dir.is_dir())
+        .map(|dir| dir.join(PROJECT_CONFIG_FILE_NAME))
+        .find(|config| config.is_file())
+}
+// Skeletons new files start from, named like `skeleton.rs`
+#[must_use]
+pub fn templates_dir() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("templates"))
+}
+// Words added to the dictionary from the spelling suggestions
+#[must_use]
+pub fn user_dictionary() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("dictionary.txt"))
+}
+// Where undo histories are kept between sessions
+#[must_use]
+pub fn undo_dir() -> Option<PathBuf> {
+    cache_dir().map(|dir| dir.join("undo"))
+}
+// Where the folds closed in each file are kept between sessions
+#[must_use]
+pub fn fold_dir() -> Option<PathBuf> {
+    cache_dir().map(|dir| dir.join("folds"))
+}
+// Previous prompt inputs, kept between sessions
+#[must_use]
+pub fn prompt_history_file() -> Option<PathBuf> {
+    cache_dir().map(|dir| dir.join("prompt_history"))
+}
+// `$XDG_CACHE_HOME/ironn` falling back to `~/.cache/ironn`
+fn cache_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .map(|dir| dir.join("ironn"))
+}
+
+// A TOML error says where it is by byte offset, this gives the line like the rest of the errors do
+fn parse(contents: &str) -> Result<ConfigFile> {
+    toml::from_str(contents).map_err(|error| {
+        let message = error.message().trim_end();
+
+        error.span().map_or_else(
+            || eyre!("{message}"),
+            |span| eyre!("line {}: {message}", contents[..span.start].matches('\n').count() + 1),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(contents: &str) -> Result<Config> {
+        let mut config = Config::default();
+
+        config.apply(parse(contents)?)?;
+
+        Ok(config)
+    }
+
+    #[test]
+    fn settings_replace_the_defaults() {
+        let config = load("scrolloff = 3 # trailing comment\nhard_wrap = ['Markdown']\nauthor = \"\"\"\nA. Person\"\"\"\n").unwrap();
+
+        assert_eq!(config.scrolloff, 3);
+        assert_eq!(config.hard_wrap, ["Markdown"]);
+        assert_eq!(config.author.as_deref(), Some("A. Person"));
+        assert_eq!(config.page_overlap, Config::default().page_overlap);
+    }
+
+    #[test]
+    fn tables_dotted_keys_and_inline_tables() {
+        let config = load("status_bar.left = [\"mode\"]\ncolors = { header_bg = \"blue\" }\n[timestamps]\ndate = \"%d/%m\"\n\"week day\" = \"%A\"\n[chords]\n\"C-x C-s\" = \"save\"\n").unwrap();
+
+        assert_eq!(config.status_bar.left, [Segment::from_name("mode").unwrap()]);
+        assert_eq!(config.colors, [("header_bg".to_owned(), "blue".to_owned())]);
+        assert!(config.timestamps.contains(&("date".to_owned(), "%d/%m".to_owned())));
+        assert!(config.timestamps.contains(&("week day".to_owned(), "%A".to_owned())));
+        assert_eq!(config.timestamps.iter().filter(|(name, _)| name == "date").count(), 1);
+    }
+
+    #[test]
+    fn unknown_keys_are_ignored() {
+        assert!(load("from_a_newer_version = true\n[new_section]\nkey = 1\n").is_ok());
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let error = |contents: &str| load(contents).err().unwrap().to_string();
+
+        assert!(error("scrolloff = 3\njust words\n").starts_with("line 2: "), "{}", error("scrolloff = 3\njust words\n"));
+        assert!(error("\nscrolloff = -1\n").starts_with("line 2: "));
+        assert!(error("undo_file = 1").contains("expected a boolean"));
+        assert!(error("theme = \"purple\"").contains("unknown theme `purple`"));
+        assert!(error("[status_bar]\nleft = [\"nothing\"]").contains("unknown status bar segment"));
+        assert!(!error("scrolloff = 3\njust words\n").contains('\n'));
+    }
+}
@@ -0,0 +1,93 @@
+use crate::row::Row;
+
+const OURS_MARKER: &str = "<<<<<<<";
+const BASE_MARKER: &str = "|||||||";
+const SEPARATOR_MARKER: &str = "=======";
+const THEIRS_MARKER: &str = ">>>>>>>";
+
+// Row indices of the markers of one merge conflict, `base` is only present in diff3 style conflicts
+pub struct Conflict {
+    pub start: usize,
+    pub base: Option<usize>,
+    pub separator: usize,
+    pub end: usize,
+}
+
+pub enum Resolution {
+    Ours,
+    Theirs,
+    Both,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPart {
+    Marker,
+    Ours,
+    Base,
+    Theirs,
+}
+
+#[allow(clippy::missing_const_for_fn)]
+impl Conflict {
+    #[must_use]
+    pub fn contains(&self, y: usize) -> bool {
+        self.start <= y && y <= self.end
+    }
+    #[must_use]
+    pub fn part(&self, y: usize) -> ConflictPart {
+        if y == self.start || y == self.end || y == self.separator || Some(y) == self.base {
+            ConflictPart::Marker
+        } else if y < self.base.unwrap_or(self.separator) {
+            ConflictPart::Ours
+        } else if y < self.separator {
+            ConflictPart::Base
+        } else {
+            ConflictPart::Theirs
+        }
+    }
+    // Rows that replace the whole conflict, markers included
+    #[must_use]
+    pub fn resolve(&self, lines: &[String], resolution: &Resolution) -> Vec<String> {
+        let ours = &lines[1..self.base.unwrap_or(self.separator) - self.start];
+        let theirs = &lines[self.separator + 1 - self.start..self.end - self.start];
+
+        match resolution {
+            Resolution::Ours => ours.to_vec(),
+            Resolution::Theirs => theirs.to_vec(),
+            Resolution::Both => [ours, theirs].concat(),
+        }
+    }
+}
+
+// Every complete conflict in `rows`, incomplete marker sequences are ignored
+#[must_use]
+pub fn find_conflicts(rows: &[Row]) -> Vec<Conflict> {
+    let mut conflicts = Vec::new();
+    let mut start = None;
+    let mut base = None;
+    let mut separator = None;
+
+    for (y, row) in rows.iter().enumerate() {
+        let line = row.as_str();
+
+        if line.starts_with(OURS_MARKER) {
+            start = Some(y);
+            base = None;
+            separator = None;
+        } else if line.starts_with(BASE_MARKER) && start.is_some() && separator.is_none() {
+            base = Some(y);
+        } else if line.starts_with(SEPARATOR_MARKER) && start.is_some() && separator.is_none() {
+            separator = Some(y);
+        } else if line.starts_with(THEIRS_MARKER) {
+            if let (Some(start), Some(separator)) = (start, separator) {
+                conflicts.push(Conflict { start, base, separator, end: y });
+            }
+
+            start = None;
+            base = None;
+            separator = None;
+        }
+    }
+
+    conflicts
+}
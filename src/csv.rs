@@ -0,0 +1,148 @@
+use std::path::Path;
+use unicode_segmentation::UnicodeSegmentation;
+use crate::row::Row;
+
+const SEPARATOR: &str = " │ ";
+const SEPARATOR_WIDTH: usize = 3;
+
+// Grapheme range of one cell in a row, `end` is the index of the delimiter that follows it
+#[derive(Clone, Copy)]
+pub struct Field {
+    pub start: usize,
+    pub end: usize,
+}
+
+// Part of an aligned row, either a padded cell or the separator between two cells
+pub struct Segment {
+    pub text: String,
+    pub column: Option<usize>,
+    pub start: usize,
+    pub width: usize,
+}
+
+// Display columns of a delimited file, wide enough for the longest cell in each column
+pub struct ColumnLayout {
+    delimiter: char,
+    widths: Vec<usize>,
+}
+
+#[must_use]
+pub fn delimiter_for(file_name: &str) -> Option<char> {
+    let extension = Path::new(file_name).extension()?.to_str()?;
+
+    match extension.to_ascii_lowercase().as_str() {
+        "csv" => Some(','),
+        "tsv" => Some('\t'),
+        _ => None,
+    }
+}
+
+#[allow(clippy::missing_const_for_fn)]
+impl ColumnLayout {
+    #[must_use]
+    pub fn new<'a>(rows: impl Iterator<Item = &'a Row>, delimiter: char) -> Self {
+        let mut widths: Vec<usize> = Vec::new();
+
+        for row in rows {
+            for (column, field) in fields(row, delimiter).iter().enumerate() {
+                let width = field_width(row, *field);
+
+                match widths.get_mut(column) {
+                    Some(existing) => *existing = (*existing).max(width),
+                    None => widths.push(width),
+                }
+            }
+        }
+
+        Self { delimiter, widths }
+    }
+    #[must_use]
+    pub fn fields(&self, row: &Row) -> Vec<Field> {
+        fields(row, self.delimiter)
+    }
+    // Display column where cell `column` starts
+    fn column_start(&self, column: usize) -> usize {
+        self.widths.iter().take(column).map(|width| width + SEPARATOR_WIDTH).sum()
+    }
+    // Aligned display column of grapheme index `x`
+    #[must_use]
+    pub fn width_to(&self, row: &Row, x: usize) -> usize {
+        let fields = self.fields(row);
+        let Some((column, field)) = fields.iter().enumerate().find(|(_, field)| x <= field.end) else {
+            return 0;
+        };
+
+        self.column_start(column) + row.width_to(x) - row.width_to(field.start)
+    }
+    // Grapheme index shown at the aligned display column `target`
+    #[must_use]
+    pub fn index_at_width(&self, row: &Row, target: usize) -> usize {
+        let fields = self.fields(row);
+        let column = (0..fields.len())
+            .rev()
+            .find(|column| self.column_start(*column) <= target)
+            .unwrap_or(0);
+        let Some(field) = fields.get(column) else {
+            return 0;
+        };
+
+        let within = target - self.column_start(column);
+
+        row.index_at_width(row.width_to(field.start) + within).min(field.end)
+    }
+    // The row split into padded cells and separators, laid out from display column 0
+    #[must_use]
+    pub fn segments(&self, row: &Row) -> Vec<Segment> {
+        let mut segments = Vec::new();
+
+        for (column, field) in self.fields(row).iter().enumerate() {
+            let start = self.column_start(column);
+            let width = self.widths.get(column).copied().unwrap_or(0);
+
+            if column > 0 {
+                segments.push(Segment {
+                    text: SEPARATOR.to_owned(),
+                    column: None,
+                    start: start - SEPARATOR_WIDTH,
+                    width: SEPARATOR_WIDTH,
+                });
+            }
+
+            let text = row.substring(field.start, field.end);
+            let padding = width.saturating_sub(field_width(row, *field));
+
+            segments.push(Segment {
+                text: format!("{text}{}", " ".repeat(padding)),
+                column: Some(column),
+                start,
+                width,
+            });
+        }
+
+        segments
+    }
+}
+
+// Split a row on `delimiter`, ignoring delimiters inside double quoted cells
+fn fields(row: &Row, delimiter: char) -> Vec<Field> {
+    let mut fields = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+
+    for (index, grapheme) in row.as_str().graphemes(true).enumerate() {
+        if grapheme == "\"" {
+            quoted = !quoted;
+        } else if !quoted && grapheme.chars().eq([delimiter]) {
+            fields.push(Field { start, end: index });
+            start = index + 1;
+        }
+    }
+
+    fields.push(Field { start, end: row.len() });
+
+    fields
+}
+
+fn field_width(row: &Row, field: Field) -> usize {
+    row.width_to(field.end) - row.width_to(field.start)
+}
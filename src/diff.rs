@@ -0,0 +1,444 @@
+use std::{fs, iter};
+use std::ops::Range;
+use color_eyre::Result;
+use unicode_segmentation::UnicodeSegmentation;
+use crate::encoding::{decode_utf8, PrivateUse};
+
+// Lines of unchanged text shown around each hunk
+const CONTEXT_LINES: usize = 3;
+// Longer lines are marked changed as a whole, comparing their graphemes would take too long
+const MAX_INLINE_GRAPHEMES: usize = 500;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+// One line of a unified diff
+pub enum DiffLine {
+    Hunk(String),
+    Context(String),
+    Removed(String),
+    Added(String),
+}
+
+// A line of each file shown side by side, None where the other file has lines this one doesn't
+#[derive(Clone, Copy)]
+pub struct AlignedRow {
+    pub old: Option<usize>,
+    pub new: Option<usize>,
+    pub changed: bool,
+}
+
+// Two files compared line by line, with the rows to show them side by side and where each hunk of
+// changes starts among those rows
+pub struct SideBySide {
+    pub old_name: String,
+    pub new_name: String,
+    pub old: Vec<String>,
+    pub new: Vec<String>,
+    pub rows: Vec<AlignedRow>,
+    pub hunks: Vec<usize>,
+}
+
+#[allow(clippy::missing_const_for_fn)]
+impl SideBySide {
+    /// # Errors
+    ///
+    /// Will return `Err` if either file can't be read
+    pub fn open(old_name: &str, new_name: &str) -> Result<Self> {
+        let old = decode_utf8(&fs::read(old_name)?, &mut PrivateUse::default())?;
+        let new = decode_utf8(&fs::read(new_name)?, &mut PrivateUse::default())?;
+
+        Ok(Self::new(old_name, new_name, &old, &new))
+    }
+    // Changed lines are paired up in the order they were removed and added, whatever is left over
+    // faces a gap
+    #[must_use]
+    pub fn new(old_name: &str, new_name: &str, old: &str, new: &str) -> Self {
+        let old: Vec<String> = old.lines().map(str::to_owned).collect();
+        let new: Vec<String> = new.lines().map(str::to_owned).collect();
+        let ops = edit_script(&old.iter().map(String::as_str).collect::<Vec<_>>(), &new.iter().map(String::as_str).collect::<Vec<_>>());
+        let mut rows = Vec::with_capacity(ops.len());
+        let mut hunks = Vec::new();
+        let (mut old_index, mut new_index) = (0, 0);
+        let mut ops = ops.iter().peekable();
+
+        while let Some(op) = ops.next() {
+            if *op == Op::Equal {
+                rows.push(AlignedRow { old: Some(old_index), new: Some(new_index), changed: false });
+                old_index += 1;
+                new_index += 1;
+                continue;
+            }
+
+            let (mut removed, mut added) = (usize::from(*op == Op::Delete), usize::from(*op == Op::Insert));
+
+            while let Some(op) = ops.next_if(|op| **op != Op::Equal) {
+                if *op == Op::Delete {
+                    removed += 1;
+                } else {
+                    added += 1;
+                }
+            }
+
+            hunks.push(rows.len());
+
+            for line in 0..removed.max(added) {
+                rows.push(AlignedRow {
+                    old: (line < removed).then_some(old_index + line),
+                    new: (line < added).then_some(new_index + line),
+                    changed: true,
+                });
+            }
+
+            old_index += removed;
+            new_index += added;
+        }
+
+        Self { old_name: old_name.to_owned(), new_name: new_name.to_owned(), old, new, rows, hunks }
+    }
+    // The first row of the next hunk after `row`, or of the last one before it
+    #[must_use]
+    pub fn next_hunk(&self, row: usize, forward: bool) -> Option<usize> {
+        if forward {
+            self.hunks.iter().copied().find(|start| *start > row)
+        } else {
+            self.hunks.iter().copied().rev().find(|start| *start < row)
+        }
+    }
+    // Which hunk, counting from 1, is the last one starting at or above `row`
+    #[must_use]
+    pub fn hunk_number(&self, row: usize) -> usize {
+        self.hunks.iter().take_while(|start| **start <= row).count()
+    }
+}
+
+// Byte ranges of what changed within a pair of lines, in the old line and the new one. Lines with
+// less than half in common are all changed, marking their scattered matches wouldn't help
+#[must_use]
+pub fn inline_changes(old: &str, new: &str) -> (Vec<Range<usize>>, Vec<Range<usize>>) {
+    let old_graphemes: Vec<(usize, &str)> = old.grapheme_indices(true).collect();
+    let new_graphemes: Vec<(usize, &str)> = new.grapheme_indices(true).collect();
+    let whole = || (vec![Range { start: 0, end: old.len() }], vec![Range { start: 0, end: new.len() }]);
+
+    if old_graphemes.len().max(new_graphemes.len()) > MAX_INLINE_GRAPHEMES {
+        return whole();
+    }
+
+    let ops = edit_script(
+        &old_graphemes.iter().map(|(_, grapheme)| *grapheme).collect::<Vec<_>>(),
+        &new_graphemes.iter().map(|(_, grapheme)| *grapheme).collect::<Vec<_>>(),
+    );
+    let equal = ops.iter().filter(|op| **op == Op::Equal).count();
+
+    if equal * 2 < old_graphemes.len().min(new_graphemes.len()) {
+        return whole();
+    }
+
+    let (mut old_ranges, mut new_ranges): (Vec<Range<usize>>, Vec<Range<usize>>) = (Vec::new(), Vec::new());
+    let (mut old_index, mut new_index) = (0, 0);
+    let extend = |ranges: &mut Vec<Range<usize>>, range: Range<usize>| match ranges.last_mut() {
+        Some(last) if last.end == range.start => last.end = range.end,
+        _ => ranges.push(range),
+    };
+
+    for op in ops {
+        match op {
+            Op::Equal => {
+                old_index += 1;
+                new_index += 1;
+            },
+            Op::Delete => {
+                let (start, grapheme) = old_graphemes[old_index];
+
+                extend(&mut old_ranges, start..start + grapheme.len());
+                old_index += 1;
+            },
+            Op::Insert => {
+                let (start, grapheme) = new_graphemes[new_index];
+
+                extend(&mut new_ranges, start..start + grapheme.len());
+                new_index += 1;
+            },
+        }
+    }
+
+    (old_ranges, new_ranges)
+}
+
+// Unified diff of `old` against `new`, grouped into hunks with a few lines of context
+#[must_use]
+pub fn diff(old: &[&str], new: &[&str]) -> Vec<DiffLine> {
+    let ops = edit_script(old, new);
+    let mut positions = Vec::with_capacity(ops.len());
+    let (mut old_index, mut new_index) = (0, 0);
+
+    for op in &ops {
+        positions.push((old_index, new_index));
+
+        match op {
+            Op::Equal => {
+                old_index += 1;
+                new_index += 1;
+            },
+            Op::Delete => old_index += 1,
+            Op::Insert => new_index += 1,
+        }
+    }
+
+    let mut lines = Vec::new();
+
+    for (start, end) in hunk_ranges(&ops) {
+        let (old_start, new_start) = positions[start];
+        let old_count = ops[start..end].iter().filter(|op| **op != Op::Insert).count();
+        let new_count = ops[start..end].iter().filter(|op| **op != Op::Delete).count();
+
+        lines.push(DiffLine::Hunk(format!("@@ -{},{old_count} +{},{new_count} @@", old_start + 1, new_start + 1)));
+
+        for (op, (old_index, new_index)) in ops[start..end].iter().zip(&positions[start..end]) {
+            lines.push(match op {
+                Op::Equal => DiffLine::Context(old[*old_index].to_owned()),
+                Op::Delete => DiffLine::Removed(old[*old_index].to_owned()),
+                Op::Insert => DiffLine::Added(new[*new_index].to_owned()),
+            });
+        }
+    }
+
+    lines
+}
+
+// Ranges of `ops` covering each change plus its context, merging changes whose context overlaps
+fn hunk_ranges(ops: &[Op]) -> Vec<(usize, usize)> {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+
+    for (index, _) in ops.iter().enumerate().filter(|(_, op)| **op != Op::Equal) {
+        let start = index.saturating_sub(CONTEXT_LINES);
+        let end = index.saturating_add(CONTEXT_LINES + 1).min(ops.len());
+
+        match ranges.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    ranges
+}
+
+// Shortest sequence of line operations turning `old` into `new`, using the linear space variant of
+// Myers' algorithm: a point halfway along a shortest path is found by searching from both ends at
+// once, and the parts on either side of it are solved the same way. Only one vector of diagonals per
+// direction is kept, however far apart the files are
+#[must_use]
+pub fn edit_script(old: &[&str], new: &[&str]) -> Vec<Op> {
+    let mut ops = Vec::with_capacity(old.len().max(new.len()));
+
+    push_edit_script(old, new, &mut ops, &mut Vec::new(), &mut Vec::new());
+
+    // Splits can land inside a change, so each run of changed lines is put back to its removals first
+    for run in ops.chunk_by_mut(|op, next| (*op == Op::Equal) == (*next == Op::Equal)) {
+        run.sort_by_key(|op| *op == Op::Insert);
+    }
+
+    ops
+}
+
+fn push_edit_script(old: &[&str], new: &[&str], ops: &mut Vec<Op>, forward: &mut Vec<Option<usize>>, backward: &mut Vec<Option<usize>>) {
+    let prefix = old.iter().zip(new).take_while(|(old, new)| old == new).count();
+    let (old, new) = (&old[prefix..], &new[prefix..]);
+    let suffix = old.iter().rev().zip(new.iter().rev()).take_while(|(old, new)| old == new).count();
+    let (old, new) = (&old[..old.len() - suffix], &new[..new.len() - suffix]);
+
+    ops.extend(iter::repeat_n(Op::Equal, prefix));
+
+    // Without common ends, two non-empty sides are at least two edits apart, so the parts on either
+    // side of the split are both smaller than the whole
+    if let Some((old_split, new_split)) = split_point(old, new, forward, backward) {
+        push_edit_script(&old[..old_split], &new[..new_split], ops, forward, backward);
+        push_edit_script(&old[old_split..], &new[new_split..], ops, forward, backward);
+    } else {
+        ops.extend(iter::repeat_n(Op::Delete, old.len()));
+        ops.extend(iter::repeat_n(Op::Insert, new.len()));
+    }
+
+    ops.extend(iter::repeat_n(Op::Equal, suffix));
+}
+
+// Where the paths from the start and from the end first overlap, None when either side is empty.
+// `forward` holds how far along `old` each diagonal reaches from the start and `backward` how far
+// back from the end, diagonal k being the old index minus the new index and stored at k + offset.
+// Diagonals whose paths have left the grid are dropped from the ends of the search
+fn split_point(old: &[&str], new: &[&str], forward: &mut Vec<Option<usize>>, backward: &mut Vec<Option<usize>>) -> Option<(usize, usize)> {
+    let (old_len, new_len) = (old.len(), new.len());
+
+    if old_len == 0 || new_len == 0 {
+        return None;
+    }
+
+    let max_edits = (old_len + new_len).div_ceil(2);
+    let offset = max_edits + 1;
+    // Forward diagonal k meets backward diagonal old_len - new_len - k
+    let opposite = |index: usize| (2 * offset + old_len).checked_sub(new_len + index).filter(|opposite| *opposite < 2 * offset + 1);
+    // The paths meet after an odd total on the forward pass and after an even one on the backward pass
+    let odd = (old_len + new_len) % 2 == 1;
+    let (mut forward_start, mut forward_end, mut backward_start, mut backward_end) = (0, 0, 0, 0);
+
+    for vector in [&mut *forward, &mut *backward] {
+        vector.clear();
+        vector.resize(2 * offset + 1, None);
+        vector[offset + 1] = Some(0);
+    }
+
+    for edits in 0..=max_edits {
+        for index in (offset + forward_start - edits..=offset + edits - forward_end).step_by(2) {
+            let (old_index, new_index) = follow_diagonal(forward, index, edits, offset, old_len, new_len, |old_index, new_index| old[old_index] == new[new_index]);
+
+            forward[index] = Some(old_index);
+
+            if old_index > old_len {
+                forward_end += 2;
+            } else if new_index > new_len {
+                forward_start += 2;
+            } else if odd && opposite(index).and_then(|opposite| backward[opposite]).is_some_and(|back| old_index + back >= old_len) {
+                return Some((old_index, new_index));
+            }
+        }
+
+        for index in (offset + backward_start - edits..=offset + edits - backward_end).step_by(2) {
+            let (old_back, new_back) = follow_diagonal(backward, index, edits, offset, old_len, new_len, |old_back, new_back| {
+                old[old_len - old_back - 1] == new[new_len - new_back - 1]
+            });
+
+            backward[index] = Some(old_back);
+
+            if old_back > old_len {
+                backward_end += 2;
+            } else if new_back > new_len {
+                backward_start += 2;
+            } else if !odd {
+                let Some((opposite, old_index)) = opposite(index).and_then(|opposite| Some((opposite, forward[opposite]?))) else {
+                    continue;
+                };
+
+                if old_index + old_back >= old_len {
+                    return Some((old_index, old_index + offset - opposite));
+                }
+            }
+        }
+    }
+
+    None
+}
+
+// Extend the path with `edits` edits onto diagonal `index`, taking an insertion from the diagonal
+// above unless a deletion from the one below reaches further, then following equal lines
+fn follow_diagonal(
+    furthest: &[Option<usize>],
+    index: usize,
+    edits: usize,
+    offset: usize,
+    old_len: usize,
+    new_len: usize,
+    equal: impl Fn(usize, usize) -> bool,
+) -> (usize, usize) {
+    let mut old_index = if index == offset - edits || (index != offset + edits && furthest[index - 1] < furthest[index + 1]) {
+        furthest[index + 1].unwrap_or_default()
+    } else {
+        furthest[index - 1].map_or(0, |old_index| old_index + 1)
+    };
+    let mut new_index = old_index + offset - index;
+
+    while old_index < old_len && new_index < new_len && equal(old_index, new_index) {
+        old_index += 1;
+        new_index += 1;
+    }
+
+    (old_index, new_index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Replay `ops` on `old`, returning what they produce and how many edits they took
+    fn apply(ops: &[Op], old: &[&str], new: &[&str]) -> (Vec<String>, usize) {
+        let (mut old_index, mut new_index, mut result, mut edits) = (0, 0, Vec::new(), 0);
+
+        for op in ops {
+            match op {
+                Op::Equal => {
+                    assert_eq!(old[old_index], new[new_index]);
+                    result.push(old[old_index].to_owned());
+                    old_index += 1;
+                    new_index += 1;
+                },
+                Op::Delete => {
+                    old_index += 1;
+                    edits += 1;
+                },
+                Op::Insert => {
+                    result.push(new[new_index].to_owned());
+                    new_index += 1;
+                    edits += 1;
+                },
+            }
+        }
+
+        assert_eq!((old_index, new_index), (old.len(), new.len()));
+
+        (result, edits)
+    }
+
+    // Edits of a shortest script, from the longest common subsequence
+    fn shortest(old: &[&str], new: &[&str]) -> usize {
+        let mut lengths = vec![vec![0; new.len() + 1]; old.len() + 1];
+
+        for (i, old_line) in old.iter().enumerate() {
+            for (j, new_line) in new.iter().enumerate() {
+                lengths[i + 1][j + 1] = if old_line == new_line { lengths[i][j] + 1 } else { lengths[i][j + 1].max(lengths[i + 1][j]) };
+            }
+        }
+
+        old.len() + new.len() - 2 * lengths[old.len()][new.len()]
+    }
+
+    #[test]
+    fn scripts_are_shortest() {
+        let alphabet = ["a", "b", "c"];
+        let mut seed = 7_u64;
+        let mut next = |below: usize| {
+            seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+            usize::try_from(seed >> 33).unwrap() % below
+        };
+
+        for _ in 0..500 {
+            let old: Vec<&str> = (0..next(12)).map(|_| alphabet[next(3)]).collect();
+            let new: Vec<&str> = (0..next(12)).map(|_| alphabet[next(3)]).collect();
+            let (result, edits) = apply(&edit_script(&old, &new), &old, &new);
+
+            assert_eq!(result, new);
+            assert_eq!(edits, shortest(&old, &new), "{old:?} -> {new:?}");
+        }
+    }
+
+    #[test]
+    fn changed_lines_are_removed_before_they_are_added() {
+        let ops = edit_script(&["a", "b", "c", "d"], &["a", "x", "y", "d"]);
+
+        assert!(ops == [Op::Equal, Op::Delete, Op::Delete, Op::Insert, Op::Insert, Op::Equal]);
+    }
+
+    #[test]
+    fn unrelated_files_are_all_replaced() {
+        let old: Vec<String> = (0..2000).map(|line| format!("old {line}")).collect();
+        let new: Vec<String> = (0..2000).map(|line| format!("new {line}")).collect();
+        let old: Vec<&str> = old.iter().map(String::as_str).collect();
+        let new: Vec<&str> = new.iter().map(String::as_str).collect();
+        let ops = edit_script(&old, &new);
+
+        assert_eq!(ops.iter().filter(|op| **op == Op::Delete).count(), 2000);
+        assert_eq!(ops.iter().filter(|op| **op == Op::Insert).count(), 2000);
+    }
+}
@@ -1,192 +1,1012 @@
-use std::fs;
+use std::{env, fs};
 use std::fs::File;
-use std::io::Write;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Instant, SystemTime};
+use color_eyre::eyre::eyre;
 use color_eyre::Result;
-use crate::{FileType, Position, Row, SearchDirection};
+use crate::{FileType, SearchDirection};
+use crate::app::Position;
+use crate::archive::{member_name, read_member, split_member, write_member};
+use crate::conflicts::{find_conflicts, Conflict};
+use crate::encoding::{decode_utf8, Encoding, PrivateUse};
+use crate::fold::Folds;
+use crate::lock::{FileLock, LockOwner};
+use crate::remote::{fetch_url, is_remote, is_url, url_path, RemoteFile};
+use crate::row::Row;
+use crate::save::write_atomically;
+use crate::history::{Change, History};
+use crate::marks::{JumpList, Marks};
+use crate::modeline::{find_modeline, Modeline};
+use crate::snippets::TabStops;
 
+// Programs tried in order by `write_out_privileged`
+const PRIVILEGE_HELPERS: [&str; 2] = ["sudo", "pkexec"];
+// Rows read ahead of what's needed each time a streamed file is read from
+const STREAM_CHUNK: usize = 1000;
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+// Positions count rows and graphemes from 0, views add any lines they draw above the text themselves
 #[derive(Default)]
 pub struct Document {
     rows: Vec<Row>,
     pub file_name: Option<String>,
+    pub file_type: FileType,
     dirty: bool,
-    file_type: FileType,
+    // Counts edits to the text, so what's worked out from it can tell when it's out of date
+    generation: u64,
+    read_only: bool,
+    // The file didn't end in a newline, so saving doesn't add one
+    no_final_newline: bool,
+    // What the file is stored as, saving writes it the same way
+    encoding: Encoding,
+    private_use: PrivateUse,
+    // Modification time of the file when it was last read or written
+    disk_modified: Option<SystemTime>,
+    // Rest of a file too large to read up front, rows are read from it as they're needed
+    stream: Option<BufReader<File>>,
+    // Why reading a streamed file stopped before its end, it can't be edited without the rest
+    read_error: Option<ReadError>,
+    history: History,
+    marks: Marks,
+    jumps: JumpList,
+    // Placeholders of the last expanded snippet that Tab hasn't reached yet
+    tab_stops: TabStops,
+    folds: Folds,
+    // Held while the file is open here, so other instances know it's being edited
+    lock: Option<FileLock>,
+    // The instance that already had the file open, until the user is told about it
+    locked_by: Option<LockOwner>,
+    // Settings the file asked for in a modeline
+    pub modeline: Modeline,
+}
+
+struct ReadError {
+    message: String,
+    // Whether the user was told about it yet
+    shown: bool,
 }
 
+#[allow(clippy::missing_const_for_fn)]
 impl Document {
     pub fn open(filename: &str) -> Result<Self> {
-        let contents = fs::read_to_string(filename)?;
+        let bytes = fs::read(filename).inspect_err(|error| tracing::warn!(filename, %error, "open failed"))?;
+
+        Self::from_bytes(filename, &bytes)
+    }
+    // Download a file named by an `sftp://` URI, saving to a name like that uploads it back
+    /// # Errors
+    ///
+    /// Will return `Err` if the URI is invalid or the file can't be downloaded
+    pub fn open_remote(uri: &str) -> Result<Self> {
+        let bytes = RemoteFile::parse(uri)?.download()?;
+
+        Self::from_bytes(uri, &bytes)
+    }
+    // Fetch an `http://` or `https://` URL into a read-only buffer, typed by the file name in its path
+    /// # Errors
+    ///
+    /// Will return `Err` if the URL can't be fetched
+    pub fn open_url(url: &str) -> Result<Self> {
+        let bytes = fetch_url(url)?;
+
+        Ok(Self { file_type: FileType::from(url_path(url)), read_only: true, ..Self::from_bytes(url, &bytes)? })
+    }
+    // Extract a member of a `.zip` or `.tar.gz` archive into a buffer named `archive!/member`. It's
+    // read-only unless `writable`, saving a writable one puts it back in the archive
+    /// # Errors
+    ///
+    /// Will return `Err` if the member can't be extracted
+    pub fn open_archive_member(archive: &str, member: &str, writable: bool) -> Result<Self> {
+        let bytes = read_member(archive, member)?;
+
+        Ok(Self { file_type: FileType::from(member), read_only: !writable, ..Self::from_bytes(&member_name(archive, member), &bytes)? })
+    }
+    fn from_bytes(filename: &str, bytes: &[u8]) -> Result<Self> {
+        let encoding = Encoding::detect(bytes);
+        let mut private_use = PrivateUse::default();
+        let contents = encoding.decode(bytes, &mut private_use)?;
         let file_type = FileType::from(filename);
         let mut rows = Vec::new();
-        
+
         for value in contents.lines() {
             rows.push(Row::from(value));
         }
+
+        tracing::info!(filename, bytes = contents.len(), rows = rows.len(), "opened");
         
-        Ok(Self {
-            rows,
+        Ok(
+            Self {
+                rows,
+                file_name: Some(filename.to_owned()),
+                file_type,
+                dirty: false,
+                generation: 0,
+                read_only: false,
+                no_final_newline: !contents.is_empty() && !contents.ends_with('\n'),
+                encoding,
+                private_use,
+                disk_modified: modified_time(filename),
+                stream: None,
+                read_error: None,
+                history: History::default(),
+                marks: Marks::default(),
+                jumps: JumpList::default(),
+                tab_stops: TabStops::default(),
+                folds: Folds::default(),
+                lock: None,
+                locked_by: None,
+                modeline: Modeline::default(),
+            }
+        )
+    }
+    // Open a large file read-only with only its first rows read, `load_to` reads more of it
+    /// # Errors
+    ///
+    /// Will return `Err` if the file can't be opened
+    pub fn open_streamed(filename: &str) -> Result<Self> {
+        let file = File::open(filename).inspect_err(|error| tracing::warn!(filename, %error, "open failed"))?;
+        let mut document = Self {
             file_name: Some(filename.to_owned()),
-            dirty: false,
-            file_type,
-        })
+            file_type: FileType::from(filename),
+            read_only: true,
+            disk_modified: modified_time(filename),
+            stream: Some(BufReader::new(file)),
+            ..Self::default()
+        };
+
+        document.load_to(0);
+
+        tracing::info!(filename, rows = document.rows.len(), "opened streamed");
+
+        Ok(document)
     }
-    #[must_use]
-    pub fn file_type(&self) -> String {
-        self.file_type.name()
+    // Make sure row `index` is loaded, reading a chunk past it at a time until the file ends
+    pub fn load_to(&mut self, index: usize) {
+        let Some(stream) = self.stream.as_mut().filter(|_| self.rows.len() <= index) else {
+            return;
+        };
+
+        let target = index.saturating_add(STREAM_CHUNK);
+        let mut line = Vec::new();
+
+        while self.rows.len() < target {
+            line.clear();
+
+            match stream.read_until(b'\n', &mut line) {
+                Ok(0) => {
+                    self.stream = None;
+                    return;
+                },
+                Err(error) => {
+                    tracing::warn!(file_name = ?self.file_name, rows = self.rows.len(), %error, "streamed read failed");
+                    self.stream = None;
+                    self.read_error = Some(ReadError { message: error.to_string(), shown: false });
+                    return;
+                },
+                Ok(_) => (),
+            }
+
+            if line.last() == Some(&b'\n') {
+                line.pop();
+
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+            } else {
+                self.no_final_newline = true;
+            }
+
+            match decode_utf8(&line, &mut self.private_use) {
+                Ok(text) => self.rows.push(Row::from(text.as_str())),
+                Err(error) => {
+                    self.stream = None;
+                    self.read_error = Some(ReadError { message: error.to_string(), shown: false });
+                    return;
+                },
+            }
+        }
     }
     #[must_use]
-    pub fn row(&self, index: usize) -> Option<&Row> {
-        self.rows.get(index)
+    pub fn is_streamed(&self) -> bool {
+        self.stream.is_some()
     }
-    #[must_use]
-    pub fn is_empty(&self) -> bool {
-        self.rows.is_empty()
+    // Why the rest of a streamed file couldn't be read, only the first time it's asked for
+    pub fn unshown_read_error(&mut self) -> Option<&str> {
+        let error = self.read_error.as_mut().filter(|error| !error.shown)?;
+
+        error.shown = true;
+
+        Some(&error.message)
+    }
+    // Read the rest of a streamed file so it can be edited. A file that couldn't be read to its end
+    // stays read-only, since saving it would cut off what wasn't read
+    /// # Errors
+    ///
+    /// Will return `Err` if reading the file failed before its end
+    pub fn load_all(&mut self) -> Result<()> {
+        while self.stream.is_some() {
+            self.load_to(self.rows.len());
+        }
+
+        if let Some(error) = self.read_error.as_mut() {
+            error.shown = true;
+
+            return Err(eyre!("couldn't read past line {}: {}", self.rows.len(), error.message));
+        }
+
+        self.read_only = false;
+
+        Ok(())
     }
+    // An unnamed buffer holding `text`, marked modified since it only exists in memory
     #[must_use]
-    #[allow(clippy::missing_const_for_fn)]
-    pub fn is_dirty(&self) -> bool {
-        self.dirty
+    pub fn from_text(text: &str) -> Self {
+        Self {
+            rows: text.lines().map(Row::from).collect(),
+            dirty: true,
+            no_final_newline: !text.is_empty() && !text.ends_with('\n'),
+            ..Self::default()
+        }
     }
+    // A file that doesn't exist yet, starting with `text` from a template. It's only dirty when there's
+    // something in it to save
     #[must_use]
-    pub fn len(&self) -> usize {
-        self.rows.len()
+    pub fn new_file(filename: &str, text: &str) -> Self {
+        Self {
+            file_name: Some(filename.to_owned()),
+            file_type: FileType::from(filename),
+            dirty: !text.is_empty(),
+            ..Self::from_text(text)
+        }
     }
-    // If user is typing on last line, add new row, otherwise type as normal
-    pub fn insert(&mut self, at: &Position, c: char) {
-        if at.y - 1 > self.rows.len() {
-            return;
+    // Read the file's modeline, which can change its file type too
+    pub fn apply_modeline(&mut self) {
+        let lines: Vec<&str> = self.rows.iter().map(Row::as_str).collect();
+
+        self.modeline = find_modeline(&lines);
+
+        if let Some(file_type) = self.modeline.file_type.as_deref().and_then(FileType::from_mode) {
+            self.file_type = file_type;
         }
 
-        self.dirty = true;
+        if !self.modeline.is_empty() {
+            tracing::info!(file_name = ?self.file_name, modeline = ?self.modeline, "modeline applied");
+        }
+    }
+    // Insert text that may span several lines, returning the position right after it
+    pub fn insert_str(&mut self, at: &Position, text: &str) -> Position {
+        self.insert_recorded(at, text, false)
+    }
+    // Insert typed text, consecutive characters of a word are undone together
+    pub fn type_str(&mut self, at: &Position, text: &str) -> Position {
+        self.insert_recorded(at, text, true)
+    }
+    fn insert_recorded(&mut self, at: &Position, text: &str, typed: bool) -> Position {
+        if at.y > self.rows.len() || self.read_only {
+            return at.clone();
+        }
 
-        if c == '\n' {
-            self.insert_newline(at);
-        } else if at.y == self.rows.len() {
-            let mut row = Row::default();
-            row.insert(0, c);
-            self.rows.push(row);
+        // Typing past the last row starts a new line, recorded as a line break after the last row
+        let (start, text) = if at.y == self.rows.len() && !self.rows.is_empty() {
+            let last = self.rows.len() - 1;
+
+            (Position { x: self.rows[last].len(), y: last }, format!("\n{text}"))
+        } else {
+            (at.clone(), text.to_owned())
+        };
+
+        let end = self.insert_unrecorded(&start, &text);
+        let change = Change::Insert { start, end: end.clone(), text };
+
+        if typed {
+            self.history.record_typed(change);
         } else {
-            let row = &mut self.rows[at.y - 1];
-            row.insert(at.x, c);
+            self.history.record(change);
         }
-        
-        self.unhighlight_rows(at.y);
+
+        end
     }
-    pub fn insert_newline(&mut self, at: &Position) {
-        if at.y > self.rows.len() {
+    pub fn delete(&mut self, at: &Position) {
+        let Some(row) = self.rows.get(at.y) else {
             return;
-        }
-        if at.y == self.rows.len() {
-            self.rows.push(Row::default());
+        };
+
+        // Deleting at the end of a row removes the newline and joins the next row onto it
+        let end = if at.x < row.len() {
+            Position { x: at.x + 1, y: at.y }
+        } else if at.y + 1 < self.rows.len() {
+            Position { x: 0, y: at.y + 1 }
+        } else {
             return;
+        };
+
+        self.delete_range(at, &end);
+    }
+    // Position of the first match of `query` searching from `at` in `direction`, matches don't span rows
+    #[must_use]
+    pub fn find(&self, query: &str, at: &Position, direction: SearchDirection) -> Option<Position> {
+        if at.y >= self.rows.len() {
+            return None;
         }
 
-        let current_row = &mut self.rows[at.y];
-        let new_row = current_row.split(at.x);
+        let mut position = at.clone();
 
-        self.rows.insert(at.y + 1, new_row);
-    }
-    pub fn highlight(&mut self, word: &Option<String>, until: Option<usize>) {
-        let mut  start_with_comment = false;
-        
-        let until = if let Some(until) = until {
-            if until.saturating_add(1) < self.rows.len() {
-                until.saturating_add(1)
+        loop {
+            let row = self.rows.get(position.y)?;
+
+            if let Some(x) = row.find(query, position.x.min(row.len()), direction) {
+                return Some(Position { x, y: position.y });
+            }
+
+            if direction == SearchDirection::Forward {
+                position = Position { x: 0, y: position.y.checked_add(1)? };
             } else {
-                self.rows.len()
+                let y = position.y.checked_sub(1)?;
+
+                position = Position { x: self.rows[y].len(), y };
+            }
+        }
+    }
+    // Text between two positions with rows joined by newlines, `start` must not come after `end`
+    #[must_use]
+    pub fn text_range(&self, start: &Position, end: &Position) -> String {
+        let mut text = String::new();
+
+        for y in start.y..=end.y.min(self.rows.len().saturating_sub(1)) {
+            let row = &self.rows[y];
+            let from = if y == start.y { start.x } else { 0 };
+            let to = if y == end.y { end.x } else { row.len() };
+
+            if y > start.y {
+                text.push('\n');
             }
+
+            text.push_str(&row.substring(from, to));
+        }
+
+        text
+    }
+    // Remove the text between two positions, returning what was removed
+    pub fn delete_range(&mut self, start: &Position, end: &Position) -> String {
+        if start.y >= self.rows.len() || self.read_only {
+            return String::new();
+        }
+
+        let end = if end.y >= self.rows.len() {
+            let last = self.rows.len() - 1;
+
+            Position { x: self.rows[last].len(), y: last }
         } else {
-            self.rows.len()
+            end.clone()
         };
+        let text = self.delete_unrecorded(start, &end);
 
-        for row in &mut self.rows[..until] {
-            start_with_comment = row.highlight(self.file_type.highlighting_options(), word, start_with_comment);
+        if !text.is_empty() {
+            self.history.record(Change::Delete { start: start.clone(), end, text: text.clone() });
         }
+
+        text
     }
-    pub fn unhighlight_rows(&mut self, start: usize) {
-        let start = start.saturating_sub(1);
-        
-        for row in self.rows.iter_mut().skip(start) {
-            row.unhighlight();
+    // Replace the whole rows `start..=end` with `lines` as a single undo step
+    pub fn replace_lines(&mut self, start: usize, end: usize, lines: &[String]) {
+        let Some(last_row) = self.rows.get(end) else {
+            return;
+        };
+
+        let mut from = Position { x: 0, y: start };
+        let mut to = Position { x: last_row.len(), y: end };
+
+        // Removing the lines entirely also takes one of the line breaks around them
+        if lines.is_empty() {
+            if end.saturating_add(1) < self.rows.len() {
+                to = Position { x: 0, y: end + 1 };
+            } else if start > 0 {
+                from = Position { x: self.rows[start - 1].len(), y: start - 1 };
+            }
+        }
+
+        self.begin_transaction();
+        self.delete_range(&from, &to);
+
+        if !lines.is_empty() {
+            self.insert_str(&from, &lines.join("\n"));
         }
+
+        self.commit();
     }
-    pub fn delete(&mut self, at: &Position) {
-        let len = self.len();
-        
-        if at.y >= len {
-            return;
+    // Record every edit until the matching `commit` as a single undo step, transactions can nest
+    pub fn begin_transaction(&mut self) {
+        self.history.begin_group();
+    }
+    pub fn commit(&mut self) {
+        self.history.end_group();
+    }
+    #[must_use]
+    pub fn lines(&self, start: usize, end: usize) -> Vec<String> {
+        self.rows
+            .iter()
+            .skip(start)
+            .take(end.saturating_sub(start).saturating_add(1))
+            .map(|row| row.as_str().to_owned())
+            .collect()
+    }
+    // Revert the most recent undo step, returning where the cursor should be placed
+    pub fn undo(&mut self) -> Option<Position> {
+        let changes = self.history.undo()?;
+        let mut cursor = None;
+
+        for change in changes.iter().rev() {
+            cursor = Some(match change {
+                Change::Insert { start, end, .. } => {
+                    self.delete_unrecorded(start, end);
+                    start.clone()
+                },
+                Change::Delete { start, text, .. } => self.insert_unrecorded(start, text),
+            });
         }
 
+        cursor
+    }
+    pub fn redo(&mut self) -> Option<Position> {
+        let changes = self.history.redo()?;
+        let mut cursor = None;
+
+        for change in &changes {
+            cursor = Some(match change {
+                Change::Insert { start, text, .. } => self.insert_unrecorded(start, text),
+                Change::Delete { start, end, .. } => {
+                    self.delete_unrecorded(start, end);
+                    start.clone()
+                },
+            });
+        }
+
+        cursor
+    }
+    // Undo and redo along the undo tree until the text is in the state after `node`, or the
+    // original text for `None`. Returns where the cursor should be placed
+    pub fn goto_undo_state(&mut self, node: Option<usize>) -> Option<Position> {
+        let (undos, redos) = self.history.path_to(node);
+        let mut cursor = None;
+
+        for _ in 0..undos {
+            cursor = self.undo().or(cursor);
+        }
+
+        for node in redos {
+            self.history.follow(node);
+            cursor = self.redo().or(cursor);
+        }
+
+        cursor
+    }
+    #[must_use]
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+    fn insert_unrecorded(&mut self, at: &Position, text: &str) -> Position {
         self.dirty = true;
+        self.generation += 1;
 
-        // Remove newline and append next line to current line
-        if at.x == self.rows[at.y].len() && at.y + 1 < len {
-            let next_row = self.rows.remove(at.y + 1);
-            let row = &mut self.rows[at.y];
-            
-            row.append(&next_row);
-        } else { // Delete like normal
-            let row = &mut self.rows[at.y];
-            
-            row.delete(at.x);
+        if self.rows.is_empty() {
+            self.rows.push(Row::default());
         }
-        
+
+        let mut position = at.clone();
+        let line_breaks = text.matches('\n').count();
+
+        for (index, line) in text.split('\n').enumerate() {
+            if index > 0 {
+                let new_row = self.rows.get_mut(position.y).unwrap().split(position.x);
+
+                self.rows.insert(position.y + 1, new_row);
+                position = Position { x: 0, y: position.y + 1 };
+            }
+
+            // A carriage return is only dropped from before a line feed, a lone one is text
+            let line = if index < line_breaks { line.strip_suffix('\r').unwrap_or(line) } else { line };
+            let row = self.rows.get_mut(position.y).unwrap();
+
+            position.x += row.insert_str(position.x, line);
+        }
+
+        self.marks.adjust_for_insert(at, &position);
+        self.jumps.adjust_for_insert(at, &position);
+        self.tab_stops.adjust_for_insert(at, &position);
+        self.folds.adjust_for_insert(at, &position);
         self.unhighlight_rows(at.y);
+
+        position
     }
-    pub fn save(&mut self) -> Result<()> {
-        if let Some(file_name) = &self.file_name {
-            let mut file = File::create(file_name)?;
-            
-            self.file_type = FileType::from(file_name);
-            
-            for row in &mut self.rows {
-                file.write_all(row.as_bytes())?;
-                file.write_all(b"\n")?;
-            }
+    fn delete_unrecorded(&mut self, start: &Position, end: &Position) -> String {
+        let text = self.text_range(start, end);
+
+        if text.is_empty() {
+            return text;
+        }
+
+        self.dirty = true;
+        self.generation += 1;
+
+        let tail = self.rows.get_mut(end.y).unwrap().split(end.x);
+        let first_row = self.rows.get_mut(start.y).unwrap();
+        let _ = first_row.split(start.x);
+
+        first_row.append(&tail);
+        self.rows.drain(start.y + 1..=end.y);
+        self.marks.adjust_for_delete(start, end);
+        self.jumps.adjust_for_delete(start, end);
+        self.tab_stops.adjust_for_delete(start, end);
+        self.folds.adjust_for_delete(start, end);
+        self.unhighlight_rows(start.y);
+
+        text
+    }
+    // Lock the file against other instances, or remember which one has it locked already
+    pub fn lock(&mut self) {
+        let Some(file_name) = self.file_name.as_ref().filter(|file_name| !is_remote(file_name) && !is_url(file_name) && split_member(file_name).is_none()) else {
+            return;
+        };
+
+        self.lock = None;
+
+        match FileLock::acquire(Path::new(file_name)) {
+            Ok(lock) => self.lock = lock,
+            Err(owner) => self.locked_by = Some(owner),
+        }
+    }
+    pub fn take_locked_by(&mut self) -> Option<LockOwner> {
+        self.locked_by.take()
+    }
+    // Write the text to `path` as it would be saved, or as UTF-8 when the encoding can't hold it. For
+    // keeping unsaved changes when the editor crashes, so the file itself is left alone
+    /// # Errors
+    ///
+    /// Will return `Err` if `path` can't be written
+    pub fn write_crash_dump(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.encoded_contents().unwrap_or_else(|_| self.contents()))?;
+
+        Ok(())
+    }
+    // Save the text to its file, keeping the file's mode, owner and attributes
+    /// # Errors
+    ///
+    /// Will return `Err` if the file can't be written
+    pub fn write_out(&mut self) -> Result<()> {
+        let Some(file_name) = &self.file_name else {
+            return Ok(());
+        };
 
+        if is_url(file_name) {
+            return Err(eyre!("can't save to a URL, choose a local file name"));
+        }
+
+        if is_remote(file_name) {
+            RemoteFile::parse(file_name)?.upload(&self.encoded_contents()?)?;
             self.dirty = false;
+
+            return Ok(());
         }
-        
+
+        if let Some((archive, member)) = split_member(file_name) {
+            write_member(archive, member, &self.encoded_contents()?)?;
+            self.dirty = false;
+
+            return Ok(());
+        }
+
+        write_atomically(Path::new(file_name), &self.encoded_contents()?)
+            .inspect_err(|error| tracing::warn!(file_name, %error, "write failed"))?;
+
+        self.file_type = FileType::from(file_name);
+        self.dirty = false;
+        self.disk_modified = modified_time(file_name);
+
+        tracing::info!(file_name, rows = self.rows.len(), "written");
+
         Ok(())
     }
-    // Iterate over all rows and call their find methods returning the row (y) and column (x) of a found query
-    // Return `None` if not found
-    #[must_use]
-    pub fn find(&self, query: &str, at: &Position, direction: SearchDirection) -> Option<Position> {
-        if at.y >= self.rows.len() {
-            return None;
+    // Write the text to `new_name` and remove the file it was read from once that worked. A buffer
+    // without a file is just saved there
+    /// # Errors
+    ///
+    /// Will return `Err` if `new_name` already exists, or writing it or removing the old file fails
+    pub fn rename(&mut self, new_name: &str) -> Result<()> {
+        if self.file_name.as_deref().is_some_and(is_remote) || is_remote(new_name) {
+            return Err(eyre!("files over SFTP can't be renamed"));
         }
-        
-        let mut position = Position { x: at.x, y: at.y };
-        let start = if direction == SearchDirection::Forward {
-            at.y
+
+        if self.file_name.as_deref().and_then(split_member).is_some() {
+            return Err(eyre!("files in an archive can't be renamed"));
+        }
+
+        if Path::new(new_name).exists() {
+            return Err(eyre!("{new_name} already exists"));
+        }
+
+        let old_name = self.file_name.replace(new_name.to_owned());
+
+        if let Err(error) = self.write_out() {
+            self.file_name = old_name;
+            return Err(error);
+        }
+
+        if let Some(old_name) = old_name.filter(|old_name| Path::new(old_name).exists()) {
+            fs::remove_file(&old_name)?;
+
+            tracing::info!(old_name, new_name, "renamed");
+        }
+
+        self.lock();
+
+        Ok(())
+    }
+    // Save through `sudo tee` or `pkexec tee`, for files the user isn't allowed to write.
+    // Returns the helper that was used, it asks for a password on the terminal
+    /// # Errors
+    ///
+    /// Will return `Err` if neither helper is installed, or the helper fails or is refused
+    pub fn write_out_privileged(&mut self) -> Result<&'static str> {
+        let Some(file_name) = self.file_name.clone() else {
+            return Err(eyre!("The buffer has no file name"));
+        };
+
+        let helper = PRIVILEGE_HELPERS
+            .into_iter()
+            .find(|helper| is_installed(helper))
+            .ok_or_else(|| eyre!("Neither {} is installed", PRIVILEGE_HELPERS.join(" nor ")))?;
+
+        let mut child = Command::new(helper)
+            .args(["tee", "--", &file_name])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()?;
+
+        // Closing stdin lets tee finish, the exit status explains a refused password better than the broken pipe
+        let contents = self.encoded_contents()?;
+        let written = child.stdin.take().map_or(Ok(()), |mut stdin| stdin.write_all(&contents));
+        let status = child.wait()?;
+
+        if !status.success() {
+            return Err(eyre!("{helper} tee failed ({status})"));
+        }
+
+        written?;
+
+        self.file_type = FileType::from(file_name.as_str());
+        self.dirty = false;
+        self.disk_modified = modified_time(&file_name);
+
+        tracing::info!(file_name, helper, rows = self.rows.len(), "written with privileges");
+
+        Ok(helper)
+    }
+    // Whether the file was modified by something else since it was last read or written
+    #[must_use]
+    pub fn changed_on_disk(&self) -> bool {
+        // Large files are usually logs that keep growing, reloading them would read them whole
+        let Some(file_name) = self.file_name.as_ref().filter(|_| self.stream.is_none()) else {
+            return false;
+        };
+
+        modified_time(file_name).is_some_and(|modified| self.disk_modified != Some(modified))
+    }
+    // Replace the text with what's on disk now. It's recorded as one change, so undo brings back
+    // what the buffer held before
+    /// # Errors
+    ///
+    /// Will return `Err` if the file can't be read
+    pub fn reload(&mut self) -> Result<()> {
+        let Some(file_name) = self.file_name.clone() else {
+            return Ok(());
+        };
+
+        let bytes = if is_remote(&file_name) {
+            RemoteFile::parse(&file_name)?.download()?
+        } else if is_url(&file_name) {
+            fetch_url(&file_name)?
+        } else if let Some((archive, member)) = split_member(&file_name) {
+            read_member(archive, member)?
         } else {
-            0
+            fs::read(&file_name)?
         };
-        let end = if direction == SearchDirection::Forward {
-            self.rows.len()
+        let encoding = Encoding::detect(&bytes);
+        let mut private_use = PrivateUse::default();
+        let contents = encoding.decode(&bytes, &mut private_use)?;
+        let lines: Vec<String> = contents.lines().map(str::to_owned).collect();
+        let read_only = std::mem::replace(&mut self.read_only, false);
+
+        if self.rows.is_empty() {
+            self.insert_str(&Position::default(), &lines.join("\n"));
         } else {
-            at.y.saturating_add(1)
+            self.replace_lines(0, self.rows.len() - 1, &lines);
+        }
+
+        self.read_only = read_only;
+        self.dirty = false;
+        self.no_final_newline = !contents.is_empty() && !contents.ends_with('\n');
+        self.encoding = encoding;
+        self.private_use = private_use;
+        self.disk_modified = modified_time(&file_name);
+
+        tracing::info!(file_name, rows = self.rows.len(), "reloaded");
+
+        Ok(())
+    }
+    // Stop reporting the current version on disk as a change, the buffer's text wins
+    pub fn ignore_disk_change(&mut self) {
+        if let Some(file_name) = &self.file_name {
+            self.disk_modified = modified_time(file_name);
+        }
+    }
+    // The text in the file's encoding
+    fn encoded_contents(&self) -> Result<Vec<u8>> {
+        self.encoding.encode(&String::from_utf8_lossy(&self.contents()), self.private_use)
+    }
+    // The text as it's saved, rows separated by newlines
+    fn contents(&self) -> Vec<u8> {
+        let mut contents = Vec::new();
+
+        for (index, row) in self.rows.iter().enumerate() {
+            if index > 0 {
+                contents.push(b'\n');
+            }
+
+            contents.extend_from_slice(row.as_bytes());
+        }
+
+        if !self.no_final_newline && !self.rows.is_empty() {
+            contents.push(b'\n');
+        }
+
+        contents
+    }
+    // Whether saving ends the file with a newline, by default it's kept the way the file was
+    pub fn set_final_newline(&mut self, final_newline: bool) {
+        self.no_final_newline = !final_newline;
+    }
+    // Write the undo history next to the other undo files in `dir`, keyed by this file's path
+    /// # Errors
+    ///
+    /// Will return `Err` if `dir` can't be created or the undo file can't be written
+    pub fn save_undo(&self, dir: &Path) -> Result<()> {
+        let Some(file_name) = &self.file_name else {
+            return Ok(());
         };
-        
-        for _ in start..end {
-            if let Some(row) = self.rows.get(position.y) {
-                if let Some(x) = row.find(query, position.x, direction) {
-                    position.x = x;
-                    return Some(position);
-                }
-                
-                if direction == SearchDirection::Forward {
-                    position.y = position.y.saturating_add(1);
-                    position.x = 0;
-                } else {
-                    position.y = position.y.saturating_sub(1);
-                    position.x = self.rows[position.y].len();
-                }
-            } else {
-                return None;
+
+        fs::create_dir_all(dir)?;
+        fs::write(dir.join(state_file_name(file_name, "undo")), self.history.serialize(self.checksum()))?;
+
+        Ok(())
+    }
+    // Restore the history saved by `save_undo`, as long as the file hasn't changed since.
+    // Returns whether there was a history to restore
+    /// # Errors
+    ///
+    /// Will return `Err` if an undo file exists but can't be read
+    pub fn load_undo(&mut self, dir: &Path) -> Result<bool> {
+        let Some(file_name) = &self.file_name else {
+            return Ok(false);
+        };
+
+        let path = dir.join(state_file_name(file_name, "undo"));
+
+        if !path.exists() {
+            return Ok(false);
+        }
+
+        let Some(history) = History::deserialize(&fs::read_to_string(path)?, self.checksum()) else {
+            return Ok(false);
+        };
+
+        self.history = history;
+
+        Ok(true)
+    }
+    // Write the closed folds to `dir` like `save_undo` does the history, removing the file once none are left
+    /// # Errors
+    ///
+    /// Will return `Err` if `dir` can't be created or the fold file can't be written or removed
+    pub fn save_folds(&self, dir: &Path) -> Result<()> {
+        let Some(file_name) = &self.file_name else {
+            return Ok(());
+        };
+
+        let path = dir.join(state_file_name(file_name, "folds"));
+
+        if self.folds.is_empty() {
+            if path.exists() {
+                fs::remove_file(path)?;
             }
+
+            return Ok(());
         }
-        
-        None
+
+        fs::create_dir_all(dir)?;
+        fs::write(path, self.folds.serialize(self.checksum()))?;
+
+        Ok(())
+    }
+    // Close the folds saved by `save_folds` again, as long as the file hasn't changed since
+    /// # Errors
+    ///
+    /// Will return `Err` if a fold file exists but can't be read
+    pub fn load_folds(&mut self, dir: &Path) -> Result<()> {
+        let Some(file_name) = &self.file_name else {
+            return Ok(());
+        };
+
+        let path = dir.join(state_file_name(file_name, "folds"));
+
+        if path.exists() {
+            if let Some(folds) = Folds::deserialize(&fs::read_to_string(path)?, self.checksum(), self.len()) {
+                self.folds = folds;
+            }
+        }
+
+        Ok(())
+    }
+    // How many edits the text has had, which changes whenever it does
+    #[must_use]
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+    // FNV-1a of the text as `write_out` saves it
+    #[must_use]
+    pub fn checksum(&self) -> u64 {
+        self.contents()
+            .iter()
+            .fold(FNV_OFFSET, |hash, byte| (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME))
+    }
+    pub fn set_mark(&mut self, name: char, position: Position) {
+        self.marks.set(name, position);
+    }
+    #[must_use]
+    pub fn mark(&self, name: char) -> Option<&Position> {
+        self.marks.get(name)
+    }
+    #[must_use]
+    pub fn marks(&self) -> &Marks {
+        &self.marks
     }
-}
\ No newline at end of file
+    #[must_use]
+    pub fn folds(&self) -> &Folds {
+        &self.folds
+    }
+    pub fn folds_mut(&mut self) -> &mut Folds {
+        &mut self.folds
+    }
+    pub fn record_jump(&mut self, from: Position) {
+        self.jumps.record(from);
+    }
+    pub fn jump_back(&mut self, current: Position) -> Option<Position> {
+        self.jumps.back(current)
+    }
+    pub fn jump_forward(&mut self) -> Option<Position> {
+        self.jumps.forward()
+    }
+    pub fn set_tab_stops(&mut self, stops: Vec<(Position, Position)>) {
+        self.tab_stops.set(stops);
+    }
+    // Start and end of the next snippet placeholder, taking it off the ones left
+    pub fn next_tab_stop(&mut self) -> Option<(Position, Position)> {
+        self.tab_stops.next()
+    }
+    // Highlight rows in place up to and including `until`, rows that are already highlighted are skipped
+    pub fn highlight(&mut self, word: &Option<String>, until: Option<usize>) {
+        let mut start_with_comment = false;
+        let until = until.map_or(self.rows.len(), |until| until.saturating_add(1).min(self.rows.len()));
+
+        for row in &mut self.rows[..until] {
+            start_with_comment = row.highlight(self.file_type.highlighting_options(), word, start_with_comment);
+        }
+    }
+    // An edit can open or close a multiline comment, so every row from `start` down needs highlighting again
+    fn unhighlight_rows(&mut self, start: usize) {
+        for row in self.rows.iter_mut().skip(start) {
+            row.unhighlight();
+        }
+    }
+    // The rows converted to highlighted editor rows, for output that needs syntax colors
+    #[must_use]
+    pub fn highlighted_rows(&self) -> Vec<Row> {
+        let started = Instant::now();
+        let mut start_with_comment = false;
+
+        let rows = self.rows
+            .iter()
+            .map(|row| {
+                let mut highlighted = Row::from(row.as_str());
+
+                start_with_comment = highlighted.highlight(self.file_type.highlighting_options(), &None, start_with_comment);
+
+                highlighted
+            })
+            .collect();
+
+        tracing::debug!(rows = self.rows.len(), elapsed = ?started.elapsed(), "highlighted");
+
+        rows
+    }
+    #[must_use]
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        find_conflicts(&self.rows)
+    }
+    // Bytes from the start of the file to `at` as it's saved, each row ending in one newline
+    #[must_use]
+    pub fn byte_offset(&self, at: &Position) -> usize {
+        let before: usize = self.rows.iter().take(at.y).map(|row| row.as_bytes().len() + 1).sum();
+
+        before + self.rows.get(at.y).map_or(0, |row| row.byte_index(at.x))
+    }
+    #[must_use]
+    pub fn row(&self, index: usize) -> Option<&Row> {
+        self.rows.get(index)
+    }
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+    // Whether there are unsaved changes, like piped in text has until it's written somewhere
+    pub fn set_dirty(&mut self, dirty: bool) {
+        self.dirty = dirty;
+    }
+    #[must_use]
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        self.encoding = encoding;
+    }
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+    #[must_use]
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+    #[must_use]
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+}
+// Undo and fold files are named after a hash of the absolute path, so they don't collide across directories
+fn state_file_name(file_name: &str, extension: &str) -> String {
+    let path = fs::canonicalize(file_name).unwrap_or_else(|_| PathBuf::from(file_name));
+    let hash = path
+        .as_os_str()
+        .as_encoded_bytes()
+        .iter()
+        .fold(FNV_OFFSET, |hash, byte| (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME));
+
+    format!("{hash:016x}.{extension}")
+}
+
+fn is_installed(program: &str) -> bool {
+    env::var_os("PATH").is_some_and(|paths| env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+}
+
+fn modified_time(file_name: &str) -> Option<SystemTime> {
+    fs::metadata(file_name).and_then(|metadata| metadata.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lone_carriage_returns_stay_in_the_row() {
+        let mut document = Document::default();
+        let end = document.insert_str(&Position::default(), "one\rtwo\r\nthree\r");
+
+        assert_eq!(document.lines(0, document.len()), ["one\rtwo", "three\r"]);
+        assert_eq!((end.x, end.y), (6, 1));
+    }
+}
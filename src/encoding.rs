@@ -0,0 +1,244 @@
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+const UTF16LE_BOM: &[u8] = b"\xff\xfe";
+// Bytes that aren't valid UTF-8 are held as the last 256 private use characters, U+10FF00 plus the
+// byte, so they're written back unchanged
+const RAW_BYTE_BASE: u32 = 0x10_FF00;
+const RAW_BYTE_RANGE: std::ops::RangeInclusive<char> = '\u{10FF00}'..='\u{10FFFF}';
+
+// How a file's text is stored on disk, buffers always hold it as UTF-8
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    // UTF-8 starting with a byte order mark, like some Windows programs write it
+    Utf8Bom,
+    Utf16Le,
+    // ISO 8859-1, one byte per character up to U+00FF
+    Latin1,
+}
+
+// What the characters U+10FF00 to U+10FFFF in a buffer are. They're valid text, so a file that
+// really has them keeps them as text, and one can't have both them and invalid bytes
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum PrivateUse {
+    // Neither has been read yet, any typed in are saved as raw bytes
+    #[default]
+    Unseen,
+    RawBytes,
+    Text,
+}
+
+impl Encoding {
+    pub const NAMES: [(&'static str, Self); 4] = [
+        ("utf-8", Self::Utf8),
+        ("utf-8-bom", Self::Utf8Bom),
+        ("utf-16le", Self::Utf16Le),
+        ("latin-1", Self::Latin1),
+    ];
+
+    // Case, hyphens and underscores don't matter, and `iso-8859-1` is Latin-1 too
+    /// # Errors
+    ///
+    /// Will return `Err` if no encoding is called `name`
+    pub fn from_name(name: &str) -> Result<Self> {
+        let normalize = |name: &str| name.to_lowercase().replace(['-', '_', ' '], "");
+        let wanted = normalize(name);
+
+        if wanted == "iso88591" {
+            return Ok(Self::Latin1);
+        }
+
+        Self::NAMES
+            .iter()
+            .find(|(encoding_name, _)| normalize(encoding_name) == wanted)
+            .map(|(_, encoding)| *encoding)
+            .ok_or_else(|| eyre!("unknown encoding `{name}`, expected one of: {}", Self::NAMES.map(|(name, _)| name).join(", ")))
+    }
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        Self::NAMES.iter().find(|(_, encoding)| *encoding == self).map_or("utf-8", |(name, _)| name)
+    }
+    // Files are told apart by their byte order mark, anything without one is read as UTF-8
+    #[must_use]
+    pub fn detect(bytes: &[u8]) -> Self {
+        if bytes.starts_with(UTF8_BOM) {
+            Self::Utf8Bom
+        } else if bytes.starts_with(UTF16LE_BOM) {
+            Self::Utf16Le
+        } else {
+            Self::Utf8
+        }
+    }
+    // The text `bytes` hold in this encoding, without its byte order mark. Invalid UTF-8 is kept as
+    // raw bytes, `private_use` says which the characters that hold them are
+    /// # Errors
+    ///
+    /// Will return `Err` if `bytes` aren't valid UTF-16, or have both invalid UTF-8 and the characters
+    /// raw bytes are held as
+    pub fn decode(self, bytes: &[u8], private_use: &mut PrivateUse) -> Result<String> {
+        match self {
+            Self::Utf8 => decode_utf8(bytes, private_use),
+            Self::Utf8Bom => decode_utf8(bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes), private_use),
+            Self::Utf16Le => {
+                let bytes = bytes.strip_prefix(UTF16LE_BOM).unwrap_or(bytes);
+
+                if !bytes.len().is_multiple_of(2) {
+                    return Err(eyre!("UTF-16 text can't have an odd number of bytes"));
+                }
+
+                let units = bytes.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]]));
+
+                let text = char::decode_utf16(units).collect::<Result<String, _>>()?;
+
+                if text.contains(|c| RAW_BYTE_RANGE.contains(&c)) {
+                    *private_use = PrivateUse::Text;
+                }
+
+                Ok(text)
+            },
+            Self::Latin1 => Ok(bytes.iter().map(|byte| char::from(*byte)).collect()),
+        }
+    }
+    // `text` as it's written to a file in this encoding, with its byte order mark. Raw bytes are written
+    // as they are, unless `private_use` says the characters are text
+    /// # Errors
+    ///
+    /// Will return `Err` if `text` has characters Latin-1 doesn't, or raw bytes UTF-16 can't hold
+    pub fn encode(self, text: &str, private_use: PrivateUse) -> Result<Vec<u8>> {
+        let raw_byte = |c: char| if private_use == PrivateUse::Text { None } else { raw_byte(c) };
+
+        match self {
+            Self::Utf8 => Ok(encode_utf8(text, raw_byte)),
+            Self::Utf8Bom => Ok([UTF8_BOM, &encode_utf8(text, raw_byte)].concat()),
+            Self::Utf16Le => {
+                if let Some(byte) = text.chars().find_map(raw_byte) {
+                    return Err(eyre!("the invalid byte 0x{byte:02X} can't be written in UTF-16"));
+                }
+
+                Ok(UTF16LE_BOM.iter().copied().chain(text.encode_utf16().flat_map(u16::to_le_bytes)).collect())
+            },
+            Self::Latin1 => text
+                .chars()
+                .map(|c| {
+                    raw_byte(c)
+                        .or_else(|| u8::try_from(c).ok())
+                        .ok_or_else(|| eyre!("'{c}' (U+{:04X}) can't be written in Latin-1", u32::from(c)))
+                })
+                .collect(),
+        }
+    }
+}
+
+// The byte `c` holds when it stands for one that wasn't valid UTF-8
+#[must_use]
+pub fn raw_byte(c: char) -> Option<u8> {
+    u32::from(c).checked_sub(RAW_BYTE_BASE).and_then(|byte| u8::try_from(byte).ok())
+}
+// `bytes` as UTF-8, with each byte of an invalid sequence kept as a raw byte. `private_use` carries
+// over between calls, so a file read a line at a time is checked as a whole
+/// # Errors
+///
+/// Will return `Err` if there are both invalid bytes and characters they'd be mistaken for
+pub fn decode_utf8(bytes: &[u8], private_use: &mut PrivateUse) -> Result<String> {
+    let mut text = String::with_capacity(bytes.len());
+
+    for chunk in bytes.utf8_chunks() {
+        if chunk.valid().contains(|c| RAW_BYTE_RANGE.contains(&c)) {
+            *private_use = match private_use {
+                PrivateUse::RawBytes => return Err(mixed_private_use()),
+                _ => PrivateUse::Text,
+            };
+        }
+
+        if !chunk.invalid().is_empty() {
+            *private_use = match private_use {
+                PrivateUse::Text => return Err(mixed_private_use()),
+                _ => PrivateUse::RawBytes,
+            };
+        }
+
+        text.push_str(chunk.valid());
+        text.extend(chunk.invalid().iter().filter_map(|byte| char::from_u32(RAW_BYTE_BASE + u32::from(*byte))));
+    }
+
+    Ok(text)
+}
+fn mixed_private_use() -> color_eyre::Report {
+    eyre!("the file has both invalid UTF-8 and characters U+10FF00 to U+10FFFF, which couldn't be told apart when saving")
+}
+fn encode_utf8(text: &str, raw_byte: impl Fn(char) -> Option<u8>) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(text.len());
+
+    for c in text.chars() {
+        match raw_byte(c) {
+            Some(byte) => bytes.push(byte),
+            None => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(encoding: Encoding, bytes: &[u8]) -> (String, PrivateUse) {
+        let mut private_use = PrivateUse::default();
+        let text = encoding.decode(bytes, &mut private_use).unwrap();
+
+        assert_eq!(encoding.encode(&text, private_use).unwrap(), bytes);
+
+        (text, private_use)
+    }
+
+    #[test]
+    fn invalid_bytes_are_written_back_unchanged() {
+        let (text, private_use) = round_trip(Encoding::Utf8, b"caf\xe9 \xff\xfe ok");
+
+        assert_eq!(private_use, PrivateUse::RawBytes);
+        assert_eq!(text.chars().filter_map(raw_byte).collect::<Vec<_>>(), [0xe9, 0xff, 0xfe]);
+        assert!(text.ends_with(" ok"));
+    }
+
+    #[test]
+    fn private_use_text_stays_text() {
+        let bytes = "glyph \u{10FF41}\u{10FFFF}".as_bytes();
+
+        for encoding in [Encoding::Utf8, Encoding::Utf8Bom] {
+            let bytes = if encoding == Encoding::Utf8Bom { [UTF8_BOM, bytes].concat() } else { bytes.to_vec() };
+            let (text, private_use) = round_trip(encoding, &bytes);
+
+            assert_eq!(private_use, PrivateUse::Text);
+            assert_eq!(text, "glyph \u{10FF41}\u{10FFFF}");
+        }
+
+        let utf16: Vec<u8> = UTF16LE_BOM.iter().copied().chain("\u{10FF41}".encode_utf16().flat_map(u16::to_le_bytes)).collect();
+
+        assert_eq!(round_trip(Encoding::Utf16Le, &utf16).1, PrivateUse::Text);
+    }
+
+    #[test]
+    fn invalid_bytes_and_private_use_text_together_are_refused() {
+        let mut private_use = PrivateUse::default();
+
+        assert!(decode_utf8("\u{10FF41}\n".as_bytes(), &mut private_use).is_ok());
+        // Read a line at a time, the second line conflicts with the first
+        assert!(decode_utf8(b"\xff", &mut private_use).is_err());
+        assert!(decode_utf8(b"\xff \xf4\x8f\xbd\x81", &mut PrivateUse::default()).is_err());
+    }
+
+    #[test]
+    fn other_encodings() {
+        assert_eq!(round_trip(Encoding::Latin1, b"caf\xe9").0, "café");
+        assert_eq!(round_trip(Encoding::Utf16Le, b"\xff\xfeh\0i\0").0, "hi");
+        assert!(Encoding::Latin1.encode("€", PrivateUse::Unseen).is_err());
+        assert!(Encoding::Utf16Le.encode("\u{10FFFF}", PrivateUse::RawBytes).is_err());
+        assert!(Encoding::Utf16Le.decode(b"\xff\xfeh", &mut PrivateUse::default()).is_err());
+        assert_eq!(Encoding::from_name("ISO-8859-1").unwrap(), Encoding::Latin1);
+        assert_eq!(Encoding::detect(b"\xef\xbb\xbfx"), Encoding::Utf8Bom);
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::regex::Regex;
+
+// The project directory as a tree of rows, directories opened by the user listing what's inside them.
+// Hidden files and those the root `.gitignore` lists are left out unless they're toggled on
+pub struct FileTree {
+    root: PathBuf,
+    entries: Vec<TreeEntry>,
+    expanded: BTreeSet<PathBuf>,
+    ignore: Gitignore,
+    show_hidden: bool,
+    show_ignored: bool,
+    selected: usize,
+    // First entry shown, kept so the selection stays in view
+    offset: usize,
+}
+
+// A file or directory shown in the tree, `depth` counting from 0 for the root's children
+pub struct TreeEntry {
+    pub path: PathBuf,
+    pub name: String,
+    pub depth: usize,
+    pub is_dir: bool,
+}
+
+// The rules of a directory's `.gitignore`, for paths relative to that directory
+#[derive(Default)]
+pub struct Gitignore {
+    rules: Vec<IgnoreRule>,
+}
+
+// One pattern of a `.gitignore`, matched against paths relative to the root
+struct IgnoreRule {
+    regex: Regex,
+    negated: bool,
+    dir_only: bool,
+}
+
+#[allow(clippy::missing_const_for_fn)]
+impl FileTree {
+    #[must_use]
+    pub fn new(root: PathBuf) -> Self {
+        let ignore = Gitignore::load(&root);
+        let mut tree = Self {
+            root,
+            entries: Vec::new(),
+            expanded: BTreeSet::new(),
+            ignore,
+            show_hidden: false,
+            show_ignored: false,
+            selected: 0,
+            offset: 0,
+        };
+
+        tree.refresh();
+        tree
+    }
+    // Read the directories again, keeping the selection on the same path where it's still shown
+    pub fn refresh(&mut self) {
+        let selected = self.selected_entry().map(|entry| entry.path.clone());
+        let mut entries = Vec::new();
+
+        self.list_dir(&self.root, 0, &mut entries);
+        self.entries = entries;
+        self.selected = selected
+            .and_then(|path| self.entries.iter().position(|entry| entry.path == path))
+            .unwrap_or(0)
+            .min(self.entries.len().saturating_sub(1));
+    }
+    fn list_dir(&self, dir: &Path, depth: usize, entries: &mut Vec<TreeEntry>) {
+        let Ok(read) = fs::read_dir(dir) else {
+            return;
+        };
+
+        let mut children: Vec<TreeEntry> = read
+            .flatten()
+            .map(|entry| TreeEntry {
+                path: entry.path(),
+                name: entry.file_name().to_string_lossy().into_owned(),
+                depth,
+                is_dir: entry.file_type().is_ok_and(|file_type| file_type.is_dir()),
+            })
+            .filter(|entry| self.show_hidden || !entry.name.starts_with('.'))
+            .filter(|entry| self.show_ignored || !self.is_ignored(&entry.path, entry.is_dir))
+            .collect();
+
+        // Directories first, then by name
+        children.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+
+        for child in children {
+            let path = child.path.clone();
+            let open = child.is_dir && self.expanded.contains(&path);
+
+            entries.push(child);
+
+            if open {
+                self.list_dir(&path, depth + 1, entries);
+            }
+        }
+    }
+    fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        path.strip_prefix(&self.root).is_ok_and(|relative| self.ignore.is_ignored(relative, is_dir))
+    }
+    // Open or close the selected directory. Returns the selected file's path instead when it isn't one
+    pub fn activate(&mut self) -> Option<PathBuf> {
+        let entry = self.selected_entry()?;
+
+        if !entry.is_dir {
+            return Some(entry.path.clone());
+        }
+
+        let path = entry.path.clone();
+
+        if !self.expanded.remove(&path) {
+            self.expanded.insert(path);
+        }
+
+        self.refresh();
+
+        None
+    }
+    pub fn expand(&mut self) {
+        if let Some(entry) = self.selected_entry().filter(|entry| entry.is_dir) {
+            self.expanded.insert(entry.path.clone());
+            self.refresh();
+        }
+    }
+    // Close the selected directory, or from inside one select the directory it's in
+    pub fn collapse(&mut self) {
+        let Some(entry) = self.selected_entry() else {
+            return;
+        };
+
+        let path = entry.path.clone();
+
+        if entry.is_dir && self.expanded.remove(&path) {
+            self.refresh();
+            return;
+        }
+
+        let parent = path.parent();
+
+        if let Some(index) = self.entries.iter().position(|entry| Some(entry.path.as_path()) == parent) {
+            self.selected = index;
+        }
+    }
+    pub fn toggle_hidden(&mut self) -> bool {
+        self.show_hidden = !self.show_hidden;
+        self.refresh();
+        self.show_hidden
+    }
+    pub fn toggle_ignored(&mut self) -> bool {
+        self.show_ignored = !self.show_ignored;
+        self.refresh();
+        self.show_ignored
+    }
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.entries.len().saturating_sub(1));
+    }
+    // Move the first entry shown so the selection fits in `height` rows
+    pub fn scroll(&mut self, height: usize) {
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if height > 0 && self.selected >= self.offset + height {
+            self.offset = self.selected + 1 - height;
+        }
+
+        self.offset = self.offset.min(self.entries.len().saturating_sub(height.max(1)));
+    }
+    // Scroll with the mouse wheel, leaving the selection where it is
+    pub fn scroll_by(&mut self, lines: usize, down: bool, height: usize) {
+        let offset = if down { self.offset.saturating_add(lines) } else { self.offset.saturating_sub(lines) };
+
+        self.offset = offset.min(self.entries.len().saturating_sub(height.max(1)));
+    }
+    #[must_use]
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+    #[must_use]
+    pub fn entries(&self) -> &[TreeEntry] {
+        &self.entries
+    }
+    #[must_use]
+    pub fn is_expanded(&self, path: &Path) -> bool {
+        self.expanded.contains(path)
+    }
+    #[must_use]
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+    #[must_use]
+    pub fn selected_entry(&self) -> Option<&TreeEntry> {
+        self.entries.get(self.selected)
+    }
+    #[must_use]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl Gitignore {
+    // No rules when `root` has no `.gitignore`
+    #[must_use]
+    pub fn load(root: &Path) -> Self {
+        let rules = fs::read_to_string(root.join(".gitignore")).map(|contents| parse_gitignore(&contents)).unwrap_or_default();
+
+        Self { rules }
+    }
+    // The last rule matching the path decides, so `!` rules can bring back what earlier ones left out
+    #[must_use]
+    pub fn is_ignored(&self, relative: &Path, is_dir: bool) -> bool {
+        let relative = relative.to_string_lossy().replace('\\', "/");
+
+        self.rules
+            .iter()
+            .rev()
+            .filter(|rule| is_dir || !rule.dir_only)
+            .find(|rule| rule.regex.captures_at(&relative, 0).is_some())
+            .is_some_and(|rule| !rule.negated)
+    }
+}
+
+// The rules of a `.gitignore`. Patterns with a `/` before their end are relative to the root, others
+// match a name at any depth. Patterns that don't compile are skipped
+fn parse_gitignore(contents: &str) -> Vec<IgnoreRule> {
+    contents
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let (negated, line) = line.strip_prefix('!').map_or((false, line), |rest| (true, rest));
+            let (dir_only, line) = line.strip_suffix('/').map_or((false, line), |rest| (true, rest));
+            let anchored = line.contains('/');
+            let glob = line.trim_start_matches('/');
+            let prefix = if anchored { "^" } else { "^(?:.*/)?" };
+            let regex = Regex::new(&format!("{prefix}{}$", glob_to_regex(glob))).ok()?;
+
+            Some(IgnoreRule { regex, negated, dir_only })
+        })
+        .collect()
+}
+
+// `*` and `?` stay within a path component, `**` crosses them and `[...]` is a class as in a regex
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::new();
+    let mut chars = glob.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.next_if_eq(&'*').is_some() => {
+                if chars.next_if_eq(&'/').is_some() {
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            },
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '[' => {
+                regex.push('[');
+
+                if chars.next_if_eq(&'!').is_some() {
+                    regex.push('^');
+                }
+            },
+            ']' | '-' => regex.push(c),
+            c if c.is_alphanumeric() || c == '_' => regex.push(c),
+            c => regex.push_str(&::regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+
+    regex
+}
@@ -1,6 +1,44 @@
+use std::path::Path;
+
+const COMMIT_MESSAGE: &str = "Git Commit";
+// Longest a commit's summary and body lines should be
+pub const SUMMARY_WIDTH: usize = 50;
+pub const BODY_WIDTH: usize = 72;
+
+// Words ending blocks in shell scripts, `done` closes any of the loops
+const SHELL_BLOCK_ENDS: &[BlockEnd] = &[
+    BlockEnd { word: "fi", openers: &["if"], inline_openers: &[] },
+    BlockEnd { word: "done", openers: &["for", "while", "until", "select"], inline_openers: &[] },
+    BlockEnd { word: "esac", openers: &["case"], inline_openers: &[] },
+];
+const RUBY_BLOCK_ENDS: &[BlockEnd] = &[BlockEnd {
+    word: "end",
+    openers: &["def", "class", "module", "if", "unless", "while", "until", "case", "begin", "for"],
+    inline_openers: &["do"],
+}];
+const LUA_BLOCK_ENDS: &[BlockEnd] = &[BlockEnd {
+    word: "end",
+    openers: &["if", "for", "while"],
+    inline_openers: &["do", "function"],
+}];
+
 pub struct FileType {
     name: String,
     hl_opts: HighlightingOptions,
+    block_ends: &'static [BlockEnd],
+    // What starts a comment running to the end of the line
+    line_comment: Option<&'static str>,
+    // Display columns shaded down the buffer, the first ones past a line length limit
+    color_columns: &'static [usize],
+}
+
+// A word that ends a block, dedented to the row that opened the block when it's typed at the start of a line
+pub struct BlockEnd {
+    pub word: &'static str,
+    // First words of rows that open the block
+    pub openers: &'static [&'static str],
+    // Words that open it anywhere in a row, like `do` after a method call
+    pub inline_openers: &'static [&'static str],
 }
 
 #[derive(Default)]
@@ -25,6 +63,24 @@ impl FileType {
         &self.hl_opts
     }
     #[must_use]
+    pub const fn block_ends(&self) -> &[BlockEnd] {
+        self.block_ends
+    }
+    #[must_use]
+    pub const fn line_comment(&self) -> Option<&'static str> {
+        self.line_comment
+    }
+    #[must_use]
+    pub const fn color_columns(&self) -> &[usize] {
+        self.color_columns
+    }
+    // Git's commit message: a summary of 50 columns at most, then a blank row and a body wrapped at 72,
+    // with rows starting with `#` left out of the commit
+    #[must_use]
+    pub fn is_commit_message(&self) -> bool {
+        self.name == COMMIT_MESSAGE
+    }
+    #[must_use]
     pub fn from(file_name: &str) -> Self {
         if std::path::Path::new(file_name)
             .extension()
@@ -107,10 +163,69 @@ impl FileType {
                         "f64".to_owned(),
                     ],
                 },
+                block_ends: &[],
+                line_comment: Some("//"),
+                color_columns: &[],
             };
         }
-        
-        Self::default()
+
+        let extension = Path::new(file_name)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        match extension.as_str() {
+            _ if Path::new(file_name).file_name().is_some_and(|name| name == "COMMIT_EDITMSG") => Self::commit_message(),
+            "sh" | "bash" | "zsh" => Self::with_block_ends("Shell", SHELL_BLOCK_ENDS, "#"),
+            "rb" => Self::with_block_ends("Ruby", RUBY_BLOCK_ENDS, "#"),
+            "lua" => Self::with_block_ends("Lua", LUA_BLOCK_ENDS, "--"),
+            "md" | "markdown" => Self::named("Markdown"),
+            "txt" => Self::named("Text"),
+            _ => Self::default(),
+        }
+    }
+    // The file type Vim or Emacs calls `mode`, like `sh`, `ruby` or `markdown-mode`
+    #[must_use]
+    pub fn from_mode(mode: &str) -> Option<Self> {
+        let mode = mode.trim().to_ascii_lowercase();
+        let mode = mode.strip_suffix("-mode").unwrap_or(&mode);
+        let mode = mode.strip_suffix("-ts").unwrap_or(mode);
+
+        let file_name = match mode {
+            "rust" => "mode.rs",
+            "sh" | "bash" | "zsh" | "shell-script" => "mode.sh",
+            "ruby" => "mode.rb",
+            "lua" => "mode.lua",
+            "markdown" | "gfm" => "mode.md",
+            "text" => "mode.txt",
+            "gitcommit" | "git-commit" => "COMMIT_EDITMSG",
+            _ => return None,
+        };
+
+        Some(Self::from(file_name))
+    }
+    fn commit_message() -> Self {
+        Self {
+            name: COMMIT_MESSAGE.to_owned(),
+            line_comment: Some("#"),
+            color_columns: &[SUMMARY_WIDTH, BODY_WIDTH],
+            ..Self::default()
+        }
+    }
+    // Prose, known only by name so settings like hard wrap can be set for it
+    fn named(name: &str) -> Self {
+        Self { name: name.to_owned(), ..Self::default() }
+    }
+    // Languages only known for where their blocks end, with numbers and strings highlighted
+    fn with_block_ends(name: &str, block_ends: &'static [BlockEnd], line_comment: &'static str) -> Self {
+        Self {
+            name: name.to_owned(),
+            hl_opts: HighlightingOptions { numbers: true, strings: true, ..HighlightingOptions::default() },
+            block_ends,
+            line_comment: Some(line_comment),
+            color_columns: &[],
+        }
     }
 }
 
@@ -119,6 +234,9 @@ impl Default for FileType {
         Self {
             name: String::from("No filetype"),
             hl_opts: HighlightingOptions::default(),
+            block_ends: &[],
+            line_comment: None,
+            color_columns: &[],
         }
     }
 }
@@ -0,0 +1,334 @@
+use std::fmt::Write;
+use crate::app::{Position, TAB_WIDTH};
+use crate::document::Document;
+
+// Fold files start with this and the checksum of the text they apply to, then one line per fold
+// holding its first and last row
+const FOLD_FILE_HEADER: &str = "ironn folds 1";
+
+// The closed folds of a buffer, each showing its first row and hiding the rest up to its last. They
+// don't overlap, closing one around others swallows them
+#[derive(Default)]
+pub struct Folds {
+    ranges: Vec<(usize, usize)>,
+}
+
+#[allow(clippy::missing_const_for_fn)]
+impl Folds {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+    pub fn close(&mut self, start: usize, end: usize) {
+        self.ranges.retain(|(first, last)| *last < start || *first > end);
+        self.ranges.push((start, end));
+        self.ranges.sort_unstable();
+    }
+    // Open the fold `y` is in, returns whether there was one
+    pub fn open(&mut self, y: usize) -> bool {
+        let count = self.ranges.len();
+
+        self.ranges.retain(|(first, last)| !(*first..=*last).contains(&y));
+
+        self.ranges.len() != count
+    }
+    pub fn open_all(&mut self) {
+        self.ranges.clear();
+    }
+    #[must_use]
+    pub fn ranges(&self) -> &[(usize, usize)] {
+        &self.ranges
+    }
+    // The rows the fold starting at `y` hides, None when no fold starts there
+    #[must_use]
+    pub fn hidden_after(&self, y: usize) -> Option<usize> {
+        self.ranges.iter().find(|(first, _)| *first == y).map(|(first, last)| last - first)
+    }
+    #[must_use]
+    pub fn is_hidden(&self, y: usize) -> bool {
+        self.ranges.iter().any(|(first, last)| *first < y && y <= *last)
+    }
+    // Hidden rows from `start` up to but not including `end`
+    #[must_use]
+    pub fn hidden_between(&self, start: usize, end: usize) -> usize {
+        self.ranges
+            .iter()
+            .map(|(first, last)| (last + 1).min(end).saturating_sub((first + 1).max(start)))
+            .sum()
+    }
+    // The row shown after `y`, past any fold starting there
+    #[must_use]
+    pub fn next_visible(&self, y: usize) -> usize {
+        self.ranges.iter().find(|(first, _)| *first == y).map_or(y, |(_, last)| *last) + 1
+    }
+    // The row shown before `y`, the first row of the fold it's hidden in if it is
+    #[must_use]
+    pub fn previous_visible(&self, y: usize) -> usize {
+        let previous = y.saturating_sub(1);
+
+        self.ranges
+            .iter()
+            .find(|(first, last)| *first < previous && previous <= *last)
+            .map_or(previous, |(first, _)| *first)
+    }
+    // The row `lines` shown rows away from `y`, going no further than `last`
+    #[must_use]
+    pub fn step(&self, mut y: usize, lines: isize, last: usize) -> usize {
+        for _ in 0..lines.unsigned_abs() {
+            y = if lines < 0 { self.previous_visible(y) } else { self.next_visible(y).min(last) };
+        }
+
+        y
+    }
+    // `y`, or the first row of the fold it's hidden in
+    #[must_use]
+    pub fn shown_row(&self, y: usize) -> usize {
+        if self.is_hidden(y) {
+            self.previous_visible(y + 1)
+        } else {
+            y
+        }
+    }
+    // The folds as a fold file for the text with `checksum`
+    #[must_use]
+    pub fn serialize(&self, checksum: u64) -> String {
+        let mut text = format!("{FOLD_FILE_HEADER} {checksum:016x}\n");
+
+        for (first, last) in &self.ranges {
+            let _ = writeln!(text, "{first} {last}");
+        }
+
+        text
+    }
+    // Read a fold file back, None if it's damaged or was written for different text of `len` rows
+    #[must_use]
+    pub fn deserialize(text: &str, checksum: u64, len: usize) -> Option<Self> {
+        let mut lines = text.lines();
+        let mut folds = Self::default();
+
+        if lines.next()? != format!("{FOLD_FILE_HEADER} {checksum:016x}") {
+            return None;
+        }
+
+        for line in lines {
+            let (first, last) = line.split_once(' ')?;
+            let (first, last): (usize, usize) = (first.parse().ok()?, last.parse().ok()?);
+
+            if first >= last || last >= len {
+                return None;
+            }
+
+            folds.close(first, last);
+        }
+
+        Some(folds)
+    }
+    // Folds around lines added or removed by an edit open, those after it move with their rows
+    pub fn adjust_for_insert(&mut self, start: &Position, end: &Position) {
+        let added = end.y - start.y;
+
+        if added == 0 {
+            return;
+        }
+
+        self.ranges.retain(|(first, last)| start.y < *first || start.y >= *last);
+
+        for (first, last) in &mut self.ranges {
+            if start.y < *first {
+                *first += added;
+                *last += added;
+            }
+        }
+    }
+    pub fn adjust_for_delete(&mut self, start: &Position, end: &Position) {
+        let removed = end.y - start.y;
+
+        if removed == 0 {
+            return;
+        }
+
+        self.ranges.retain(|(first, last)| *last < start.y || *first > end.y);
+
+        for (first, last) in &mut self.ranges {
+            if *first > end.y {
+                *first -= removed;
+                *last -= removed;
+            }
+        }
+    }
+}
+
+// The smallest syntactic region around `y`, or its indented block in files without any
+#[must_use]
+pub fn fold_at(document: &Document, y: usize) -> Option<(usize, usize)> {
+    syntax_regions(document)
+        .into_iter()
+        .filter(|(first, last)| (*first..=*last).contains(&y))
+        .min_by_key(|(first, last)| last - first)
+        .or_else(|| indent_fold(document, y))
+}
+// The regions folding everything closes, each one not inside another
+#[must_use]
+pub fn outer_folds(document: &Document) -> Vec<(usize, usize)> {
+    let mut regions = syntax_regions(document);
+
+    if regions.is_empty() {
+        let mut y = 0;
+
+        while y < document.len() {
+            if let Some((first, last)) = block_after(document, y) {
+                regions.push((first, last));
+                y = last;
+            }
+
+            y += 1;
+        }
+    }
+
+    let mut outer: Vec<(usize, usize)> = Vec::new();
+
+    for (first, last) in regions {
+        if outer.last().is_none_or(|(_, previous)| first > *previous) {
+            outer.push((first, last));
+        }
+    }
+
+    outer
+}
+// Brace blocks, block comments and runs of line comments spanning more than one row, sorted by first
+// row with the outer of two starting together first. Braces in strings, characters and comments don't count
+fn syntax_regions(document: &Document) -> Vec<(usize, usize)> {
+    let options = document.file_type.highlighting_options();
+    let leader = document.file_type.line_comment().map(str::as_bytes);
+    let mut regions = Vec::new();
+    let mut braces = Vec::new();
+    let mut block_comment = None;
+    let mut comment_run = None;
+    let mut in_string = false;
+
+    for y in 0..document.len() {
+        let Some(row) = document.row(y) else {
+            break;
+        };
+
+        // Braces and quotes are ASCII, so they never match part of a longer character
+        let bytes = row.as_str().as_bytes();
+        let is_comment_row = block_comment.is_none()
+            && !in_string
+            && leader.is_some_and(|leader| bytes.trim_ascii_start().starts_with(leader));
+
+        match (is_comment_row, comment_run) {
+            (true, None) => comment_run = Some(y),
+            (false, Some(first)) => {
+                regions.extend(spanning(first, y - 1));
+                comment_run = None;
+            },
+            _ => (),
+        }
+
+        if is_comment_row {
+            continue;
+        }
+
+        let mut index = 0;
+
+        while index < bytes.len() {
+            let rest = &bytes[index..];
+
+            if let Some(first) = block_comment {
+                if rest.starts_with(b"*/") {
+                    regions.extend(spanning(first, y));
+                    block_comment = None;
+                    index += 1;
+                }
+            } else if in_string {
+                match rest[0] {
+                    b'\\' => index += 1,
+                    b'"' => in_string = false,
+                    _ => (),
+                }
+            } else if leader.is_some_and(|leader| rest.starts_with(leader)) {
+                break;
+            } else if options.multiline_comments() && rest.starts_with(b"/*") {
+                block_comment = Some(y);
+                index += 1;
+            } else if options.characters() && rest.starts_with(b"'\\") && rest.get(3) == Some(&b'\'') {
+                index += 3;
+            } else if options.characters() && rest.get(2) == Some(&b'\'') && rest[0] == b'\'' {
+                index += 2;
+            } else {
+                match rest[0] {
+                    b'"' if options.strings() => in_string = true,
+                    b'{' => braces.push(y),
+                    b'}' => regions.extend(braces.pop().and_then(|first| brace_region(document, first, y))),
+                    _ => (),
+                }
+            }
+
+            index += 1;
+        }
+    }
+
+    if let Some(first) = comment_run {
+        regions.extend(spanning(first, document.len().saturating_sub(1)));
+    }
+
+    regions.sort_unstable_by_key(|(first, last)| (*first, std::cmp::Reverse(*last)));
+    regions
+}
+
+// A block folds up to its closing row when nothing but closers follow the brace there, so the row of
+// a `} else {` stays shown
+fn brace_region(document: &Document, first: usize, closing: usize) -> Option<(usize, usize)> {
+    let closers = document.row(closing)?.as_str().trim().chars().all(|c| matches!(c, '}' | ')' | ']' | ';' | ','));
+    let last = if closers { closing } else { closing.checked_sub(1)? };
+
+    spanning(first, last)
+}
+
+fn spanning(first: usize, last: usize) -> Option<(usize, usize)> {
+    (last > first).then_some((first, last))
+}
+
+// The indented block `y` starts, or the one it's in otherwise: the row before the block and the rows
+// after it indented deeper, up to the last of them that isn't blank
+fn indent_fold(document: &Document, y: usize) -> Option<(usize, usize)> {
+    if let Some(fold) = block_after(document, y) {
+        return Some(fold);
+    }
+
+    // A blank row belongs to the block of the next row with text
+    let depth = (y..document.len()).find_map(|y| indent_depth(document, y))?;
+
+    (0..y)
+        .rev()
+        .find(|above| indent_depth(document, *above).is_some_and(|above| above < depth))
+        .and_then(|start| block_after(document, start))
+        .filter(|(_, end)| y <= *end)
+}
+
+fn block_after(document: &Document, y: usize) -> Option<(usize, usize)> {
+    let depth = indent_depth(document, y)?;
+    let mut end = y;
+
+    for below in y + 1..document.len() {
+        match indent_depth(document, below) {
+            Some(below_depth) if below_depth > depth => end = below,
+            Some(_) => break,
+            None => (),
+        }
+    }
+
+    (end > y).then_some((y, end))
+}
+
+// Columns of indentation with tabs at full width, None for blank rows
+fn indent_depth(document: &Document, y: usize) -> Option<usize> {
+    let row = document.row(y)?.as_str();
+
+    if row.trim().is_empty() {
+        return None;
+    }
+
+    Some(row.chars().take_while(|c| matches!(c, ' ' | '\t')).map(|c| if c == '\t' { TAB_WIDTH } else { 1 }).sum())
+}
@@ -0,0 +1,359 @@
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::SystemTime;
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+use crate::diff::{edit_script, DiffLine, Op};
+
+// Rows of the buffer that differ from the other version, and the rows of that version they replace.
+// Removed lines leave `start == end`, with `start` the row after where they were
+#[derive(Clone, Copy, Debug)]
+pub struct Hunk {
+    pub start: usize,
+    pub end: usize,
+    pub base_start: usize,
+    pub base_end: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HunkKind {
+    Added,
+    Modified,
+    Removed,
+}
+
+impl Hunk {
+    #[must_use]
+    pub const fn kind(&self) -> HunkKind {
+        if self.start == self.end {
+            HunkKind::Removed
+        } else if self.base_start == self.base_end {
+            HunkKind::Added
+        } else {
+            HunkKind::Modified
+        }
+    }
+    // Removed lines are found from the rows on either side of them
+    #[must_use]
+    pub const fn contains(&self, y: usize) -> bool {
+        if self.start == self.end {
+            y == self.start || y + 1 == self.start
+        } else {
+            self.start <= y && y < self.end
+        }
+    }
+}
+
+// A buffer compared with its file in git's index, and the index with the last commit. The index is
+// read again when git changes it, the comparison whenever the buffer's text does
+pub struct GitStatus {
+    pub file_name: String,
+    // The buffer's edit generation the comparison was made at
+    generation: u64,
+    index_file: PathBuf,
+    index_modified: Option<SystemTime>,
+    index: Vec<String>,
+    index_final_newline: bool,
+    head: Vec<String>,
+    // The buffer's changes that aren't staged
+    pub hunks: Vec<Hunk>,
+    // Changes staged in the index, with the index's rows as `start..end`
+    pub staged: Vec<Hunk>,
+    // The buffer row each index row is on, None for index rows the buffer changed
+    index_rows: Vec<Option<usize>>,
+}
+
+#[allow(clippy::missing_const_for_fn)]
+impl GitStatus {
+    // None for files outside a work tree or that git doesn't track
+    #[must_use]
+    pub fn load(file_name: &str, lines: &[&str], generation: u64) -> Option<Self> {
+        let dir = parent_dir(file_name);
+        let index_file = dir.join(String::from_utf8(git(&dir, &["rev-parse", "--git-path", "index"], None).ok()?).ok()?.trim());
+        let index = git(&dir, &["show", &format!(":./{}", base_name(file_name))], None).ok()?;
+        let head = git(&dir, &["show", &format!("HEAD:./{}", base_name(file_name))], None).unwrap_or_default();
+        let index = String::from_utf8_lossy(&index).into_owned();
+
+        let mut status = Self {
+            file_name: file_name.to_owned(),
+            generation,
+            index_modified: modified_time(&index_file),
+            index_file,
+            index_final_newline: index.is_empty() || index.ends_with('\n'),
+            index: index.lines().map(str::to_owned).collect(),
+            head: String::from_utf8_lossy(&head).lines().map(str::to_owned).collect(),
+            hunks: Vec::new(),
+            staged: Vec::new(),
+            index_rows: Vec::new(),
+        };
+
+        status.staged = hunks(&as_strs(&status.head), &as_strs(&status.index)).0;
+        status.compare(lines);
+
+        Some(status)
+    }
+    // Whether it has to be loaded again, for another file or because git wrote the index
+    #[must_use]
+    pub fn is_stale(&self, file_name: &str) -> bool {
+        self.file_name != file_name || modified_time(&self.index_file) != self.index_modified
+    }
+    // Whether the comparison is up to date with the buffer at `generation`
+    #[must_use]
+    pub fn is_current(&self, generation: u64) -> bool {
+        self.generation == generation
+    }
+    // Compare the buffer with the index again after its text changed
+    pub fn update(&mut self, lines: &[&str], generation: u64) {
+        self.generation = generation;
+        self.compare(lines);
+    }
+    fn compare(&mut self, lines: &[&str]) {
+        (self.hunks, self.index_rows) = hunks(&as_strs(&self.index), lines);
+    }
+    #[must_use]
+    pub fn hunk_at(&self, y: usize) -> Option<Hunk> {
+        self.hunks.iter().copied().find(|hunk| hunk.contains(y))
+    }
+    // Whether row `y` of the buffer is one staged in the index
+    #[must_use]
+    pub fn is_staged(&self, y: usize) -> bool {
+        self.staged_hunk_at(y).is_some()
+    }
+    // The staged change on row `y` of the buffer, which has to be unchanged from the index there
+    #[must_use]
+    pub fn staged_hunk_at(&self, y: usize) -> Option<Hunk> {
+        let index_row = self.index_rows.iter().position(|row| *row == Some(y))?;
+
+        self.staged.iter().copied().find(|hunk| hunk.contains(index_row))
+    }
+    // The index with `hunk` of the buffer's changes staged
+    #[must_use]
+    pub fn staged_with(&self, hunk: Hunk, lines: &[&str]) -> String {
+        let mut index: Vec<&str> = as_strs(&self.index);
+
+        index.splice(hunk.base_start..hunk.base_end, lines[hunk.start..hunk.end].iter().copied());
+
+        self.index_text(&index)
+    }
+    // The index with staged `hunk` put back the way the last commit has it
+    #[must_use]
+    pub fn unstaged_with(&self, hunk: Hunk) -> String {
+        let mut index: Vec<&str> = as_strs(&self.index);
+
+        index.splice(hunk.start..hunk.end, self.head[hunk.base_start..hunk.base_end].iter().map(String::as_str));
+
+        self.index_text(&index)
+    }
+    // The index's lines `hunk` replaced in the buffer
+    #[must_use]
+    pub fn index_lines(&self, hunk: Hunk) -> &[String] {
+        &self.index[hunk.base_start..hunk.base_end]
+    }
+    fn index_text(&self, lines: &[&str]) -> String {
+        let mut text = lines.join("\n");
+
+        if self.index_final_newline && !lines.is_empty() {
+            text.push('\n');
+        }
+
+        text
+    }
+}
+
+// Store `contents` as the file's version in the index, like `git add` would for a file holding them
+/// # Errors
+///
+/// Will return `Err` if git fails or the file isn't tracked
+pub fn write_index(file_name: &str, contents: &str) -> Result<()> {
+    let dir = parent_dir(file_name);
+    let name = base_name(file_name);
+    let listed = String::from_utf8(git(&dir, &["ls-files", "--stage", "--", &name], None)?)?;
+    let mode = listed.split_whitespace().next().ok_or_else(|| eyre!("{file_name} isn't tracked by git"))?;
+    let object = String::from_utf8(git(&dir, &["hash-object", "-w", "--stdin", "--path", &name], Some(contents.as_bytes()))?)?;
+
+    git(&dir, &["update-index", "--cacheinfo", &format!("{mode},{},{name}", object.trim())], None)?;
+
+    tracing::info!(file_name, "index updated");
+
+    Ok(())
+}
+
+// What `git commit` would commit, run from the work tree git starts the editor in
+/// # Errors
+///
+/// Will return `Err` if git fails, like outside a work tree
+pub fn staged_diff() -> Result<Vec<DiffLine>> {
+    let output = git(Path::new("."), &["--no-pager", "diff", "--cached", "--no-color", "--no-ext-diff"], None)?;
+
+    Ok(String::from_utf8_lossy(&output)
+        .lines()
+        .map(|line| match line.as_bytes().first() {
+            _ if line.starts_with("+++ ") || line.starts_with("--- ") => DiffLine::Hunk(line.to_owned()),
+            Some(b'+') => DiffLine::Added(line[1..].to_owned()),
+            Some(b'-') => DiffLine::Removed(line[1..].to_owned()),
+            Some(b' ') => DiffLine::Context(line[1..].to_owned()),
+            _ => DiffLine::Hunk(line.to_owned()),
+        })
+        .collect())
+}
+
+// Files under `dir` that git tracks or would add, leaving out the ones it ignores, relative to `dir`
+/// # Errors
+///
+/// Will return `Err` if `dir` isn't in a work tree or git isn't installed
+pub fn list_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let output = git(dir, &["ls-files", "-z", "--cached", "--others", "--exclude-standard"], None)?;
+
+    Ok(output.split(|byte| *byte == 0).filter(|name| !name.is_empty()).map(|name| PathBuf::from(OsStr::from_bytes(name))).collect())
+}
+
+// Where `base` and `current` differ, and the row of `current` each row of `base` stayed on
+fn hunks(base: &[&str], current: &[&str]) -> (Vec<Hunk>, Vec<Option<usize>>) {
+    let mut hunks: Vec<Hunk> = Vec::new();
+    let mut base_rows = Vec::with_capacity(base.len());
+    let (mut base_index, mut index) = (0, 0);
+
+    for op in edit_script(base, current) {
+        if op == Op::Equal {
+            base_rows.push(Some(index));
+            base_index += 1;
+            index += 1;
+            continue;
+        }
+
+        // Changes next to each other are one hunk
+        if !hunks.last().is_some_and(|hunk| hunk.end == index && hunk.base_end == base_index) {
+            hunks.push(Hunk { start: index, end: index, base_start: base_index, base_end: base_index });
+        }
+
+        let Some(hunk) = hunks.last_mut() else {
+            continue;
+        };
+
+        if op == Op::Delete {
+            base_rows.push(None);
+            base_index += 1;
+            hunk.base_end = base_index;
+        } else {
+            index += 1;
+            hunk.end = index;
+        }
+    }
+
+    (hunks, base_rows)
+}
+
+// Run git in `dir`, returning what it printed
+fn git(dir: &Path, args: &[&str], input: Option<&[u8]>) -> Result<Vec<u8>> {
+    let mut child = Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| match error.kind() {
+            ErrorKind::NotFound => eyre!("git isn't installed"),
+            _ => error.into(),
+        })?;
+
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin.write_all(input)?;
+    }
+
+    let output = child.wait_with_output()?;
+
+    if output.status.success() {
+        return Ok(output.stdout);
+    }
+
+    let error = String::from_utf8_lossy(&output.stderr);
+
+    Err(eyre!("{}", error.lines().next().unwrap_or("git failed").trim_start_matches("fatal: ")))
+}
+
+fn parent_dir(file_name: &str) -> PathBuf {
+    Path::new(file_name).parent().filter(|dir| !dir.as_os_str().is_empty()).map_or_else(|| PathBuf::from("."), Path::to_path_buf)
+}
+
+fn base_name(file_name: &str) -> String {
+    Path::new(file_name).file_name().unwrap_or_default().to_string_lossy().into_owned()
+}
+
+fn as_strs(lines: &[String]) -> Vec<&str> {
+    lines.iter().map(String::as_str).collect()
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::save::private_directory;
+
+    fn spans(hunks: &[Hunk]) -> Vec<(usize, usize, usize, usize, HunkKind)> {
+        hunks.iter().map(|hunk| (hunk.start, hunk.end, hunk.base_start, hunk.base_end, hunk.kind())).collect()
+    }
+
+    #[test]
+    fn changes_are_grouped_into_hunks() {
+        let base = ["a", "b", "c", "d", "e"];
+        let (changed, base_rows) = hunks(&base, &["new", "a", "B", "c", "e"]);
+
+        assert_eq!(
+            spans(&changed),
+            [(0, 1, 0, 0, HunkKind::Added), (2, 3, 1, 2, HunkKind::Modified), (4, 4, 3, 4, HunkKind::Removed)]
+        );
+        assert_eq!(base_rows, [Some(1), None, Some(3), None, Some(4)]);
+        assert!(hunks(&base, &base).0.is_empty());
+    }
+
+    #[test]
+    fn removed_lines_are_found_from_either_side() {
+        let removed = Hunk { start: 3, end: 3, base_start: 3, base_end: 5 };
+        let modified = Hunk { start: 3, end: 5, base_start: 3, base_end: 4 };
+
+        assert_eq!((2..6).map(|y| removed.contains(y)).collect::<Vec<_>>(), [true, true, false, false]);
+        assert_eq!((2..6).map(|y| modified.contains(y)).collect::<Vec<_>>(), [false, true, true, false]);
+    }
+
+    #[test]
+    fn staging_a_hunk_writes_it_to_the_index() {
+        let Ok(dir) = private_directory("git-test") else {
+            return;
+        };
+        let file = dir.join("a.txt");
+        let file_name = file.to_string_lossy();
+
+        fs::write(&file, "one\ntwo\nthree\n").unwrap();
+
+        let committed = [&["init", "-q"][..], &["add", "a.txt"], &["-c", "user.name=t", "-c", "user.email=t", "commit", "-qm", "a"]]
+            .iter()
+            .all(|args| git(&dir, args, None).is_ok());
+
+        if committed {
+            let lines = ["zero", "one", "2", "three"];
+            let status = GitStatus::load(&file_name, &lines, 1).unwrap();
+
+            assert_eq!(spans(&status.hunks), [(0, 1, 0, 0, HunkKind::Added), (2, 3, 1, 2, HunkKind::Modified)]);
+            assert!(status.staged.is_empty());
+
+            write_index(&file_name, &status.staged_with(status.hunks[1], &lines)).unwrap();
+
+            let status = GitStatus::load(&file_name, &lines, 1).unwrap();
+
+            assert_eq!(spans(&status.hunks), [(0, 1, 0, 0, HunkKind::Added)]);
+            assert_eq!(spans(&status.staged), [(1, 2, 1, 2, HunkKind::Modified)]);
+            assert!(status.is_staged(2) && !status.is_staged(1));
+            assert_eq!(status.unstaged_with(status.staged[0]), "one\ntwo\nthree\n");
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,359 @@
+use std::fmt::Write;
+use std::num::ParseIntError;
+use std::time::{Duration, SystemTime};
+use crate::app::Position;
+
+// Undo files start with this and the checksum of the text they apply to, then the current node
+// and the node redo follows from the original text. Each node then opens with a `node` line
+// holding its parent, when it was made and the child redo follows, and one `+`/`-` line per change:
+// start, end and the text
+const UNDO_FILE_HEADER: &str = "ironn undo 2";
+
+// A single reversible edit, `end` is where the text ends while it is present in the document
+#[derive(Clone)]
+pub enum Change {
+    Insert { start: Position, end: Position, text: String },
+    Delete { start: Position, end: Position, text: String },
+}
+
+// One undo step, the changes that lead from the parent's state to this one
+pub struct UndoNode {
+    pub parent: Option<usize>,
+    pub changes: Vec<Change>,
+    pub time: SystemTime,
+    // The child redo moves to, the one most recently undone or made
+    redo_child: Option<usize>,
+}
+
+// Every state the text has been in as a tree of undo steps. A new edit after an undo starts
+// another branch instead of dropping what was undone. `None` is the text before any edit
+#[derive(Default)]
+pub struct History {
+    nodes: Vec<UndoNode>,
+    current: Option<usize>,
+    // The node redo moves to from the original text
+    root_redo: Option<usize>,
+    group: Option<Vec<Change>>,
+    // Groups opened and not yet ended, only the outermost one becomes an undo step
+    depth: usize,
+    // The current node is typing that the next typed character can join
+    typing: bool,
+}
+
+#[allow(clippy::missing_const_for_fn)]
+impl History {
+    pub fn record(&mut self, change: Change) {
+        self.typing = false;
+
+        if let Some(group) = &mut self.group {
+            group.push(change);
+        } else {
+            self.add_node(vec![change]);
+        }
+    }
+    // Record a typed character, joining it onto the typing before it while that's the same word.
+    // A space starts a new step along with the word after it, a line break is always a step of its own
+    pub fn record_typed(&mut self, change: Change) {
+        let Change::Insert { start, end, text } = change else {
+            self.record(change);
+            return;
+        };
+
+        if self.typing && self.group.is_none() {
+            let changes = self.current.map(|current| self.nodes[current].changes.as_mut_slice());
+
+            if let Some([Change::Insert { end: previous_end, text: previous_text, .. }]) = changes {
+                let starts_word = text.starts_with(char::is_whitespace) && !previous_text.ends_with(char::is_whitespace);
+
+                if *previous_end == start && !text.contains('\n') && !previous_text.contains('\n') && !starts_word {
+                    *previous_end = end;
+                    previous_text.push_str(&text);
+                    return;
+                }
+            }
+        }
+
+        self.record(Change::Insert { start, end, text });
+        self.typing = self.group.is_none();
+    }
+    // Collect every change recorded until the matching `end_group` into one undo step, groups can nest
+    pub fn begin_group(&mut self) {
+        self.depth += 1;
+        self.group.get_or_insert_with(Vec::new);
+    }
+    pub fn end_group(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+
+        if self.depth > 0 {
+            return;
+        }
+
+        if let Some(group) = self.group.take() {
+            if !group.is_empty() {
+                self.add_node(group);
+            }
+        }
+    }
+    fn add_node(&mut self, changes: Vec<Change>) {
+        let index = self.nodes.len();
+
+        self.nodes.push(UndoNode { parent: self.current, changes, time: SystemTime::now(), redo_child: None });
+        *self.redo_child_mut(self.current) = Some(index);
+        self.current = Some(index);
+    }
+    // Step back to the parent, returning the changes to revert
+    pub fn undo(&mut self) -> Option<Vec<Change>> {
+        let current = self.current?;
+        let parent = self.nodes[current].parent;
+
+        self.typing = false;
+        *self.redo_child_mut(parent) = Some(current);
+        self.current = parent;
+
+        Some(self.nodes[current].changes.clone())
+    }
+    // Step forward along the branch last undone or made, returning the changes to apply
+    pub fn redo(&mut self) -> Option<Vec<Change>> {
+        let child = match self.current {
+            Some(current) => self.nodes[current].redo_child,
+            None => self.root_redo,
+        }?;
+
+        self.typing = false;
+        self.current = Some(child);
+
+        Some(self.nodes[child].changes.clone())
+    }
+    // How many undos and which nodes to redo, in order, to get from the current state to `target`
+    #[must_use]
+    pub fn path_to(&self, target: Option<usize>) -> (usize, Vec<usize>) {
+        let ancestors = |node: Option<usize>| {
+            let mut path = Vec::new();
+            let mut node = node;
+
+            while let Some(index) = node {
+                path.push(index);
+                node = self.nodes[index].parent;
+            }
+
+            path.reverse();
+            path
+        };
+
+        let from = ancestors(self.current);
+        let to = ancestors(target);
+        let shared = from.iter().zip(&to).take_while(|(from, to)| from == to).count();
+
+        (from.len() - shared, to[shared..].to_vec())
+    }
+    // Make `node` the child redo moves to from its parent
+    pub fn follow(&mut self, node: usize) {
+        let parent = self.nodes[node].parent;
+
+        *self.redo_child_mut(parent) = Some(node);
+    }
+    fn redo_child_mut(&mut self, node: Option<usize>) -> &mut Option<usize> {
+        match node {
+            Some(index) => &mut self.nodes[index].redo_child,
+            None => &mut self.root_redo,
+        }
+    }
+    // The tree in display order with each node's indentation, starting with the original text.
+    // The first child of a node continues its line, later ones branch off one level deeper
+    #[must_use]
+    pub fn tree(&self) -> Vec<(Option<usize>, usize)> {
+        // Children of each node in the order they were made, the original text's come last
+        let mut children = vec![Vec::new(); self.nodes.len() + 1];
+
+        for (index, node) in self.nodes.iter().enumerate() {
+            children[node.parent.unwrap_or(self.nodes.len())].push(index);
+        }
+
+        let mut rows = Vec::new();
+        let mut stack = vec![(None, 0)];
+
+        while let Some((node, indent)) = stack.pop() {
+            rows.push((node, indent));
+
+            // Pushed in reverse so the oldest child is visited next
+            for (position, &child) in children[node.unwrap_or(self.nodes.len())].iter().enumerate().rev() {
+                stack.push((Some(child), if position == 0 { indent } else { indent + 1 }));
+            }
+        }
+
+        rows
+    }
+    #[must_use]
+    pub fn nodes(&self) -> &[UndoNode] {
+        &self.nodes
+    }
+    #[must_use]
+    pub fn current(&self) -> Option<usize> {
+        self.current
+    }
+    // The tree as an undo file for the text with `checksum`
+    #[must_use]
+    pub fn serialize(&self, checksum: u64) -> String {
+        let mut text = format!("{UNDO_FILE_HEADER} {checksum:016x}\n");
+
+        let _ = writeln!(text, "current {} {}", optional_index(self.current), optional_index(self.root_redo));
+
+        for node in &self.nodes {
+            let time = node.time.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs();
+
+            let _ = writeln!(text, "node {} {time} {}", optional_index(node.parent), optional_index(node.redo_child));
+
+            for change in &node.changes {
+                let (sign, start, end, change_text) = match change {
+                    Change::Insert { start, end, text } => ('+', start, end, text),
+                    Change::Delete { start, end, text } => ('-', start, end, text),
+                };
+
+                let _ = writeln!(text, "{sign} {} {} {} {} {}", start.y, start.x, end.y, end.x, escape(change_text));
+            }
+        }
+
+        text
+    }
+    // Read an undo file back, None if it's damaged or was written for different text
+    #[must_use]
+    pub fn deserialize(text: &str, checksum: u64) -> Option<Self> {
+        let mut lines = text.lines();
+        let mut history = Self::default();
+
+        if lines.next()? != format!("{UNDO_FILE_HEADER} {checksum:016x}") {
+            return None;
+        }
+
+        let mut fields = lines.next()?.strip_prefix("current ")?.split(' ');
+
+        history.current = parse_index(fields.next()?).ok()?;
+        history.root_redo = parse_index(fields.next()?).ok()?;
+
+        for line in lines {
+            if let Some(node) = line.strip_prefix("node ") {
+                let mut fields = node.split(' ');
+                let parent = parse_index(fields.next()?).ok()?;
+                let time = SystemTime::UNIX_EPOCH + Duration::from_secs(fields.next()?.parse().ok()?);
+                let redo_child = parse_index(fields.next()?).ok()?;
+
+                history.nodes.push(UndoNode { parent, changes: Vec::new(), time, redo_child });
+            } else {
+                history.nodes.last_mut()?.changes.push(parse_change(line)?);
+            }
+        }
+
+        let len = history.nodes.len();
+        let in_range = |index: Option<usize>| index.is_none_or(|index| index < len);
+        let linked = history.nodes.iter().enumerate().all(|(index, node)| {
+            node.parent.is_none_or(|parent| parent < index) && in_range(node.redo_child)
+        });
+
+        if !linked || !in_range(history.current) || !in_range(history.root_redo) {
+            return None;
+        }
+
+        Some(history)
+    }
+}
+
+fn optional_index(index: Option<usize>) -> String {
+    index.map_or_else(|| "-".to_owned(), |index| index.to_string())
+}
+
+// `-` for no node, otherwise the index
+fn parse_index(field: &str) -> Result<Option<usize>, ParseIntError> {
+    if field == "-" {
+        Ok(None)
+    } else {
+        field.parse().map(Some)
+    }
+}
+
+fn parse_change(line: &str) -> Option<Change> {
+    let mut fields = line.splitn(6, ' ');
+    let sign = fields.next()?;
+    let mut number = || fields.next()?.parse().ok();
+    let start = Position { y: number()?, x: number()? };
+    let end = Position { y: number()?, x: number()? };
+    let text = unescape(fields.next()?);
+
+    match sign {
+        "+" => Some(Change::Insert { start, end, text }),
+        "-" => Some(Change::Delete { start, end, text }),
+        _ => None,
+    }
+}
+
+// Keep each change on one line of the undo file
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\n', "\\n").replace('\r', "\\r")
+}
+
+fn unescape(text: &str) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some(other) => result.push(other),
+            None => result.push('\\'),
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(y: usize, x: usize, text: &str) -> Change {
+        Change::Insert { start: Position { x, y }, end: Position { x: x + text.len(), y }, text: text.to_owned() }
+    }
+
+    #[test]
+    fn undo_files_round_trip() {
+        let mut history = History::default();
+
+        history.record(insert(0, 0, "one"));
+        history.record(Change::Delete { start: Position { x: 0, y: 0 }, end: Position { x: 0, y: 1 }, text: "a\\b\r\n".to_owned() });
+        history.undo();
+        history.record(insert(0, 3, "two words"));
+
+        let text = history.serialize(0xabc);
+        let restored = History::deserialize(&text, 0xabc).unwrap();
+
+        assert_eq!(restored.serialize(0xabc), text);
+        assert_eq!(restored.current(), history.current());
+        assert_eq!(restored.tree(), history.tree());
+
+        let Change::Delete { text: deleted, .. } = &restored.nodes()[1].changes[0] else {
+            panic!("expected a deletion");
+        };
+
+        assert_eq!(deleted, "a\\b\r\n");
+    }
+
+    #[test]
+    fn damaged_or_foreign_files_are_refused() {
+        let mut history = History::default();
+
+        history.record(insert(0, 0, "one"));
+
+        let text = history.serialize(1);
+
+        assert!(History::deserialize(&text, 2).is_none());
+        assert!(History::deserialize(&text.replace("node - ", "node 5 "), 1).is_none());
+        assert!(History::deserialize(&text.replace("current 0", "current 3"), 1).is_none());
+        assert!(History::deserialize(&text.replace("+ 0", "* 0"), 1).is_none());
+        assert!(History::deserialize(&format!("{UNDO_FILE_HEADER} {:016x}\ncurrent - -\n+ 0 0 0 1 x\n", 1), 1).is_none());
+    }
+}
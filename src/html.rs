@@ -0,0 +1,75 @@
+use std::fmt::Write;
+use crossterm::style::Color;
+use crate::document::Document;
+use crate::highlighting;
+use crate::row::Row;
+
+const BACKGROUND: &str = "#000000";
+const FOREGROUND: &str = "#ffffff";
+
+// Render the document as a standalone HTML page, colored with the syntax highlighting colors
+#[must_use]
+pub fn export(document: &Document) -> String {
+    let title = escape(document.file_name.as_deref().unwrap_or("New Buffer"));
+    let mut body = String::new();
+
+    for row in document.highlighted_rows() {
+        push_highlighted(&mut body, &row);
+        body.push('\n');
+    }
+
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n</head>\n<body>\n\
+        <pre style=\"background: {BACKGROUND}; color: {FOREGROUND}; padding: 1em;\">{body}</pre>\n</body>\n</html>\n"
+    )
+}
+
+// Append `text` to `html`, wrapping each run of chars with the same highlight type in a colored span
+fn push_highlighted(html: &mut String, row: &Row) {
+    let highlighting = row.highlighting();
+    let mut run = String::new();
+    let mut run_type = highlighting::Type::None;
+
+    for (index, c) in row.as_str().chars().enumerate() {
+        let highlight_type = highlighting.get(index).copied().unwrap_or(highlighting::Type::None);
+
+        if highlight_type != run_type {
+            push_run(html, &run, run_type);
+            run.clear();
+            run_type = highlight_type;
+        }
+
+        run.push(c);
+    }
+
+    push_run(html, &run, run_type);
+}
+
+fn push_run(html: &mut String, run: &str, highlight_type: highlighting::Type) {
+    if run.is_empty() {
+        return;
+    }
+
+    match highlight_type.to_color() {
+        Color::Rgb { r, g, b } if highlight_type != highlighting::Type::None => {
+            let _ = write!(html, "<span style=\"color: #{r:02x}{g:02x}{b:02x};\">{}</span>", escape(run));
+        },
+        _ => html.push_str(&escape(run)),
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
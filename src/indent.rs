@@ -0,0 +1,145 @@
+use unicode_segmentation::UnicodeSegmentation;
+use crate::app::Position;
+use crate::document::Document;
+use crate::textobject::TextObject;
+
+// The spaces and tabs a line starts with
+#[must_use]
+pub fn leading_whitespace(line: &str) -> &str {
+    &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
+
+// Indentation for the row at `at` when all that's before the cursor is a closing brace or a word
+// ending a block, taken from the row that opened it. None when there's nothing to match
+#[must_use]
+pub fn closing_indent(document: &Document, at: &Position) -> Option<String> {
+    let row = document.row(at.y)?;
+    let before: String = row.as_str().graphemes(true).take(at.x).collect();
+    let typed = &before[leading_whitespace(&before).len()..];
+
+    let opening_row = if typed == "}" {
+        let closing = Position { x: at.x.checked_sub(1)?, y: at.y };
+
+        TextObject::Brackets('{', '}').find(document, &closing, false)?.0.y
+    } else {
+        block_start(document, at.y, typed)?
+    };
+
+    Some(leading_whitespace(document.row(opening_row)?.as_str()).to_owned())
+}
+
+// What a line split off the row at `at` starts with to carry on its comment: the indentation, the
+// comment leader and the spaces after it, with block comments' stars lined up. None outside a comment
+// or with the cursor still in the leader
+#[must_use]
+pub fn comment_leader(document: &Document, at: &Position) -> Option<String> {
+    let row = document.row(at.y)?.as_str();
+    let options = document.file_type.highlighting_options();
+    let indent = leading_whitespace(row);
+    let rest = &row[indent.len()..];
+    let spaces = |text: &str| leading_whitespace(text).to_owned();
+
+    // Leaders are ASCII, so their length in bytes is also their length in graphemes
+    let (leader, length) = if options.comments() && rest.starts_with("//") {
+        // `///` and `//!` doc comments carry on as themselves
+        let marker = if rest.starts_with("///") || rest.starts_with("//!") { &rest[..3] } else { &rest[..2] };
+        let spaces = spaces(&rest[marker.len()..]);
+
+        (format!("{indent}{marker}{spaces}"), indent.len() + marker.len() + spaces.len())
+    } else if options.multiline_comments() && rest.starts_with("/*") && !rest.contains("*/") {
+        (format!("{indent} * "), indent.len() + 2)
+    } else if options.multiline_comments() && (rest == "*" || rest.starts_with("* ")) && !rest.contains("*/") {
+        // A lone star or one followed by a space, so dereferences starting a line don't count
+        let spaces = spaces(&rest[1..]);
+
+        (format!("{indent}*{spaces}"), indent.len() + 1 + spaces.len())
+    } else {
+        return None;
+    };
+
+    (at.x >= length).then_some(leader)
+}
+
+// `lines` commented out with `leader` at the shallowest indentation among them, or uncommented when
+// every line with text already starts with it. Blank lines are left alone
+#[must_use]
+pub fn toggle_line_comments(lines: &[String], leader: &str) -> Vec<String> {
+    let filled: Vec<&String> = lines.iter().filter(|line| !line.trim().is_empty()).collect();
+
+    if filled.iter().all(|line| line.trim_start().starts_with(leader)) {
+        return lines
+            .iter()
+            .map(|line| {
+                let indent = leading_whitespace(line);
+                let Some(rest) = line[indent.len()..].strip_prefix(leader) else {
+                    return line.clone();
+                };
+
+                format!("{indent}{}", rest.strip_prefix(' ').unwrap_or(rest))
+            })
+            .collect();
+    }
+
+    let depth = filled.iter().map(|line| leading_whitespace(line).len()).min().unwrap_or_default();
+
+    lines
+        .iter()
+        .map(|line| if line.trim().is_empty() { line.clone() } else { format!("{}{leader} {}", &line[..depth], &line[depth..]) })
+        .collect()
+}
+
+// Columns of the indentation levels in `whitespace`, each tab being a level and one column wide as
+// it's drawn, and every `tab_width` spaces another level
+#[must_use]
+pub fn indent_levels(whitespace: &str, tab_width: usize) -> Vec<usize> {
+    let mut levels = Vec::new();
+    let mut spaces = 0;
+
+    for (column, c) in whitespace.chars().enumerate() {
+        if c == '\t' {
+            levels.push(column);
+            spaces = 0;
+        } else {
+            if spaces % tab_width == 0 {
+                levels.push(column);
+            }
+
+            spaces += 1;
+        }
+    }
+
+    levels
+}
+
+// The row before `y` opening the block `word` ends, skipping blocks nested inside it. Rows are
+// matched by their first word, so blocks opened and closed on one row don't count
+fn block_start(document: &Document, y: usize, word: &str) -> Option<usize> {
+    let block_end = document.file_type.block_ends().iter().find(|block_end| block_end.word == word)?;
+    let mut depth = 0usize;
+
+    for row_y in (0..y).rev() {
+        let Some(row) = document.row(row_y) else {
+            continue;
+        };
+
+        let words: Vec<&str> = row.as_str().unicode_words().collect();
+        let Some(first) = words.first() else {
+            continue;
+        };
+
+        let opens = block_end.openers.contains(first) || words.iter().any(|word| block_end.inline_openers.contains(word));
+        let closes = words.iter().skip(1).any(|word| *word == block_end.word);
+
+        if *first == block_end.word {
+            depth += 1;
+        } else if opens && !closes {
+            if depth == 0 {
+                return Some(row_y);
+            }
+
+            depth -= 1;
+        }
+    }
+
+    None
+}
@@ -0,0 +1,193 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+use crate::action::Action;
+
+// A key as bindings compare it, Shift left out of characters since it's in the character already
+pub type Key = (KeyModifiers, KeyCode);
+
+// Bindings of two or more keys pressed one after another, like `C-x C-s`. The first key of a chord
+// stops doing what it does on its own, so binding `C-x C-s` means `C-x C-c` needs binding to quit
+pub struct Keymap {
+    chords: Vec<Chord>,
+}
+
+pub struct Chord {
+    pub keys: Vec<Key>,
+    pub action: Action,
+}
+
+// What the keys pressed so far add up to
+pub enum Lookup {
+    Action(Action),
+    // The start of at least one chord, more keys are needed
+    Prefix,
+    Unbound,
+}
+
+impl Default for Keymap {
+    // Ctrl-G leads into the commands people most often want without reaching for Alt
+    fn default() -> Self {
+        let mut keymap = Self { chords: Vec::new() };
+
+        for (keys, action) in [
+            ("C-g s", "save"),
+            ("C-g g", "goto_line"),
+            ("C-g b", "build"),
+            ("C-g c", "toggle_comment"),
+            ("C-g d", "goto_definition"),
+            ("C-g f", "search_in_files"),
+            ("C-g q", "quickfix"),
+            ("C-g t", "file_tree"),
+            ("C-g l", "buffer_list"),
+            ("C-g a", "archive_list"),
+            ("C-g h s", "stage_hunk"),
+            ("C-g h u", "unstage_hunk"),
+            ("C-g h r", "revert_hunk"),
+            ("C-g n", "new_buffer"),
+            ("C-g r", "rename_file"),
+            ("C-g e", "save_encoding"),
+            ("C-g T", "toggle_theme"),
+            ("C-g u", "insert_unicode"),
+            ("C-g i", "inspect_char"),
+            ("C-g w", "toggle_hard_wrap"),
+            ("C-g =", "spell_suggest"),
+            ("C-g $", "toggle_spell_check"),
+            ("C-g x", "evaluate"),
+            ("C-g D", "insert_timestamp"),
+            ("C-g z", "toggle_fold"),
+            ("C-g Z", "fold_all"),
+            ("C-g o", "unfold_all"),
+        ] {
+            // The defaults are known to parse
+            let _ = keymap.bind(keys, action);
+        }
+
+        keymap
+    }
+}
+
+impl Keymap {
+    // Bind `keys`, names like `C-x` separated by spaces, to the action named `action`, replacing what
+    // they were bound to
+    /// # Errors
+    ///
+    /// Will return `Err` if a key or the action isn't known, or fewer than two keys are given
+    pub fn bind(&mut self, keys: &str, action: &str) -> Result<()> {
+        let keys = keys
+            .split_whitespace()
+            .map(parse_key)
+            .collect::<Result<Vec<Key>>>()?;
+
+        if keys.len() < 2 {
+            return Err(eyre!("a chord needs two or more keys"));
+        }
+
+        let action = Action::from_name(action)?;
+
+        self.chords.retain(|chord| chord.keys != keys);
+        self.chords.push(Chord { keys, action });
+
+        Ok(())
+    }
+    #[must_use]
+    pub fn lookup(&self, keys: &[Key]) -> Lookup {
+        let mut prefix = false;
+
+        for chord in &self.chords {
+            if chord.keys == keys {
+                return Lookup::Action(chord.action);
+            }
+
+            prefix |= chord.keys.starts_with(keys);
+        }
+
+        if prefix { Lookup::Prefix } else { Lookup::Unbound }
+    }
+    // The keys that can follow `keys` and what each does, `...` for those starting longer chords
+    #[must_use]
+    pub fn continuations(&self, keys: &[Key]) -> Vec<(String, String)> {
+        let mut continuations: Vec<(String, String)> = Vec::new();
+
+        for chord in self.chords.iter().filter(|chord| chord.keys.len() > keys.len() && chord.keys.starts_with(keys)) {
+            let name = key_name(chord.keys[keys.len()]);
+            let action = if chord.keys.len() == keys.len() + 1 { chord.action.name() } else { "...".to_owned() };
+
+            if !continuations.iter().any(|(key, _)| *key == name) {
+                continuations.push((name, action));
+            }
+        }
+
+        continuations
+    }
+}
+
+#[must_use]
+pub fn key_from_event(key: KeyEvent) -> Key {
+    match key.code {
+        KeyCode::Char(_) => (key.modifiers - KeyModifiers::SHIFT, key.code),
+        code => (key.modifiers, code),
+    }
+}
+
+// `C-x`, `M-x` or `C-M-x` with Control and Alt, a character on its own, or a named key like `F5`,
+// `Esc`, `Enter`, `Tab` or `Space`
+/// # Errors
+///
+/// Will return `Err` if `name` isn't a key
+pub fn parse_key(name: &str) -> Result<Key> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = name;
+
+    loop {
+        if let Some(after) = rest.strip_prefix("C-").filter(|after| !after.is_empty()) {
+            modifiers |= KeyModifiers::CONTROL;
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("M-").filter(|after| !after.is_empty()) {
+            modifiers |= KeyModifiers::ALT;
+            rest = after;
+        } else {
+            break;
+        }
+    }
+
+    let code = match rest {
+        "Esc" => KeyCode::Esc,
+        "Enter" => KeyCode::Enter,
+        "Tab" => KeyCode::Tab,
+        "Space" => KeyCode::Char(' '),
+        _ => {
+            let mut chars = rest.chars();
+
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => KeyCode::Char(c),
+                _ => rest
+                    .strip_prefix('F')
+                    .and_then(|number| number.parse().ok())
+                    .map(KeyCode::F)
+                    .ok_or_else(|| eyre!("unknown key `{name}`"))?,
+            }
+        },
+    };
+
+    Ok((modifiers, code))
+}
+
+// The name `parse_key` reads back as the same key
+#[must_use]
+pub fn key_name((modifiers, code): Key) -> String {
+    let control = if modifiers.contains(KeyModifiers::CONTROL) { "C-" } else { "" };
+    let alt = if modifiers.contains(KeyModifiers::ALT) { "M-" } else { "" };
+
+    let key = match code {
+        KeyCode::Char(' ') => "Space".to_owned(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::F(number) => format!("F{number}"),
+        KeyCode::Esc => "Esc".to_owned(),
+        KeyCode::Enter => "Enter".to_owned(),
+        KeyCode::Tab => "Tab".to_owned(),
+        code => format!("{code:?}"),
+    };
+
+    format!("{control}{alt}{key}")
+}
@@ -0,0 +1,104 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+// The ironn editing a file, as its lock file records it
+#[derive(Clone, Debug)]
+pub struct LockOwner {
+    pub pid: u32,
+    pub host: String,
+}
+
+// A lock file beside a file being edited, `.name.ironn-lock` holding the pid and host of the
+// instance editing it. It's only advisory, other instances check it when they open the file
+pub struct FileLock {
+    path: PathBuf,
+    contents: String,
+}
+
+impl FileLock {
+    // Lock `file` for this instance. Fails with the owner when another instance still running holds
+    // the lock, and gives None when there's nowhere to write one
+    /// # Errors
+    ///
+    /// Will return `Err` if another live instance has `file` locked
+    pub fn acquire(file: &Path) -> Result<Option<Self>, LockOwner> {
+        let path = lock_path(file);
+
+        if let Some(owner) = fs::read_to_string(&path).ok().as_deref().and_then(parse_owner) {
+            if owner.pid != process::id() && is_alive(&owner) {
+                return Err(owner);
+            }
+
+            // Left behind by an instance that crashed or was killed
+            let _ = fs::remove_file(&path);
+        }
+
+        let contents = format!("{} {}\n", process::id(), hostname());
+
+        let written = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .and_then(|mut lock| lock.write_all(contents.as_bytes()));
+
+        match written {
+            Ok(()) => Ok(Some(Self { path, contents })),
+            // Another instance got there between reading and creating the lock
+            Err(error) if error.kind() == ErrorKind::AlreadyExists => {
+                fs::read_to_string(&path).ok().as_deref().and_then(parse_owner).map_or(Ok(None), Err)
+            },
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        // Only our own lock is removed, not one another instance took over
+        if fs::read_to_string(&self.path).is_ok_and(|contents| contents == self.contents) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+fn lock_path(file: &Path) -> PathBuf {
+    let name = file.file_name().unwrap_or_default().to_string_lossy();
+
+    file.with_file_name(format!(".{name}.ironn-lock"))
+}
+
+fn parse_owner(contents: &str) -> Option<LockOwner> {
+    let (pid, host) = contents.trim().split_once(' ')?;
+
+    Some(LockOwner { pid: pid.parse().ok()?, host: host.to_owned() })
+}
+
+// Processes on other hosts can't be checked, so their locks are kept
+fn is_alive(owner: &LockOwner) -> bool {
+    if owner.host != hostname() {
+        return true;
+    }
+
+    let Ok(pid) = libc::pid_t::try_from(owner.pid) else {
+        return false;
+    };
+
+    // Signal 0 only checks the process exists, permission errors mean it does but isn't ours
+    let exists = unsafe { libc::kill(pid, 0) == 0 };
+
+    exists || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+fn hostname() -> String {
+    let mut buffer = [0u8; 256];
+
+    if unsafe { libc::gethostname(buffer.as_mut_ptr().cast(), buffer.len()) } != 0 {
+        return String::from("localhost");
+    }
+
+    let end = buffer.iter().position(|byte| *byte == 0).unwrap_or(buffer.len());
+
+    String::from_utf8_lossy(&buffer[..end]).into_owned()
+}
@@ -0,0 +1,60 @@
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use color_eyre::eyre::{eyre, WrapErr};
+use color_eyre::Result;
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::fmt;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+// Used when `--log` is given without `RUST_LOG`
+const DEFAULT_FILTER: &str = "ironn=debug";
+
+// Write logs to `path`, or to the default log file when only `RUST_LOG` is set.
+// Nothing is logged when neither is given, the editor owns the terminal so logs can't go to stderr
+/// # Errors
+///
+/// Will return `Err` if `RUST_LOG` can't be parsed or the log file can't be opened
+pub fn init(path: Option<&Path>) -> Result<()> {
+    let filter = env::var("RUST_LOG").ok().filter(|filter| !filter.is_empty());
+
+    let Some(path) = path.map(Path::to_path_buf).or_else(|| filter.as_ref().and_then(|_| log_file())) else {
+        return Ok(());
+    };
+
+    let targets: Targets = filter
+        .as_deref()
+        .unwrap_or(DEFAULT_FILTER)
+        .parse()
+        .map_err(|error| eyre!("invalid RUST_LOG: {error}"))?;
+
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).wrap_err_with(|| format!("Failed to create {}", parent.display()))?;
+    }
+
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .wrap_err_with(|| format!("Failed to open log file {}", path.display()))?;
+
+    tracing_subscriber::registry()
+        .with(fmt::layer().with_writer(Mutex::new(file)).with_ansi(false))
+        .with(targets)
+        .try_init()?;
+
+    tracing::info!(version = env!("CARGO_PKG_VERSION"), "ironn started");
+
+    Ok(())
+}
+
+// `$XDG_STATE_HOME/ironn/ironn.log`, falling back to `~/.local/state/ironn/ironn.log`
+fn log_file() -> Option<PathBuf> {
+    env::var_os("XDG_STATE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("state")))
+        .map(|dir| dir.join("ironn").join("ironn.log"))
+}
@@ -11,45 +11,150 @@
     clippy::exhaustive_structs,
     clippy::exhaustive_enums
 )]
-mod editor;
-mod terminal;
+
+// Reading piped stdin, saving with the old file's owner and mode, lock files, suspending and local
+// time all go through Unix APIs, there's no fallback for other platforms
+#[cfg(not(unix))]
+compile_error!("ironn only runs on Unix-like systems (Linux, macOS, the BSDs)");
+
 mod row;
 mod document;
 mod highlighting;
 mod filetype;
 mod app;
 mod ui;
-mod doc;
-mod doc_row;
+mod clipboard;
+mod history;
+mod config;
+mod marks;
+mod diff;
+mod conflicts;
+mod csv;
+mod html;
+mod cat;
+mod cli;
+mod script;
+mod logging;
+mod status;
+mod action;
+mod prompt;
+mod textobject;
+mod snippets;
+mod completion;
+mod indent;
+mod regex;
+mod search;
+mod project;
+mod filetree;
+mod switcher;
+mod build;
+mod quickfix;
+mod tags;
+mod statusbar;
+mod keymap;
+mod theme;
+mod palette;
+mod unicode;
+mod wrap;
+mod fold;
+mod popup;
+mod save;
+mod encoding;
+mod lock;
+mod remote;
+mod archive;
+mod git;
+mod spell;
+mod calc;
+mod timestamp;
+mod template;
+mod modeline;
+mod watch;
 
 use std::error::Error;
-use std::io::{stderr, stdout, Stdout};
-use std::panic;
+use std::fs::File;
+use std::io::{stderr, stdin, stdout, Read, Stdout};
+use std::os::fd::AsRawFd;
+use std::panic::{self, AssertUnwindSafe};
+use std::process;
+use std::sync::Mutex;
+use std::thread;
 use color_eyre::eyre;
-use editor::Editor;
 use color_eyre::eyre::Result;
-use crossterm::event::{DisableMouseCapture, EnableMouseCapture};
+use crossterm::event::{DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture};
 use crossterm::{ExecutableCommand, execute};
+use crossterm::cursor::SetCursorStyle;
 use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
 use ratatui::backend::CrosstermBackend;
-pub use terminal::Terminal;
-pub use editor::Position;
-pub use editor::SearchDirection;
+pub use app::Position;
+pub use search::SearchDirection;
 pub use document::Document;
 pub use filetype::{FileType, HighlightingOptions};
 pub use row::Row;
 
 use crate::app::App;
+use crate::cli::Cli;
+
+// The report of a panic on the main thread, printed after unsaved changes are written out
+static PANIC_REPORT: Mutex<String> = Mutex::new(String::new());
 
 fn main() -> Result<(), Box<dyn Error>> {
+    let cli = match Cli::from_env() {
+        Ok(cli) => cli,
+        Err(error) => {
+            eprintln!("ironn: {error}\nTry 'ironn --help' for more information.");
+            process::exit(2);
+        }
+    };
+
+    if let Err(error) = logging::init(cli.log.as_deref()) {
+        eprintln!("ironn: {error:#}");
+        process::exit(1);
+    }
+
+    if let Some(script) = &cli.script {
+        if let Err(error) = script::run(script, &cli.files) {
+            eprintln!("ironn: {error:#}");
+            process::exit(1);
+        }
+
+        return Ok(());
+    }
+
+    if cli.cat {
+        for file in &cli.files {
+            cat::print(&file.path)?;
+        }
+
+        return Ok(());
+    }
+
+    let piped_text = if cli.reads_stdin() { Some(read_piped_stdin()?) } else { None };
+
     // Set up terminal
     let mut terminal = init_terminal()?;
 
     install_hooks()?;
 
     // Create app and run it
-    let mut app = App::default();
-    app.run(&mut terminal)?;
+    let mut app = App::new(&cli, piped_text.as_deref());
+
+    let run = panic::catch_unwind(AssertUnwindSafe(|| app.run(&mut terminal)));
+
+    // Unsaved changes are written out when the editor stops on an error or a panic
+    if !matches!(run, Ok(Ok(()))) {
+        for path in app.dump_dirty_buffers() {
+            eprintln!("ironn: unsaved changes written to {path}");
+        }
+    }
+
+    let Ok(result) = run else {
+        eprintln!("{}", PANIC_REPORT.lock().map(|report| report.clone()).unwrap_or_default());
+        process::exit(101);
+    };
+
+    result?;
+
 
     // Restore terminal
     restore()?;
@@ -59,28 +164,44 @@ fn main() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+// Read everything piped in, then put the terminal back on stdin so keys can still be read
+fn read_piped_stdin() -> Result<String> {
+    let mut text = String::new();
+
+    stdin().read_to_string(&mut text)?;
+
+    let tty = File::open("/dev/tty")?;
+
+    if unsafe { libc::dup2(tty.as_raw_fd(), libc::STDIN_FILENO) } < 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+
+    Ok(text)
+}
+
 fn init_terminal() -> Result<ratatui::Terminal<CrosstermBackend<Stdout>>> {
-    enable_raw_mode()?;
-    let mut stdout = stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
+    resume()?;
+    let backend = CrosstermBackend::new(stdout());
     let terminal = ratatui::Terminal::new(backend)?;
 
     Ok(terminal)
 }
 
-fn restore() -> Result<()> {
-    disable_raw_mode()?;
-    stderr().execute(LeaveAlternateScreen)?;
-    stderr().execute(DisableMouseCapture)?;
+// Take the terminal back after `restore` handed it to another program
+fn resume() -> Result<()> {
+    enable_raw_mode()?;
+    execute!(stdout(), EnterAlternateScreen, EnableMouseCapture, EnableBracketedPaste)?;
 
     Ok(())
 }
 
-fn main_old() -> Result<()> {
-    install_hooks_old()?;
-
-    Editor::default().run();
+fn restore() -> Result<()> {
+    disable_raw_mode()?;
+    // The terminal can't say what shape the cursor had, this puts back the one the user configured
+    stderr().execute(SetCursorStyle::DefaultUserShape)?;
+    stderr().execute(LeaveAlternateScreen)?;
+    stderr().execute(DisableMouseCapture)?;
+    stderr().execute(DisableBracketedPaste)?;
 
     Ok(())
 }
@@ -89,34 +210,24 @@ fn install_hooks() -> Result<()> {
     let hook_builder = color_eyre::config::HookBuilder::default();
     let (panic_hook, eyre_hook) = hook_builder.into_hooks();
 
-    let panic_hook = panic_hook.into_panic_hook();
     panic::set_hook(Box::new(move |panic_info| {
         restore().unwrap();
-        panic_hook(panic_info);
-    }));
-
-    let eyre_hook = eyre_hook.into_eyre_hook();
-    eyre::set_hook(Box::new(move |error| {
-        restore().unwrap();
-        eyre_hook(error)
-    }))?;
-
-    Ok(())
-}
 
-fn install_hooks_old() -> Result<()> {
-    let hook_builder = color_eyre::config::HookBuilder::default();
-    let (panic_hook, eyre_hook) = hook_builder.into_hooks();
+        let report = panic_hook.panic_report(panic_info).to_string();
 
-    let panic_hook = panic_hook.into_panic_hook();
-    panic::set_hook(Box::new(move |panic_info| {
-        Terminal::restore();
-        panic_hook(panic_info);
+        // The editor's own panics are reported once `main` has saved what it can of the buffers
+        if thread::current().name() == Some("main") {
+            if let Ok(mut panic_report) = PANIC_REPORT.lock() {
+                *panic_report = report;
+            }
+        } else {
+            eprintln!("{report}");
+        }
     }));
 
     let eyre_hook = eyre_hook.into_eyre_hook();
     eyre::set_hook(Box::new(move |error| {
-        Terminal::restore();
+        restore().unwrap();
         eyre_hook(error)
     }))?;
 
@@ -0,0 +1,124 @@
+use std::collections::BTreeMap;
+use crate::app::Position;
+
+// Named positions in a document that follow the text they point at as the document is edited
+#[derive(Default)]
+pub struct Marks {
+    positions: BTreeMap<char, Position>,
+}
+
+#[allow(clippy::missing_const_for_fn)]
+impl Marks {
+    pub fn set(&mut self, name: char, position: Position) {
+        self.positions.insert(name, position);
+    }
+    #[must_use]
+    pub fn get(&self, name: char) -> Option<&Position> {
+        self.positions.get(&name)
+    }
+    // Name of the first mark on row `y`
+    #[must_use]
+    pub fn name_at_line(&self, y: usize) -> Option<char> {
+        self.positions
+            .iter()
+            .find(|(_, position)| position.y == y)
+            .map(|(name, _)| *name)
+    }
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+    pub fn adjust_for_insert(&mut self, start: &Position, end: &Position) {
+        for position in self.positions.values_mut() {
+            shift_for_insert(position, start, end);
+        }
+    }
+    pub fn adjust_for_delete(&mut self, start: &Position, end: &Position) {
+        for position in self.positions.values_mut() {
+            shift_for_delete(position, start, end);
+        }
+    }
+}
+
+// Positions the cursor left by large jumps, walked back and forth like browser history
+#[derive(Default)]
+pub struct JumpList {
+    entries: Vec<Position>,
+    // Index of the entry being visited, equal to the length when not walking the list
+    index: usize,
+}
+
+#[allow(clippy::missing_const_for_fn)]
+impl JumpList {
+    // Starting a new jump drops any entries ahead of the current one
+    pub fn record(&mut self, position: Position) {
+        self.entries.truncate(self.index);
+
+        if self.entries.last() != Some(&position) {
+            self.entries.push(position);
+        }
+
+        self.index = self.entries.len();
+    }
+    pub fn back(&mut self, current: Position) -> Option<Position> {
+        if self.index == 0 {
+            return None;
+        }
+
+        // Remember where the walk started so forward can return to it
+        if self.index == self.entries.len() {
+            self.entries.push(current);
+        }
+
+        self.index -= 1;
+
+        self.entries.get(self.index).cloned()
+    }
+    pub fn forward(&mut self) -> Option<Position> {
+        if self.index.saturating_add(1) >= self.entries.len() {
+            return None;
+        }
+
+        self.index += 1;
+
+        self.entries.get(self.index).cloned()
+    }
+    pub fn adjust_for_insert(&mut self, start: &Position, end: &Position) {
+        for position in &mut self.entries {
+            shift_for_insert(position, start, end);
+        }
+    }
+    pub fn adjust_for_delete(&mut self, start: &Position, end: &Position) {
+        for position in &mut self.entries {
+            shift_for_delete(position, start, end);
+        }
+    }
+}
+
+// Move `position` so it points at the same text after `start..end` was inserted before it
+pub fn shift_for_insert(position: &mut Position, start: &Position, end: &Position) {
+    if *position < *start {
+        return;
+    }
+
+    if position.y == start.y {
+        position.x = end.x + (position.x - start.x);
+    }
+
+    position.y += end.y - start.y;
+}
+
+// Move `position` so it points at the same text after `start..end` was removed, positions inside collapse to `start`
+pub fn shift_for_delete(position: &mut Position, start: &Position, end: &Position) {
+    if *position <= *start {
+        return;
+    }
+
+    if *position < *end {
+        *position = start.clone();
+    } else if position.y == end.y {
+        *position = Position { x: start.x + (position.x - end.x), y: start.y };
+    } else {
+        position.y -= end.y - start.y;
+    }
+}
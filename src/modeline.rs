@@ -0,0 +1,151 @@
+// Rows at the top and bottom of a file searched for a modeline, as Vim does by default
+const MODELINE_ROWS: usize = 5;
+
+// Settings a file asks for in a Vim modeline like `# vim: ts=2 sw=2 et` or an Emacs one like
+// `-*- mode: python; indent-tabs-mode: nil -*-`. Only settings that can't do anything but change how
+// the buffer is edited are read, never ones that run commands
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct Modeline {
+    // Indent with spaces rather than tabs
+    pub expand_tab: Option<bool>,
+    // Spaces an indentation level is, from the shift width or else the tab stop
+    pub indent_width: Option<usize>,
+    pub fill_column: Option<usize>,
+    // The file type by the name Vim or Emacs gives it, like `python` or `sh`
+    pub file_type: Option<String>,
+}
+
+impl Modeline {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+// The settings from modelines in the first and last rows of `lines`, later ones overriding earlier ones
+#[must_use]
+pub fn find_modeline(lines: &[&str]) -> Modeline {
+    let head = lines.len().min(MODELINE_ROWS);
+    let tail = lines.len().saturating_sub(MODELINE_ROWS).max(head);
+    let mut modeline = Modeline::default();
+
+    for line in lines[..head].iter().chain(&lines[tail..]) {
+        if let Some(settings) = emacs_settings(line) {
+            apply_emacs(&mut modeline, settings);
+        } else if let Some(settings) = vim_settings(line) {
+            apply_vim(&mut modeline, settings);
+        }
+    }
+
+    modeline
+}
+
+// What follows `vim:`, `vi:` or `ex:` at the start of the line or after a space, up to the end of
+// the line, or the `:` closing a `set` form
+fn vim_settings(line: &str) -> Option<&str> {
+    let (index, marker) = ["vim:", "vi:", "ex:"]
+        .iter()
+        .filter_map(|marker| line.match_indices(marker).find(|(index, _)| *index == 0 || line[..*index].ends_with([' ', '\t'])))
+        .min_by_key(|(index, _)| *index)?;
+
+    let rest = line[index + marker.len()..].trim_start();
+
+    // `vim: set ts=2 sw=2 et :` ends at the colon, leaving the comment closer after it
+    if let Some(set) = rest.strip_prefix("set ").or_else(|| rest.strip_prefix("se ")) {
+        return Some(set.split_once(':').map_or(set, |(settings, _)| settings));
+    }
+
+    Some(rest)
+}
+fn apply_vim(modeline: &mut Modeline, settings: &str) {
+    let mut tab_stop = None;
+
+    for setting in settings.split([' ', '\t', ':']).filter(|setting| !setting.is_empty()) {
+        let (name, value) = setting.split_once('=').map_or((setting, None), |(name, value)| (name, Some(value)));
+        let number = value.and_then(|value| value.parse::<usize>().ok()).filter(|number| *number > 0);
+
+        match name {
+            "et" | "expandtab" => modeline.expand_tab = Some(true),
+            "noet" | "noexpandtab" => modeline.expand_tab = Some(false),
+            "sw" | "shiftwidth" => modeline.indent_width = number.or(modeline.indent_width),
+            "ts" | "tabstop" | "sts" | "softtabstop" => tab_stop = number.or(tab_stop),
+            "tw" | "textwidth" => modeline.fill_column = number.or(modeline.fill_column),
+            "ft" | "filetype" | "syn" | "syntax" => modeline.file_type = value.map(str::to_owned).or_else(|| modeline.file_type.clone()),
+            _ => (),
+        }
+    }
+
+    if modeline.indent_width.is_none() {
+        modeline.indent_width = tab_stop;
+    }
+}
+
+// What's between the `-*-` markers
+fn emacs_settings(line: &str) -> Option<&str> {
+    let (_, rest) = line.split_once("-*-")?;
+    let (settings, _) = rest.split_once("-*-")?;
+
+    Some(settings.trim())
+}
+// `mode: python; tab-width: 4`, or only the mode as in `-*- python -*-`
+fn apply_emacs(modeline: &mut Modeline, settings: &str) {
+    if !settings.contains(':') {
+        modeline.file_type = Some(settings.to_owned());
+        return;
+    }
+
+    for setting in settings.split(';') {
+        let Some((name, value)) = setting.split_once(':') else {
+            continue;
+        };
+
+        let value = value.trim();
+        let number = value.parse::<usize>().ok().filter(|number| *number > 0);
+
+        match name.trim().to_ascii_lowercase().as_str() {
+            "mode" => modeline.file_type = Some(value.to_owned()),
+            "indent-tabs-mode" => modeline.expand_tab = Some(value == "nil"),
+            "tab-width" => modeline.indent_width = modeline.indent_width.or(number),
+            "c-basic-offset" | "indent-offset" | "python-indent-offset" | "sh-basic-offset" | "js-indent-level" => modeline.indent_width = number.or(modeline.indent_width),
+            "fill-column" => modeline.fill_column = number.or(modeline.fill_column),
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vim_modelines() {
+        let modeline = find_modeline(&["#!/bin/sh", "# vim: set ts=8 sw=2 et ft=sh :"]);
+
+        assert_eq!(modeline, Modeline { expand_tab: Some(true), indent_width: Some(2), fill_column: None, file_type: Some("sh".to_owned()) });
+        assert_eq!(find_modeline(&["// vi:noet:ts=4:tw=72"]).indent_width, Some(4));
+        assert_eq!(find_modeline(&["// vi:noet:ts=4:tw=72"]).fill_column, Some(72));
+        // `vim:` inside a word isn't a modeline
+        assert!(find_modeline(&["novim: et"]).is_empty());
+    }
+
+    #[test]
+    fn emacs_modelines() {
+        let modeline = find_modeline(&["# -*- mode: python; indent-tabs-mode: nil; python-indent-offset: 4 -*-"]);
+
+        assert_eq!(modeline.file_type.as_deref(), Some("python"));
+        assert_eq!(modeline.expand_tab, Some(true));
+        assert_eq!(modeline.indent_width, Some(4));
+        assert_eq!(find_modeline(&[";; -*- lisp -*-"]).file_type.as_deref(), Some("lisp"));
+    }
+
+    #[test]
+    fn only_the_first_and_last_rows_are_read() {
+        let mut lines = vec!["text"; 20];
+
+        lines[10] = "vim: et";
+        assert!(find_modeline(&lines).is_empty());
+
+        lines[19] = "vim: tw=60";
+        assert_eq!(find_modeline(&lines).fill_column, Some(60));
+    }
+}
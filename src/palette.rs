@@ -0,0 +1,194 @@
+use std::env;
+use std::sync::OnceLock;
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+use crossterm::style::Color as TermColor;
+use ratatui::style::Color;
+
+// The 16 basic colors as xterm shows them by default
+const ANSI_16: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+// Levels of each channel in the 6x6x6 color cube of the 256 color palette
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+static COLOR_SUPPORT: OnceLock<ColorSupport> = OnceLock::new();
+
+// How many colors the terminal can show, fewest first
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum ColorSupport {
+    Ansi16,
+    Ansi256,
+    TrueColor,
+}
+
+impl ColorSupport {
+    // What `COLORTERM` and `TERM` say the terminal supports. Terminals that don't say assume the least
+    #[must_use]
+    pub fn from_env(colorterm: Option<&str>, term: Option<&str>) -> Self {
+        if colorterm.is_some_and(|colorterm| matches!(colorterm, "truecolor" | "24bit")) {
+            return Self::TrueColor;
+        }
+
+        match term {
+            Some(term) if term.ends_with("-direct") || term.ends_with("-truecolor") => Self::TrueColor,
+            Some(term) if term.contains("256color") => Self::Ansi256,
+            _ => Self::Ansi16,
+        }
+    }
+    // `truecolor`, `256` or `16`, or None for `auto` to detect it
+    /// # Errors
+    ///
+    /// Will return `Err` if `name` isn't one of those
+    pub fn from_name(name: &str) -> Result<Option<Self>> {
+        match name {
+            "auto" => Ok(None),
+            "truecolor" | "24bit" => Ok(Some(Self::TrueColor)),
+            "256" => Ok(Some(Self::Ansi256)),
+            "16" => Ok(Some(Self::Ansi16)),
+            _ => Err(eyre!("unknown color support `{name}`, expected one of: auto, truecolor, 256, 16")),
+        }
+    }
+}
+
+// Detected from the environment the first time it's asked for, unless it was set before then
+#[must_use]
+pub fn color_support() -> ColorSupport {
+    *COLOR_SUPPORT.get_or_init(|| ColorSupport::from_env(env::var("COLORTERM").ok().as_deref(), env::var("TERM").ok().as_deref()))
+}
+
+// Only has an effect before anything is drawn
+pub fn set_color_support(support: ColorSupport) {
+    let _ = COLOR_SUPPORT.set(support);
+}
+
+// The nearest color the terminal can show to `color`
+#[must_use]
+pub fn fit(color: Color) -> Color {
+    let support = color_support();
+
+    let rgb = match color {
+        Color::Rgb(r, g, b) if support < ColorSupport::TrueColor => (r, g, b),
+        Color::Indexed(index) if index >= 16 && support < ColorSupport::Ansi256 => indexed_rgb(index),
+        color => return color,
+    };
+
+    if support == ColorSupport::Ansi256 {
+        return Color::Indexed(nearest_256(rgb));
+    }
+
+    match nearest_16(rgb) {
+        0 => Color::Black,
+        1 => Color::Red,
+        2 => Color::Green,
+        3 => Color::Yellow,
+        4 => Color::Blue,
+        5 => Color::Magenta,
+        6 => Color::Cyan,
+        7 => Color::Gray,
+        8 => Color::DarkGray,
+        9 => Color::LightRed,
+        10 => Color::LightGreen,
+        11 => Color::LightYellow,
+        12 => Color::LightBlue,
+        13 => Color::LightMagenta,
+        14 => Color::LightCyan,
+        _ => Color::White,
+    }
+}
+
+// `fit` for colors written straight to the terminal
+#[must_use]
+pub fn fit_term(color: TermColor) -> TermColor {
+    let TermColor::Rgb { r, g, b } = color else {
+        return color;
+    };
+
+    match fit(Color::Rgb(r, g, b)) {
+        Color::Indexed(index) => TermColor::AnsiValue(index),
+        Color::Black => TermColor::Black,
+        Color::Red => TermColor::DarkRed,
+        Color::Green => TermColor::DarkGreen,
+        Color::Yellow => TermColor::DarkYellow,
+        Color::Blue => TermColor::DarkBlue,
+        Color::Magenta => TermColor::DarkMagenta,
+        Color::Cyan => TermColor::DarkCyan,
+        Color::Gray => TermColor::Grey,
+        Color::DarkGray => TermColor::DarkGrey,
+        Color::LightRed => TermColor::Red,
+        Color::LightGreen => TermColor::Green,
+        Color::LightYellow => TermColor::Yellow,
+        Color::LightBlue => TermColor::Blue,
+        Color::LightMagenta => TermColor::Magenta,
+        Color::LightCyan => TermColor::Cyan,
+        Color::White => TermColor::White,
+        _ => color,
+    }
+}
+
+// The closest entry of the color cube or the gray ramp of the 256 color palette
+fn nearest_256((r, g, b): (u8, u8, u8)) -> u8 {
+    let level = |channel: u8| {
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|index| CUBE_LEVELS[*index].abs_diff(channel))
+            .unwrap_or_default()
+    };
+    let (r_level, g_level, b_level) = (level(r), level(g), level(b));
+    let cube = (CUBE_LEVELS[r_level], CUBE_LEVELS[g_level], CUBE_LEVELS[b_level]);
+
+    let average = (u16::from(r) + u16::from(g) + u16::from(b)) / 3;
+    let gray_step = u8::try_from(average.saturating_sub(3) / 10).unwrap_or(u8::MAX).min(23);
+    let gray_level = 8 + gray_step * 10;
+
+    let cube_index = 16 + 36 * r_level + 6 * g_level + b_level;
+
+    if distance((r, g, b), (gray_level, gray_level, gray_level)) < distance((r, g, b), cube) {
+        232 + gray_step
+    } else {
+        u8::try_from(cube_index).unwrap_or(u8::MAX)
+    }
+}
+
+fn nearest_16(rgb: (u8, u8, u8)) -> usize {
+    (0..ANSI_16.len())
+        .min_by_key(|index| distance(rgb, ANSI_16[*index]))
+        .unwrap_or_default()
+}
+
+// The color an entry of the 256 color palette shows, the basic 16 as xterm shows them
+fn indexed_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => ANSI_16[usize::from(index)],
+        16..=231 => {
+            let cube = usize::from(index - 16);
+
+            (CUBE_LEVELS[cube / 36], CUBE_LEVELS[cube / 6 % 6], CUBE_LEVELS[cube % 6])
+        },
+        _ => {
+            let gray = 8 + (index - 232) * 10;
+
+            (gray, gray, gray)
+        },
+    }
+}
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let channel = |x: u8, y: u8| u32::from(x.abs_diff(y)).pow(2);
+
+    channel(a.0, b.0) + channel(a.1, b.1) + channel(a.2, b.2)
+}
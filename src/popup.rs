@@ -0,0 +1,102 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+// The filter and selection of a list popup. Keys move through the list, and while filtering typed
+// characters go into the filter. Entering the filter and what other keys do is up to the list using it
+#[derive(Default)]
+pub struct PopupList {
+    pub filter: String,
+    pub filtering: bool,
+    pub index: usize,
+    // Moving up from the first entry goes to the last and down from the last to the first
+    pub wraps: bool,
+}
+
+// What a key did to a popup list
+pub enum PopupKey {
+    Handled,
+    Chosen(usize),
+    Closed,
+    // The list doesn't use the key
+    Other,
+}
+
+impl PopupList {
+    #[must_use]
+    pub fn wrapping() -> Self {
+        Self { wraps: true, ..Self::default() }
+    }
+    // Handle `key` for a list of `len` entries, `page` of them shown at once
+    pub fn handle_key(&mut self, key: KeyEvent, len: usize, page: usize) -> PopupKey {
+        let last = len.saturating_sub(1);
+
+        match (key.modifiers, key.code) {
+            (_, KeyCode::Up) | (KeyModifiers::CONTROL, KeyCode::Char('p')) => {
+                self.index = match self.index.checked_sub(1) {
+                    Some(index) => index,
+                    None if self.wraps => last,
+                    None => 0,
+                };
+            },
+            (_, KeyCode::Down) | (KeyModifiers::CONTROL, KeyCode::Char('n')) => {
+                self.index = if self.index < last {
+                    self.index + 1
+                } else if self.wraps {
+                    0
+                } else {
+                    last
+                };
+            },
+            (_, KeyCode::PageUp) => self.index = self.index.saturating_sub(page.max(1)),
+            (_, KeyCode::PageDown) => self.index = self.index.saturating_add(page.max(1)).min(last),
+            (_, KeyCode::Home) if !self.filtering => self.index = 0,
+            (_, KeyCode::End) if !self.filtering => self.index = last,
+            (_, KeyCode::Enter) if self.index < len => return PopupKey::Chosen(self.index),
+            // Escape clears the filter first, then closes the list
+            (_, KeyCode::Esc) if self.filtering && !self.filter.is_empty() => {
+                self.filter.clear();
+                self.index = 0;
+            },
+            (_, KeyCode::Enter | KeyCode::Esc) | (KeyModifiers::CONTROL, KeyCode::Char('c')) => return PopupKey::Closed,
+            (_, KeyCode::Backspace) if self.filtering => {
+                self.filter.pop();
+                self.index = 0;
+            },
+            (KeyModifiers::NONE | KeyModifiers::SHIFT, KeyCode::Char(c)) if self.filtering => {
+                self.filter.push(c);
+                self.index = 0;
+            },
+            _ => return PopupKey::Other,
+        }
+
+        PopupKey::Handled
+    }
+    // Keep the selection on the list after entries were removed
+    pub fn clamp(&mut self, len: usize) {
+        self.index = self.index.min(len.saturating_sub(1));
+    }
+}
+
+// How loosely `text` contains the characters of `pattern` in order, ignoring case: the characters
+// skipped between the first and last matched, then how far in the first one is. None when it doesn't
+#[must_use]
+pub fn fuzzy_score(pattern: &str, text: &str) -> Option<usize> {
+    let mut pattern = pattern.chars().flat_map(char::to_lowercase).peekable();
+    let mut first = None;
+    let mut gaps = 0;
+    let mut last = 0;
+
+    for (index, c) in text.chars().flat_map(char::to_lowercase).enumerate() {
+        let Some(wanted) = pattern.peek() else {
+            break;
+        };
+
+        if c == *wanted {
+            pattern.next();
+            gaps += first.map_or(0, |_| index - last - 1);
+            first.get_or_insert(index);
+            last = index;
+        }
+    }
+
+    pattern.peek().is_none().then(|| gaps * 100 + first.unwrap_or(0))
+}
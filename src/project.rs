@@ -0,0 +1,181 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use color_eyre::eyre::{eyre, WrapErr};
+use color_eyre::Result;
+use crate::filetree::Gitignore;
+use crate::git::list_files;
+use crate::quickfix::{ItemKind, QuickfixItem};
+use crate::save::write_atomically;
+use crate::search::SearchPattern;
+
+// The replacements a project-wide replace would make in one file
+pub struct FileChanges {
+    pub path: PathBuf,
+    pub hunks: Vec<Hunk>,
+}
+
+// One changed line, counted from 0. Only selected hunks are written
+pub struct Hunk {
+    pub line: usize,
+    pub old: String,
+    pub new: String,
+    pub selected: bool,
+}
+
+// A line of the preview, either a file or one of its hunks
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PreviewRow {
+    File(usize),
+    Hunk(usize, usize),
+}
+
+// Every line under `root` the pattern matches with its replacement, grouped by file in path order.
+// Files git ignores, hidden files and directories are skipped, as are files over `size_limit` bytes
+// and ones that aren't UTF-8
+#[must_use]
+pub fn find_changes(root: &Path, pattern: &SearchPattern, replacement: &str, size_limit: u64) -> Vec<FileChanges> {
+    readable_files(root, size_limit)
+        .filter_map(|(path, contents)| {
+            let hunks: Vec<Hunk> = contents
+                .split('\n')
+                .enumerate()
+                .filter_map(|(line, text)| {
+                    // Carriage returns of CRLF lines stay out of the match and are put back after it
+                    let (text, ending) = text.strip_suffix('\r').map_or((text, ""), |text| (text, "\r"));
+                    let new = pattern.replace_line(text, replacement)?;
+
+                    (new != text).then(|| Hunk { line, old: format!("{text}{ending}"), new: format!("{new}{ending}"), selected: true })
+                })
+                .collect();
+
+            (!hunks.is_empty()).then_some(FileChanges { path, hunks })
+        })
+        .collect()
+}
+
+// The first match on each line of the files `find_changes` would look through, as quickfix items
+#[must_use]
+pub fn find_matches(root: &Path, pattern: &SearchPattern, size_limit: u64) -> Vec<QuickfixItem> {
+    let cwd = std::env::current_dir().unwrap_or_default();
+
+    readable_files(root, size_limit)
+        .flat_map(|(path, contents)| {
+            let name = path.strip_prefix(&cwd).unwrap_or(&path).to_string_lossy().into_owned();
+
+            contents
+                .lines()
+                .enumerate()
+                .filter_map(|(line, text)| {
+                    let column = pattern.first_match(text)?;
+
+                    Some(QuickfixItem { path: name.clone(), line: line + 1, column: column + 1, kind: ItemKind::Match, text: text.trim().to_owned() })
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+// Write the selected hunks of each file, copying it to `<name>~` first when `backup` is set. Returns
+// how many files were written and the errors for those that weren't, files changed since the preview
+// are left alone
+#[must_use]
+pub fn apply(changes: &[FileChanges], backup: bool) -> (usize, Vec<String>) {
+    let mut written = 0;
+    let mut errors = Vec::new();
+
+    for file in changes.iter().filter(|file| file.hunks.iter().any(|hunk| hunk.selected)) {
+        match apply_file(file, backup) {
+            Ok(()) => written += 1,
+            Err(error) => errors.push(format!("{error:#}")),
+        }
+    }
+
+    (written, errors)
+}
+
+// Files first, each followed by its hunks
+#[must_use]
+pub fn preview_rows(changes: &[FileChanges]) -> Vec<PreviewRow> {
+    changes
+        .iter()
+        .enumerate()
+        .flat_map(|(file, changes)| std::iter::once(PreviewRow::File(file)).chain((0..changes.hunks.len()).map(move |hunk| PreviewRow::Hunk(file, hunk))))
+        .collect()
+}
+
+fn apply_file(file: &FileChanges, backup: bool) -> Result<()> {
+    let path = &file.path;
+    let contents = fs::read_to_string(path).wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+    let mut lines: Vec<&str> = contents.split('\n').collect();
+
+    for hunk in file.hunks.iter().filter(|hunk| hunk.selected) {
+        let line = lines.get_mut(hunk.line).filter(|line| **line == hunk.old);
+        let Some(line) = line else {
+            return Err(eyre!("{} changed since the preview", path.display()));
+        };
+
+        *line = &hunk.new;
+    }
+
+    if backup {
+        let mut backup = path.clone().into_os_string();
+
+        backup.push("~");
+        fs::copy(path, &backup).wrap_err_with(|| format!("Failed to back up {}", path.display()))?;
+    }
+
+    write_atomically(path, lines.join("\n").as_bytes()).wrap_err_with(|| format!("Failed to write {}", path.display()))
+}
+
+// The files `find_changes` looks through, with their contents
+fn readable_files(root: &Path, size_limit: u64) -> impl Iterator<Item = (PathBuf, String)> {
+    let mut files = project_files(root);
+
+    files.sort();
+
+    files
+        .into_iter()
+        .filter(move |path| fs::metadata(path).is_ok_and(|metadata| metadata.len() <= size_limit))
+        .filter_map(|path| {
+            let contents = fs::read_to_string(&path).ok()?;
+
+            Some((path, contents))
+        })
+}
+
+// In a work tree git says which files aren't ignored or build output, elsewhere the root's
+// `.gitignore` is followed while walking the directories
+fn project_files(root: &Path) -> Vec<PathBuf> {
+    let is_hidden = |path: &Path| path.components().any(|component| component.as_os_str().to_string_lossy().starts_with('.'));
+
+    if let Ok(files) = list_files(root) {
+        return files.into_iter().filter(|path| !is_hidden(path)).map(|path| root.join(path)).collect();
+    }
+
+    let mut files = Vec::new();
+
+    collect_files(root, root, &Gitignore::load(root), &mut files);
+
+    files
+}
+
+fn collect_files(root: &Path, dir: &Path, ignore: &Gitignore, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+
+        let path = entry.path();
+        let is_ignored = |is_dir| path.strip_prefix(root).is_ok_and(|relative| ignore.is_ignored(relative, is_dir));
+
+        match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() && !is_ignored(true) => collect_files(root, &path, ignore, files),
+            Ok(file_type) if file_type.is_file() && !is_ignored(false) => files.push(path),
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use color_eyre::Result;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use unicode_segmentation::UnicodeSegmentation;
+use crate::row::Row;
+
+// Entries remembered for each kind of prompt
+const HISTORY_LIMIT: usize = 100;
+
+// Prompts asking for the same kind of input share a history
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum PromptKind {
+    File,
+    Line,
+    Search,
+    Replace,
+    Encoding,
+}
+
+impl PromptKind {
+    const fn name(self) -> &'static str {
+        match self {
+            Self::File => "file",
+            Self::Line => "line",
+            Self::Search => "search",
+            Self::Replace => "replace",
+            Self::Encoding => "encoding",
+        }
+    }
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "file" => Some(Self::File),
+            "line" => Some(Self::Line),
+            "search" => Some(Self::Search),
+            "replace" => Some(Self::Replace),
+            "encoding" => Some(Self::Encoding),
+            _ => None,
+        }
+    }
+}
+
+// A single line of input with a cursor, shared by the save, goto, export and search prompts
+#[derive(Default)]
+pub struct PromptState {
+    input: Row,
+    cursor: usize,
+    // How far back in the history Up has gone, and what was typed before it was first pressed
+    history_index: Option<usize>,
+    draft: String,
+}
+
+// Previous inputs of each kind of prompt, oldest first
+#[derive(Default)]
+pub struct PromptHistory {
+    entries: BTreeMap<PromptKind, Vec<String>>,
+}
+
+#[allow(clippy::missing_const_for_fn)]
+impl PromptState {
+    // Replace the input, leaving the cursor at its end
+    pub fn set(&mut self, text: &str) {
+        self.input = Row::from(text);
+        self.cursor = self.input.len();
+        self.history_index = None;
+    }
+    pub fn clear(&mut self) {
+        self.set("");
+    }
+    // Insert at the cursor, control characters such as line breaks in pasted text are dropped
+    pub fn insert_str(&mut self, text: &str) {
+        let text: String = text.chars().filter(|c| !c.is_control()).collect();
+
+        self.cursor += self.input.insert_str(self.cursor, &text);
+    }
+    // Apply a line editing key, returns whether the key was one
+    pub fn process_key(&mut self, key: KeyEvent) -> bool {
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+
+        match key.code {
+            KeyCode::Char('a') if control => self.cursor = 0,
+            KeyCode::Char('e') if control => self.cursor = self.input.len(),
+            KeyCode::Char('k') if control => self.delete_to(self.input.len()),
+            KeyCode::Char('w') if control => self.delete_to(self.previous_word()),
+            KeyCode::Char('b') if alt => self.cursor = self.previous_word(),
+            KeyCode::Char('f') if alt => self.cursor = self.next_word(),
+            KeyCode::Char('d') if alt => self.delete_to(self.next_word()),
+            KeyCode::Char(c) if !control && !alt => self.insert_str(c.encode_utf8(&mut [0; 4])),
+            KeyCode::Backspace if control || alt => self.delete_to(self.previous_word()),
+            KeyCode::Backspace => self.delete_to(self.cursor.saturating_sub(1)),
+            KeyCode::Delete if control => self.delete_to(self.next_word()),
+            KeyCode::Delete => self.delete_to(self.cursor.saturating_add(1).min(self.input.len())),
+            KeyCode::Left if control => self.cursor = self.previous_word(),
+            KeyCode::Right if control => self.cursor = self.next_word(),
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = self.cursor.saturating_add(1).min(self.input.len()),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.input.len(),
+            _ => return false,
+        }
+
+        true
+    }
+    // Show the entry before the one shown, keeping what was typed to come back to
+    pub fn history_previous(&mut self, history: &[String]) {
+        let index = self.history_index.unwrap_or(history.len()).checked_sub(1);
+        let Some(index) = index else {
+            return;
+        };
+
+        if self.history_index.is_none() {
+            self.draft = self.text().to_owned();
+        }
+
+        self.show_history(history, index);
+    }
+    // Show the entry after the one shown, or what was typed once past the newest
+    pub fn history_next(&mut self, history: &[String]) {
+        let Some(index) = self.history_index else {
+            return;
+        };
+
+        if index + 1 < history.len() {
+            self.show_history(history, index + 1);
+        } else {
+            let draft = std::mem::take(&mut self.draft);
+
+            self.set(&draft);
+        }
+    }
+    fn show_history(&mut self, history: &[String], index: usize) {
+        self.set(&history[index]);
+        self.history_index = Some(index);
+    }
+    // Delete the graphemes between the cursor and `to`, on either side of it
+    fn delete_to(&mut self, to: usize) {
+        let start = self.cursor.min(to);
+        let end = self.cursor.max(to);
+
+        for _ in start..end {
+            self.input.delete(start);
+        }
+
+        self.cursor = start;
+    }
+    // Start of the word before the cursor, skipping any separators right before it
+    fn previous_word(&self) -> usize {
+        let graphemes: Vec<&str> = self.input.as_str().graphemes(true).take(self.cursor).collect();
+        let separators = graphemes.iter().rev().take_while(|grapheme| !is_word(grapheme)).count();
+        let word = graphemes.iter().rev().skip(separators).take_while(|grapheme| is_word(grapheme)).count();
+
+        self.cursor - separators - word
+    }
+    // End of the word after the cursor, skipping any separators right after it
+    fn next_word(&self) -> usize {
+        let graphemes: Vec<&str> = self.input.as_str().graphemes(true).skip(self.cursor).collect();
+        let separators = graphemes.iter().take_while(|grapheme| !is_word(grapheme)).count();
+        let word = graphemes.iter().skip(separators).take_while(|grapheme| is_word(grapheme)).count();
+
+        self.cursor + separators + word
+    }
+    #[must_use]
+    pub fn text(&self) -> &str {
+        self.input.as_str()
+    }
+    #[must_use]
+    pub fn input(&self) -> &Row {
+        &self.input
+    }
+    #[must_use]
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.input.is_empty()
+    }
+}
+
+#[allow(clippy::missing_const_for_fn)]
+impl PromptHistory {
+    // One `kind<TAB>entry` line per entry, prompt input never holds control characters
+    /// # Errors
+    ///
+    /// Will return `Err` if the history file exists but can't be read
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut history = Self::default();
+
+        if !path.exists() {
+            return Ok(history);
+        }
+
+        for line in fs::read_to_string(path)?.lines() {
+            let Some((kind, entry)) = line.split_once('\t') else {
+                continue;
+            };
+
+            if let Some(kind) = PromptKind::from_name(kind) {
+                history.add(kind, entry);
+            }
+        }
+
+        Ok(history)
+    }
+    /// # Errors
+    ///
+    /// Will return `Err` if the history file or its directory can't be written
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let contents: String = self.entries
+            .iter()
+            .flat_map(|(kind, entries)| entries.iter().map(|entry| format!("{}\t{entry}\n", kind.name())))
+            .collect();
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(path, contents)?;
+
+        Ok(())
+    }
+    // Entering the same input again moves it to the newest place instead of repeating it
+    pub fn add(&mut self, kind: PromptKind, entry: &str) {
+        if entry.is_empty() {
+            return;
+        }
+
+        let entries = self.entries.entry(kind).or_default();
+
+        entries.retain(|existing| existing != entry);
+        entries.push(entry.to_owned());
+
+        if entries.len() > HISTORY_LIMIT {
+            entries.remove(0);
+        }
+    }
+    #[must_use]
+    pub fn entries(&self, kind: PromptKind) -> &[String] {
+        self.entries.get(&kind).map_or(&[], Vec::as_slice)
+    }
+}
+
+fn is_word(grapheme: &str) -> bool {
+    grapheme.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
@@ -0,0 +1,99 @@
+use crate::build::{BuildError, BuildSeverity};
+
+// Places in files to work through one at a time, like a build's errors or a search's matches. The list
+// stays until another replaces it, remembering which item was reached
+pub struct QuickfixList {
+    title: String,
+    items: Vec<QuickfixItem>,
+    // None until an item is gone to
+    index: Option<usize>,
+}
+
+// Lines and columns count from 1, as tools print them
+#[derive(Clone, Debug)]
+pub struct QuickfixItem {
+    pub path: String,
+    pub line: usize,
+    pub column: usize,
+    pub kind: ItemKind,
+    pub text: String,
+}
+
+// An error, warning or note in the shown buffer, underlining graphemes `start` to `end` of line `y`,
+// counted from 0
+pub struct Diagnostic<'a> {
+    pub item: &'a QuickfixItem,
+    pub y: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ItemKind {
+    Error,
+    Warning,
+    Note,
+    Match,
+}
+
+#[allow(clippy::missing_const_for_fn)]
+impl QuickfixList {
+    #[must_use]
+    pub fn new(title: String, items: Vec<QuickfixItem>) -> Self {
+        Self { title, items, index: None }
+    }
+    // The item after or before the current one, which becomes current. Before any item is reached that's
+    // the first or last one. None past either end
+    pub fn step(&mut self, forward: bool) -> Option<&QuickfixItem> {
+        let index = match self.index {
+            Some(index) if forward => index.checked_add(1)?,
+            Some(index) => index.checked_sub(1)?,
+            None if forward => 0,
+            None => self.items.len().checked_sub(1)?,
+        };
+
+        self.items.get(index)?;
+        self.index = Some(index);
+
+        self.items.get(index)
+    }
+    pub fn select(&mut self, index: usize) {
+        self.index = Some(index.min(self.items.len().saturating_sub(1)));
+    }
+    #[must_use]
+    pub fn current(&self) -> Option<&QuickfixItem> {
+        self.items.get(self.index?)
+    }
+    #[must_use]
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+    #[must_use]
+    pub fn items(&self) -> &[QuickfixItem] {
+        &self.items
+    }
+    #[must_use]
+    pub fn index(&self) -> Option<usize> {
+        self.index
+    }
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+impl From<BuildError> for QuickfixItem {
+    fn from(error: BuildError) -> Self {
+        let kind = match error.severity {
+            BuildSeverity::Error => ItemKind::Error,
+            BuildSeverity::Warning => ItemKind::Warning,
+            BuildSeverity::Note => ItemKind::Note,
+        };
+
+        Self { path: error.path, line: error.line, column: error.column, kind, text: error.message }
+    }
+}
@@ -0,0 +1,133 @@
+use std::error::Error;
+use std::fmt;
+
+// A regular expression matched one line at a time, in the syntax of the `regex` crate. A leading
+// `(?i)` ignores case
+pub struct Regex {
+    regex: ::regex::Regex,
+}
+
+// What's wrong with a pattern, in one line for the status bar
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RegexError {
+    message: String,
+}
+
+// Byte ranges of a match and its groups, group 0 being the whole match
+pub struct Captures {
+    locations: ::regex::CaptureLocations,
+}
+
+impl Regex {
+    /// # Errors
+    ///
+    /// Will return `Err` if the pattern isn't valid, saying what's wrong
+    pub fn new(pattern: &str) -> Result<Self, RegexError> {
+        let regex = ::regex::Regex::new(pattern).map_err(|error| RegexError::from(&error))?;
+
+        Ok(Self { regex })
+    }
+    // The leftmost match starting at or after byte `start` of `text`. Anchors and `\b` still see
+    // the text before `start`
+    #[must_use]
+    pub fn captures_at(&self, text: &str, start: usize) -> Option<Captures> {
+        let mut locations = self.regex.capture_locations();
+
+        self.regex.captures_read_at(&mut locations, text, start)?;
+
+        Some(Captures { locations })
+    }
+}
+
+impl Captures {
+    // Start and end byte of `group`, None when it didn't take part in the match
+    #[must_use]
+    pub fn get(&self, group: usize) -> Option<(usize, usize)> {
+        self.locations.get(group)
+    }
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.locations.len()
+    }
+}
+
+// The crate's errors draw the pattern with a caret under the problem over several lines, only the
+// last line saying what it is
+impl From<&::regex::Error> for RegexError {
+    fn from(error: &::regex::Error) -> Self {
+        let message = match error {
+            ::regex::Error::Syntax(text) => text.lines().rev().find_map(|line| line.strip_prefix("error: ")).unwrap_or(text).to_owned(),
+            ::regex::Error::CompiledTooBig(_) => "pattern is too large".to_owned(),
+            _ => error.to_string(),
+        };
+
+        Self { message }
+    }
+}
+
+impl fmt::Display for RegexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for RegexError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(pattern: &str, text: &str) -> Option<(usize, usize)> {
+        Regex::new(pattern).unwrap().captures_at(text, 0)?.get(0)
+    }
+
+    #[test]
+    fn alternation_takes_the_leftmost_match() {
+        assert_eq!(find("cat|dog", "hotdog cat"), Some((3, 6)));
+        assert_eq!(find("a(?:b|c)d", "xacd"), Some((1, 4)));
+        assert_eq!(find("cat|dog", "bird"), None);
+    }
+
+    #[test]
+    fn classes_and_escapes() {
+        assert_eq!(find("[a-c_]+", "xx_cab9"), Some((2, 6)));
+        assert_eq!(find("[^\"]+", "\"quoted\""), Some((1, 7)));
+        assert_eq!(find(r"\d+", "line 42:"), Some((5, 7)));
+        assert_eq!(find(r"\bis\b", "this is"), Some((5, 7)));
+        assert_eq!(find("(?i)hello", "Say HELLO"), Some((4, 9)));
+    }
+
+    #[test]
+    fn anchors_see_text_before_the_start() {
+        let regex = Regex::new("^ab").unwrap();
+
+        assert!(regex.captures_at("abab", 0).is_some());
+        assert!(regex.captures_at("abab", 2).is_none());
+        assert_eq!(find("b$", "abab"), Some((3, 4)));
+    }
+
+    #[test]
+    fn counted_repeats() {
+        assert_eq!(find("a{2}", "aaaa"), Some((0, 2)));
+        assert_eq!(find("a{2,}", "baaab"), Some((1, 4)));
+        assert_eq!(find("x{1,3}?", "xxx"), Some((0, 1)));
+        assert_eq!(find("^a{3}$", "aa"), None);
+    }
+
+    #[test]
+    fn groups_that_took_no_part_are_none() {
+        let captures = Regex::new("(a)|(b)").unwrap().captures_at("b", 0).unwrap();
+
+        assert_eq!(captures.len(), 3);
+        assert_eq!(captures.get(1), None);
+        assert_eq!(captures.get(2), Some((0, 1)));
+    }
+
+    #[test]
+    fn errors_fit_on_one_line() {
+        let error = Regex::new("(ab").err().unwrap().to_string();
+
+        assert!(!error.contains('\n'));
+        assert!(error.contains("unclosed group"));
+    }
+}
@@ -0,0 +1,225 @@
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+use crate::save::private_directory;
+
+const SCHEME: &str = "sftp://";
+
+// A file on another machine named by an `sftp://[user@]host[:port]/path` URI, with an IPv6 host in
+// brackets like `[::1]`. It's copied with the `sftp` client in batch mode, so the host has to let it
+// in without asking for a password
+#[derive(Clone, Debug)]
+pub struct RemoteFile {
+    destination: String,
+    port: Option<u16>,
+    path: String,
+}
+
+impl RemoteFile {
+    // Paths starting with `/~/` are in the user's home directory, others are absolute
+    /// # Errors
+    ///
+    /// Will return `Err` if `uri` has no host or path, an invalid port, or a user or host `sftp`
+    /// would take for an option
+    pub fn parse(uri: &str) -> Result<Self> {
+        let rest = uri.strip_prefix(SCHEME).ok_or_else(|| eyre!("{uri} isn't an sftp:// URI"))?;
+        let (authority, path) = rest.split_once('/').ok_or_else(|| eyre!("{uri} has no path"))?;
+        let (user, host_port) = authority.rsplit_once('@').map_or((None, authority), |(user, host_port)| (Some(user), host_port));
+
+        // The brackets stay on, `sftp` would otherwise read an IPv6 address's colons as a path
+        let (host, port) = if let Some(bracketed) = host_port.strip_prefix('[') {
+            let (address, after) = bracketed.split_once(']').ok_or_else(|| eyre!("unclosed `[` in {uri}"))?;
+            let port = match after {
+                "" => None,
+                _ => Some(after.strip_prefix(':').ok_or_else(|| eyre!("invalid port `{after}` in {uri}"))?),
+            };
+
+            (&host_port[..address.len() + 2], port)
+        } else {
+            host_port.rsplit_once(':').map_or((host_port, None), |(host, port)| (host, Some(port)))
+        };
+        let port = port.map(|port| port.parse().map_err(|_| eyre!("invalid port `{port}` in {uri}"))).transpose()?;
+
+        if host.is_empty() || host == "[]" || path.is_empty() || user.is_some_and(str::is_empty) {
+            return Err(eyre!("{uri} needs a host and a path"));
+        }
+
+        if host.contains(':') && !host.starts_with('[') {
+            return Err(eyre!("the IPv6 address in {uri} needs brackets, like [::1]"));
+        }
+
+        if host.starts_with('-') || user.is_some_and(|user| user.starts_with('-')) {
+            return Err(eyre!("{uri} has a user or host starting with `-`"));
+        }
+
+        let path = path.strip_prefix("~/").map_or_else(|| format!("/{path}"), str::to_owned);
+        let destination = user.map_or_else(|| host.to_owned(), |user| format!("{user}@{host}"));
+
+        Ok(Self { destination, port, path })
+    }
+    /// # Errors
+    ///
+    /// Will return `Err` if `sftp` can't connect or the file can't be read
+    pub fn download(&self) -> Result<Vec<u8>> {
+        let scratch = private_directory("sftp")?;
+        let local = local_copy(&scratch);
+        let result = self.run(&format!("get {} {}", quote(&self.path), quote(&local.to_string_lossy()))).and_then(|()| fs::read(&local).map_err(Into::into));
+
+        let _ = fs::remove_dir_all(&scratch);
+
+        tracing::info!(destination = self.destination, path = self.path, ok = result.is_ok(), "sftp download");
+
+        result
+    }
+    /// # Errors
+    ///
+    /// Will return `Err` if `sftp` can't connect or the file can't be written
+    pub fn upload(&self, contents: &[u8]) -> Result<()> {
+        let scratch = private_directory("sftp")?;
+        let local = local_copy(&scratch);
+        let result = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&local)
+            .and_then(|mut file| file.write_all(contents))
+            .map_err(Into::into)
+            .and_then(|()| self.run(&format!("put {} {}", quote(&local.to_string_lossy()), quote(&self.path))));
+
+        let _ = fs::remove_dir_all(&scratch);
+
+        tracing::info!(destination = self.destination, path = self.path, ok = result.is_ok(), "sftp upload");
+
+        result
+    }
+    // Run one batch command, failing with the first line `sftp` complained with
+    fn run(&self, command: &str) -> Result<()> {
+        let mut sftp = Command::new("sftp");
+
+        sftp.args(["-q", "-b", "-"]);
+
+        if let Some(port) = self.port {
+            sftp.args(["-P", &port.to_string()]);
+        }
+
+        let mut child = sftp
+            .arg("--")
+            .arg(&self.destination)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|error| match error.kind() {
+                ErrorKind::NotFound => eyre!("sftp isn't installed"),
+                _ => error.into(),
+            })?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            writeln!(stdin, "{command}")?;
+        }
+
+        let output = child.wait_with_output()?;
+
+        if output.status.success() {
+            return Ok(());
+        }
+
+        let error = String::from_utf8_lossy(&output.stderr);
+        let reason = error.lines().find(|line| !line.trim().is_empty()).unwrap_or("sftp failed");
+
+        Err(eyre!("{}: {reason}", self.destination))
+    }
+}
+
+#[must_use]
+pub fn is_remote(file_name: &str) -> bool {
+    file_name.starts_with(SCHEME)
+}
+#[must_use]
+pub fn is_url(file_name: &str) -> bool {
+    file_name.starts_with("http://") || file_name.starts_with("https://")
+}
+// The path part of a URL, without its query and fragment, for telling the file type from
+#[must_use]
+pub fn url_path(url: &str) -> &str {
+    url.split(['?', '#']).next().unwrap_or(url)
+}
+// Fetch a URL with `curl`, following redirects
+/// # Errors
+///
+/// Will return `Err` if `curl` isn't installed, or the URL can't be fetched or answers with an error
+pub fn fetch_url(url: &str) -> Result<Vec<u8>> {
+    let output = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--location", "--", url])
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|error| match error.kind() {
+            ErrorKind::NotFound => eyre!("curl isn't installed"),
+            _ => error.into(),
+        })?;
+
+    tracing::info!(url, ok = output.status.success(), bytes = output.stdout.len(), "fetched");
+
+    if !output.status.success() {
+        let error = String::from_utf8_lossy(&output.stderr);
+
+        return Err(eyre!("{}", error.trim().trim_start_matches("curl: ")));
+    }
+
+    Ok(output.stdout)
+}
+
+// Where a remote file is copied to on its way, inside a directory only this user can read
+fn local_copy(scratch: &Path) -> PathBuf {
+    scratch.join("copy")
+}
+
+// Batch commands take paths in double quotes with quotes and backslashes escaped
+fn quote(path: &str) -> String {
+    format!("\"{}\"", path.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parts(uri: &str) -> (String, Option<u16>, String) {
+        let remote = RemoteFile::parse(uri).unwrap();
+
+        (remote.destination, remote.port, remote.path)
+    }
+
+    #[test]
+    fn hosts_ports_and_paths() {
+        assert_eq!(parts("sftp://host/etc/hosts"), ("host".to_owned(), None, "/etc/hosts".to_owned()));
+        assert_eq!(parts("sftp://me@host:2222/~/notes.txt"), ("me@host".to_owned(), Some(2222), "notes.txt".to_owned()));
+        assert_eq!(parts("sftp://[::1]/x"), ("[::1]".to_owned(), None, "/x".to_owned()));
+        assert_eq!(parts("sftp://me@[fe80::1]:22/x"), ("me@[fe80::1]".to_owned(), Some(22), "/x".to_owned()));
+    }
+
+    #[test]
+    fn invalid_uris() {
+        for uri in ["http://host/x", "sftp://host", "sftp:///x", "sftp://host/", "sftp://host:port/x", "sftp://::1/x", "sftp://[::1/x", "sftp://[::1]22/x", "sftp://[]/x", "sftp://@host/x"] {
+            assert!(RemoteFile::parse(uri).is_err(), "{uri}");
+        }
+    }
+
+    #[test]
+    fn options_are_refused() {
+        assert!(RemoteFile::parse("sftp://-oProxyCommand=touch pwned/x").is_err());
+        assert!(RemoteFile::parse("sftp://-oProxyCommand=x@host/x").is_err());
+        assert!(RemoteFile::parse("sftp://user@-oProxyCommand=x/x").is_err());
+    }
+
+    #[test]
+    fn urls() {
+        assert!(is_url("https://example.com/a.rs?raw=1"));
+        assert!(!is_url("sftp://host/a.rs"));
+        assert_eq!(url_path("https://example.com/a.rs?raw=1#L3"), "https://example.com/a.rs");
+        assert_eq!(quote(r#"a "b"\c"#), r#""a \"b\"\\c""#);
+    }
+}
@@ -1,40 +1,83 @@
-use core::cmp;
+use std::borrow::Cow;
+use std::ops::Range;
 use crossterm::style::{Color, SetForegroundColor};
 use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+use crate::encoding::raw_byte;
 use crate::{highlighting, HighlightingOptions, SearchDirection};
+use crate::palette::fit_term;
 
 #[derive(Default)]
 pub struct Row {
     string: String,
     highlighting: Vec<highlighting::Type>,
-    len: usize,
+    // Byte offset where each grapheme starts, so finding one doesn't walk the line
+    offsets: Vec<usize>,
     is_highlighted: bool,
 }
 
 impl Row {
+    // Visible part of the row between display columns `start` and `start + width`
     #[must_use]
-    pub fn render(&self, start: usize, end: usize) -> String {
-        let end = cmp::min(end, self.string.len());
-        let start = cmp::min(start, end);
+    pub fn render(&self, start: usize, width: usize) -> String {
+        let end = start.saturating_add(width);
+        let mut result = String::new();
+        let mut column = 0;
+
+        for grapheme in self.string.graphemes(true) {
+            if column >= end {
+                break;
+            }
+
+            let next_column = column + grapheme_width(grapheme);
+
+            if column >= start && next_column <= end {
+                result.push_str(&shown_grapheme(grapheme));
+            } else if next_column > start {
+                // Wide grapheme cut in half by the viewport edge
+                result.push_str(&" ".repeat(next_column.min(end) - column.max(start)));
+            }
+
+            column = next_column;
+        }
+
+        result
+    }
+    // Render the part of the row between display columns `start` and `end`, colored by `highlight`
+    #[must_use]
+    pub fn render_highlighted(&self, start: usize, end: usize) -> String {
         let mut result = String::new();
         let mut current_highlighting = &highlighting::Type::None;
+        let mut column = 0;
 
-        for (index, grapheme) in self.string.graphemes(true).enumerate().skip(start).take(end - start) {
-            if let Some(c) = grapheme.chars().next() {
-                let highlighting_type = self.highlighting.get(index).unwrap_or(&highlighting::Type::None);
+        for (index, grapheme) in self.string.graphemes(true).enumerate() {
+            if column >= end {
+                break;
+            }
 
-                if highlighting_type != current_highlighting {
-                    current_highlighting = highlighting_type;
+            let next_column = column + grapheme_width(grapheme);
 
-                    result.push_str(format!("{}", SetForegroundColor(highlighting_type.to_color())).as_str());
-                }
+            if next_column <= start {
+                column = next_column;
+                continue;
+            }
 
-                if c == '\t' {
-                    result.push(' ');
-                } else {
-                    result.push_str(grapheme);
-                }
+            let highlighting_type = self.highlighting.get(index).unwrap_or(&highlighting::Type::None);
+
+            if highlighting_type != current_highlighting {
+                current_highlighting = highlighting_type;
+
+                result.push_str(format!("{}", SetForegroundColor(fit_term(highlighting_type.to_color()))).as_str());
+            }
+
+            if column < start || next_column > end {
+                // Wide grapheme cut in half by the viewport edge
+                result.push_str(&" ".repeat(next_column.min(end) - column.max(start)));
+            } else {
+                result.push_str(&shown_grapheme(grapheme));
             }
+
+            column = next_column;
         }
 
         result.push_str(format!("{}", SetForegroundColor(Color::Reset)).as_str());
@@ -42,105 +85,69 @@ impl Row {
         result
     }
     pub fn insert(&mut self, at: usize, c: char) {
-        if at >= self.len() {
-            self.string.push(c);
-            self.len += 1;
-
-            return;
-        }
+        let byte_index = self.byte_index(at);
 
-        let mut result = String::new();
-
-        for (index, grapheme) in self.string.graphemes(true).enumerate() {
-            if index == at {
-                result.push(c);
-            }
+        self.splice(byte_index..byte_index, c.encode_utf8(&mut [0; 4]));
+    }
+    // Insert a whole string at grapheme index `at`, returning how many graphemes the row grew by.
+    // Combining marks merge into the preceding grapheme, so this can be less than the number of chars
+    pub fn insert_str(&mut self, at: usize, text: &str) -> usize {
+        let byte_index = self.byte_index(at);
+        let old_len = self.len();
 
-            result.push_str(grapheme);
-        }
+        self.splice(byte_index..byte_index, text);
+        self.is_highlighted = false;
 
-        self.len += 1;
-        self.string = result;
+        self.len().saturating_sub(old_len)
     }
     pub fn delete(&mut self, at: usize) {
         if at >= self.len() {
             return;
         }
 
-        let mut result = String::new();
-        
-        for (index, grapheme) in self.string.graphemes(true).enumerate() {
-            if index != at {
-                result.push_str(grapheme);
-            }
-        }
-        
-        self.len -= 1;
-        self.string = result;
+        self.splice(self.byte_index(at)..self.byte_index(at + 1), "");
     }
     pub fn append(&mut self, new: &Self) {
-        self.string = format!("{}{}", self.string, new.string);
-        self.len += new.len;
+        let end = self.string.len();
+
+        self.splice(end..end, &new.string);
     }
-    // Iterate over current row to search for `query`, return None if not found
+    // Grapheme index of the first match of `query` searching from `at` in `direction`
     #[must_use]
     pub fn find(&self, query: &str, at: usize, direction: SearchDirection) -> Option<usize> {
-        if at > self.len || query.is_empty() {
+        if at > self.len() || query.is_empty() {
             return None;
         }
-        
-        let start = if direction == SearchDirection::Forward {
-            at
-        } else {
-            0
-        };
-        let end = if direction == SearchDirection::Forward {
-            self.len
+
+        let (start, end) = if direction == SearchDirection::Forward {
+            (at, self.len())
         } else {
-            at
+            (0, at)
         };
-        
-        let substring: String = self.string.graphemes(true).skip(start).take(end - start).collect();
-        let matching_byte_index = if direction == SearchDirection::Forward {
+
+        let start_byte = self.byte_index(start);
+        let substring = &self.string[start_byte..self.byte_index(end)];
+        let matching_byte_index = start_byte + if direction == SearchDirection::Forward {
             substring.find(query)
         } else {
             substring.rfind(query)
-        };
-        
-        // If query found, return grapheme index
-        if let Some(matching_byte_index) = matching_byte_index {
-            for (grapheme_index, (byte_index, _)) in substring.grapheme_indices(true).enumerate() {
-                if matching_byte_index == byte_index {
-                    return Some(start + grapheme_index)
-                }
-            }
-        }
-        None
+        }?;
+
+        // A match starting inside a grapheme isn't one
+        self.offsets.binary_search(&matching_byte_index).ok()
     }
     #[must_use]
     pub fn split(&mut self, at: usize) -> Self {
-        let mut row = String::new();
-        let mut length = 0;
-        let mut split_row = String::new();
-        
-        for (index, grapheme) in self.string.graphemes(true).enumerate() {
-            if index < at {
-                length += 1;
-                row.push_str(grapheme);
-            } else {
-                split_row.push_str(grapheme);
-            }
-        }
+        let byte_index = self.byte_index(at);
+        let split_row = self.string.split_off(byte_index);
+        let split_offsets = self.offsets.split_off(at.min(self.len())).into_iter().map(|offset| offset - byte_index).collect();
 
-        let split_length = self.len - length;
-        self.string = row;
-        self.len = length;
         self.is_highlighted = false;
 
         Self {
             string: split_row,
             highlighting: Vec::new(),
-            len: split_length,
+            offsets: split_offsets,
             is_highlighted: false,
         }
     }
@@ -195,21 +202,104 @@ impl Row {
 
         false
     }
+    // Display width of the graphemes before `at`, wide characters take two cells
+    #[must_use]
+    pub fn width_to(&self, at: usize) -> usize {
+        self.string[..self.byte_index(at)].graphemes(true).map(grapheme_width).sum()
+    }
+    // Grapheme index covering the display column `column`
+    #[must_use]
+    pub fn index_at_width(&self, column: usize) -> usize {
+        let mut width = 0;
+
+        for (index, grapheme) in self.string.graphemes(true).enumerate() {
+            width += grapheme_width(grapheme);
+
+            if width > column {
+                return index;
+            }
+        }
+
+        self.len()
+    }
     #[must_use]
     pub fn len(&self) -> usize {
-        self.len
+        self.offsets.len()
     }
     #[must_use]
     pub fn is_empty(&self) -> bool {
-        self.len == 0
+        self.offsets.is_empty()
+    }
+    // The graphemes between `start` and `end`
+    #[must_use]
+    pub fn substring(&self, start: usize, end: usize) -> String {
+        self.string[self.byte_index(start)..self.byte_index(end.max(start))].to_owned()
     }
     #[must_use]
     pub fn as_bytes(&self) -> &[u8] {
         self.string.as_bytes()
     }
+    #[must_use]
+    pub const fn as_str(&self) -> &str {
+        self.string.as_str()
+    }
+    // Highlight type of each char, filled in by `highlight`
+    #[must_use]
+    pub fn highlighting(&self) -> &[highlighting::Type] {
+        &self.highlighting
+    }
     pub fn unhighlight(&mut self) {
         self.is_highlighted = false;
     }
+    // Byte offset where grapheme `at` starts, the end of the string when `at` is past it
+    #[must_use]
+    pub fn byte_index(&self, at: usize) -> usize {
+        self.offsets.get(at).copied().unwrap_or(self.string.len())
+    }
+    // Graphemes starting before byte offset `at`, so an offset inside a grapheme counts it
+    #[must_use]
+    pub fn grapheme_index(&self, at: usize) -> usize {
+        self.offsets.partition_point(|&offset| offset < at)
+    }
+    // Replace the bytes in `range` with `text`. Only the graphemes from the one before `range` up to
+    // where the old boundaries line up again are segmented anew, the rest are just moved along
+    fn splice(&mut self, range: Range<usize>, text: &str) {
+        let first = self.offsets.partition_point(|&offset| offset < range.start).saturating_sub(1);
+        let segment_start = self.byte_index(first);
+        let mut old = self.offsets.partition_point(|&offset| offset < range.end);
+        let inserted_end = range.start + text.len();
+        let moved = |offset: usize| offset - range.end + inserted_end;
+
+        self.string.replace_range(range.clone(), text);
+
+        let mut offsets = Vec::new();
+        let mut replaced = first..self.offsets.len();
+
+        for (index, _) in self.string[segment_start..].grapheme_indices(true) {
+            let offset = segment_start + index;
+
+            if offset > inserted_end {
+                while self.offsets.get(old).is_some_and(|&old_offset| moved(old_offset) < offset) {
+                    old += 1;
+                }
+
+                if self.offsets.get(old).is_some_and(|&old_offset| moved(old_offset) == offset) {
+                    replaced.end = old;
+                    break;
+                }
+            }
+
+            offsets.push(offset);
+        }
+
+        let resumed = first + offsets.len();
+
+        self.offsets.splice(replaced, offsets);
+
+        for offset in &mut self.offsets[resumed..] {
+            *offset = moved(*offset);
+        }
+    }
     fn highlight_match(&mut self, word: &Option<String>) {
         if let Some(word) = word {
             if word.is_empty() {
@@ -399,6 +489,25 @@ impl Row {
     }
 }
 
+#[must_use]
+pub fn grapheme_width(grapheme: &str) -> usize {
+    shown_grapheme(grapheme).width()
+}
+// How `grapheme` is drawn: tabs as a space and invalid bytes as their value, like `<0xFF>`
+#[must_use]
+pub fn shown_grapheme(grapheme: &str) -> Cow<'_, str> {
+    if grapheme == "\t" {
+        return Cow::Borrowed(" ");
+    }
+
+    let mut chars = grapheme.chars();
+
+    chars
+        .next()
+        .and_then(raw_byte)
+        .map_or(Cow::Borrowed(grapheme), |byte| Cow::Owned(format!("<0x{byte:02X}>{}", chars.as_str())))
+}
+
 fn is_separator(c: char) -> bool {
     c.is_ascii_punctuation() || c.is_ascii_whitespace()
 }
@@ -408,8 +517,60 @@ impl From<&str> for Row {
         Self {
             string: String::from(slice),
             highlighting: Vec::new(),
-            len: slice.graphemes(true).count(),
+            offsets: slice.grapheme_indices(true).map(|(index, _)| index).collect(),
             is_highlighted: false,
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_indexed(row: &Row) {
+        let offsets: Vec<usize> = row.string.grapheme_indices(true).map(|(index, _)| index).collect();
+
+        assert_eq!(row.offsets, offsets, "{:?}", row.string);
+    }
+
+    #[test]
+    fn edits_keep_the_grapheme_index() {
+        // Combining marks, joiners and flags all join with what's around them
+        let pieces = ["a", "b", "\u{301}", "\u{200D}", "👩", "🇫", "🇷", "\r", "\n", "界"];
+        let mut seed = 11_u64;
+        let mut next = |below: usize| {
+            seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+            usize::try_from(seed >> 33).unwrap() % below
+        };
+        let mut row = Row::default();
+
+        for _ in 0..5000 {
+            match next(4) {
+                0 | 1 => {
+                    let text: String = (0..=next(3)).map(|_| pieces[next(pieces.len())]).collect();
+
+                    row.insert_str(next(row.len() + 1), &text);
+                },
+                2 => row.delete(next(row.len() + 1)),
+                _ => {
+                    let tail = row.split(next(row.len() + 1));
+
+                    assert_indexed(&tail);
+                    row.append(&tail);
+                },
+            }
+
+            assert_indexed(&row);
+        }
+    }
+
+    #[test]
+    fn matches_inside_a_grapheme_are_skipped() {
+        let row = Row::from("e\u{301}e");
+
+        assert_eq!(row.find("e", 0, SearchDirection::Forward), Some(0));
+        assert_eq!(row.find("e", 1, SearchDirection::Forward), Some(1));
+        assert_eq!(row.substring(1, 2), "e");
+        assert_eq!(row.grapheme_index(1), 1);
+    }
+}
@@ -0,0 +1,157 @@
+use std::ffi::CString;
+use std::fs::{self, DirBuilder, File, OpenOptions, Permissions};
+use std::io::{ErrorKind, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{fchown, DirBuilderExt, MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use color_eyre::Result;
+
+// Write `contents` to `path` through a temporary file renamed over it, so a crash or a full disk never
+// leaves it half written. The new file gets the old one's mode, owner and extended attributes, which
+// hold its ACLs, and when it can't the file is written in place
+/// # Errors
+///
+/// Will return `Err` if the file isn't writable, or writing or renaming the temporary file fails
+pub fn write_atomically(path: &Path, contents: &[u8]) -> Result<()> {
+    // Saving through a symlink replaces the file it points to, not the link
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let original = fs::metadata(&path).ok();
+
+    // Renaming would get around a file's own permissions, so they're checked like writing it would
+    if original.is_some() {
+        OpenOptions::new().append(true).open(&path)?;
+    }
+
+    // Renaming over a file with other hard links would split it from them
+    if original.as_ref().is_some_and(|metadata| metadata.nlink() > 1) {
+        return write_in_place(&path, contents);
+    }
+
+    let temporary = temporary_path(&path);
+
+    let mut file = match OpenOptions::new().write(true).create_new(true).mode(0o666).open(&temporary) {
+        Ok(file) => file,
+        // A directory that can't be written to can't hold the temporary file, but the file may still be writable
+        Err(error) if error.kind() == ErrorKind::PermissionDenied => return write_in_place(&path, contents),
+        Err(error) => return Err(error.into()),
+    };
+
+    let written = file
+        .write_all(contents)
+        .map(|()| original.as_ref().map(|metadata| keep_attributes(&file, &path, metadata)).unwrap_or_default());
+
+    // A new file that can't have the old one's owner, attributes or mode would quietly change them, so
+    // the old file is overwritten where it is instead, like vim's `backupcopy=auto`
+    let written = match written {
+        Ok(lost) if !lost.is_empty() => {
+            let _ = fs::remove_file(&temporary);
+
+            tracing::info!(path = %path.display(), ?lost, "saving in place to keep attributes");
+
+            return write_in_place(&path, contents);
+        },
+        written => written.and_then(|_| file.sync_all()).and_then(|()| fs::rename(&temporary, &path)),
+    };
+
+    if written.is_err() {
+        let _ = fs::remove_file(&temporary);
+    }
+
+    Ok(written?)
+}
+
+fn write_in_place(path: &Path, contents: &[u8]) -> Result<()> {
+    Ok(File::create(path)?.write_all(contents)?)
+}
+
+// A new directory under the system's temporary directory that only this user can enter. It's made
+// with `mkdir`, which fails on anything already there, so a planted directory or symlink is never
+// followed; names are retried until one is free
+/// # Errors
+///
+/// Will return `Err` if the temporary directory can't be written to
+pub fn private_directory(purpose: &str) -> Result<PathBuf> {
+    static CREATED: AtomicUsize = AtomicUsize::new(0);
+
+    loop {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.subsec_nanos());
+        let count = CREATED.fetch_add(1, Ordering::Relaxed);
+        let directory = std::env::temp_dir().join(format!("ironn-{purpose}-{}-{count}-{nanos:08x}", process::id()));
+
+        match DirBuilder::new().mode(0o700).create(&directory) {
+            Ok(()) => return Ok(directory),
+            Err(error) if error.kind() == ErrorKind::AlreadyExists => {}
+            Err(error) => return Err(error.into()),
+        }
+    }
+}
+
+// Beside the file, so renaming it stays on the same filesystem
+fn temporary_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+    path.with_file_name(format!(".{name}.{}.ironn-save", process::id()))
+}
+
+// Give `file` the owner, extended attributes and mode of `metadata`, the file at `path`. The mode
+// goes last since changing the owner clears set-user-ID and set-group-ID bits
+fn keep_attributes(file: &File, path: &Path, metadata: &fs::Metadata) -> Vec<&'static str> {
+    let mut lost = Vec::new();
+
+    if fchown(file, Some(metadata.uid()), Some(metadata.gid())).is_err() {
+        lost.push("owner");
+    }
+
+    if !copy_extended_attributes(path, file) {
+        lost.push("extended attributes or ACLs");
+    }
+
+    if file.set_permissions(Permissions::from_mode(metadata.mode())).is_err() {
+        lost.push("permissions");
+    }
+
+    lost
+}
+
+// Copy every extended attribute of the file at `from` to `to`, returns whether they all were
+#[cfg(target_os = "linux")]
+fn copy_extended_attributes(from: &Path, to: &File) -> bool {
+    let Ok(from) = CString::new(from.as_os_str().as_bytes()) else {
+        return false;
+    };
+
+    let Some(names) = read_attribute(|buffer, size| unsafe { libc::listxattr(from.as_ptr(), buffer.cast(), size) }) else {
+        // Filesystems without extended attributes have none to lose
+        return true;
+    };
+
+    names.split(|byte| *byte == 0).filter(|name| !name.is_empty()).all(|name| {
+        let Ok(name) = CString::new(name) else {
+            return false;
+        };
+
+        read_attribute(|buffer, size| unsafe { libc::getxattr(from.as_ptr(), name.as_ptr(), buffer, size) }).is_some_and(|value| {
+            unsafe { libc::fsetxattr(to.as_raw_fd(), name.as_ptr(), value.as_ptr().cast(), value.len(), 0) == 0 }
+        })
+    })
+}
+#[cfg(not(target_os = "linux"))]
+fn copy_extended_attributes(_from: &Path, _to: &File) -> bool {
+    true
+}
+
+// Call an xattr function first for the size of what it returns and then to fill a buffer of that size
+#[cfg(target_os = "linux")]
+fn read_attribute(read: impl Fn(*mut libc::c_void, usize) -> isize) -> Option<Vec<u8>> {
+    let size = usize::try_from(read(std::ptr::null_mut(), 0)).ok()?;
+    let mut buffer = vec![0u8; size];
+    let read = usize::try_from(read(buffer.as_mut_ptr().cast(), size)).ok()?;
+
+    buffer.truncate(read);
+
+    Some(buffer)
+}
@@ -0,0 +1,232 @@
+use std::fs;
+use color_eyre::eyre::{eyre, WrapErr};
+use color_eyre::Result;
+use unicode_segmentation::UnicodeSegmentation;
+use crate::app::Position;
+use crate::cli::FileArg;
+use crate::document::Document;
+use crate::row::Row;
+use crate::SearchDirection;
+
+// One command per line, `#` starts a comment line. Arguments are bare words or
+// double quoted strings understanding \n, \t, \" and \\
+//
+//   goto LINE [COLUMN]      move the cursor, counting from 1
+//   find TEXT               move the cursor to the next match of TEXT
+//   insert TEXT             insert TEXT at the cursor and move past it
+//   delete COUNT            delete COUNT characters at the cursor
+//   replace OLD NEW         replace every OLD in the buffer with NEW
+//   save [PATH]             write the buffer, to PATH if given
+enum Command {
+    Goto(usize, usize),
+    Find(String),
+    Insert(String),
+    Delete(usize),
+    Replace(String, String),
+    Save(Option<String>),
+}
+
+/// # Errors
+///
+/// Will return `Err` if the script can't be read or parsed, or a command fails on one of the files
+pub fn run(script_path: &str, files: &[FileArg]) -> Result<()> {
+    let contents = fs::read_to_string(script_path).wrap_err_with(|| format!("Failed to read {script_path}"))?;
+    let commands = parse(&contents).wrap_err_with(|| format!("Invalid script {script_path}"))?;
+
+    for file in files {
+        let mut document = Document::open(&file.path).wrap_err_with(|| format!("Failed to open {}", file.path))?;
+        let mut cursor = Position::default();
+
+        for (line_number, command) in &commands {
+            execute(&mut document, &mut cursor, command)
+                .wrap_err_with(|| format!("{script_path}:{line_number} failed on {}", file.path))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn parse(contents: &str) -> Result<Vec<(usize, Command)>> {
+    let mut commands = Vec::new();
+
+    for (index, line) in contents.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let words = split_words(line).ok_or_else(|| eyre!("line {line_number}: unterminated string"))?;
+        let command = match words.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+            ["goto", line] => Command::Goto(parse_number(line, line_number)?, 1),
+            ["goto", line, column] => Command::Goto(parse_number(line, line_number)?, parse_number(column, line_number)?),
+            ["find", text] => Command::Find((*text).to_owned()),
+            ["insert", text] => Command::Insert((*text).to_owned()),
+            ["delete", count] => Command::Delete(parse_number(count, line_number)?),
+            ["replace", old, new] => Command::Replace((*old).to_owned(), (*new).to_owned()),
+            ["save"] => Command::Save(None),
+            ["save", path] => Command::Save(Some((*path).to_owned())),
+            [name, ..] => return Err(eyre!("line {line_number}: invalid `{name}` command")),
+            [] => continue,
+        };
+
+        commands.push((line_number, command));
+    }
+
+    Ok(commands)
+}
+
+fn execute(document: &mut Document, cursor: &mut Position, command: &Command) -> Result<()> {
+    match command {
+        Command::Goto(line, column) => {
+            let y = line.saturating_sub(1).min(document.len().saturating_sub(1));
+            let x = column.saturating_sub(1).min(document.row(y).map_or(0, Row::len));
+
+            *cursor = Position { x, y };
+        },
+        Command::Find(text) => {
+            *cursor = document
+                .find(text, cursor, SearchDirection::Forward)
+                .ok_or_else(|| eyre!("`{text}` not found"))?;
+        },
+        Command::Insert(text) => {
+            *cursor = document.insert_str(cursor, text);
+        },
+        Command::Delete(count) => {
+            for _ in 0..*count {
+                document.delete(cursor);
+            }
+        },
+        Command::Replace(old, new) => {
+            let mut position = Position::default();
+
+            document.begin_transaction();
+
+            while let Some(start) = document.find(old, &position, SearchDirection::Forward) {
+                let end = Position { x: start.x + old.graphemes(true).count(), y: start.y };
+
+                document.delete_range(&start, &end);
+                position = document.insert_str(&start, new);
+            }
+
+            document.commit();
+        },
+        Command::Save(path) => {
+            if let Some(path) = path {
+                document.file_name = Some(path.clone());
+            }
+
+            document.write_out()?;
+        },
+    }
+
+    Ok(())
+}
+
+fn parse_number(word: &str, line_number: usize) -> Result<usize> {
+    word.parse().map_err(|_| eyre!("line {line_number}: `{word}` isn't a number"))
+}
+
+// Split a line into words, a double quoted string is one word
+fn split_words(line: &str) -> Option<Vec<String>> {
+    let mut words = Vec::new();
+    let mut chars = line.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let mut word = String::new();
+
+        if c == '"' {
+            chars.next();
+
+            loop {
+                match chars.next()? {
+                    '"' => break,
+                    '\\' => word.push(match chars.next()? {
+                        'n' => '\n',
+                        't' => '\t',
+                        other => other,
+                    }),
+                    other => word.push(other),
+                }
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                word.push(c);
+            }
+        }
+
+        words.push(word);
+    }
+
+    Some(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use crate::save::private_directory;
+    use super::*;
+
+    // Run `script` on a file holding `contents`, returning what the file holds afterwards
+    fn edit(contents: &str, script: &str) -> Result<String> {
+        let directory = private_directory("script-test")?;
+        let file = directory.join("file.txt");
+        let script_path = directory.join("edits.ironn");
+
+        fs::write(&file, contents)?;
+        fs::write(&script_path, script)?;
+
+        let result = run(&path_string(&script_path), &[FileArg { path: path_string(&file), line: None, column: None }])
+            .and_then(|()| Ok(fs::read_to_string(&file)?));
+
+        fs::remove_dir_all(&directory)?;
+
+        result
+    }
+
+    fn path_string(path: &Path) -> String {
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn edits_are_saved() {
+        let script = "# fix the greeting\ngoto 2 7\ninsert \"big \"\nfind world\ndelete 5\ninsert there\nreplace one \"1\\n\"\nsave\n";
+
+        assert_eq!(edit("one\nhello world\n", script).unwrap(), "1\n\nhello big there\n");
+    }
+
+    #[test]
+    fn unsaved_edits_are_dropped() {
+        assert_eq!(edit("keep\n", "insert lost\n").unwrap(), "keep\n");
+    }
+
+    #[test]
+    fn failures_name_the_script_line() {
+        let error = format!("{:#}", edit("text\n", "goto 1\nfind missing\nsave\n").unwrap_err());
+
+        assert!(error.contains("edits.ironn:2 failed"), "{error}");
+        assert!(error.contains("`missing` not found"), "{error}");
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = |script: &str| parse(script).err().unwrap().to_string();
+
+        assert_eq!(error("goto x"), "line 1: `x` isn't a number");
+        assert_eq!(error("\n\njump 3"), "line 3: invalid `jump` command");
+        assert_eq!(error("insert \"open"), "line 1: unterminated string");
+        assert_eq!(error("replace a"), "line 1: invalid `replace` command");
+    }
+
+    #[test]
+    fn quoted_words() {
+        assert_eq!(split_words(r#"insert "a \"b\"\tc\\" d"#).unwrap(), ["insert", "a \"b\"\tc\\", "d"]);
+        assert_eq!(split_words("  save   out.txt ").unwrap(), ["save", "out.txt"]);
+    }
+}
@@ -0,0 +1,212 @@
+use unicode_segmentation::UnicodeSegmentation;
+use crate::app::Position;
+use crate::document::Document;
+use crate::regex::{Regex, RegexError};
+use crate::row::Row;
+
+#[derive(PartialEq, Eq, Copy, Clone)]
+pub enum SearchDirection {
+    Forward,
+    Backward
+}
+
+// What a search looks for, the query as typed or the regex compiled from it
+pub struct SearchPattern {
+    query: String,
+    regex: Option<Regex>,
+}
+
+// Where a match is, and with a regex the text of each group with the whole match first
+pub struct SearchMatch {
+    pub start: Position,
+    pub end: Position,
+    groups: Option<Vec<Option<String>>>,
+}
+
+impl SearchPattern {
+    /// # Errors
+    ///
+    /// Will return `Err` if `regex` is set and `query` isn't a valid pattern
+    pub fn new(query: &str, regex: bool) -> Result<Self, RegexError> {
+        let regex = if regex { Some(Regex::new(query)?) } else { None };
+
+        Ok(Self { query: query.to_owned(), regex })
+    }
+    // The first match at or after `at` going forward, or the last one starting before it going
+    // backward. Matches don't span rows
+    #[must_use]
+    pub fn find(&self, document: &Document, at: &Position, direction: SearchDirection) -> Option<SearchMatch> {
+        if direction == SearchDirection::Forward {
+            (at.y..document.len()).find_map(|y| self.match_at(document, y, if y == at.y { at.x } else { 0 }))
+        } else {
+            (0..=at.y.min(document.len().saturating_sub(1)))
+                .rev()
+                .find_map(|y| self.last_match_before(document, y, if y == at.y { at.x } else { usize::MAX }))
+        }
+    }
+    fn last_match_before(&self, document: &Document, y: usize, before: usize) -> Option<SearchMatch> {
+        let mut found = None;
+        let mut x = 0;
+
+        while let Some(next) = self.match_at(document, y, x).filter(|next| next.start.x < before) {
+            // An empty match would be found again at the same place, so carry on past it
+            x = next.end.x.max(next.start.x + 1);
+            found = Some(next);
+        }
+
+        found
+    }
+    // The first match in row `y` starting at or after `x`
+    fn match_at(&self, document: &Document, y: usize, x: usize) -> Option<SearchMatch> {
+        self.match_in_row(document.row(y)?, y, x)
+    }
+    fn match_in_row(&self, row: &Row, y: usize, x: usize) -> Option<SearchMatch> {
+        if x > row.len() {
+            return None;
+        }
+
+        let Some(regex) = &self.regex else {
+            let start = row.find(&self.query, x, SearchDirection::Forward)?;
+            let end = start + self.query.graphemes(true).count();
+
+            return Some(SearchMatch { start: Position { x: start, y }, end: Position { x: end, y }, groups: None });
+        };
+
+        let text = row.as_str();
+        let captures = regex.captures_at(text, row.byte_index(x))?;
+        let (start, end) = captures.get(0)?;
+        let groups = (0..captures.len())
+            .map(|group| captures.get(group).map(|(start, end)| text[start..end].to_owned()))
+            .collect();
+
+        Some(SearchMatch {
+            start: Position { x: row.grapheme_index(start), y },
+            end: Position { x: row.grapheme_index(end), y },
+            groups: Some(groups),
+        })
+    }
+    // Where the first match in `line` starts, counted in graphemes
+    #[must_use]
+    pub fn first_match(&self, line: &str) -> Option<usize> {
+        self.match_in_row(&Row::from(line), 0, 0).map(|found| found.start.x)
+    }
+    // `line` with every match replaced, None when nothing matches
+    #[must_use]
+    pub fn replace_line(&self, line: &str, replacement: &str) -> Option<String> {
+        let row = Row::from(line);
+        let mut replaced = String::new();
+        let mut x = 0;
+        let mut copied = 0;
+        let mut matched = false;
+
+        while let Some(found) = self.match_in_row(&row, 0, x) {
+            replaced.push_str(&row.substring(copied, found.start.x));
+            replaced.push_str(&found.expand(replacement));
+            copied = found.end.x;
+            matched = true;
+
+            // An empty match would be found again where it was, so carry on after the next character
+            x = found.end.x.max(found.start.x + 1);
+        }
+
+        if !matched {
+            return None;
+        }
+
+        replaced.push_str(&row.substring(copied, row.len()));
+
+        Some(replaced)
+    }
+    #[must_use]
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+}
+
+impl SearchMatch {
+    // The text to replace the match with. With a regex `$1` or `${1}` stand for a group's text, `$0` for
+    // the whole match and `$$` for `$`, plain searches replace with `replacement` as it is
+    #[must_use]
+    pub fn expand(&self, replacement: &str) -> String {
+        let Some(groups) = &self.groups else {
+            return replacement.to_owned();
+        };
+
+        let mut text = String::new();
+        let mut chars = replacement.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            if c != '$' {
+                text.push(c);
+                continue;
+            }
+
+            if chars.next_if_eq(&'$').is_some() {
+                text.push('$');
+                continue;
+            }
+
+            let braced = chars.next_if_eq(&'{').is_some();
+            let mut number = String::new();
+
+            while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                number.push(digit);
+            }
+
+            let closed = !braced || chars.next_if_eq(&'}').is_some();
+
+            match number.parse::<usize>() {
+                Ok(group) if closed => text.push_str(groups.get(group).and_then(Option::as_deref).unwrap_or_default()),
+                // Anything else after a `$` is kept as it was written
+                _ => {
+                    text.push('$');
+
+                    if braced {
+                        text.push('{');
+                    }
+
+                    text.push_str(&number);
+                },
+            }
+        }
+
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replace(query: &str, line: &str, replacement: &str) -> Option<String> {
+        SearchPattern::new(query, true).unwrap().replace_line(line, replacement)
+    }
+
+    #[test]
+    fn groups_expand_in_replacements() {
+        assert_eq!(replace(r"(\w+)=(\w+)", "a=1, b=2", "$2=$1").as_deref(), Some("1=a, 2=b"));
+        assert_eq!(replace(r"(\d)", "x1", "${1}0").as_deref(), Some("x10"));
+        assert_eq!(replace("o", "foo", "[$0]").as_deref(), Some("f[o][o]"));
+    }
+
+    #[test]
+    fn unknown_references_are_kept_as_written() {
+        assert_eq!(replace("b", "abc", "$$").as_deref(), Some("a$c"));
+        assert_eq!(replace("b", "abc", "$9").as_deref(), Some("ac"));
+        assert_eq!(replace("b", "abc", "${x}").as_deref(), Some("a${x}c"));
+    }
+
+    #[test]
+    fn empty_matches_move_on_by_one_character() {
+        assert_eq!(replace("x*", "ab", "-").as_deref(), Some("-a-b-"));
+        assert_eq!(replace("q", "ab", "-"), None);
+    }
+
+    #[test]
+    fn plain_searches_replace_literally() {
+        let pattern = SearchPattern::new("a.c", false).unwrap();
+
+        assert_eq!(pattern.replace_line("abc a.c", "$1").as_deref(), Some("abc $1"));
+        assert_eq!(pattern.first_match("éa.c"), Some(1));
+    }
+}
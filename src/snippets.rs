@@ -0,0 +1,208 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::path::Path;
+use color_eyre::eyre::WrapErr;
+use color_eyre::Result;
+use unicode_segmentation::UnicodeSegmentation;
+use crate::app::Position;
+use crate::marks::{shift_for_delete, shift_for_insert};
+
+// Snippets in this file apply to every file type
+const ALL_FILE_TYPES: &str = "all";
+
+// A `snippet TRIGGER [description]` line followed by the body indented with one tab, like snipMate.
+// The body holds tab stops `$1` or `${1:default}`, visited in order with `$0` last
+pub struct Snippet {
+    pub trigger: String,
+    body: String,
+}
+
+// Snippets for each file type, read from `<config dir>/snippets/<file type>.snippets`
+#[derive(Default)]
+pub struct SnippetLibrary {
+    file_types: BTreeMap<String, Vec<Snippet>>,
+}
+
+// The text a snippet expands to and where its tab stops are, relative to where it's inserted
+pub struct Expansion {
+    pub text: String,
+    pub stops: Vec<(Position, Position)>,
+}
+
+// Tab stops of an expanded snippet still to visit, kept pointing at their text as the document is edited
+#[derive(Default)]
+pub struct TabStops {
+    stops: VecDeque<(Position, Position)>,
+}
+
+#[allow(clippy::missing_const_for_fn)]
+impl SnippetLibrary {
+    /// # Errors
+    ///
+    /// Will return `Err` if a snippet file can't be read
+    pub fn load(dir: &Path) -> Result<Self> {
+        let mut library = Self::default();
+
+        let Ok(entries) = fs::read_dir(dir) else {
+            return Ok(library);
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+
+            if path.extension().is_none_or(|extension| extension != "snippets") {
+                continue;
+            }
+
+            let Some(file_type) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+
+            let contents = fs::read_to_string(&path).wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+
+            library.file_types.insert(file_type.to_lowercase(), parse(&contents));
+        }
+
+        Ok(library)
+    }
+    // The snippet for `trigger` in files of `file_type`, snippets for the file type win over ones for all
+    #[must_use]
+    pub fn get(&self, file_type: &str, trigger: &str) -> Option<&Snippet> {
+        [file_type.to_lowercase().as_str(), ALL_FILE_TYPES]
+            .iter()
+            .filter_map(|file_type| self.file_types.get(*file_type))
+            .flatten()
+            .find(|snippet| snippet.trigger == trigger)
+    }
+    // Triggers starting with `prefix` that apply to `file_type`, for the completion popup
+    #[must_use]
+    pub fn triggers(&self, file_type: &str, prefix: &str) -> Vec<&str> {
+        let mut triggers: Vec<&str> = [file_type.to_lowercase().as_str(), ALL_FILE_TYPES]
+            .iter()
+            .filter_map(|file_type| self.file_types.get(*file_type))
+            .flatten()
+            .map(|snippet| snippet.trigger.as_str())
+            .filter(|trigger| trigger.starts_with(prefix))
+            .collect();
+
+        triggers.sort_unstable();
+        triggers.dedup();
+
+        triggers
+    }
+}
+
+impl Snippet {
+    // Fill in the defaults of the tab stops, continuing lines with `indent`
+    #[must_use]
+    pub fn expand(&self, indent: &str) -> Expansion {
+        let mut text = String::new();
+        let mut position = Position::default();
+        let mut stops: BTreeMap<usize, (Position, Position)> = BTreeMap::new();
+        let mut chars = self.body.chars().peekable();
+
+        let push = |text: &mut String, position: &mut Position, s: &str| {
+            for (index, line) in s.split('\n').enumerate() {
+                if index > 0 {
+                    text.push('\n');
+                    text.push_str(indent);
+                    *position = Position { x: indent.graphemes(true).count(), y: position.y + 1 };
+                }
+
+                text.push_str(line);
+                position.x += line.graphemes(true).count();
+            }
+        };
+
+        while let Some(c) = chars.next() {
+            let stop = match (c, chars.peek()) {
+                ('\\', Some('$')) => {
+                    chars.next();
+                    push(&mut text, &mut position, "$");
+                    continue;
+                },
+                ('$', Some('{')) => {
+                    chars.next();
+
+                    let inside: String = chars.by_ref().take_while(|c| *c != '}').collect();
+                    let (number, default) = inside.split_once(':').unwrap_or((&inside, ""));
+
+                    number.parse::<usize>().ok().map(|number| (number, default.to_owned()))
+                },
+                ('$', Some(next)) if next.is_ascii_digit() => {
+                    let mut number = String::new();
+
+                    while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                        number.push(digit);
+                    }
+
+                    number.parse::<usize>().ok().map(|number| (number, String::new()))
+                },
+                _ => {
+                    push(&mut text, &mut position, c.encode_utf8(&mut [0; 4]));
+                    continue;
+                },
+            };
+
+            let Some((number, default)) = stop else {
+                continue;
+            };
+
+            let start = position.clone();
+
+            push(&mut text, &mut position, &default);
+            stops.entry(number).or_insert_with(|| (start, position.clone()));
+        }
+
+        // `$0` is visited last, and without one the snippet ends at its end
+        let last = stops.remove(&0).unwrap_or_else(|| (position.clone(), position));
+        let mut stops: Vec<(Position, Position)> = stops.into_values().collect();
+
+        stops.push(last);
+
+        Expansion { text, stops }
+    }
+}
+
+#[allow(clippy::missing_const_for_fn)]
+impl TabStops {
+    pub fn set(&mut self, stops: Vec<(Position, Position)>) {
+        self.stops = stops.into();
+    }
+    pub fn next(&mut self) -> Option<(Position, Position)> {
+        self.stops.pop_front()
+    }
+    pub fn adjust_for_insert(&mut self, start: &Position, end: &Position) {
+        for (stop_start, stop_end) in &mut self.stops {
+            shift_for_insert(stop_start, start, end);
+            shift_for_insert(stop_end, start, end);
+        }
+    }
+    pub fn adjust_for_delete(&mut self, start: &Position, end: &Position) {
+        for (stop_start, stop_end) in &mut self.stops {
+            shift_for_delete(stop_start, start, end);
+            shift_for_delete(stop_end, start, end);
+        }
+    }
+}
+
+fn parse(contents: &str) -> Vec<Snippet> {
+    let mut snippets: Vec<Snippet> = Vec::new();
+
+    for line in contents.lines() {
+        if let Some(header) = line.strip_prefix("snippet ") {
+            // Anything after the trigger describes the snippet
+            let trigger = header.split_whitespace().next().unwrap_or_default();
+
+            snippets.push(Snippet { trigger: trigger.to_owned(), body: String::new() });
+        } else if let (Some(body), Some(snippet)) = (line.strip_prefix('\t'), snippets.last_mut()) {
+            if !snippet.body.is_empty() {
+                snippet.body.push('\n');
+            }
+
+            snippet.body.push_str(body);
+        }
+    }
+
+    snippets
+}
@@ -0,0 +1,211 @@
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+use unicode_segmentation::UnicodeSegmentation;
+use crate::completion::is_word_char;
+
+// Suggestions are words at most this many edits away
+const MAX_EDITS: usize = 2;
+const MAX_SUGGESTIONS: usize = 10;
+
+// Words known from a word list with one word per line, like `/usr/share/dict/words`, and the user's
+// own dictionary beside the config. Without a word list nothing is flagged
+#[derive(Default)]
+pub struct SpellChecker {
+    words: HashSet<String>,
+    user_dictionary: Option<PathBuf>,
+    // Words accepted until the editor closes
+    ignored: HashSet<String>,
+}
+
+// A word in a row, by the graphemes it covers
+#[derive(Clone, Debug)]
+pub struct Word {
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+#[allow(clippy::missing_const_for_fn)]
+impl SpellChecker {
+    /// # Errors
+    ///
+    /// Will return `Err` if the word list or user dictionary exists but can't be read
+    pub fn load(word_list: &Path, user_dictionary: Option<PathBuf>) -> Result<Self> {
+        let mut words = HashSet::new();
+
+        for path in std::iter::once(word_list).chain(user_dictionary.as_deref()) {
+            match fs::read_to_string(path) {
+                Ok(contents) => words.extend(contents.lines().map(str::trim).filter(|word| !word.is_empty()).map(str::to_owned)),
+                Err(error) if error.kind() == ErrorKind::NotFound => (),
+                Err(error) => return Err(eyre!("{}: {error}", path.display())),
+            }
+        }
+
+        tracing::info!(word_list = %word_list.display(), words = words.len(), "dictionary loaded");
+
+        Ok(Self { words, user_dictionary, ignored: HashSet::new() })
+    }
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+    // Capitalized words are also found in lowercase, for the first word of a sentence
+    #[must_use]
+    pub fn is_correct(&self, word: &str) -> bool {
+        self.words.is_empty()
+            || self.ignored.contains(word)
+            || self.words.contains(word)
+            || self.words.contains(&word.to_lowercase())
+    }
+    // The words of `text` that aren't known
+    #[must_use]
+    pub fn misspelled(&self, text: &str) -> Vec<Word> {
+        words(text).into_iter().filter(|word| !self.is_correct(&word.text)).collect()
+    }
+    // Known words closest to `word`, fewest edits first, capitalized like it
+    #[must_use]
+    pub fn suggestions(&self, word: &str) -> Vec<String> {
+        let lower = word.to_lowercase();
+        let length = lower.chars().count();
+        let mut scored: Vec<(usize, &String)> = self.words
+            .iter()
+            .filter(|known| known.chars().count().abs_diff(length) <= MAX_EDITS)
+            .filter_map(|known| Some((edit_distance(&lower, &known.to_lowercase()).filter(|edits| *edits <= MAX_EDITS)?, known)))
+            .collect();
+
+        scored.sort();
+
+        let capitalized = word.chars().next().is_some_and(char::is_uppercase);
+        let mut suggestions: Vec<String> = Vec::new();
+
+        for (_, known) in scored {
+            let suggestion = if capitalized { capitalize(known) } else { known.clone() };
+
+            if !suggestions.contains(&suggestion) {
+                suggestions.push(suggestion);
+            }
+
+            if suggestions.len() == MAX_SUGGESTIONS {
+                break;
+            }
+        }
+
+        suggestions
+    }
+    // Known words that start with `prefix` ignoring case, shortest first and keeping the case it was
+    // typed in, so choosing one only adds the rest of it
+    #[must_use]
+    pub fn completions(&self, prefix: &str, limit: usize) -> Vec<String> {
+        let lower = prefix.to_lowercase();
+        let typed = prefix.chars().count();
+        let mut found: Vec<&String> = self.words
+            .iter()
+            .filter(|known| known.chars().count() > typed && known.to_lowercase().starts_with(&lower))
+            .collect();
+
+        found.sort_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+
+        let mut completions: Vec<String> = Vec::new();
+
+        for known in found {
+            let completion = format!("{prefix}{}", known.chars().skip(typed).collect::<String>());
+
+            if !completions.contains(&completion) {
+                completions.push(completion);
+            }
+
+            if completions.len() == limit {
+                break;
+            }
+        }
+
+        completions
+    }
+    // Remember `word` in the user's dictionary
+    /// # Errors
+    ///
+    /// Will return `Err` if there's no config directory or the dictionary can't be written
+    pub fn add(&mut self, word: &str) -> Result<()> {
+        let path = self.user_dictionary.as_ref().ok_or_else(|| eyre!("there's no config directory for a dictionary"))?;
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        writeln!(OpenOptions::new().create(true).append(true).open(path)?, "{word}")?;
+
+        self.words.insert(word.to_owned());
+
+        Ok(())
+    }
+    pub fn ignore(&mut self, word: &str) {
+        self.ignored.insert(word.to_owned());
+    }
+}
+
+// Runs of letters, with apostrophes inside them like in "don't". Runs touching digits or
+// underscores are identifiers rather than words, and ones with capitals past their first letter
+// are names or acronyms, so neither are checked
+#[must_use]
+pub fn words(text: &str) -> Vec<Word> {
+    let graphemes: Vec<&str> = text.graphemes(true).collect();
+    let is_letter = |index: usize| graphemes.get(index).is_some_and(|grapheme| grapheme.chars().all(char::is_alphabetic));
+    let is_apostrophe = |index: usize| graphemes.get(index).is_some_and(|grapheme| matches!(*grapheme, "'" | "’"));
+    let mut words = Vec::new();
+    let mut index = 0;
+
+    while index < graphemes.len() {
+        if !graphemes[index].chars().all(is_word_char) {
+            index += 1;
+            continue;
+        }
+
+        let start = index;
+
+        while index < graphemes.len() && (graphemes[index].chars().all(is_word_char) || (is_apostrophe(index) && is_letter(index + 1))) {
+            index += 1;
+        }
+
+        let text: String = graphemes[start..index].concat();
+        let checked = (start..index).all(|index| is_letter(index) || is_apostrophe(index))
+            && !text.chars().skip(1).any(char::is_uppercase);
+
+        if checked {
+            words.push(Word { start, end: index, text });
+        }
+    }
+
+    words
+}
+
+// Levenshtein distance, None once it's certain to be over `MAX_EDITS`
+fn edit_distance(a: &str, b: &str) -> Option<usize> {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+
+        for (j, b) in b.iter().enumerate() {
+            current.push((previous[j] + usize::from(a != *b)).min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+
+        if current.iter().min().is_some_and(|edits| *edits > MAX_EDITS) {
+            return None;
+        }
+
+        previous = current;
+    }
+
+    previous.last().copied()
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+
+    chars.next().map_or_else(String::new, |first| first.to_uppercase().chain(chars).collect())
+}
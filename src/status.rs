@@ -0,0 +1,145 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+// Messages kept for the message history screen
+const HISTORY_LIMIT: usize = 100;
+// How long a message stays in the status bar once it's on screen
+const MESSAGE_TIMEOUT: Duration = Duration::from_secs(5);
+// How long a toast stays up, keys or not
+const TOAST_TIMEOUT: Duration = Duration::from_secs(3);
+// Most toasts shown at once, the oldest makes way for a new one
+const TOAST_LIMIT: usize = 3;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Severity {
+    Info,
+    Warn,
+    Error,
+}
+
+#[derive(Clone)]
+pub struct StatusMessage {
+    pub text: String,
+    pub severity: Severity,
+    pub time: Instant,
+    shown: bool,
+}
+
+// Messages waiting their turn in the status bar, plus the recent ones for review.
+// Each queued message stays up for at least one keypress instead of being overwritten
+pub struct StatusQueue {
+    queue: VecDeque<StatusMessage>,
+    history: VecDeque<StatusMessage>,
+    // When the message at the front of the queue reached the screen
+    front_since: Instant,
+    // Notifications popped up over the buffer for things that happened by themselves, like a build
+    // finishing. They don't wait for a keypress and are kept in the history with the rest
+    toasts: VecDeque<StatusMessage>,
+}
+
+#[allow(clippy::missing_const_for_fn)]
+impl StatusQueue {
+    pub fn info(&mut self, text: impl Into<String>) {
+        self.push(text.into(), Severity::Info);
+    }
+    pub fn warn(&mut self, text: impl Into<String>) {
+        self.push(text.into(), Severity::Warn);
+    }
+    pub fn error(&mut self, text: impl Into<String>) {
+        self.push(text.into(), Severity::Error);
+    }
+    pub fn toast(&mut self, text: impl Into<String>, severity: Severity) {
+        let text = text.into();
+
+        tracing::debug!(?severity, text, "toast");
+
+        let message = StatusMessage { text, severity, time: Instant::now(), shown: true };
+
+        if self.toasts.len() == TOAST_LIMIT {
+            self.toasts.pop_front();
+        }
+
+        self.toasts.push_back(message.clone());
+        self.remember(message);
+    }
+    pub fn push(&mut self, text: String, severity: Severity) {
+        tracing::debug!(?severity, text, "status");
+
+        let message = StatusMessage { text, severity, time: Instant::now(), shown: false };
+
+        // A message that already had its keypress on screen makes way for the new one
+        self.queue.retain(|message| !message.shown);
+
+        if self.queue.is_empty() {
+            self.front_since = Instant::now();
+        }
+
+        self.queue.push_back(message.clone());
+        self.remember(message);
+    }
+    fn remember(&mut self, message: StatusMessage) {
+        if self.history.len() == HISTORY_LIMIT {
+            self.history.pop_front();
+        }
+
+        self.history.push_back(message);
+    }
+    // Called once per keypress: drop the message that was just on screen if another is waiting
+    pub fn advance(&mut self) {
+        if self.queue.len() > 1 {
+            self.next();
+        } else if let Some(message) = self.queue.front_mut() {
+            message.shown = true;
+        }
+    }
+    // Drop the message on screen and the toasts once they've been there long enough, returns whether
+    // any were
+    pub fn expire(&mut self) -> bool {
+        let toasts_expired = self.expire_toasts();
+
+        if self.queue.is_empty() || self.front_since.elapsed() < MESSAGE_TIMEOUT {
+            return toasts_expired;
+        }
+
+        self.next();
+
+        true
+    }
+    fn next(&mut self) {
+        self.queue.pop_front();
+        self.front_since = Instant::now();
+    }
+    #[must_use]
+    pub fn current(&self) -> Option<&StatusMessage> {
+        self.queue.front()
+    }
+    // Toasts go away on their own even while a message waits on a key
+    pub fn expire_toasts(&mut self) -> bool {
+        let count = self.toasts.len();
+
+        self.toasts.retain(|toast| toast.time.elapsed() < TOAST_TIMEOUT);
+
+        self.toasts.len() != count
+    }
+    // Oldest first
+    #[must_use]
+    pub fn toasts(&self) -> &VecDeque<StatusMessage> {
+        &self.toasts
+    }
+    // Oldest first
+    #[must_use]
+    pub fn history(&self) -> &VecDeque<StatusMessage> {
+        &self.history
+    }
+}
+
+impl Default for StatusQueue {
+    fn default() -> Self {
+        Self {
+            queue: VecDeque::new(),
+            history: VecDeque::new(),
+            front_since: Instant::now(),
+            toasts: VecDeque::new(),
+        }
+    }
+}
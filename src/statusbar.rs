@@ -0,0 +1,120 @@
+use std::fs;
+use std::path::Path;
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+
+// Something the status bar can show, named in the config by the string `from_name` takes
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Segment {
+    // The editor's name and version
+    Title,
+    // Which screen or mode keys go to, like a search prompt or column mode
+    Mode,
+    FileName,
+    // Modified and read-only markers
+    Flags,
+    Lines,
+    // Line and column of the cursor, counted from 1
+    Position,
+    // Columns in view while rows are wider than it
+    Columns,
+    GitBranch,
+    // Errors and warnings from the last build
+    Diagnostics,
+    Clock,
+}
+
+// Segments in the order they're drawn in each part of the bar, the middle one taking the room the others
+// leave
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct StatusBarLayout {
+    pub left: Vec<Segment>,
+    pub center: Vec<Segment>,
+    pub right: Vec<Segment>,
+}
+
+impl Default for StatusBarLayout {
+    fn default() -> Self {
+        Self {
+            left: vec![Segment::Title],
+            center: vec![Segment::FileName],
+            right: vec![Segment::Flags, Segment::Columns, Segment::Lines],
+        }
+    }
+}
+
+impl Segment {
+    const NAMES: [(&'static str, Self); 10] = [
+        ("title", Self::Title),
+        ("mode", Self::Mode),
+        ("filename", Self::FileName),
+        ("flags", Self::Flags),
+        ("lines", Self::Lines),
+        ("position", Self::Position),
+        ("columns", Self::Columns),
+        ("branch", Self::GitBranch),
+        ("diagnostics", Self::Diagnostics),
+        ("clock", Self::Clock),
+    ];
+
+    /// # Errors
+    ///
+    /// Will return `Err` if `name` isn't a segment
+    pub fn from_name(name: &str) -> Result<Self> {
+        Self::NAMES
+            .iter()
+            .find(|(segment_name, _)| *segment_name == name)
+            .map(|(_, segment)| *segment)
+            .ok_or_else(|| {
+                let names: Vec<&str> = Self::NAMES.iter().map(|(name, _)| *name).collect();
+
+                eyre!("unknown status bar segment `{name}`, expected one of: {}", names.join(", "))
+            })
+    }
+}
+
+impl StatusBarLayout {
+    #[must_use]
+    pub fn contains(&self, segment: Segment) -> bool {
+        [&self.left, &self.center, &self.right].iter().any(|group| group.contains(&segment))
+    }
+}
+
+// The branch checked out in the repository `dir` is in, or the start of the commit when none is.
+// Read from `.git/HEAD` so nothing has to be run
+#[must_use]
+pub fn git_branch(dir: &Path) -> Option<String> {
+    let git = dir.ancestors().map(|dir| dir.join(".git")).find(|git| git.exists())?;
+
+    // Worktrees and submodules have a file pointing at the real git directory
+    let git = if git.is_file() {
+        let contents = fs::read_to_string(&git).ok()?;
+
+        git.parent()?.join(contents.strip_prefix("gitdir:")?.trim())
+    } else {
+        git
+    };
+
+    let head = fs::read_to_string(git.join("HEAD")).ok()?;
+    let head = head.trim();
+
+    head.strip_prefix("ref: refs/heads/")
+        .map(str::to_owned)
+        .or_else(|| head.get(..7).map(str::to_owned))
+}
+
+// The local time as `HH:MM`
+#[must_use]
+pub fn clock_text() -> String {
+    // SAFETY: `time` and `localtime_r` only write to the values passed to them
+    let local = unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut local: libc::tm = std::mem::zeroed();
+
+        let converted = !libc::localtime_r(&raw const now, &raw mut local).is_null();
+
+        converted.then_some(local)
+    };
+
+    local.map_or_else(String::new, |local| format!("{:02}:{:02}", local.tm_hour, local.tm_min))
+}
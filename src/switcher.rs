@@ -0,0 +1,41 @@
+use std::path::Path;
+use crate::document::Document;
+use crate::popup::fuzzy_score;
+
+// A buffer shown in the list, `buffer` being 0 for the one being edited and 1 onwards for the others
+// in the order switching forward reaches them
+pub struct BufferEntry {
+    pub buffer: usize,
+    pub name: String,
+    pub path: String,
+    pub dirty: bool,
+}
+
+impl BufferEntry {
+    #[must_use]
+    pub fn new(buffer: usize, document: &Document) -> Self {
+        let path = document.file_name.clone().unwrap_or_default();
+        let name = Path::new(&path)
+            .file_name()
+            .map_or_else(|| "New Buffer".to_owned(), |name| name.to_string_lossy().into_owned());
+
+        Self { buffer, name, path, dirty: document.is_dirty() }
+    }
+}
+
+// The entries matching `filter`, best first. Without a filter they're all kept in order
+#[must_use]
+pub fn filter_entries(entries: Vec<BufferEntry>, filter: &str) -> Vec<BufferEntry> {
+    let mut scored: Vec<(usize, BufferEntry)> = entries
+        .into_iter()
+        .filter_map(|entry| {
+            // Matching the name is better than only matching somewhere in the path
+            let score = fuzzy_score(filter, &entry.name).or_else(|| fuzzy_score(filter, &entry.path).map(|score| score + 1000))?;
+
+            Some((score, entry))
+        })
+        .collect();
+
+    scored.sort_by_key(|(score, _)| *score);
+    scored.into_iter().map(|(_, entry)| entry).collect()
+}
@@ -0,0 +1,134 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use color_eyre::eyre::{eyre, WrapErr};
+use color_eyre::Result;
+
+pub const TAGS_FILE: &str = "tags";
+
+// A definition from a ctags file, its path relative to the directory the file is in
+#[derive(Clone, Debug)]
+pub struct Tag {
+    pub name: String,
+    pub path: PathBuf,
+    pub address: TagAddress,
+    // The single letter or word ctags gives for what was defined, like `f` or `function`
+    pub kind: Option<String>,
+}
+
+// Where in its file a tag is, a line counted from 1 or the text of the line to search for
+#[derive(Clone, Debug)]
+pub enum TagAddress {
+    Line(usize),
+    Pattern(String),
+}
+
+impl Tag {
+    // The line of `contents` the tag points at, counted from 0
+    #[must_use]
+    pub fn line_in(&self, contents: &str) -> Option<usize> {
+        match &self.address {
+            TagAddress::Line(line) => line.checked_sub(1),
+            TagAddress::Pattern(pattern) => {
+                let anchored_start = pattern.starts_with('^');
+                let anchored_end = pattern.ends_with('$') && !pattern.ends_with("\\$");
+                let text = pattern.strip_prefix('^').unwrap_or(pattern);
+                let text = if anchored_end { &text[..text.len() - 1] } else { text };
+
+                contents.lines().position(|line| match (anchored_start, anchored_end) {
+                    (true, true) => line == text,
+                    (true, false) => line.starts_with(text),
+                    (false, true) => line.ends_with(text),
+                    (false, false) => line.contains(text),
+                })
+            },
+        }
+    }
+}
+
+// Every tag named `name` in the tags file at `path`, which is read again each time so it's never stale
+/// # Errors
+///
+/// Will return `Err` if the tags file can't be read
+pub fn find_tags(path: &Path, name: &str) -> Result<Vec<Tag>> {
+    let contents = fs::read_to_string(path).wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+    Ok(contents
+        .lines()
+        .filter(|line| line.split('\t').next() == Some(name))
+        .filter_map(|line| parse_line(line, dir))
+        .collect())
+}
+
+// Run `ctags -R` in `root`, writing its tags file
+/// # Errors
+///
+/// Will return `Err` if ctags isn't installed or fails
+pub fn generate_tags(root: &Path) -> Result<()> {
+    let output = Command::new("ctags")
+        .args(["-R", "-f", TAGS_FILE, "."])
+        .current_dir(root)
+        .output()
+        .wrap_err("Failed to run ctags, is it installed?")?;
+
+    if !output.status.success() {
+        let error = String::from_utf8_lossy(&output.stderr);
+
+        return Err(eyre!("ctags failed: {}", error.trim()));
+    }
+
+    Ok(())
+}
+
+// `name<Tab>file<Tab>address;"<Tab>fields`, where the address is a line number or `/pattern/`.
+// Lines starting `!_TAG_` describe the file rather than a tag and have no file after the name
+fn parse_line(line: &str, dir: &Path) -> Option<Tag> {
+    let mut fields = line.splitn(3, '\t');
+    let name = fields.next()?;
+    let file = fields.next()?;
+    let rest = fields.next()?;
+
+    if name.starts_with("!_TAG_") {
+        return None;
+    }
+
+    // The address ends at `;"` when extension fields follow it
+    let (address, extensions) = rest.split_once(";\"").unwrap_or((rest, ""));
+    let address = if let Ok(line) = address.trim().parse() {
+        TagAddress::Line(line)
+    } else {
+        let pattern = address.trim();
+        let delimiter = pattern.chars().next().filter(|c| matches!(c, '/' | '?'))?;
+        let body = &pattern[1..];
+        let pattern = body.strip_suffix(delimiter).unwrap_or(body);
+
+        TagAddress::Pattern(unescape_pattern(pattern, delimiter))
+    };
+
+    let kind = extensions
+        .split('\t')
+        .map(str::trim)
+        .find_map(|field| field.strip_prefix("kind:").map(str::to_owned).or_else(|| (field.len() == 1).then(|| field.to_owned())));
+
+    Some(Tag { name: name.to_owned(), path: dir.join(file), address, kind })
+}
+
+// ctags escapes the delimiter and backslashes in patterns
+fn unescape_pattern(pattern: &str, delimiter: char) -> String {
+    let mut text = String::new();
+    let mut chars = pattern.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(escaped) = chars.next_if(|next| *next == delimiter || *next == '\\') {
+                text.push(escaped);
+                continue;
+            }
+        }
+
+        text.push(c);
+    }
+
+    text
+}
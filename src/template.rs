@@ -0,0 +1,68 @@
+use std::env;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+
+// A new file named on the command line starts as the skeleton for its extension from the templates
+// directory, `skeleton.rs` for `.rs` files. Placeholders like `{{filename}}` are filled in
+/// # Errors
+///
+/// Will return `Err` if there's a template for the file but it can't be read
+pub fn load_template(dir: &Path, file_name: &str) -> Result<Option<String>> {
+    let Some(extension) = Path::new(file_name).extension() else {
+        return Ok(None);
+    };
+
+    let path = dir.join(format!("skeleton.{}", extension.to_string_lossy()));
+
+    match std::fs::read_to_string(&path) {
+        Ok(template) => Ok(Some(template)),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(eyre!("{}: {error}", path.display())),
+    }
+}
+
+// The values placeholders are replaced with
+pub struct Placeholders {
+    pub file_name: String,
+    pub date: String,
+    pub year: String,
+    pub author: String,
+}
+
+impl Placeholders {
+    // `{{filename}}` is the name without its directory and `{{name}}` without its extension too.
+    // Unknown placeholders are left as they are
+    #[must_use]
+    pub fn fill(&self, template: &str) -> String {
+        let path = Path::new(&self.file_name);
+        let base_name = path.file_name().map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+        let stem = path.file_stem().map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+
+        [("filename", base_name.as_str()), ("name", &stem), ("date", &self.date), ("year", &self.year), ("author", &self.author)]
+            .into_iter()
+            .fold(template.to_owned(), |text, (key, value)| text.replace(&format!("{{{{{key}}}}}"), value))
+    }
+}
+
+// The config's author, else the name git commits with, else the login name
+#[must_use]
+pub fn author(configured: Option<&str>) -> String {
+    if let Some(author) = configured {
+        return author.to_owned();
+    }
+
+    let git_name = Command::new("git")
+        .args(["config", "user.name"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_owned())
+        .filter(|name| !name.is_empty());
+
+    git_name.or_else(|| env::var("USER").ok()).unwrap_or_default()
+}
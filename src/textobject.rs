@@ -0,0 +1,157 @@
+use unicode_segmentation::UnicodeSegmentation;
+use crate::app::Position;
+use crate::document::Document;
+
+// Structured ranges of text around the cursor, like vim's `iw`, `a"` and `i(`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TextObject {
+    Word,
+    Quote(char),
+    Brackets(char, char),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Class {
+    Word,
+    Space,
+    Punctuation,
+}
+
+#[allow(clippy::missing_const_for_fn)]
+impl TextObject {
+    // The object named by the key typed after `i` or `a`, either bracket names its pair
+    #[must_use]
+    pub fn from_char(c: char) -> Option<Self> {
+        match c {
+            'w' => Some(Self::Word),
+            '"' | '\'' | '`' => Some(Self::Quote(c)),
+            '(' | ')' | 'b' => Some(Self::Brackets('(', ')')),
+            '[' | ']' => Some(Self::Brackets('[', ']')),
+            '{' | '}' | 'B' => Some(Self::Brackets('{', '}')),
+            '<' | '>' => Some(Self::Brackets('<', '>')),
+            _ => None,
+        }
+    }
+    // Start and end of the object at `at`. The inner object leaves out the quotes or brackets, or the
+    // whitespace after a word, which the outer one includes
+    #[must_use]
+    pub fn find(self, document: &Document, at: &Position, inner: bool) -> Option<(Position, Position)> {
+        match self {
+            Self::Word => find_word(document, at, inner),
+            Self::Quote(quote) => find_quotes(document, at, quote, inner),
+            Self::Brackets(open, close) => find_brackets(document, at, open, close, inner),
+        }
+    }
+}
+
+fn graphemes(document: &Document, y: usize) -> Vec<&str> {
+    document.row(y).map_or_else(Vec::new, |row| row.as_str().graphemes(true).collect())
+}
+
+fn class(grapheme: &str) -> Class {
+    if grapheme.chars().all(char::is_whitespace) {
+        Class::Space
+    } else if grapheme.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        Class::Word
+    } else {
+        Class::Punctuation
+    }
+}
+
+// The run of graphemes of one class under the cursor. The outer word takes the whitespace after it,
+// or before it when there is none after, and outer whitespace takes the word after it
+fn find_word(document: &Document, at: &Position, inner: bool) -> Option<(Position, Position)> {
+    let row = graphemes(document, at.y);
+    let x = at.x.min(row.len().checked_sub(1)?);
+    let run_class = class(row[x]);
+    let run_end = |from: usize, run_class: Class| from + row[from..].iter().take_while(|grapheme| class(grapheme) == run_class).count();
+
+    let mut start = x - row[..x].iter().rev().take_while(|grapheme| class(grapheme) == run_class).count();
+    let mut end = run_end(x, run_class);
+
+    if !inner {
+        if run_class == Class::Space {
+            end = row.get(end).map_or(end, |grapheme| run_end(end, class(grapheme)));
+        } else if row.get(end).is_some_and(|grapheme| class(grapheme) == Class::Space) {
+            end = run_end(end, Class::Space);
+        } else {
+            start -= row[..start].iter().rev().take_while(|grapheme| class(grapheme) == Class::Space).count();
+        }
+    }
+
+    Some((Position { x: start, y: at.y }, Position { x: end, y: at.y }))
+}
+
+// Quotes pair up from the start of the row, escaped ones are skipped. The pair around the cursor
+// is used, or the next one after it
+fn find_quotes(document: &Document, at: &Position, quote: char, inner: bool) -> Option<(Position, Position)> {
+    let row = graphemes(document, at.y);
+    let quote = quote.to_string();
+    let quotes: Vec<usize> = (0..row.len())
+        .filter(|&x| row[x] == quote && (x == 0 || row[x - 1] != "\\"))
+        .collect();
+
+    let (start, end) = quotes
+        .chunks_exact(2)
+        .map(|pair| (pair[0], pair[1]))
+        .find(|&(_, end)| at.x <= end)?;
+
+    let (start, end) = if inner { (start + 1, end) } else { (start, end + 1) };
+
+    Some((Position { x: start, y: at.y }, Position { x: end, y: at.y }))
+}
+
+// The innermost bracket pair around the cursor, which may span rows
+fn find_brackets(document: &Document, at: &Position, open: char, close: char, inner: bool) -> Option<(Position, Position)> {
+    let (open, close) = (open.to_string(), close.to_string());
+    let mut depth = 0usize;
+    let mut start = None;
+
+    // Back to the unmatched opening bracket, starting on the cursor so a bracket under it counts
+    'rows: for y in (0..=at.y).rev() {
+        let row = graphemes(document, y);
+        let last = if y == at.y { at.x.min(row.len().saturating_sub(1)) } else { row.len().saturating_sub(1) };
+
+        for x in (0..row.len()).rev().filter(|&x| x <= last) {
+            if row[x] == close && !(y == at.y && x == at.x) {
+                depth += 1;
+            } else if row[x] == open {
+                if depth == 0 {
+                    start = Some(Position { x, y });
+                    break 'rows;
+                }
+
+                depth -= 1;
+            }
+        }
+    }
+
+    let start = start?;
+    let mut depth = 0usize;
+
+    for y in start.y..document.len() {
+        let row = graphemes(document, y);
+        let first = if y == start.y { start.x + 1 } else { 0 };
+
+        for (x, grapheme) in row.iter().enumerate().skip(first) {
+            if *grapheme == open {
+                depth += 1;
+            } else if *grapheme == close {
+                if depth > 0 {
+                    depth -= 1;
+                    continue;
+                }
+
+                let end = Position { x, y };
+
+                return Some(if inner {
+                    (Position { x: start.x + 1, y: start.y }, end)
+                } else {
+                    (start, Position { x: x + 1, y })
+                });
+            }
+        }
+    }
+
+    None
+}
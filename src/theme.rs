@@ -0,0 +1,223 @@
+use std::env;
+use std::fs::OpenOptions;
+use std::io::{Read, Write};
+use std::os::fd::AsRawFd;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+use ratatui::style::{Color, Modifier, Style};
+use crate::status::Severity;
+
+// How long the terminal gets to say what its background color is
+const BACKGROUND_QUERY_TIMEOUT: Duration = Duration::from_millis(100);
+
+// Colors of everything around the text, set in the config under `[colors]`
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Theme {
+    pub header_fg: Color,
+    pub header_bg: Color,
+    // The bottom line while it's a prompt
+    pub status_fg: Color,
+    pub status_bg: Color,
+    // Status messages are drawn on the color of their severity
+    pub message_fg: Color,
+    pub message_info: Color,
+    pub message_warning: Color,
+    pub message_error: Color,
+    // Left unset, selected text swaps its own colors
+    pub selection_fg: Option<Color>,
+    pub selection_bg: Option<Color>,
+    // Mark names in the gutter
+    pub gutter_fg: Color,
+    pub indent_guide_fg: Color,
+    // Columns marking line length limits, like the ones of commit messages
+    pub color_column_bg: Color,
+    // Modals, pickers and other boxes drawn over the buffer
+    pub popup_fg: Color,
+    pub popup_bg: Color,
+}
+
+// Whether the terminal's background is dark or light, which decides the theme
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Background {
+    Dark,
+    Light,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            header_fg: Color::Black,
+            header_bg: Color::White,
+            status_fg: Color::Black,
+            status_bg: Color::White,
+            message_fg: Color::Black,
+            message_info: Color::Cyan,
+            message_warning: Color::Yellow,
+            message_error: Color::Red,
+            selection_fg: None,
+            selection_bg: None,
+            gutter_fg: Color::Yellow,
+            indent_guide_fg: Color::DarkGray,
+            color_column_bg: Color::Rgb(48, 48, 48),
+            popup_fg: Color::Black,
+            popup_bg: Color::White,
+        }
+    }
+}
+
+impl Theme {
+    #[must_use]
+    pub fn for_background(background: Background) -> Self {
+        match background {
+            Background::Dark => Self::default(),
+            Background::Light => Self {
+                header_fg: Color::White,
+                header_bg: Color::Blue,
+                status_fg: Color::Black,
+                status_bg: Color::Gray,
+                gutter_fg: Color::Rgb(175, 95, 0),
+                indent_guide_fg: Color::Gray,
+                color_column_bg: Color::Rgb(230, 230, 230),
+                popup_fg: Color::Black,
+                popup_bg: Color::Gray,
+                ..Self::default()
+            },
+        }
+    }
+    // Set the color called `name`, like `header_bg`, to a color name, `#rrggbb` or a palette index
+    /// # Errors
+    ///
+    /// Will return `Err` if `name` isn't one of the theme's colors or `color` isn't a color
+    pub fn set(&mut self, name: &str, color: &str) -> Result<()> {
+        let color = Color::from_str(color).map_err(|_| eyre!("`{color}` isn't a color"))?;
+
+        match name {
+            "header_fg" => self.header_fg = color,
+            "header_bg" => self.header_bg = color,
+            "status_fg" => self.status_fg = color,
+            "status_bg" => self.status_bg = color,
+            "message_fg" => self.message_fg = color,
+            "message_info" => self.message_info = color,
+            "message_warning" => self.message_warning = color,
+            "message_error" => self.message_error = color,
+            "selection_fg" => self.selection_fg = Some(color),
+            "selection_bg" => self.selection_bg = Some(color),
+            "gutter_fg" => self.gutter_fg = color,
+            "indent_guide_fg" => self.indent_guide_fg = color,
+            "color_column_bg" => self.color_column_bg = color,
+            "popup_fg" => self.popup_fg = color,
+            "popup_bg" => self.popup_bg = color,
+            _ => return Err(eyre!("unknown color `{name}`")),
+        }
+
+        Ok(())
+    }
+    #[must_use]
+    pub fn header_style(&self) -> Style {
+        Style::default().fg(self.header_fg).bg(self.header_bg)
+    }
+    #[must_use]
+    pub fn status_style(&self) -> Style {
+        Style::default().fg(self.status_fg).bg(self.status_bg)
+    }
+    #[must_use]
+    pub const fn message_color(&self, severity: Severity) -> Color {
+        match severity {
+            Severity::Info => self.message_info,
+            Severity::Warn => self.message_warning,
+            Severity::Error => self.message_error,
+        }
+    }
+    #[must_use]
+    pub fn selection_style(&self) -> Style {
+        match (self.selection_fg, self.selection_bg) {
+            (None, None) => Style::default().add_modifier(Modifier::REVERSED),
+            (fg, bg) => Style { fg, bg, ..Style::default() },
+        }
+    }
+    #[must_use]
+    pub fn popup_style(&self) -> Style {
+        Style::default().fg(self.popup_fg).bg(self.popup_bg)
+    }
+}
+
+impl Background {
+    #[must_use]
+    pub const fn toggled(self) -> Self {
+        match self {
+            Self::Dark => Self::Light,
+            Self::Light => Self::Dark,
+        }
+    }
+}
+
+// `COLORFGBG` when the terminal sets it, otherwise what the terminal answers when asked for its
+// background color. None when neither says
+#[must_use]
+pub fn detect_background() -> Option<Background> {
+    env::var("COLORFGBG")
+        .ok()
+        .and_then(|colors| background_from_colorfgbg(&colors))
+        .or_else(query_background)
+}
+
+// `fg;bg` or `fg;default;bg` with palette indexes, where 7 and 9 to 15 are light
+fn background_from_colorfgbg(colors: &str) -> Option<Background> {
+    let background: u8 = colors.rsplit(';').next()?.parse().ok()?;
+
+    Some(if matches!(background, 7 | 9..=15) { Background::Light } else { Background::Dark })
+}
+
+// Ask the terminal with OSC 11, which it answers like `ESC ] 11 ; rgb:ffff/ffff/ffff ESC \`. Terminals
+// that don't support it say nothing, so the answer is only waited for briefly
+fn query_background() -> Option<Background> {
+    let mut tty = OpenOptions::new().read(true).write(true).open("/dev/tty").ok()?;
+
+    tty.write_all(b"\x1b]11;?\x1b\\").ok()?;
+    tty.flush().ok()?;
+
+    let started = Instant::now();
+    let mut answer = Vec::new();
+
+    while !answer.ends_with(b"\x07") && !answer.ends_with(b"\x1b\\") {
+        let remaining = BACKGROUND_QUERY_TIMEOUT.checked_sub(started.elapsed())?;
+        let mut poll = libc::pollfd { fd: tty.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+        let timeout = i32::try_from(remaining.as_millis()).unwrap_or(i32::MAX);
+
+        // SAFETY: `poll` only writes to the one `pollfd` it's given
+        if unsafe { libc::poll(&raw mut poll, 1, timeout) } <= 0 {
+            return None;
+        }
+
+        let mut buffer = [0; 64];
+        let read = tty.read(&mut buffer).ok()?;
+
+        answer.extend_from_slice(&buffer[..read]);
+    }
+
+    background_from_answer(&String::from_utf8_lossy(&answer))
+}
+
+// Light when the color's luminance is over half, each channel being 1 to 4 hex digits
+fn background_from_answer(answer: &str) -> Option<Background> {
+    let rgb = answer.split_once("rgb:")?.1.trim_end_matches(['\x07', '\x1b', '\\']);
+    let channels: Vec<f64> = rgb
+        .split('/')
+        .map(|channel| {
+            let value = u32::from_str_radix(channel, 16).ok()?;
+            let max = 16_u32.checked_pow(u32::try_from(channel.len()).ok()?)? - 1;
+
+            Some(f64::from(value) / f64::from(max))
+        })
+        .collect::<Option<_>>()?;
+
+    let [r, g, b] = channels[..] else {
+        return None;
+    };
+
+    let luminance = 0.114_f64.mul_add(b, 0.299_f64.mul_add(r, 0.587 * g));
+
+    Some(if luminance > 0.5 { Background::Light } else { Background::Dark })
+}
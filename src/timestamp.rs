@@ -0,0 +1,50 @@
+use std::ffi::CString;
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+
+// Longest stamp a format can make, strftime gives up on longer ones
+const MAX_LENGTH: usize = 256;
+
+// Named formats offered when the config doesn't change them
+pub const DEFAULT_FORMATS: &[(&str, &str)] = &[
+    ("date", "%Y-%m-%d"),
+    ("datetime", "%Y-%m-%d %H:%M"),
+    ("iso8601", "%Y-%m-%dT%H:%M:%S%z"),
+    ("changelog", "%a %b %d %Y"),
+];
+
+// The local time now written with a strftime format like `%Y-%m-%d %H:%M`
+/// # Errors
+///
+/// Will return `Err` if the format has a NUL, the local time can't be found, or the stamp is too long
+pub fn format_now(format: &str) -> Result<String> {
+    if format.is_empty() {
+        return Ok(String::new());
+    }
+
+    let format = CString::new(format).map_err(|_| eyre!("the format has a NUL character"))?;
+    let mut buffer = vec![0u8; MAX_LENGTH];
+
+    // SAFETY: `time` and `localtime_r` only write to the values passed to them, and `strftime` writes
+    // at most `buffer.len()` bytes into it
+    let written = unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut local: libc::tm = std::mem::zeroed();
+
+        if libc::localtime_r(&raw const now, &raw mut local).is_null() {
+            return Err(eyre!("the local time isn't known"));
+        }
+
+        libc::strftime(buffer.as_mut_ptr().cast(), buffer.len(), format.as_ptr(), &raw const local)
+    };
+
+    // Formats like `%p` can be empty in some locales, but a non-empty format making nothing is
+    // almost always one that didn't fit
+    if written == 0 {
+        return Err(eyre!("the timestamp is longer than {MAX_LENGTH} bytes"));
+    }
+
+    buffer.truncate(written);
+
+    Ok(String::from_utf8_lossy(&buffer).into_owned())
+}
@@ -1,14 +1,57 @@
+use std::ops::Range;
+use std::time::Duration;
 use ratatui::Frame;
+use unicode_segmentation::UnicodeSegmentation;
 use ratatui::layout::{Constraint, Direction, Layout, Rect};
-use ratatui::style::{Color, Style};
+use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Span, Text};
-use ratatui::widgets::{Block, List, ListItem, Paragraph};
-use crate::app::{App, CurrentScreen};
+use ratatui::widgets::{Block, Borders, Clear, List, ListItem, ListState, Padding, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState, Wrap};
+use crate::app::{App, CurrentScreen, Position};
+use crate::completion::Completion;
+use crate::conflicts::ConflictPart;
+use crate::csv::ColumnLayout;
+use crate::diff::{inline_changes, DiffLine};
+use crate::encoding::Encoding;
+use crate::filetype::{BODY_WIDTH, SUMMARY_WIDTH};
+use crate::git::{GitStatus, HunkKind};
+use crate::history::Change;
+use crate::indent::{indent_levels, leading_whitespace};
+use crate::quickfix::ItemKind;
+use crate::palette::{color_support, fit, ColorSupport};
+use crate::project::{preview_rows, PreviewRow};
+use crate::row::{shown_grapheme, Row};
+use crate::status::Severity;
+use crate::statusbar::Segment;
+use crate::tags::TagAddress;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
+const SAVE_PROMPT: &str = "File Name to Write: ";
+const GOTO_PROMPT: &str = "Enter line number, column number: ";
+const EXPORT_HTML_PROMPT: &str = "Export HTML to: ";
+const RENAME_PROMPT: &str = "Rename File to: ";
+const ENCODING_PROMPT: &str = "Save with encoding (Tab for the others): ";
+const UNICODE_PROMPT: &str = "Insert Unicode (U+hex or name): ";
+const SEARCH_PROMPT: &str = "Search";
+const SEARCH_IN_FILES_PROMPT: &str = "Search in files";
+const REPLACE_PROMPT: &str = "Search (to replace)";
+const REPLACE_IN_FILES_PROMPT: &str = "Search in files (to replace)";
+const REPLACE_WITH_PROMPT: &str = "Replace with: ";
+const SIDEBAR_WIDTH: u16 = 30;
+// Most rows the quickfix pane takes, borders included
+const QUICKFIX_HEIGHT: u16 = 10;
+const COLUMN_COLORS: [Color; 5] = [Color::Cyan, Color::Green, Color::Yellow, Color::Magenta, Color::Blue];
 
 pub fn ui(f: &mut Frame, app: &mut App) {
     ui_main(f, app);
+
+    // Colors are picked in RGB and brought down to what the terminal can show once everything's drawn
+    if color_support() < ColorSupport::TrueColor {
+        for cell in &mut f.buffer_mut().content {
+            cell.fg = fit(cell.fg);
+            cell.bg = fit(cell.bg);
+            cell.underline_color = fit(cell.underline_color);
+        }
+    }
 }
 
 fn ui_main(f: &mut Frame, app: &mut App) {
@@ -19,52 +62,302 @@ fn ui_main(f: &mut Frame, app: &mut App) {
         Constraint::Length(2), // Controls
     ]).split(f.size());
 
+    let sidebar_width = if app.file_tree().is_some() { SIDEBAR_WIDTH.min(chunks[1].width / 2) } else { 0 };
+
+    let editor_chunks = Layout::new(Direction::Horizontal, [
+        Constraint::Length(sidebar_width), // File tree
+        Constraint::Min(1), // Buffer
+    ]).split(chunks[1]);
+
+    let quickfix_height = app.quickfix().map_or(0, |list| {
+        u16::try_from(list.len()).unwrap_or(u16::MAX).saturating_add(2).min(QUICKFIX_HEIGHT).min(editor_chunks[1].height / 2)
+    });
+
+    let staged_diff_height = u16::try_from(app.staged_diff().len()).unwrap_or(u16::MAX)
+        .saturating_add(1)
+        .min(editor_chunks[1].height.saturating_sub(quickfix_height) / 2);
+    let staged_diff_height = if app.staged_diff().is_empty() { 0 } else { staged_diff_height };
+
+    let buffer_chunks = Layout::new(Direction::Vertical, [
+        Constraint::Min(1), // Buffer
+        Constraint::Length(staged_diff_height), // Staged changes below a commit message
+        Constraint::Length(quickfix_height), // Quickfix
+    ]).split(editor_chunks[1]);
+
+    let editor = buffer_chunks[0];
+    let gutter_width = if app.show_gutter() { 2 } else { 0 };
+
+    let document_chunks = Layout::new(Direction::Horizontal, [
+        Constraint::Length(gutter_width), // Gutter
+        Constraint::Min(1), // Document rows
+        Constraint::Length(1), // Scrollbar
+    ]).split(editor);
+
     draw_header_bar(f, app, chunks[0]);
 
-    draw_document_rows(f, app, chunks[1]);
-    draw_cursor(f, app);
+    draw_file_tree(f, app, editor_chunks[0]);
+    draw_staged_diff(f, app, buffer_chunks[1]);
+    draw_quickfix(f, app, buffer_chunks[2]);
+    draw_gutter(f, app, document_chunks[0]);
+    draw_document_rows(f, app, document_chunks[1]);
+    draw_scrollbar(f, app, document_chunks[2]);
+
+    if matches!(app.current_screen, CurrentScreen::Main | CurrentScreen::Completion) {
+        draw_cursor(f, app);
+    }
 
     draw_status(f, app, chunks[2]);
     draw_controls(f, app, chunks[3]);
+
+    match app.current_screen {
+        CurrentScreen::ConfirmQuit => draw_quit_modal(f, app, editor),
+        CurrentScreen::PasteHistory => draw_paste_history(f, app, editor),
+        CurrentScreen::Diff => draw_diff(f, app, editor),
+        CurrentScreen::Messages => draw_messages(f, app, editor),
+        CurrentScreen::UndoTree => draw_undo_tree(f, app, editor),
+        CurrentScreen::Completion => draw_completion(f, app, editor),
+        CurrentScreen::FileChanges => draw_file_changes(f, app, editor),
+        CurrentScreen::BufferList => draw_buffer_list(f, app, editor),
+        CurrentScreen::TagPicker => draw_tag_picker(f, app, editor),
+        CurrentScreen::ArchiveList => draw_archive_list(f, app, editor),
+        CurrentScreen::SideBySide => draw_side_by_side(f, app, chunks[1]),
+        CurrentScreen::SpellSuggestions => draw_spell_suggestions(f, app, editor),
+        CurrentScreen::TimestampPicker => draw_timestamp_picker(f, app, editor),
+        _ => ()
+    }
+
+    draw_toasts(f, app, editor);
+    draw_chord_hints(f, app, editor);
+    draw_tooltip(f, app);
 }
 
+// The bar is drawn from the configured segments. The left and right groups take the room they need and
+// the middle one is centered in what's left, keeping the end of it when it doesn't fit
 fn draw_header_bar(f: &mut Frame, app: &App, chunk: Rect) {
-    let title_text = format!("  IronN {VERSION}");
+    let layout = app.status_bar_layout();
+    let left = segments_text(app, &layout.left).map_or_else(String::new, |text| format!("  {text}"));
+    let right = segments_text(app, &layout.right).map_or_else(String::new, |text| format!("{text}  "));
+    let center = segments_text(app, &layout.center).unwrap_or_default();
 
     let title_chunks = Layout::new(Direction::Horizontal, [
-        Constraint::Length(title_text.len() as u16),
+        Constraint::Length(u16::try_from(left.chars().count()).unwrap_or(u16::MAX)),
         Constraint::Min(1),
+        Constraint::Length(u16::try_from(right.chars().count()).unwrap_or(u16::MAX)),
     ]).split(chunk);
 
-    let title_block_style = Style::default()
-        .fg(Color::Black)
-        .bg(Color::White);
+    let title_block_style = app.theme().header_style();
 
-    let title = Paragraph::new(Text::styled(
-        title_text,
-        Style::default()
-    )).block(Block::default().style(title_block_style));
+    let texts = [left, centered_text(&center, title_chunks[1]), right];
 
-    let title_filename = Paragraph::new(Text::styled(
-        file_text(app, &title_chunks),
-        Style::default()
-    )).block(Block::default().style(title_block_style));
+    for (text, area) in texts.into_iter().zip(title_chunks.iter()) {
+        let paragraph = Paragraph::new(Text::styled(text, Style::default())).block(Block::default().style(title_block_style));
+
+        f.render_widget(paragraph, *area);
+    }
+}
 
-    f.render_widget(title, title_chunks[0]);
-    f.render_widget(title_filename, title_chunks[1]);
+// The texts of a group's segments that have something to show, two spaces apart
+fn segments_text(app: &App, segments: &[Segment]) -> Option<String> {
+    let texts: Vec<String> = segments.iter().filter_map(|segment| segment_text(app, *segment)).collect();
+
+    (!texts.is_empty()).then(|| texts.join("  "))
+}
+
+fn segment_text(app: &App, segment: Segment) -> Option<String> {
+    let document = app.document();
+
+    let text = match segment {
+        Segment::Title => format!("IronN {VERSION}"),
+        Segment::Mode => mode_text(app).to_owned(),
+        Segment::FileName => document.file_name.clone().unwrap_or_else(|| "New Buffer".to_owned()),
+        Segment::Flags => {
+            let encoding = document.encoding();
+            let flags: Vec<&str> = [(encoding != Encoding::Utf8, encoding.name()), (document.is_read_only(), "Read-only"), (document.is_dirty(), "Modified")]
+                .into_iter()
+                .filter_map(|(set, flag)| set.then_some(flag))
+                .collect();
+
+            flags.join("  ")
+        },
+        Segment::Lines => format!("{} lines", document.len()),
+        Segment::Position => format!("Ln {}, Col {}", app.cursor_position().y + 1, app.cursor_column() + 1),
+        Segment::Columns => {
+            let (first, last, widest) = app.shown_columns()?;
+
+            format!("Cols {first}-{last} of {widest}")
+        },
+        Segment::GitBranch => app.git_branch()?.to_owned(),
+        Segment::Diagnostics => {
+            let (errors, warnings) = app.diagnostics();
+
+            if errors + warnings == 0 {
+                return None;
+            }
+
+            format!("E:{errors} W:{warnings}")
+        },
+        Segment::Clock => app.clock().to_owned(),
+    };
+
+    (!text.is_empty()).then_some(text)
+}
+
+// Where keys are going, empty while editing normally
+fn mode_text(app: &App) -> &'static str {
+    match app.current_screen {
+        CurrentScreen::Main if app.pager() => "PAGER",
+        CurrentScreen::Main if app.column_layout().is_some() => "COLUMN",
+        CurrentScreen::Main if app.selection().is_some() => "SELECT",
+        CurrentScreen::Main if app.overwrite() => "OVERWRITE",
+        CurrentScreen::Main | CurrentScreen::Completion => "",
+        CurrentScreen::Saving | CurrentScreen::SaveEncoding | CurrentScreen::ExportHtml => "WRITE",
+        CurrentScreen::RenameFile => "RENAME",
+        CurrentScreen::InsertUnicode => "UNICODE",
+        CurrentScreen::GotoLine => "GOTO",
+        CurrentScreen::Search | CurrentScreen::Replace | CurrentScreen::ReplaceWith => "SEARCH",
+        CurrentScreen::FileChanges => "REPLACE",
+        CurrentScreen::FileTree => "FILES",
+        CurrentScreen::BufferList => "BUFFERS",
+        CurrentScreen::Quickfix => "QUICKFIX",
+        CurrentScreen::TagPicker => "TAGS",
+        CurrentScreen::ArchiveList => "ARCHIVE",
+        CurrentScreen::SideBySide => "DIFF",
+        CurrentScreen::SpellSuggestions => "SPELL",
+        CurrentScreen::TimestampPicker => "TIMESTAMP",
+        CurrentScreen::ConfirmQuit | CurrentScreen::PasteHistory | CurrentScreen::Diff | CurrentScreen::Messages | CurrentScreen::UndoTree => "VIEW",
+    }
+}
+
+fn draw_file_tree(f: &mut Frame, app: &mut App, chunk: Rect) {
+    if chunk.width == 0 {
+        app.update_sidebar_bounds(Rect::default());
+        return;
+    }
+
+    let focused = matches!(app.current_screen, CurrentScreen::FileTree);
+    let Some(tree) = app.file_tree() else {
+        return;
+    };
+
+    let root = tree.root().file_name().map_or_else(|| tree.root().display().to_string(), |name| name.to_string_lossy().into_owned());
+    let block = Block::default()
+        .title(format!(" {root} "))
+        .borders(Borders::RIGHT);
+    let inner = block.inner(chunk);
+
+    app.update_sidebar_bounds(inner);
+
+    let Some(tree) = app.file_tree() else {
+        return;
+    };
+
+    let entries: Vec<ListItem> = tree
+        .entries()
+        .iter()
+        .map(|entry| {
+            let indent = "  ".repeat(entry.depth);
+
+            if entry.is_dir {
+                let marker = if tree.is_expanded(&entry.path) { '▾' } else { '▸' };
+
+                ListItem::new(Line::styled(format!("{indent}{marker} {}/", entry.name), Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD)))
+            } else {
+                ListItem::new(Line::raw(format!("{indent}  {}", entry.name)))
+            }
+        })
+        .collect();
+
+    // The selection is only shown in full while keys go to the tree
+    let highlight = if focused { Style::default().add_modifier(Modifier::REVERSED) } else { Style::default().add_modifier(Modifier::UNDERLINED) };
+    let list = List::new(entries).highlight_style(highlight);
+    let mut state = ListState::default().with_offset(tree.offset()).with_selected(Some(tree.selected()));
+
+    f.render_widget(block, chunk);
+    f.render_stateful_widget(list, inner, &mut state);
+}
+
+// A dot in the color of the worst diagnostic on a line takes the place of a mark's name
+fn draw_gutter(f: &mut Frame, app: &mut App, chunk: Rect) {
+    app.update_gutter_bounds(chunk);
+
+    if chunk.width == 0 {
+        return;
+    }
+
+    let diagnostics = app.buffer_diagnostics();
+    let lines: Vec<Line> = app
+        .visible_rows(chunk.height as usize)
+        .into_iter()
+        .map(|y| {
+            let worst = diagnostics
+                .iter()
+                .filter(|diagnostic| diagnostic.y == y)
+                .map(|diagnostic| diagnostic.item.kind)
+                .min_by_key(|kind| match kind {
+                    ItemKind::Error => 0,
+                    ItemKind::Warning => 1,
+                    ItemKind::Note | ItemKind::Match => 2,
+                });
+
+            if let Some(kind) = worst {
+                return Line::styled("●", Style::default().fg(item_label(kind).1));
+            }
+
+            if app.document().folds().hidden_after(y).is_some() {
+                return Line::styled("▸", Style::default().fg(app.theme().gutter_fg));
+            }
+
+            if let Some(name) = app.document().marks().name_at_line(y) {
+                return Line::styled(name.to_string(), Style::default().fg(app.theme().gutter_fg));
+            }
+
+            app.git_status().map_or_else(Line::default, |status| git_marker(status, y))
+        })
+        .collect();
+
+    f.render_widget(Paragraph::new(lines), chunk);
+}
+
+// Unstaged changes in the colors of a diff, and rows whose change is staged dimmed
+fn git_marker(status: &GitStatus, y: usize) -> Line<'static> {
+    match status.hunks.iter().find(|hunk| hunk.contains(y)).map(|hunk| (hunk.kind(), hunk.start)) {
+        Some((HunkKind::Added, _)) => Line::styled("▎", Style::default().fg(Color::Green)),
+        Some((HunkKind::Modified, _)) => Line::styled("▎", Style::default().fg(Color::Yellow)),
+        // Drawn under the row before the removed lines, or over the row after them at the top
+        Some((HunkKind::Removed, start)) if y + 1 == start => Line::styled("▁", Style::default().fg(Color::Red)),
+        Some((HunkKind::Removed, 0)) => Line::styled("▔", Style::default().fg(Color::Red)),
+        _ if status.is_staged(y) => Line::styled("▎", Style::default().fg(Color::DarkGray)),
+        _ => Line::default(),
+    }
 }
 
 fn draw_document_rows(f: &mut Frame, app: &mut App, chunk: Rect) {
+    if app.column_layout().is_some() {
+        draw_column_rows(f, app, chunk);
+        return;
+    }
+
+    app.update_header_bounds(Rect::default());
     app.update_bounds(chunk);
 
     let mut rows = Vec::<ListItem>::new();
+    let selection = app.selection();
+    let conflicts = app.document().conflicts();
 
-    for terminal_row in 0..chunk.height as usize {
-        if let Some(row) = app.document().row(app.offset().y.saturating_add(terminal_row)) {
-            let new_list_item = ListItem::new(Line::from(Span::styled(
-                row.as_str(),
-                Style::default()
-            )));
+    for y in app.visible_rows(chunk.height as usize) {
+        if let Some(row) = app.document().row(y) {
+            let conflict_style = conflicts
+                .iter()
+                .find(|conflict| conflict.contains(y))
+                .map_or_else(Style::default, |conflict| conflict_style(conflict.part(y)));
+            let mut line = row_line(row, y, app.offset().x, chunk.width as usize, selection.as_ref(), app.theme().selection_style());
+
+            // A folded block shows as its first row followed by how much it hides
+            if let Some(hidden) = app.document().folds().hidden_after(y) {
+                line.spans.push(Span::styled(format!(" … {hidden} {}", if hidden == 1 { "line" } else { "lines" }), Style::default().fg(app.theme().gutter_fg)));
+            }
+
+            let new_list_item = ListItem::new(line).style(conflict_style);
 
             rows.push(new_list_item);
         }
@@ -74,43 +367,365 @@ fn draw_document_rows(f: &mut Frame, app: &mut App, chunk: Rect) {
     let list = List::new(rows);
 
     f.render_widget(list, chunk);
+    draw_overflow_markers(f, app, chunk);
+
+    if app.indent_guides() {
+        draw_indent_guides(f, app, chunk);
+    }
+
+    draw_color_columns(f, app, chunk);
+
+    if app.document().file_type.is_commit_message() {
+        draw_commit_message_hints(f, app, chunk);
+    }
+
+    underline_misspellings(f, app, chunk);
+    underline_diagnostics(f, app, chunk);
+}
+
+// `«` and `»` over the edge cells of rows that go on past the left or right of the view
+fn draw_overflow_markers(f: &mut Frame, app: &App, chunk: Rect) {
+    let offset = app.offset().x;
+    let width = chunk.width as usize;
+    let style = Style::default().fg(app.theme().gutter_fg);
+
+    if width < 2 {
+        return;
+    }
+
+    for (terminal_row, y) in (0..chunk.height).zip(app.visible_rows(chunk.height as usize)) {
+        let Some(row) = app.document().row(y) else {
+            continue;
+        };
+
+        let row_width = row.width_to(row.len());
+
+        if offset > 0 && row_width > offset {
+            f.buffer_mut().get_mut(chunk.x, chunk.y + terminal_row).set_symbol("«").set_style(style);
+        }
+
+        if row_width > offset.saturating_add(width) {
+            f.buffer_mut().get_mut(chunk.x + chunk.width - 1, chunk.y + terminal_row).set_symbol("»").set_style(style);
+        }
+    }
+}
+
+fn draw_color_columns(f: &mut Frame, app: &App, chunk: Rect) {
+    let offset = app.offset().x;
+    let rows = u16::try_from(app.visible_rows(chunk.height as usize).len()).unwrap_or(chunk.height);
+
+    for column in app.document().file_type.color_columns() {
+        let Some(x) = column.checked_sub(offset).and_then(|x| u16::try_from(x).ok()).filter(|x| *x < chunk.width) else {
+            continue;
+        };
+
+        for terminal_row in 0..rows.min(chunk.height) {
+            f.buffer_mut().get_mut(chunk.x + x, chunk.y + terminal_row).set_bg(app.theme().color_column_bg);
+        }
+    }
+}
+
+// Comment rows dimmed, the summary in bold, and what runs past the summary's or body's width or
+// fills the row that should separate them in the warning color
+fn draw_commit_message_hints(f: &mut Frame, app: &App, chunk: Rect) {
+    let offset = app.offset().x;
+    let warning = app.theme().message_warning;
+
+    for (terminal_row, y) in (0..chunk.height).zip(app.visible_rows(chunk.height as usize)) {
+        let Some(row) = app.document().row(y) else {
+            continue;
+        };
+
+        let row_width = row.width_to(row.len());
+        let (style, limit) = match y {
+            _ if row.as_str().starts_with('#') => (Style::default().fg(Color::DarkGray), usize::MAX),
+            0 => (Style::default().add_modifier(Modifier::BOLD), SUMMARY_WIDTH),
+            1 => (Style::default(), 0),
+            _ => (Style::default(), BODY_WIDTH),
+        };
+
+        for column in offset..row_width.min(offset + chunk.width as usize) {
+            let Ok(x) = u16::try_from(column - offset) else {
+                break;
+            };
+
+            let cell = f.buffer_mut().get_mut(chunk.x + x, chunk.y + terminal_row);
+
+            cell.set_style(if column >= limit { style.fg(warning) } else { style });
+        }
+    }
+}
+
+// Over the indentation of each row, blank rows taking the shallower of the rows around them so
+// guides run unbroken through gaps in a block
+fn draw_indent_guides(f: &mut Frame, app: &App, chunk: Rect) {
+    let document = app.document();
+    let offset = app.offset();
+    let style = Style::default().fg(app.theme().indent_guide_fg);
+    let whitespace = |y: usize| document.row(y).map(|row| leading_whitespace(row.as_str()).to_owned());
+    let is_blank = |y: usize| document.row(y).is_some_and(|row| row.as_str().trim().is_empty());
+
+    for (terminal_row, y) in (0..chunk.height).zip(app.visible_rows(chunk.height as usize)) {
+        let indentation = if is_blank(y) {
+            let previous = (0..y).rev().find(|y| !is_blank(*y)).and_then(whitespace);
+            let next = (y + 1..document.len().min(y + chunk.height as usize)).find(|y| !is_blank(*y)).and_then(whitespace);
+
+            previous.zip(next).map(|(previous, next)| if previous.len() < next.len() { previous } else { next })
+        } else {
+            whitespace(y)
+        };
+
+        for level in indent_levels(&indentation.unwrap_or_default(), app.indent_width()) {
+            let Some(x) = level.checked_sub(offset.x).and_then(|x| u16::try_from(x).ok()).filter(|x| *x < chunk.width) else {
+                continue;
+            };
+
+            let cell = f.buffer_mut().get_mut(chunk.x + x, chunk.y + terminal_row);
+
+            if cell.symbol() == " " {
+                cell.set_symbol("│").set_style(style);
+            }
+        }
+    }
+}
+
+// Underline the text diagnostics point at in the color of their kind, over whatever else styles it
+fn underline_diagnostics(f: &mut Frame, app: &App, chunk: Rect) {
+    let offset = app.offset();
+
+    for diagnostic in app.buffer_diagnostics() {
+        let Some(terminal_row) = app.screen_row(diagnostic.y).filter(|row| *row < chunk.height as usize) else {
+            continue;
+        };
+
+        let Some(row) = app.document().row(diagnostic.y) else {
+            continue;
+        };
+
+        let start = row.width_to(diagnostic.start);
+        // Diagnostics past the end of a line underline the cell after it
+        let end = row.width_to(diagnostic.end).max(start + 1);
+        let style = Style::default().add_modifier(Modifier::UNDERLINED).underline_color(item_label(diagnostic.item.kind).1);
+
+        for column in start.max(offset.x)..end.min(offset.x.saturating_add(chunk.width as usize)) {
+            let (Ok(x), Ok(y)) = (u16::try_from(column - offset.x), u16::try_from(terminal_row)) else {
+                continue;
+            };
+
+            f.buffer_mut().get_mut(chunk.x + x, chunk.y + y).set_style(style);
+        }
+    }
+}
+// Words the spell checker doesn't know get a red underline, diagnostics drawn after take over the cells they share
+fn underline_misspellings(f: &mut Frame, app: &App, chunk: Rect) {
+    let offset = app.offset();
+    let style = Style::default().add_modifier(Modifier::UNDERLINED).underline_color(Color::Red);
+
+    for y in app.visible_rows(chunk.height as usize) {
+        let (Some(terminal_row), Some(row)) = (app.screen_row(y), app.document().row(y)) else {
+            continue;
+        };
+
+        for word in app.misspelled_words(y) {
+            for column in row.width_to(word.start).max(offset.x)..row.width_to(word.end).min(offset.x.saturating_add(chunk.width as usize)) {
+                let (Ok(x), Ok(y)) = (u16::try_from(column - offset.x), u16::try_from(terminal_row)) else {
+                    continue;
+                };
+
+                f.buffer_mut().get_mut(chunk.x + x, chunk.y + y).set_style(style);
+            }
+        }
+    }
+}
+
+// Delimited files shown as aligned, colored columns with the header row pinned to the top
+fn draw_column_rows(f: &mut Frame, app: &mut App, chunk: Rect) {
+    let chunks = Layout::new(Direction::Vertical, [
+        Constraint::Length(1), // Header row
+        Constraint::Min(1), // Document rows
+    ]).split(chunk);
+
+    app.update_header_bounds(chunks[0]);
+    app.update_bounds(chunks[1]);
+
+    let Some(layout) = app.column_layout() else {
+        return;
+    };
+
+    let offset = app.offset();
+    let width = chunk.width as usize;
+
+    if let Some(header) = app.document().row(0) {
+        let header_style = Style::default().add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+
+        f.render_widget(Paragraph::new(column_line(layout, header, offset.x, width)).style(header_style), chunks[0]);
+    }
+
+    let lines: Vec<Line> = (0..chunks[1].height as usize)
+        .filter_map(|terminal_row| app.document().row(offset.y.saturating_add(terminal_row)))
+        .map(|row| column_line(layout, row, offset.x, width))
+        .collect();
+
+    f.render_widget(Paragraph::new(lines), chunks[1]);
+}
+
+fn column_line(layout: &ColumnLayout, row: &Row, offset: usize, width: usize) -> Line<'static> {
+    let view_end = offset.saturating_add(width);
+
+    let spans: Vec<Span> = layout.segments(row)
+        .into_iter()
+        .filter_map(|segment| {
+            let start = segment.start.max(offset);
+            let end = segment.start.saturating_add(segment.width).min(view_end);
+
+            if start >= end {
+                return None;
+            }
+
+            let style = segment.column.map_or_else(
+                || Style::default().fg(Color::DarkGray),
+                |column| Style::default().fg(COLUMN_COLORS[column % COLUMN_COLORS.len()]),
+            );
+
+            Some(Span::styled(Row::from(segment.text.as_str()).render(start - segment.start, end - start), style))
+        })
+        .collect();
+
+    Line::from(spans)
+}
+
+fn conflict_style(part: ConflictPart) -> Style {
+    match part {
+        ConflictPart::Marker => Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD),
+        ConflictPart::Ours => Style::default().fg(Color::Green),
+        ConflictPart::Base => Style::default().fg(Color::DarkGray),
+        ConflictPart::Theirs => Style::default().fg(Color::Blue),
+    }
+}
+
+// Render the visible part of a row, splitting out the selected columns so they can be highlighted
+fn row_line(row: &Row, y: usize, offset: usize, width: usize, selection: Option<&(Position, Position)>, selection_style: Style) -> Line<'static> {
+    let Some((start, end)) = selection.filter(|(start, end)| start.y <= y && y <= end.y) else {
+        return Line::from(row.render(offset, width));
+    };
+
+    let row_width = row.width_to(row.len());
+    let view_end = offset.saturating_add(width);
+    let selection_start = if start.y == y { row.width_to(start.x) } else { 0 };
+    // A selected line break is shown as one highlighted cell past the end of the row
+    let selection_end = if end.y == y { row.width_to(end.x) } else { row_width.saturating_add(1) };
+    let selected_from = selection_start.clamp(offset, view_end);
+    let selected_to = selection_end.clamp(offset, view_end);
+    let padding = selected_to.saturating_sub(selected_from.max(row_width));
+
+    Line::from(vec![
+        Span::raw(row.render(offset, selected_from - offset)),
+        Span::styled(
+            format!("{}{}", row.render(selected_from, selected_to - selected_from), " ".repeat(padding)),
+            selection_style
+        ),
+        Span::raw(row.render(selected_to, view_end - selected_to)),
+    ])
+}
+
+fn draw_scrollbar(f: &mut Frame, app: &mut App, chunk: Rect) {
+    app.update_scrollbar_bounds(chunk);
+
+    let mut scrollbar_state = ScrollbarState::new(app.document().len())
+        .viewport_content_length(chunk.height as usize)
+        .position(app.offset().y);
+
+    let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight)
+        .begin_symbol(None)
+        .end_symbol(None);
+
+    f.render_stateful_widget(scrollbar, chunk, &mut scrollbar_state);
 }
 
 fn draw_cursor(f: &mut Frame, app: &App) {
+    if let Some((x, y)) = cursor_cell(app) {
+        f.set_cursor(x, y);
+    }
+}
+// Where the cursor is on screen, None while it's scrolled out of view
+fn cursor_cell(app: &App) -> Option<(u16, u16)> {
     let position = app.cursor_position();
+    let column = app.cursor_column();
     let offset = app.offset();
-    
-    let x = position.x.saturating_sub(offset.x) as u16;
-    let y = position.y.saturating_sub(offset.y) as u16;
+    let bounds = app.bounds();
+
+    let on_pinned_header = app.column_layout().is_some() && position.y == 0;
+
+    // The mouse can scroll the cursor out of view, hide it until it's back on screen
+    if (position.y < offset.y && !on_pinned_header) || column < offset.x {
+        return None;
+    }
+
+    let x = column.saturating_sub(offset.x);
+    let y = app.screen_row(position.y).unwrap_or(usize::MAX);
 
-    f.set_cursor(x, y.saturating_add(1));
+    if x >= bounds.width as usize || (y >= bounds.height as usize && !on_pinned_header) {
+        return None;
+    }
+
+    // The pinned header row is drawn directly above the document rows
+    let cursor_y = if on_pinned_header {
+        bounds.y.saturating_sub(1)
+    } else {
+        bounds.y.saturating_add(u16::try_from(y).unwrap_or(u16::MAX))
+    };
+
+    Some((bounds.x.saturating_add(u16::try_from(x).unwrap_or(u16::MAX)), cursor_y))
 }
 
 fn draw_status(f: &mut Frame, app: &App, chunk: Rect) {
     match app.current_screen { 
-        CurrentScreen::Main => {
+        CurrentScreen::Main | CurrentScreen::ConfirmQuit | CurrentScreen::PasteHistory | CurrentScreen::Diff | CurrentScreen::Messages | CurrentScreen::UndoTree | CurrentScreen::Completion | CurrentScreen::FileChanges | CurrentScreen::FileTree | CurrentScreen::BufferList | CurrentScreen::Quickfix | CurrentScreen::TagPicker | CurrentScreen::ArchiveList | CurrentScreen::SideBySide | CurrentScreen::SpellSuggestions | CurrentScreen::TimestampPicker => {
+            let message = app.status().current();
             let title_block_style = Style::default()
-            .fg(Color::Black)
-            .bg(Color::Red);
+            .fg(app.theme().message_fg)
+            .bg(message.map_or(Color::Reset, |message| app.theme().message_color(message.severity)));
 
             let status = Paragraph::new(Text::styled(
-            "test",
+            message.map_or("", |message| message.text.as_str()),
             Style::default()
             )).block(Block::default().style(title_block_style));
 
             f.render_widget(status, chunk);
         },
-        CurrentScreen::Saving => {
-            let title_block_style = Style::default()
-                .fg(Color::Black)
-                .bg(Color::White);
+        CurrentScreen::Saving | CurrentScreen::SaveEncoding | CurrentScreen::RenameFile | CurrentScreen::GotoLine | CurrentScreen::InsertUnicode | CurrentScreen::ExportHtml | CurrentScreen::Search | CurrentScreen::Replace | CurrentScreen::ReplaceWith => {
+            let prompt = match app.current_screen {
+                CurrentScreen::GotoLine => GOTO_PROMPT.to_owned(),
+                CurrentScreen::ExportHtml => EXPORT_HTML_PROMPT.to_owned(),
+                CurrentScreen::SaveEncoding => ENCODING_PROMPT.to_owned(),
+                CurrentScreen::RenameFile => RENAME_PROMPT.to_owned(),
+                CurrentScreen::InsertUnicode => UNICODE_PROMPT.to_owned(),
+                CurrentScreen::ReplaceWith => REPLACE_WITH_PROMPT.to_owned(),
+                // Alt-R switches between plain text and regex searches
+                CurrentScreen::Search | CurrentScreen::Replace => {
+                    let name = match app.current_screen {
+                        CurrentScreen::Search if app.in_files() => SEARCH_IN_FILES_PROMPT,
+                        CurrentScreen::Search => SEARCH_PROMPT,
+                        _ if app.in_files() => REPLACE_IN_FILES_PROMPT,
+                        _ => REPLACE_PROMPT,
+                    };
+                    let mode = if app.search_regex() { " [Regexp]" } else { "" };
+
+                    format!("{name}{mode}: ")
+                },
+                _ => SAVE_PROMPT.to_owned(),
+            };
+            let title_block_style = app.theme().status_style();
 
             let status = Paragraph::new(Text::styled(
-                "test",
+                format!("{prompt}{}", app.prompt().text()),
                 Style::default()
             )).block(Block::default().style(title_block_style));
 
+            let cursor_x = u16::try_from(prompt.len().saturating_add(app.prompt().input().width_to(app.prompt().cursor()))).unwrap_or(u16::MAX);
+
             f.render_widget(status, chunk);
+            f.set_cursor(chunk.x.saturating_add(cursor_x), chunk.y);
         }
     }
 }
@@ -125,7 +740,7 @@ fn draw_controls(f: &mut Frame, app: &App, chunk: Rect) {
 
     test.spans.push(Span::styled(
         "^X",
-        Style::from((Color::Black, Color::White))
+        app.theme().header_style()
     ));
 
     test.spans.push(Span::styled(
@@ -133,25 +748,630 @@ fn draw_controls(f: &mut Frame, app: &App, chunk: Rect) {
         Style::default()
     ));
 
+    if app.pager() {
+        for (key, label) in [("q", " Quit"), ("Space", " Page down"), ("b", " Page up"), ("/", " Search")] {
+            test.spans.push(Span::raw("  "));
+            test.spans.push(Span::styled(key, app.theme().header_style()));
+            test.spans.push(Span::raw(label));
+        }
+    }
+
     f.render_widget(test, control_chunks[1]);
 }
 
-fn file_text(app: &App, areas: &[Rect]) -> String {
-    let mut welcome_message: String;
+fn draw_quit_modal(f: &mut Frame, app: &App, chunk: Rect) {
+    let area = centered_rect(44, 5, chunk);
+
+    let (title, question) = if app.keep_editor_open() {
+        (" Close ", "Save modified buffer before closing?")
+    } else {
+        (" Quit ", "Save modified buffer before quitting?")
+    };
+
+    let modal_block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .style(app.theme().popup_style());
+
+    let mut options = Line::default();
+
+    for (key, label) in [("S", "ave"), ("D", "iscard"), ("C", "ancel")] {
+        options.spans.push(Span::styled(format!(" {key}"), Style::from((app.theme().popup_bg, app.theme().popup_fg))));
+        options.spans.push(Span::styled(format!("{label} "), Style::default()));
+    }
+
+    let modal = Paragraph::new(vec![
+        Line::from(question),
+        Line::default(),
+        options,
+    ]).block(modal_block).centered();
+
+    f.render_widget(Clear, area);
+    f.render_widget(modal, area);
+}
+
+fn draw_paste_history(f: &mut Frame, app: &App, chunk: Rect) {
+    let entries: Vec<ListItem> = app.kill_ring().iter().map(|entry| {
+        let preview = entry.lines().next().unwrap_or_default();
+        let extra_lines = entry.lines().count().saturating_sub(1);
+
+        if extra_lines > 0 {
+            ListItem::new(format!("{preview} (+{extra_lines} lines)"))
+        } else {
+            ListItem::new(preview.to_owned())
+        }
+    }).collect();
+
+    let height = u16::try_from(entries.len()).unwrap_or(u16::MAX).saturating_add(2);
+    let area = centered_rect(chunk.width.saturating_sub(8).min(60), height, chunk);
+
+    draw_popup_list(f, app, entries, " Paste from history ", app.paste_history_index(), area);
+}
+
+// A star marks buffers with unsaved changes
+fn draw_buffer_list(f: &mut Frame, app: &App, chunk: Rect) {
+    let popup = app.buffer_list();
+    let entries: Vec<ListItem> = app.buffer_list_entries().into_iter().map(|entry| {
+        let marker = if entry.dirty { "* " } else { "  " };
+
+        ListItem::new(Line::from(vec![
+            Span::styled(marker, Style::default().fg(Color::Red)),
+            Span::styled(entry.name, Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled(format!("  {}", entry.path), Style::default().fg(Color::DarkGray)),
+        ]))
+    }).collect();
+
+    let title = if popup.filtering { format!(" Buffers /{} ", popup.filter) } else { " Buffers (/ filters, d closes) ".to_owned() };
+    let height = u16::try_from(entries.len()).unwrap_or(u16::MAX).saturating_add(2);
+    let area = centered_rect(chunk.width.saturating_sub(8).min(70), height, chunk);
+
+    draw_popup_list(f, app, entries, title, popup.index, area);
+}
+
+// Each format's name beside the stamp it makes
+fn draw_timestamp_picker(f: &mut Frame, app: &App, chunk: Rect) {
+    let name_width = app.timestamps().iter().map(|(name, _)| name.chars().count()).max().unwrap_or(0);
+    let entries: Vec<ListItem> = app.timestamps().iter().map(|(name, stamp)| {
+        ListItem::new(Line::from(vec![
+            Span::styled(format!("{name:<name_width$}  "), Style::default().fg(Color::Blue)),
+            Span::raw(stamp.clone()),
+        ]))
+    }).collect();
+
+    let height = u16::try_from(entries.len()).unwrap_or(u16::MAX).saturating_add(2);
+    let area = centered_rect(chunk.width.saturating_sub(8).min(60), height, chunk);
+
+    draw_popup_list(f, app, entries, " Insert timestamp ", app.timestamp_index(), area);
+}
+
+// Each definition with its kind and file, and the line it's on when ctags gave its text
+fn draw_tag_picker(f: &mut Frame, app: &App, chunk: Rect) {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let entries: Vec<ListItem> = app.tag_matches().iter().map(|tag| {
+        let path = tag.path.strip_prefix(&cwd).unwrap_or(&tag.path);
+        let place = match &tag.address {
+            TagAddress::Line(line) => format!("{}:{line}", path.display()),
+            TagAddress::Pattern(pattern) => format!("{}  {}", path.display(), pattern.trim_matches(['^', '$']).trim()),
+        };
+
+        ListItem::new(Line::from(vec![
+            Span::styled(format!("{:<10} ", tag.kind.as_deref().unwrap_or_default()), Style::default().fg(Color::Blue)),
+            Span::raw(place),
+        ]))
+    }).collect();
+
+    let title = app.tag_matches().first().map_or_else(String::new, |tag| format!(" Definitions of {} ", tag.name));
+    let height = u16::try_from(entries.len()).unwrap_or(u16::MAX).saturating_add(2);
+    let area = centered_rect(chunk.width.saturating_sub(8).min(90), height, chunk);
+
+    draw_popup_list(f, app, entries, title, app.tag_index(), area);
+}
+
+fn draw_archive_list(f: &mut Frame, app: &App, chunk: Rect) {
+    let Some(archive) = app.archive() else {
+        return;
+    };
+
+    let popup = app.archive_list();
+    let entries: Vec<ListItem> = app.archive_entries().into_iter().map(|member| ListItem::new(Line::from(member.to_owned()))).collect();
+    let keys = if archive.can_write() { "Enter views, ^E edits, / filters" } else { "Enter views, / filters" };
+    let title = if popup.filtering { format!(" {} /{} ", archive.path, popup.filter) } else { format!(" {} ({keys}) ", archive.path) };
+    let height = u16::try_from(entries.len()).unwrap_or(u16::MAX).saturating_add(2);
+    let area = centered_rect(chunk.width.saturating_sub(8).min(80), height, chunk);
+
+    draw_popup_list(f, app, entries, title, popup.index, area);
+}
+
+// The list popups share their look: a bordered box over what's behind it with the selected entry
+// reversed, scrolled to keep it in view
+fn draw_popup_list<'a>(f: &mut Frame, app: &App, entries: Vec<ListItem<'a>>, title: impl Into<Line<'a>>, selected: usize, area: Rect) {
+    let list = List::new(entries)
+        .block(Block::default()
+            .title(title)
+            .borders(Borders::ALL)
+            .style(app.theme().popup_style()))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+    let mut state = ListState::default().with_selected(Some(selected));
+
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+// In the bottom right corner of the buffer, out of the way of the cursor's line more often than not
+fn draw_chord_hints(f: &mut Frame, app: &App, chunk: Rect) {
+    let Some((keys, continuations)) = app.chord_hints() else {
+        return;
+    };
+
+    let key_width = continuations.iter().map(|(key, _)| key.chars().count()).max().unwrap_or_default();
+    let lines: Vec<Line> = continuations.iter().map(|(key, action)| {
+        Line::from(vec![
+            Span::styled(format!("{key:<key_width$}  "), Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(action.clone()),
+        ])
+    }).collect();
+
+    let width = lines.iter().map(Line::width).max().unwrap_or_default().max(keys.chars().count() + 2);
+    let width = u16::try_from(width).unwrap_or(u16::MAX).saturating_add(2).min(chunk.width);
+    let height = u16::try_from(lines.len()).unwrap_or(u16::MAX).saturating_add(2).min(chunk.height);
+    let area = Rect::new(chunk.right() - width, chunk.bottom() - height, width, height);
+
+    let hints = Paragraph::new(lines)
+        .block(Block::default()
+            .title(format!(" {keys} "))
+            .borders(Borders::ALL)
+            .style(app.theme().popup_style()));
+
+    f.render_widget(Clear, area);
+    f.render_widget(hints, area);
+}
+
+// Stacked down from the top right corner of the buffer, newest at the bottom, each bordered in the
+// color of its severity
+fn draw_toasts(f: &mut Frame, app: &App, chunk: Rect) {
+    let mut y = chunk.y;
+
+    for toast in app.status().toasts() {
+        let width = u16::try_from(Line::raw(toast.text.as_str()).width()).unwrap_or(u16::MAX).saturating_add(4).min(chunk.width);
+
+        if y + 3 > chunk.bottom() || width < 5 {
+            break;
+        }
+
+        let area = Rect::new(chunk.right() - width, y, width, 3);
+        let text = Paragraph::new(toast.text.as_str())
+            .block(Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(app.theme().message_color(toast.severity)))
+                .padding(Padding::horizontal(1))
+                .style(app.theme().popup_style()));
+
+        f.render_widget(Clear, area);
+        f.render_widget(text, area);
+
+        y += 3;
+    }
+}
+
+// Below the hovered cell, or above it when there's no room, wrapped to at most 60 columns
+fn draw_tooltip(f: &mut Frame, app: &App) {
+    let Some((cell, items)) = app.tooltip() else {
+        return;
+    };
+
+    let screen = f.size();
+    let lines: Vec<Line> = items.iter().map(|item| {
+        let (label, color) = item_label(item.kind);
+
+        Line::from(vec![
+            Span::styled(format!("{label}: "), Style::default().fg(color).add_modifier(Modifier::BOLD)),
+            Span::raw(item.text.clone()),
+        ])
+    }).collect();
+
+    let inner_width = lines.iter().map(Line::width).max().unwrap_or_default().min(60).min(screen.width.saturating_sub(2) as usize).max(1);
+    let wrapped_rows: usize = lines.iter().map(|line| line.width().div_ceil(inner_width).max(1)).sum();
+    let width = u16::try_from(inner_width).unwrap_or(u16::MAX).saturating_add(2);
+    let height = u16::try_from(wrapped_rows).unwrap_or(u16::MAX).saturating_add(2).min(screen.height);
+
+    let x = cell.x.min(screen.width.saturating_sub(width));
+    let y = if cell.y.saturating_add(1).saturating_add(height) <= screen.height { cell.y + 1 } else { cell.y.saturating_sub(height) };
+    let area = Rect::new(x, y, width.min(screen.width), height);
+
+    let tooltip = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .block(Block::default()
+            .borders(Borders::ALL)
+            .style(app.theme().popup_style()));
+
+    f.render_widget(Clear, area);
+    f.render_widget(tooltip, area);
+}
+
+// A pane under the buffer, so the code stays in view while working through the items
+fn draw_quickfix(f: &mut Frame, app: &App, chunk: Rect) {
+    let Some(list) = app.quickfix().filter(|_| chunk.height > 0) else {
+        return;
+    };
+
+    let entries: Vec<ListItem> = list.items().iter().map(|item| {
+        let (label, color) = item_label(item.kind);
+
+        ListItem::new(Line::from(vec![
+            Span::styled(format!("{}:{}:{} ", item.path, item.line, item.column), Style::default().fg(Color::DarkGray)),
+            // Matches have no label, so their text lines up with the path
+            Span::styled(if label.is_empty() { String::new() } else { format!("{label:<8}") }, Style::default().fg(color)),
+            Span::raw(item.text.clone()),
+        ]))
+    }).collect();
 
-    if let Some(file_name) = &app.document().file_name {
-        welcome_message = file_name.to_owned();
+    // The current item is only shown in full while keys go to the pane
+    let highlight = if matches!(app.current_screen, CurrentScreen::Quickfix) {
+        Style::default().add_modifier(Modifier::REVERSED)
     } else {
-        welcome_message = "New Buffer".to_owned();
+        Style::default().add_modifier(Modifier::BOLD)
+    };
+
+    let quickfix = List::new(entries)
+        .block(Block::default()
+            .title(format!(" {} ({}/{}) ", list.title(), list.index().map_or(0, |index| index + 1), list.len()))
+            .borders(Borders::TOP))
+        .highlight_style(highlight);
+
+    let mut state = ListState::default().with_selected(list.index());
+
+    f.render_stateful_widget(quickfix, chunk, &mut state);
+}
+
+fn draw_diff(f: &mut Frame, app: &App, chunk: Rect) {
+    let lines: Vec<Line> = app.diff()
+        .iter()
+        .skip(app.diff_scroll())
+        .take(chunk.height.saturating_sub(2) as usize)
+        .map(|line| match line {
+            DiffLine::Hunk(text) => Line::styled(text.clone(), Style::default().fg(Color::Cyan)),
+            DiffLine::Context(text) => Line::from(format!(" {text}")),
+            DiffLine::Removed(text) => Line::styled(format!("-{text}"), Style::default().fg(Color::Red)),
+            DiffLine::Added(text) => Line::styled(format!("+{text}"), Style::default().fg(Color::Green)),
+        })
+        .collect();
+
+    let diff = Paragraph::new(lines).block(Block::default()
+        .title(" Unsaved changes ")
+        .borders(Borders::ALL));
+
+    f.render_widget(Clear, chunk);
+    f.render_widget(diff, chunk);
+}
+
+// Each file in its own pane with the rows lined up. Changed lines are colored like the unified
+// diff, with what changed within them reversed
+fn draw_side_by_side(f: &mut Frame, app: &App, chunk: Rect) {
+    let Some(side_by_side) = app.side_by_side() else {
+        return;
+    };
+
+    let panes = Layout::new(Direction::Horizontal, [Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)]).split(chunk);
+    let rows: Vec<_> = side_by_side.rows
+        .iter()
+        .skip(app.side_by_side_scroll())
+        .take(chunk.height.saturating_sub(2) as usize)
+        .collect();
+    let number_width = side_by_side.old.len().max(side_by_side.new.len()).to_string().len();
+    let mut old_lines = Vec::with_capacity(rows.len());
+    let mut new_lines = Vec::with_capacity(rows.len());
+
+    for row in rows {
+        let old = row.old.map(|index| (index, side_by_side.old[index].as_str()));
+        let new = row.new.map(|index| (index, side_by_side.new[index].as_str()));
+        let whole = |line: Option<(usize, &str)>| line.map(|(_, text)| vec![Range { start: 0, end: text.len() }]).unwrap_or_default();
+        let (old_changes, new_changes) = match (old, new) {
+            _ if !row.changed => (Vec::new(), Vec::new()),
+            (Some((_, old)), Some((_, new))) => inline_changes(old, new),
+            (old, new) => (whole(old), whole(new)),
+        };
+
+        let (old_style, new_style) = if row.changed { (Style::default().fg(Color::Red), Style::default().fg(Color::Green)) } else { (Style::default(), Style::default()) };
+
+        old_lines.push(side_by_side_line(old, &old_changes, number_width, old_style));
+        new_lines.push(side_by_side_line(new, &new_changes, number_width, new_style));
+    }
+
+    let hunks = side_by_side.hunks.len();
+    let progress = match side_by_side.hunk_number(app.side_by_side_scroll() + chunk.height as usize / 2) {
+        _ if hunks == 0 => "no changes".to_owned(),
+        0 => format!("{hunks} changes below"),
+        hunk => format!("change {hunk} of {hunks}"),
+    };
+
+    f.render_widget(Clear, chunk);
+    f.render_widget(
+        Paragraph::new(old_lines).block(Block::default().title(format!(" {} ", side_by_side.old_name)).borders(Borders::ALL)),
+        panes[0],
+    );
+    f.render_widget(
+        Paragraph::new(new_lines).block(Block::default().title(format!(" {} ({progress}, n/p move) ", side_by_side.new_name)).borders(Borders::ALL)),
+        panes[1],
+    );
+}
+
+// A line of one pane, its number dimmed before it. `changes` are byte ranges to reverse
+fn side_by_side_line<'a>(line: Option<(usize, &'a str)>, changes: &[Range<usize>], number_width: usize, style: Style) -> Line<'a> {
+    let Some((index, text)) = line else {
+        return Line::from(Span::styled("~", Style::default().fg(Color::DarkGray)));
+    };
+
+    let mut spans = vec![Span::styled(format!("{:>number_width$} ", index + 1), Style::default().fg(Color::DarkGray))];
+    let mut shown = String::new();
+    let mut reversed = false;
+
+    for (start, grapheme) in text.grapheme_indices(true) {
+        let in_change = changes.iter().any(|range| range.contains(&start));
+
+        if in_change != reversed && !shown.is_empty() {
+            spans.push(Span::styled(std::mem::take(&mut shown), if reversed { style.add_modifier(Modifier::REVERSED) } else { style }));
+        }
+
+        reversed = in_change;
+        shown.push_str(&shown_grapheme(grapheme));
+    }
+
+    spans.push(Span::styled(shown, if reversed { style.add_modifier(Modifier::REVERSED) } else { style }));
+
+    Line::from(spans)
+}
+
+// `git diff --cached` in a pane under the commit message, as much of it as fits
+fn draw_staged_diff(f: &mut Frame, app: &App, chunk: Rect) {
+    if chunk.height == 0 {
+        return;
+    }
+
+    let lines: Vec<Line> = app.staged_diff()
+        .iter()
+        .take(chunk.height.saturating_sub(1) as usize)
+        .map(|line| match line {
+            DiffLine::Hunk(text) => Line::styled(text.clone(), Style::default().fg(Color::Cyan)),
+            DiffLine::Context(text) => Line::from(format!(" {text}")),
+            DiffLine::Removed(text) => Line::styled(format!("-{text}"), Style::default().fg(Color::Red)),
+            DiffLine::Added(text) => Line::styled(format!("+{text}"), Style::default().fg(Color::Green)),
+        })
+        .collect();
+
+    let diff = Paragraph::new(lines).block(Block::default()
+        .title(format!(" Staged changes ({} lines) ", app.staged_diff().len()))
+        .borders(Borders::TOP));
+
+    f.render_widget(diff, chunk);
+}
+
+fn draw_messages(f: &mut Frame, app: &App, chunk: Rect) {
+    let lines: Vec<Line> = app.status().history()
+        .iter()
+        .rev()
+        .skip(app.messages_scroll())
+        .take(chunk.height.saturating_sub(2) as usize)
+        .map(|message| {
+            let label = match message.severity {
+                Severity::Info => "info ",
+                Severity::Warn => "warn ",
+                Severity::Error => "error",
+            };
+
+            Line::from(vec![
+                Span::styled(format!("{:>8} ", elapsed_text(message.time.elapsed())), Style::default().fg(Color::DarkGray)),
+                Span::styled(label, Style::default().fg(app.theme().message_color(message.severity))),
+                Span::raw(format!(" {}", message.text)),
+            ])
+        })
+        .collect();
+
+    let messages = Paragraph::new(lines).block(Block::default()
+        .title(" Messages ")
+        .borders(Borders::ALL));
+
+    f.render_widget(Clear, chunk);
+    f.render_widget(messages, chunk);
+}
+
+// How long ago a message was shown, in its largest whole unit
+// Each state on its own line, indented where it branches off, the current one marked
+fn draw_undo_tree(f: &mut Frame, app: &App, chunk: Rect) {
+    let history = app.document().history();
+    let entries: Vec<ListItem> = history.tree()
+        .iter()
+        .map(|&(node, indent)| {
+            let marker = if node == history.current() { "●" } else { "○" };
+            let (label, age) = node.map_or_else(
+                || ("original".to_owned(), String::new()),
+                |index| {
+                    let node = &history.nodes()[index];
+
+                    (format!("#{} {}", index + 1, change_summary(&node.changes)), elapsed_text(node.time.elapsed().unwrap_or_default()))
+                },
+            );
+
+            ListItem::new(Line::from(vec![
+                Span::raw(format!("{}{marker} ", "  ".repeat(indent))),
+                Span::raw(label),
+                Span::styled(format!("  {age}"), Style::default().fg(Color::DarkGray)),
+            ]))
+        })
+        .collect();
+
+    let list = List::new(entries)
+        .block(Block::default()
+            .title(" Undo tree (Enter to restore) ")
+            .borders(Borders::ALL))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+    let mut state = ListState::default().with_selected(Some(app.undo_tree_index()));
+
+    f.render_widget(Clear, chunk);
+    f.render_stateful_widget(list, chunk, &mut state);
+}
+// A small list under the cursor, or above it when there's no room below
+fn draw_completion(f: &mut Frame, app: &App, chunk: Rect) {
+    let Some((x, y)) = cursor_cell(app) else {
+        return;
+    };
+
+    let entries: Vec<ListItem> = app.completions().iter().map(|completion| match completion {
+        Completion::Word(word) => ListItem::new(word.clone()),
+        Completion::Snippet(trigger) => ListItem::new(Line::from(vec![
+            Span::raw(trigger.clone()),
+            Span::styled(" snippet", Style::default().fg(Color::DarkGray)),
+        ])),
+        Completion::Dictionary(word) => ListItem::new(Line::from(vec![
+            Span::raw(word.clone()),
+            Span::styled(" dict", Style::default().fg(Color::DarkGray)),
+        ])),
+    }).collect();
+
+    let longest = app.completions().iter().map(|completion| completion.text().chars().count() + 8).max().unwrap_or(0);
+    let width = u16::try_from(longest).unwrap_or(u16::MAX).clamp(12, chunk.width);
+    let height = u16::try_from(entries.len()).unwrap_or(u16::MAX).min(8).saturating_add(2);
+    let below = chunk.bottom().saturating_sub(y.saturating_add(1));
+
+    let area = Rect {
+        x: x.min(chunk.right().saturating_sub(width)),
+        y: if below >= height { y.saturating_add(1) } else { y.saturating_sub(height).max(chunk.y) },
+        width,
+        height: height.min(chunk.height),
+    };
+
+    draw_popup_list(f, app, entries, "", app.completion_index(), area);
+}
+// Corrections under the misspelled word, followed by accepting it as it is
+fn draw_spell_suggestions(f: &mut Frame, app: &App, chunk: Rect) {
+    let (Some((x, y)), Some(word)) = (cursor_cell(app), app.spell_word()) else {
+        return;
+    };
+
+    let extra = [format!("Add \"{}\" to the dictionary", word.text), format!("Ignore \"{}\" this session", word.text)];
+    let hint = Style::default().fg(Color::DarkGray);
+    let entries: Vec<ListItem> = app.spell_suggestions()
+        .iter()
+        .map(|suggestion| ListItem::new(suggestion.clone()))
+        .chain(extra.iter().map(|entry| ListItem::new(Span::styled(entry.clone(), hint))))
+        .collect();
+
+    let longest = app.spell_suggestions().iter().chain(&extra).map(|entry| entry.chars().count() + 4).max().unwrap_or(0);
+    let width = u16::try_from(longest).unwrap_or(u16::MAX).clamp(12, chunk.width);
+    let height = u16::try_from(entries.len()).unwrap_or(u16::MAX).min(12).saturating_add(2);
+    let below = chunk.bottom().saturating_sub(y.saturating_add(1));
+
+    let area = Rect {
+        x: x.min(chunk.right().saturating_sub(width)),
+        y: if below >= height { y.saturating_add(1) } else { y.saturating_sub(height).max(chunk.y) },
+        width,
+        height: height.min(chunk.height),
+    };
+
+    let title = if app.spell_suggestions().is_empty() { " No suggestions " } else { "" };
+
+    draw_popup_list(f, app, entries, title, app.spell_suggestion_index(), area);
+}
+// Each file with how many of its lines will change, then those lines before and after
+fn draw_file_changes(f: &mut Frame, app: &App, chunk: Rect) {
+    let changes = app.file_changes();
+    let entries: Vec<ListItem> = preview_rows(changes)
+        .into_iter()
+        .map(|row| match row {
+            PreviewRow::File(file) => {
+                let file = &changes[file];
+                let selected = file.hunks.iter().filter(|hunk| hunk.selected).count();
+
+                ListItem::new(Line::from(vec![
+                    Span::styled(file.path.display().to_string(), Style::default().add_modifier(Modifier::BOLD)),
+                    Span::styled(format!("  {selected} of {}", file.hunks.len()), Style::default().fg(Color::DarkGray)),
+                ]))
+            },
+            PreviewRow::Hunk(file, hunk) => {
+                let hunk = &changes[file].hunks[hunk];
+                let marker = if hunk.selected { "[x]" } else { "[ ]" };
+
+                ListItem::new(Line::from(vec![
+                    Span::raw(format!("  {marker} {:>5}  ", hunk.line + 1)),
+                    Span::styled(hunk.old.trim().to_owned(), Style::default().fg(Color::Red)),
+                    Span::raw("  →  "),
+                    Span::styled(hunk.new.trim().to_owned(), Style::default().fg(Color::Green)),
+                ]))
+            },
+        })
+        .collect();
+
+    let list = List::new(entries)
+        .block(Block::default()
+            .title(" Replace in files (Space toggles, Enter writes) ")
+            .borders(Borders::ALL))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+    let mut state = ListState::default().with_selected(Some(app.file_changes_index()));
+
+    f.render_widget(Clear, chunk);
+    f.render_stateful_widget(list, chunk, &mut state);
+}
+// The first line of text an undo step added or removed
+fn change_summary(changes: &[Change]) -> String {
+    let Some(change) = changes.first() else {
+        return String::new();
+    };
+
+    let (sign, text) = match change {
+        Change::Insert { text, .. } => ('+', text),
+        Change::Delete { text, .. } => ('-', text),
+    };
+    let preview: String = text.lines().next().unwrap_or_default().chars().take(40).collect();
+    let more = if changes.len() > 1 { format!(" (+{} more)", changes.len() - 1) } else { String::new() };
+
+    format!("{sign}{preview:?}{more}")
+}
+fn elapsed_text(elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs();
+
+    match seconds {
+        0..=59 => format!("{seconds}s ago"),
+        60..=3599 => format!("{}m ago", seconds / 60),
+        _ => format!("{}h ago", seconds / 3600),
+    }
+}
+
+fn centered_rect(width: u16, height: u16, area: Rect) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+
+    Rect {
+        x: area.x.saturating_add(area.width.saturating_sub(width) / 2),
+        y: area.y.saturating_add(area.height.saturating_sub(height) / 2),
+        width,
+        height,
+    }
+}
+
+fn centered_text(text: &str, area: Rect) -> String {
+    let width = area.width as usize;
+    let len = text.chars().count();
+
+    // Keep the tail of long paths visible, since the file name is the useful part
+    if len > width {
+        let tail: String = text.chars().skip(len.saturating_sub(width.saturating_sub(1))).collect();
+
+        return format!("…{tail}");
     }
 
-    let width = areas.iter().fold(0, |_, area| area.width) as usize;
-    let len = welcome_message.len();
     let padding = width.saturating_sub(len) / 2;
-    let spaces = " ".repeat(padding.saturating_sub(4));
 
-    welcome_message = format!("{spaces}{welcome_message}");
-    welcome_message.truncate(areas.last().unwrap().width as usize);
+    format!("{}{text}", " ".repeat(padding))
+}
 
-    welcome_message
-}
\ No newline at end of file
+// What an item is called and the color it's shown in
+const fn item_label(kind: ItemKind) -> (&'static str, Color) {
+    match kind {
+        ItemKind::Error => ("error", Color::Red),
+        ItemKind::Warning => ("warning", Color::Yellow),
+        ItemKind::Note => ("note", Color::Cyan),
+        ItemKind::Match => ("", Color::Reset),
+    }
+}
@@ -0,0 +1,137 @@
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+
+// Names of the characters most often wanted that keyboards don't have, as Unicode names them
+const NAMES: &[(&str, char)] = &[
+    ("no-break space", '\u{a0}'),
+    ("inverted exclamation mark", '¡'),
+    ("cent sign", '¢'),
+    ("pound sign", '£'),
+    ("yen sign", '¥'),
+    ("section sign", '§'),
+    ("copyright sign", '©'),
+    ("left-pointing double angle quotation mark", '«'),
+    ("not sign", '¬'),
+    ("soft hyphen", '\u{ad}'),
+    ("registered sign", '®'),
+    ("degree sign", '°'),
+    ("plus-minus sign", '±'),
+    ("superscript two", '²'),
+    ("superscript three", '³'),
+    ("micro sign", 'µ'),
+    ("pilcrow sign", '¶'),
+    ("middle dot", '·'),
+    ("right-pointing double angle quotation mark", '»'),
+    ("vulgar fraction one quarter", '¼'),
+    ("vulgar fraction one half", '½'),
+    ("vulgar fraction three quarters", '¾'),
+    ("inverted question mark", '¿'),
+    ("multiplication sign", '×'),
+    ("division sign", '÷'),
+    ("greek small letter alpha", 'α'),
+    ("greek small letter beta", 'β'),
+    ("greek small letter gamma", 'γ'),
+    ("greek small letter delta", 'δ'),
+    ("greek small letter epsilon", 'ε'),
+    ("greek small letter theta", 'θ'),
+    ("greek small letter lamda", 'λ'),
+    ("greek small letter mu", 'μ'),
+    ("greek small letter pi", 'π'),
+    ("greek small letter sigma", 'σ'),
+    ("greek small letter tau", 'τ'),
+    ("greek small letter phi", 'φ'),
+    ("greek small letter omega", 'ω'),
+    ("greek capital letter delta", 'Δ'),
+    ("greek capital letter sigma", 'Σ'),
+    ("greek capital letter omega", 'Ω'),
+    ("en dash", '–'),
+    ("em dash", '—'),
+    ("left single quotation mark", '‘'),
+    ("right single quotation mark", '’'),
+    ("left double quotation mark", '“'),
+    ("right double quotation mark", '”'),
+    ("dagger", '†'),
+    ("bullet", '•'),
+    ("horizontal ellipsis", '…'),
+    ("per mille sign", '‰'),
+    ("euro sign", '€'),
+    ("trade mark sign", '™'),
+    ("leftwards arrow", '←'),
+    ("upwards arrow", '↑'),
+    ("rightwards arrow", '→'),
+    ("downwards arrow", '↓'),
+    ("left right arrow", '↔'),
+    ("rightwards double arrow", '⇒'),
+    ("for all", '∀'),
+    ("there exists", '∃'),
+    ("empty set", '∅'),
+    ("element of", '∈'),
+    ("n-ary summation", '∑'),
+    ("square root", '√'),
+    ("infinity", '∞'),
+    ("logical and", '∧'),
+    ("logical or", '∨'),
+    ("intersection", '∩'),
+    ("union", '∪'),
+    ("integral", '∫'),
+    ("almost equal to", '≈'),
+    ("not equal to", '≠'),
+    ("identical to", '≡'),
+    ("less-than or equal to", '≤'),
+    ("greater-than or equal to", '≥'),
+    ("check mark", '✓'),
+    ("ballot x", '✗'),
+    ("black star", '★'),
+    ("black heart suit", '♥'),
+    ("zero width space", '\u{200b}'),
+    ("zero width joiner", '\u{200d}'),
+    ("byte order mark", '\u{feff}'),
+    ("replacement character", '\u{fffd}'),
+];
+
+// The character `input` stands for, a hex codepoint like `U+00E9`, `0xe9` or `e9`, or a name from
+// the table. A name only has to be part of one of them, so `ellipsis` finds the horizontal ellipsis
+/// # Errors
+///
+/// Will return `Err` if `input` isn't a codepoint or matches no name or several of them
+pub fn parse_char(input: &str) -> Result<char> {
+    let input = input.trim();
+    let hex = input
+        .strip_prefix("U+")
+        .or_else(|| input.strip_prefix("u+"))
+        .or_else(|| input.strip_prefix("0x"))
+        .unwrap_or(input);
+
+    if let Ok(codepoint) = u32::from_str_radix(hex, 16) {
+        return char::from_u32(codepoint).ok_or_else(|| eyre!("U+{codepoint:04X} isn't a character"));
+    }
+
+    let query = normalize(input);
+
+    if let Some((_, c)) = NAMES.iter().find(|(name, _)| normalize(name) == query) {
+        return Ok(*c);
+    }
+
+    let matches: Vec<&(&str, char)> = NAMES.iter().filter(|(name, _)| normalize(name).contains(&query)).collect();
+
+    match matches[..] {
+        [] => Err(eyre!("No character is called `{input}`")),
+        [(_, c)] => Ok(*c),
+        _ => {
+            let names: Vec<&str> = matches.iter().take(3).map(|(name, _)| *name).collect();
+
+            Err(eyre!("{} characters match `{input}`: {}...", matches.len(), names.join(", ")))
+        },
+    }
+}
+
+// The table's name for `c`, if it has one
+#[must_use]
+pub fn char_name(c: char) -> Option<&'static str> {
+    NAMES.iter().find(|(_, named)| *named == c).map(|(name, _)| *name)
+}
+
+// Case, hyphens and underscores don't matter when looking up a name
+fn normalize(name: &str) -> String {
+    name.to_lowercase().replace(['-', '_'], " ")
+}
@@ -0,0 +1,82 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+
+// Watches the directories of open files for changes made by other programs. Directories rather than
+// the files, since saving by renaming a new file over the old one would leave a watch on the old one
+pub struct DiskWatcher {
+    watcher: RecommendedWatcher,
+    receiver: Receiver<notify::Result<Event>>,
+    // The file names last asked for, the directories are only worked out again when they change
+    files: Vec<String>,
+    directories: HashSet<PathBuf>,
+    // Changes put off until later, returned again with the next ones
+    deferred: HashSet<PathBuf>,
+}
+
+impl DiskWatcher {
+    // None when the platform can't watch files
+    #[must_use]
+    pub fn new() -> Option<Self> {
+        let (sender, receiver) = mpsc::channel();
+        let watcher = notify::recommended_watcher(move |event| {
+            // The app may have quit while the watcher's thread was sending
+            let _ = sender.send(event);
+        })
+        .inspect_err(|error| tracing::warn!(%error, "file watcher unavailable"))
+        .ok()?;
+
+        Some(Self { watcher, receiver, files: Vec::new(), directories: HashSet::new(), deferred: HashSet::new() })
+    }
+    // Watch the directories holding `files` and stop watching those that don't hold any anymore
+    pub fn watch(&mut self, mut files: Vec<String>) {
+        files.sort_unstable();
+        files.dedup();
+
+        if files == self.files {
+            return;
+        }
+
+        let directories: HashSet<PathBuf> = files.iter().filter_map(|file| canonical(file)?.parent().map(Path::to_path_buf)).collect();
+
+        for directory in self.directories.difference(&directories) {
+            let _ = self.watcher.unwatch(directory);
+        }
+
+        for directory in directories.difference(&self.directories) {
+            if let Err(error) = self.watcher.watch(directory, RecursiveMode::NonRecursive) {
+                tracing::warn!(directory = %directory.display(), %error, "couldn't watch directory");
+            }
+        }
+
+        self.files = files;
+        self.directories = directories;
+    }
+    // Paths created, written, renamed or removed since the last call, and those deferred then
+    #[must_use]
+    pub fn changed(&mut self) -> HashSet<PathBuf> {
+        let mut paths = std::mem::take(&mut self.deferred);
+
+        paths.extend(self.receiver.try_iter().filter_map(Result::ok).filter(|event| !event.kind.is_access()).flat_map(|event| event.paths));
+
+        paths
+    }
+    // Report a change again on the next call
+    pub fn defer(&mut self, path: PathBuf) {
+        self.deferred.insert(path);
+    }
+}
+
+// Events name files by the watched directory's real path. A file that doesn't exist yet is named by
+// its directory's, so it's noticed once something creates it
+#[must_use]
+pub fn canonical(file: &str) -> Option<PathBuf> {
+    fs::canonicalize(file).ok().or_else(|| {
+        let path = Path::new(file);
+        let directory = path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or_else(|| Path::new("."));
+
+        Some(fs::canonicalize(directory).ok()?.join(path.file_name()?))
+    })
+}
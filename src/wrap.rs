@@ -0,0 +1,177 @@
+use unicode_width::UnicodeWidthStr;
+use crate::document::Document;
+use crate::indent::leading_whitespace;
+
+// `lines` rewrapped to fit in `width` columns, each paragraph filled on its own. Paragraphs are split
+// by blank lines, list bullets and changes of comment leader, and keep their leader and bullet
+#[must_use]
+pub fn justify(lines: &[String], leader: Option<&str>, width: usize) -> Vec<String> {
+    let mut justified = Vec::new();
+    let mut start = 0;
+
+    for index in 0..=lines.len() {
+        let ends_paragraph = index == lines.len()
+            || is_blank(&lines[index], leader)
+            || (index > start && starts_paragraph(&lines[index - 1], &lines[index], leader));
+
+        if !ends_paragraph {
+            continue;
+        }
+
+        if index > start {
+            justified.extend(fill_paragraph(&lines[start..index], leader, width));
+        }
+
+        if let Some(line) = lines.get(index).filter(|line| is_blank(line, leader)) {
+            justified.push(line.clone());
+            start = index + 1;
+        } else {
+            start = index;
+        }
+    }
+
+    justified
+}
+
+// The rows of the paragraph `y` is in, None on a blank row
+#[must_use]
+pub fn paragraph_bounds(document: &Document, y: usize, leader: Option<&str>) -> Option<(usize, usize)> {
+    let line = |y: usize| document.row(y).map(|row| row.as_str().to_owned());
+
+    if is_blank(&line(y)?, leader) {
+        return None;
+    }
+
+    let mut start = y;
+    let mut end = y;
+
+    while let (Some(previous), Some(current)) = (start.checked_sub(1).and_then(line), line(start)) {
+        if is_blank(&previous, leader) || starts_paragraph(&previous, &current, leader) {
+            break;
+        }
+
+        start -= 1;
+    }
+
+    while let (Some(current), Some(next)) = (line(end), line(end + 1)) {
+        if is_blank(&next, leader) || starts_paragraph(&current, &next, leader) {
+            break;
+        }
+
+        end += 1;
+    }
+
+    Some((start, end))
+}
+
+// The spaces to break `line` at so what's before them fits in `width` columns, as the byte range they
+// take up. None when the line fits or nothing after its leader and bullet can be moved
+#[must_use]
+pub fn break_point(line: &str, leader: Option<&str>, width: usize) -> Option<(usize, usize)> {
+    if line.width() <= width {
+        return None;
+    }
+
+    let body = comment_prefix(line, leader).len();
+    let prefix = body + bullet(&line[body..]).map_or(0, str::len);
+
+    let start = line
+        .char_indices()
+        .filter(|(index, c)| *index > prefix && *c == ' ' && !line[..*index].ends_with([' ', '\t']))
+        .map(|(index, _)| index)
+        .take_while(|index| line[..*index].width() <= width)
+        .last()?;
+
+    Some((start, start + leading_whitespace(&line[start..]).len()))
+}
+// What lines carrying on `line` start with, its comment leader with a bullet's width in spaces
+#[must_use]
+pub fn continuation_prefix(line: &str, leader: Option<&str>) -> String {
+    let prefix = comment_prefix(line, leader);
+    let bullet = bullet(&line[prefix.len()..]).unwrap_or_default();
+
+    format!("{prefix}{}", " ".repeat(bullet.width()))
+}
+
+fn fill_paragraph(lines: &[String], leader: Option<&str>, width: usize) -> Vec<String> {
+    let prefix = comment_prefix(&lines[0], leader);
+    let bullet = bullet(&lines[0][prefix.len()..]).unwrap_or_default();
+
+    // Without a bullet the second line shows how the rest are indented
+    let continuation = match lines.get(1) {
+        Some(second) if bullet.is_empty() => comment_prefix(second, leader).to_owned(),
+        _ => continuation_prefix(&lines[0], leader),
+    };
+
+    let words = lines.iter().enumerate().flat_map(|(index, line)| {
+        let skip = if index == 0 { prefix.len() + bullet.len() } else { comment_prefix(line, leader).len() };
+
+        line[skip..].split_whitespace()
+    });
+
+    let mut filled = Vec::new();
+    let mut current = format!("{prefix}{bullet}");
+    let mut empty = true;
+
+    for word in words {
+        if !empty && current.width() + 1 + word.width() > width {
+            filled.push(std::mem::replace(&mut current, continuation.clone()));
+            empty = true;
+        }
+
+        if !empty {
+            current.push(' ');
+        }
+
+        current.push_str(word);
+        empty = false;
+    }
+
+    filled.push(current);
+    filled
+}
+
+// A bullet or a changed leader starts a new paragraph even without a blank line before it
+fn starts_paragraph(previous: &str, line: &str, leader: Option<&str>) -> bool {
+    let prefix = comment_prefix(line, leader);
+
+    comment_prefix(previous, leader).trim_end() != prefix.trim_end() || bullet(&line[prefix.len()..]).is_some()
+}
+
+fn is_blank(line: &str, leader: Option<&str>) -> bool {
+    line[comment_prefix(line, leader).len()..].trim().is_empty()
+}
+
+// The indentation, the comment leader with any repeats of its last character like `///` or `##`, and
+// the spaces after it
+fn comment_prefix<'a>(line: &'a str, leader: Option<&str>) -> &'a str {
+    let indent = leading_whitespace(line).len();
+    let rest = &line[indent..];
+
+    let Some(leader) = leader.filter(|leader| rest.starts_with(*leader)) else {
+        return &line[..indent];
+    };
+
+    let after = &rest[leader.len()..];
+    let repeats = after.len() - after.trim_start_matches(|c| c == '!' || leader.ends_with(c)).len();
+    let marker = indent + leader.len() + repeats;
+
+    &line[..marker + leading_whitespace(&line[marker..]).len()]
+}
+
+// `- `, `* `, `+ ` or a number followed by `.` or `)` starting `text`, with the spaces after it
+fn bullet(text: &str) -> Option<&str> {
+    let digits = text.len() - text.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+
+    let marker = if text.starts_with(['-', '*', '+']) {
+        1
+    } else if digits > 0 && text[digits..].starts_with(['.', ')']) {
+        digits + 1
+    } else {
+        return None;
+    };
+
+    let spaces = leading_whitespace(&text[marker..]).len();
+
+    (spaces > 0 && !text[marker..].trim().is_empty()).then(|| &text[..marker + spaces])
+}